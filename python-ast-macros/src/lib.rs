@@ -0,0 +1,234 @@
+//! Macros that transpile Python to Rust at compile time.
+//!
+//! [`include_python!`] transpiles a Python file, and [`python!`] Python written inline, into the items
+//! [`python_ast`] would generate for them, so they can be called like any other Rust code. Both
+//! expand in item position, and a Python syntax error fails the build at the offending line.
+//!
+//! ```ignore
+//! use python_ast_macros::{include_python, python};
+//!
+//! include_python!("algo.py");
+//!
+//! python! {
+//!     def greet(name):
+//!         print(name)
+//! }
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+use python_ast::{
+    CodeGen, CodeGenContext, Error, ExprType, PythonOptions, StatementType, SymbolTableScopes, parse_enhanced,
+};
+use quote::{quote, quote_spanned};
+use syn::LitStr;
+
+/// Transpiles the Python file at the given path, relative to the file the macro is used in.
+///
+/// The file is a dependency of the crate, so cargo rebuilds it when the Python changes.
+#[proc_macro]
+pub fn include_python(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let path = syn::parse_macro_input!(input as LitStr);
+    expand_file(&path).into()
+}
+
+/// Transpiles the Python written inside it.
+///
+/// The Python is rebuilt from the Rust tokens and their positions, so its indentation is kept. Python
+/// that Rust can't tokenize, such as `'single-quoted'` strings, `//` or `#` comments, can be given as a
+/// single string literal instead: `python!(r#"..."#)`.
+#[proc_macro]
+pub fn python(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = TokenStream::from(input);
+    match syn::parse2::<LitStr>(input.clone()) {
+        Ok(literal) => expand(&literal.value(), "inline.py", |_| literal.span()),
+        Err(_) => {
+            let source = PythonSource::from_tokens(input);
+            expand(&source.text, "inline.py", |line| source.span_of_line(line))
+        }
+    }
+    .into()
+}
+
+/// The Python filename to parse a file under. The module is named after it, so it has to be an
+/// identifier.
+fn module_filename(path: &Path) -> String {
+    let stem = path.file_stem().map_or_else(|| "python".into(), |s| s.to_string_lossy());
+    format!("{}.py", stem.replace(|c: char| !c.is_ascii_alphanumeric() && c != '_', "_"))
+}
+
+fn expand_file(path: &LitStr) -> TokenStream {
+    let relative = PathBuf::from(path.value());
+    let base = Span::call_site()
+        .local_file()
+        .and_then(|file| std::path::absolute(file).ok())
+        .and_then(|file| file.parent().map(Path::to_path_buf))
+        .or_else(|| std::env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from))
+        .unwrap_or_default();
+    let full = base.join(&relative);
+    let source = match std::fs::read_to_string(&full) {
+        Ok(source) => source,
+        Err(e) => {
+            let message = format!("couldn't read {}: {}", full.display(), e);
+            return quote_spanned!(path.span()=> compile_error!(#message););
+        }
+    };
+
+    let tokens = expand(&source, &module_filename(&relative), |_| path.span());
+    let tracked = full.to_string_lossy().into_owned();
+    quote! {
+        const _: &[u8] = include_bytes!(#tracked);
+        #tokens
+    }
+}
+
+/// Parses and transpiles `source`. `span_of_line` places a syntax error on the Python line it is at.
+fn expand(source: &str, filename: &str, span_of_line: impl Fn(usize) -> Span) -> TokenStream {
+    let mut module = match parse_enhanced(source, filename) {
+        Ok(module) => module,
+        Err(e) => {
+            let span = match &e {
+                Error::ParseError { location, .. } => location.line.map_or_else(Span::call_site, &span_of_line),
+                _ => Span::call_site(),
+            };
+            let message = e.to_string();
+            return quote_spanned!(span=> compile_error!(#message););
+        }
+    };
+
+    // A module docstring becomes inner `#![doc]` attributes, which have no place in an expansion.
+    let docstring = module.raw.body.first().is_some_and(|first| {
+        matches!(&first.statement, StatementType::Expr(expr) if matches!(&expr.value, ExprType::Constant(c) if c.string_value().is_some()))
+    });
+    if docstring {
+        module.raw.body.remove(0);
+    }
+
+    let name = module.name.as_ref().map_or_else(|| "python".to_string(), |name| name.id.clone());
+    match module.to_rust(CodeGenContext::Module(name), PythonOptions::default(), SymbolTableScopes::new()) {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            let message = format!("{}: {}", filename, e);
+            quote!(compile_error!(#message);)
+        }
+    }
+}
+
+/// Python source rebuilt from Rust tokens, using their positions for the line breaks and indentation.
+#[derive(Default)]
+struct PythonSource {
+    text: String,
+    /// The span of the first token on each Python line.
+    lines: Vec<Span>,
+    /// The Rust line and column the Python starts at, which become line 1, column 0.
+    origin: Option<(usize, usize)>,
+    line: usize,
+    column: usize,
+}
+
+impl PythonSource {
+    fn from_tokens(tokens: TokenStream) -> Self {
+        let mut source = Self { line: 1, ..Default::default() };
+        source.push_tokens(tokens);
+        source.text.push('\n');
+        source
+    }
+
+    fn span_of_line(&self, line: usize) -> Span {
+        line.checked_sub(1)
+            .and_then(|index| self.lines.get(index))
+            .or(self.lines.last())
+            .copied()
+            .unwrap_or_else(Span::call_site)
+    }
+
+    fn push_tokens(&mut self, tokens: TokenStream) {
+        for token in tokens {
+            match token {
+                TokenTree::Group(group) => {
+                    let (open, close) = match group.delimiter() {
+                        Delimiter::Parenthesis => ("(", ")"),
+                        Delimiter::Bracket => ("[", "]"),
+                        Delimiter::Brace => ("{", "}"),
+                        Delimiter::None => ("", ""),
+                    };
+                    self.push(open, group.span_open());
+                    self.push_tokens(group.stream());
+                    self.push(close, group.span_close());
+                }
+                TokenTree::Ident(ident) => self.push(&ident.to_string(), ident.span()),
+                TokenTree::Punct(punct) => self.push(&punct.as_char().to_string(), punct.span()),
+                TokenTree::Literal(literal) => self.push(&literal.to_string(), literal.span()),
+            }
+        }
+    }
+
+    fn push(&mut self, text: &str, span: Span) {
+        if text.is_empty() {
+            return;
+        }
+        let (start, end) = (span.start(), span.end());
+        let (first_line, first_column) = *self.origin.get_or_insert((start.line, start.column));
+        let line = (start.line + 1).saturating_sub(first_line).max(self.line);
+        let column = start.column.saturating_sub(first_column);
+
+        if line > self.line {
+            self.text.extend(std::iter::repeat_n('\n', line - self.line));
+            self.line = line;
+            self.column = 0;
+        }
+        if self.lines.len() < self.line {
+            self.lines.resize(self.line, span);
+        }
+        if column > self.column {
+            self.text.extend(std::iter::repeat_n(' ', column - self.column));
+        } else if column < self.column {
+            // Tokens without real positions, such as those from another macro, still need separating.
+            self.text.push(' ');
+        }
+        self.text.push_str(text);
+
+        let end_line = (end.line + 1).saturating_sub(first_line);
+        if end_line > self.line {
+            self.line = end_line;
+            self.column = end.column.saturating_sub(first_column);
+        } else {
+            self.column = column.max(self.column) + text.chars().count();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rebuild(rust: &str) -> String {
+        PythonSource::from_tokens(rust.parse().unwrap()).text
+    }
+
+    #[test]
+    fn test_rebuilds_indentation() {
+        let python = rebuild("def f(x):\n    if x == 1:\n        return [x, -x]\n    return None");
+        assert_eq!(python, "def f(x):\n    if x == 1:\n        return [x, -x]\n    return None\n");
+
+        let indented = rebuild("\n        class A:\n            y = {\"k\": 2}\n        z = A()");
+        assert_eq!(indented, "class A:\n    y = {\"k\": 2}\nz = A()\n");
+    }
+
+    #[test]
+    fn test_syntax_error_is_compile_error() {
+        let source = PythonSource::from_tokens("x = 1\ny = = 2".parse().unwrap());
+        let expanded = expand(&source.text, "inline.py", |line| source.span_of_line(line)).to_string();
+        assert!(expanded.starts_with("compile_error !"), "{}", expanded);
+        assert!(expanded.contains("inline.py:2"), "{}", expanded);
+    }
+
+    #[test]
+    fn test_expands_to_items() {
+        let expanded = expand("\"\"\"Docs.\"\"\"\ndef f():\n    pass\n", "inline.py", |_| Span::call_site());
+        let file: syn::File = syn::parse2(expanded).unwrap();
+        assert!(file.attrs.is_empty());
+        assert!(file.items.iter().any(|item| matches!(item, syn::Item::Fn(f) if f.sig.ident == "f")));
+    }
+}
@@ -0,0 +1,3 @@
+def shout(word):
+    # Comments are fine in files.
+    print(word)
@@ -0,0 +1,47 @@
+use std::cell::RefCell;
+
+use python_ast_macros::{include_python, python};
+
+/// Stands in for the Python runtime crate that the generated code imports.
+mod stdpython {
+    use std::cell::RefCell;
+
+    pub type PyObject = String;
+
+    thread_local! {
+        pub static PRINTED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    pub fn print(value: impl Into<PyObject>) {
+        PRINTED.with(|printed| printed.borrow_mut().push(value.into()));
+    }
+}
+
+fn printed() -> Vec<String> {
+    stdpython::PRINTED.with(RefCell::take)
+}
+
+python! {
+    def greet(name):
+        print(name)
+}
+
+python!(r#"
+def quoted():
+    print('single')  # not valid Rust
+"#);
+
+include_python!("fixtures/shout.py");
+
+#[test]
+fn test_inline_python() {
+    greet("hello");
+    quoted();
+    assert_eq!(printed(), ["hello", "single"]);
+}
+
+#[test]
+fn test_included_python() {
+    shout("HEY");
+    assert_eq!(printed(), ["HEY"]);
+}
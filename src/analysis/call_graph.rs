@@ -0,0 +1,756 @@
+//! A call graph over a set of modules: which function, class body or module calls what, and where.
+//!
+//! Each module is named by its dotted path, taken from its filename relative to the project root, so
+//! `pkg/util.py` is `pkg.util` and `pkg/__init__.py` is `pkg`. Functions and classes are named after where
+//! they're defined, such as `pkg.util.Parser.parse`.
+//!
+//! A call's target is resolved through the symbol tables of the scopes around it:
+//!
+//! * a function or class defined in the set is a [`Callee::Function`], where calling a class calls its
+//!   `__init__` if it, or a base class in the set, has one;
+//! * `self.m()` and `cls.m()` in a method look `m` up on the class and its bases;
+//! * names bound by `import` and `from ... import`, including relative imports, are followed to the module
+//!   they name, and through whatever it imports in turn;
+//! * a module outside the set, or a builtin, is a [`Callee::External`];
+//! * a call through a variable, parameter or any other expression is a [`Callee::Dynamic`];
+//! * a name that isn't bound anywhere, or isn't in the module it's imported from, is a
+//!   [`Callee::Unresolved`].
+//!
+//! Calls in a class body, and in decorators and default values, are made by the enclosing function or
+//! module, since that is what runs them.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt;
+
+use serde_json::{Value, json};
+
+use crate::{
+    Call, CodeGen, Comprehension, ExceptHandler, ExprType, ImportFrom, Module, SourceLocation, Statement, StatementType, SymbolTableNode,
+    SymbolTableScopes, UnitKind, WithItem, locate,
+    visit::{Visitor, walk_comprehension, walk_except_handler, walk_expr, walk_stmt},
+};
+
+use super::{cfg::nested_bodies, names::builtins};
+
+/// How deep to follow re-exports and base classes, in case they go round in a circle.
+const MAX_HOPS: usize = 8;
+
+/// What a call calls.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Callee {
+    /// A function defined in the set, by qualified name.
+    Function(String),
+    /// Something outside the set, such as `os.path.join` or `builtins.print`.
+    External(String),
+    /// A name that isn't bound where it's called, or isn't defined where it's imported from.
+    Unresolved(String),
+    /// A call through a variable, parameter, attribute or other expression, described as written.
+    Dynamic(String),
+}
+
+impl Callee {
+    pub fn name(&self) -> &str {
+        match self {
+            Callee::Function(name) | Callee::External(name) | Callee::Unresolved(name) | Callee::Dynamic(name) => name,
+        }
+    }
+
+    fn resolution(&self) -> &'static str {
+        match self {
+            Callee::Function(_) => "function",
+            Callee::External(_) => "external",
+            Callee::Unresolved(_) => "unresolved",
+            Callee::Dynamic(_) => "dynamic",
+        }
+    }
+}
+
+impl fmt::Display for Callee {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Callee::Function(name) => f.write_str(name),
+            _ => write!(f, "{} ({})", self.name(), self.resolution()),
+        }
+    }
+}
+
+/// A call, from the function, class or module it's made in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallSite {
+    pub caller: String,
+    pub callee: Callee,
+    pub location: SourceLocation,
+}
+
+/// A module, class or function that can make calls.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Definition {
+    pub kind: UnitKind,
+    pub location: SourceLocation,
+}
+
+/// The calls made across a set of modules.
+#[derive(Clone, Debug, Default)]
+pub struct CallGraph {
+    /// Every module, class and function in the set, by qualified name.
+    pub definitions: BTreeMap<String, Definition>,
+    /// Every call, in the order they appear in each module.
+    pub calls: Vec<CallSite>,
+}
+
+impl CallGraph {
+    /// Builds the graph of the calls made in `modules`, resolving those between them.
+    pub fn new(modules: &[Module]) -> Self {
+        let mut builder = Builder::default();
+        for module in modules {
+            builder.add_module(module);
+        }
+        for module in modules {
+            builder.walk_module(module);
+        }
+        CallGraph { definitions: builder.definitions, calls: builder.calls }
+    }
+
+    /// The calls made directly by `caller`.
+    pub fn calls_from<'a>(&'a self, caller: &'a str) -> impl Iterator<Item = &'a CallSite> + 'a {
+        self.calls.iter().filter(move |call| call.caller == caller)
+    }
+
+    /// The calls of the function `callee`.
+    pub fn calls_to<'a>(&'a self, callee: &'a str) -> impl Iterator<Item = &'a CallSite> + 'a {
+        self.calls.iter().filter(move |call| matches!(&call.callee, Callee::Function(name) if name == callee))
+    }
+
+    /// The definitions reachable from `roots` by following resolved calls, including the roots.
+    pub fn reachable(&self, roots: &[&str]) -> BTreeSet<String> {
+        self.closure(roots, |call| match &call.callee {
+            Callee::Function(callee) => Some((call.caller.as_str(), callee.as_str())),
+            _ => None,
+        })
+    }
+
+    /// The functions nothing reachable from `roots` calls, such as the modules run as scripts. These are
+    /// only dead if none of the dynamic calls can reach them either.
+    pub fn unreachable_functions(&self, roots: &[&str]) -> Vec<&str> {
+        let reachable = self.reachable(roots);
+        self.definitions
+            .iter()
+            .filter(|(name, definition)| definition.kind == UnitKind::Function && !reachable.contains(*name))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Everything that calls `name`, directly or through other calls, and so is affected by changing it.
+    pub fn impact(&self, name: &str) -> BTreeSet<String> {
+        // Starting from its direct callers, so it's only in there itself if it's recursive.
+        let direct: BTreeSet<&str> = self.calls_to(name).map(|call| call.caller.as_str()).collect();
+        self.closure(&direct.into_iter().collect::<Vec<_>>(), |call| match &call.callee {
+            Callee::Function(callee) => Some((callee.as_str(), call.caller.as_str())),
+            _ => None,
+        })
+    }
+
+    /// The names reached from `roots` along the edges `edge` picks out of the calls.
+    fn closure<'a>(&'a self, roots: &[&str], edge: impl Fn(&'a CallSite) -> Option<(&'a str, &'a str)>) -> BTreeSet<String> {
+        let mut edges: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (from, to) in self.calls.iter().filter_map(edge) {
+            edges.entry(from).or_default().push(to);
+        }
+        let mut seen: BTreeSet<String> = roots.iter().map(|root| root.to_string()).collect();
+        let mut queue: VecDeque<String> = seen.iter().cloned().collect();
+        while let Some(name) = queue.pop_front() {
+            for &next in edges.get(name.as_str()).into_iter().flatten() {
+                if seen.insert(next.to_string()) {
+                    queue.push_back(next.to_string());
+                }
+            }
+        }
+        seen
+    }
+
+    /// The graph in Graphviz's DOT language. Calls that didn't resolve to a definition go to dashed nodes.
+    pub fn to_dot(&self) -> String {
+        let quoted = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut dot = String::from("digraph calls {\n    node [shape=box];\n");
+        for (name, definition) in self.definitions.iter() {
+            let shape = match definition.kind {
+                UnitKind::Module => "folder",
+                UnitKind::Class => "component",
+                UnitKind::Function => "box",
+            };
+            dot.push_str(&format!("    {} [shape={}];\n", quoted(name), shape));
+        }
+        let mut edges = BTreeSet::new();
+        let mut outside = BTreeSet::new();
+        for call in self.calls.iter() {
+            if !matches!(call.callee, Callee::Function(_)) {
+                outside.insert(&call.callee);
+            }
+            edges.insert((call.caller.as_str(), &call.callee));
+        }
+        for callee in outside {
+            dot.push_str(&format!("    {} [style=dashed];\n", quoted(&callee.to_string())));
+        }
+        for (caller, callee) in edges {
+            dot.push_str(&format!("    {} -> {};\n", quoted(caller), quoted(&callee.to_string())));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The definitions and calls as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        let location = |location: &SourceLocation| json!({ "file": location.filename, "line": location.line, "column": location.column });
+        let definitions: Vec<Value> = self
+            .definitions
+            .iter()
+            .map(|(name, definition)| json!({ "name": name, "kind": definition.kind.to_string(), "location": location(&definition.location) }))
+            .collect();
+        let calls: Vec<Value> = self
+            .calls
+            .iter()
+            .map(|call| {
+                json!({
+                    "caller": call.caller,
+                    "callee": call.callee.name(),
+                    "resolution": call.callee.resolution(),
+                    "location": location(&call.location),
+                })
+            })
+            .collect();
+        serde_json::to_string_pretty(&json!({ "definitions": definitions, "calls": calls })).unwrap_or_default()
+    }
+}
+
+/// The dotted path of a module, from its filename, and the package its relative imports are relative to.
+fn module_path(module: &Module) -> (String, String) {
+    let stem = module.filename.as_deref().map(|f| f.strip_suffix(".py").unwrap_or(f));
+    let mut parts: Vec<&str> = stem.map_or_else(Vec::new, |s| s.split(['/', '\\']).filter(|p| !p.is_empty() && *p != ".").collect());
+    let is_package = parts.last() == Some(&"__init__");
+    if is_package {
+        parts.pop();
+    }
+    if parts.is_empty() {
+        let name = module.name.as_ref().map_or("<module>", |n| n.id.as_str());
+        return (name.to_string(), String::new());
+    }
+    let path = parts.join(".");
+    if !is_package {
+        parts.pop();
+    }
+    (path, parts.join("."))
+}
+
+/// What the builder knows about a module in the set.
+struct ModuleInfo {
+    package: String,
+    symbols: SymbolTableScopes,
+}
+
+/// The module a class is defined in, and the names of its bases there.
+struct ClassInfo {
+    module: String,
+    bases: Vec<String>,
+}
+
+/// A method's first parameter, which calls on find the methods of its class.
+#[derive(Clone)]
+struct Receiver {
+    class: String,
+    name: String,
+}
+
+/// The scopes around the code being walked. `prefixes` holds the qualified name of each scope in
+/// `symbols`, outermost first.
+#[derive(Clone)]
+struct Scope {
+    module: String,
+    symbols: SymbolTableScopes,
+    prefixes: Vec<String>,
+    receiver: Option<Receiver>,
+    in_class: bool,
+}
+
+impl Scope {
+    fn prefix(&self) -> &str {
+        self.prefixes.last().map_or("", String::as_str)
+    }
+}
+
+#[derive(Default)]
+struct Builder {
+    modules: BTreeMap<String, ModuleInfo>,
+    classes: HashMap<String, ClassInfo>,
+    definitions: BTreeMap<String, Definition>,
+    calls: Vec<CallSite>,
+}
+
+impl Builder {
+    fn add_module(&mut self, module: &Module) {
+        let (path, package) = module_path(module);
+        let filename = module.filename.as_deref().unwrap_or("<unknown>");
+        let location = SourceLocation::with_span(filename, Some(1), Some(1), None, None);
+        self.definitions.insert(path.clone(), Definition { kind: UnitKind::Module, location });
+        self.define(filename, &path, &path, &module.raw.body);
+        let mut symbols = SymbolTableScopes::new();
+        symbols.new_scope();
+        for statement in module.raw.body.iter() {
+            symbols = statement.clone().find_symbols(symbols);
+        }
+        self.modules.insert(path, ModuleInfo { package, symbols });
+    }
+
+    /// Records the classes and functions defined in `body`, which is inside `prefix`.
+    fn define(&mut self, filename: &str, module: &str, prefix: &str, body: &[Statement]) {
+        for statement in body {
+            match &statement.statement {
+                StatementType::FunctionDef(f) | StatementType::AsyncFunctionDef(f) => {
+                    let name = format!("{}.{}", prefix, f.name);
+                    let definition = Definition { kind: UnitKind::Function, location: locate(filename, statement) };
+                    self.definitions.insert(name.clone(), definition);
+                    self.define(filename, module, &name, &f.body);
+                }
+                StatementType::ClassDef(c) => {
+                    let name = format!("{}.{}", prefix, c.name);
+                    let definition = Definition { kind: UnitKind::Class, location: locate(filename, statement) };
+                    self.definitions.insert(name.clone(), definition);
+                    let bases = c.bases.iter().map(|base| base.id.clone()).collect();
+                    self.classes.insert(name.clone(), ClassInfo { module: module.to_string(), bases });
+                    self.define(filename, module, &name, &c.body);
+                }
+                _ => nested_bodies(statement).into_iter().for_each(|block| self.define(filename, module, prefix, block)),
+            }
+        }
+    }
+
+    fn walk_module(&mut self, module: &Module) {
+        let (path, _) = module_path(module);
+        let filename = module.filename.as_deref().unwrap_or("<unknown>");
+        let scope = self.module_scope(&path);
+        self.walk_unit(filename, &scope, &path, &module.raw.body);
+    }
+
+    fn module_scope(&self, module: &str) -> Scope {
+        Scope {
+            module: module.to_string(),
+            symbols: self.modules.get(module).map_or_else(SymbolTableScopes::new, |info| info.symbols.clone()),
+            prefixes: vec![module.to_string()],
+            receiver: None,
+            in_class: false,
+        }
+    }
+
+    /// Records the calls `caller` makes in `body`, then walks the definitions in it.
+    fn walk_unit(&mut self, filename: &str, scope: &Scope, caller: &str, body: &[Statement]) {
+        let mut sites = CallSites::default();
+        for statement in body {
+            sites.visit_stmt(statement);
+        }
+        let mut local = scope.clone();
+        for name in sites.bound {
+            if local.symbols.get_local(name).is_none() {
+                let value = ExprType::Name(crate::Name::new(name));
+                local.symbols.insert(name.to_string(), SymbolTableNode::Assign { position: 0, value });
+            }
+        }
+        for (call, statement) in sites.calls {
+            let callee = self.resolve(&local, &call.func);
+            let location = match leftmost_name(&call.func) {
+                Some((line, column)) => SourceLocation::with_span(filename, Some(line), Some(column + 1), None, None),
+                None => locate(filename, statement),
+            };
+            self.calls.push(CallSite { caller: caller.to_string(), callee, location });
+        }
+        self.walk_definitions(filename, scope, caller, body);
+    }
+
+    fn walk_definitions(&mut self, filename: &str, scope: &Scope, caller: &str, body: &[Statement]) {
+        for statement in body {
+            match &statement.statement {
+                StatementType::FunctionDef(f) | StatementType::AsyncFunctionDef(f) => {
+                    let name = format!("{}.{}", scope.prefix(), f.name);
+                    let args = &f.args;
+                    let parameters: Vec<&str> = args
+                        .posonlyargs
+                        .iter()
+                        .chain(&args.args)
+                        .chain(&args.vararg)
+                        .chain(&args.kwonlyargs)
+                        .chain(&args.kwarg)
+                        .map(|p| p.arg.as_str())
+                        .collect();
+                    let mut inner = scope.clone();
+                    // A method can't see the names in its class's body.
+                    if scope.in_class {
+                        inner.symbols.pop();
+                        inner.prefixes.pop();
+                    }
+                    inner.symbols.new_scope();
+                    for parameter in parameters.iter() {
+                        let value = ExprType::Name(crate::Name::new(*parameter));
+                        inner.symbols.insert(parameter.to_string(), SymbolTableNode::Assign { position: 0, value });
+                    }
+                    for statement in f.body.iter() {
+                        inner.symbols = statement.clone().find_symbols(inner.symbols);
+                    }
+                    inner.prefixes.push(name.clone());
+                    inner.in_class = false;
+                    let is_static = f.decorator_list.iter().any(|d| matches!(d, ExprType::Name(n) if n.id == "staticmethod"));
+                    inner.receiver = match (scope.in_class, args.posonlyargs.iter().chain(&args.args).next()) {
+                        (true, Some(first)) if !is_static => Some(Receiver { class: scope.prefix().to_string(), name: first.arg.clone() }),
+                        (true, _) => None,
+                        // A nested function can call methods on its method's receiver, unless it rebinds it.
+                        (false, _) => scope.receiver.clone().filter(|r| !parameters.contains(&r.name.as_str())),
+                    };
+                    self.walk_unit(filename, &inner, &name, &f.body);
+                }
+                StatementType::ClassDef(c) => {
+                    let name = format!("{}.{}", scope.prefix(), c.name);
+                    let mut inner = scope.clone();
+                    inner.symbols.new_scope();
+                    for statement in c.body.iter() {
+                        inner.symbols = statement.clone().find_symbols(inner.symbols);
+                    }
+                    inner.prefixes.push(name);
+                    inner.in_class = true;
+                    // The class body runs when the class is defined, as part of the enclosing code.
+                    self.walk_unit(filename, &inner, caller, &c.body);
+                }
+                _ => nested_bodies(statement).into_iter().for_each(|block| self.walk_definitions(filename, scope, caller, block)),
+            }
+        }
+    }
+
+    /// What calling `func` in `scope` calls.
+    fn resolve(&self, scope: &Scope, func: &ExprType) -> Callee {
+        let Some(chain) = dotted(func) else {
+            return Callee::Dynamic(describe(func));
+        };
+        if let (Some(receiver), [first, member]) = (&scope.receiver, chain.as_slice())
+            && *first == receiver.name
+        {
+            return match self.member(&receiver.class, member, 0) {
+                Some(method) => Callee::Function(method),
+                None => Callee::Dynamic(chain.join(".")),
+            };
+        }
+        match self.lookup(scope, &chain, 0) {
+            Some(callee) => self.constructor(callee),
+            None if builtins().contains(chain[0]) => Callee::External(format!("builtins.{}", chain.join("."))),
+            None => Callee::Unresolved(chain.join(".")),
+        }
+    }
+
+    /// Calling a class in the set calls its `__init__`, when it has one.
+    fn constructor(&self, callee: Callee) -> Callee {
+        match callee {
+            Callee::Function(name) if self.definitions.get(&name).is_some_and(|d| d.kind == UnitKind::Class) => {
+                Callee::Function(self.member(&name, "__init__", 0).unwrap_or(name))
+            }
+            callee => callee,
+        }
+    }
+
+    /// Resolves a dotted name through the longest prefix of it bound in `scope`, or `None` if none is.
+    fn lookup(&self, scope: &Scope, chain: &[&str], hops: usize) -> Option<Callee> {
+        let written = chain.join(".");
+        for split in (1..=chain.len()).rev() {
+            let key = chain[..split].join(".");
+            let Some((depth, node)) = scope.symbols.get_with_depth(&key) else {
+                continue;
+            };
+            let base = match node {
+                SymbolTableNode::FunctionDef(_) | SymbolTableNode::ClassDef(_) => {
+                    let prefix = scope.prefixes.iter().rev().nth(depth).map_or("", String::as_str);
+                    format!("{}.{}", prefix, key)
+                }
+                SymbolTableNode::Import(_) => key,
+                SymbolTableNode::Alias(target) => target.clone(),
+                SymbolTableNode::ImportFrom(import) => {
+                    let alias = import.names.iter().find(|a| a.asname.as_deref().unwrap_or(&a.name) == key)?;
+                    let module = self.absolute(&scope.module, import);
+                    if module.is_empty() { alias.name.clone() } else { format!("{}.{}", module, alias.name) }
+                }
+                _ => return Some(Callee::Dynamic(written)),
+            };
+            let qualified = std::iter::once(base.as_str()).chain(chain[split..].iter().copied()).collect::<Vec<_>>().join(".");
+            return Some(self.resolve_qualified(&qualified, hops));
+        }
+        None
+    }
+
+    /// Resolves a fully qualified name, following imports in the module it's in.
+    fn resolve_qualified(&self, qualified: &str, hops: usize) -> Callee {
+        if self.definitions.get(qualified).is_some_and(|d| d.kind != UnitKind::Module) {
+            return Callee::Function(qualified.to_string());
+        }
+        let module = self.modules.keys().filter(|m| qualified.starts_with(&format!("{}.", m))).max_by_key(|m| m.len());
+        let Some(module) = module else {
+            return match self.definitions.contains_key(qualified) {
+                // Calling a module fails.
+                true => Callee::Unresolved(qualified.to_string()),
+                false => Callee::External(qualified.to_string()),
+            };
+        };
+        if hops >= MAX_HOPS {
+            return Callee::Unresolved(qualified.to_string());
+        }
+        let rest: Vec<&str> = qualified[module.len() + 1..].split('.').collect();
+        let head = format!("{}.{}", module, rest[0]);
+        if self.definitions.contains_key(&head) {
+            // A method called on its class, like `Parser.parse`; anything else is an attribute of an object.
+            return match rest.as_slice() {
+                [_, member] => self.member(&head, member, 0).map(Callee::Function),
+                _ => None,
+            }
+            .unwrap_or_else(|| Callee::Dynamic(qualified.to_string()));
+        }
+        let scope = self.module_scope(module);
+        self.lookup(&scope, &rest, hops + 1).unwrap_or_else(|| Callee::Unresolved(qualified.to_string()))
+    }
+
+    /// The method `member` of `class`, or of the first of its bases in the set that has one.
+    fn member(&self, class: &str, member: &str, hops: usize) -> Option<String> {
+        let name = format!("{}.{}", class, member);
+        if self.definitions.get(&name).is_some_and(|d| d.kind == UnitKind::Function) {
+            return Some(name);
+        }
+        let info = self.classes.get(class)?;
+        if hops >= MAX_HOPS {
+            return None;
+        }
+        let scope = self.module_scope(&info.module);
+        info.bases.iter().find_map(|base| match self.lookup(&scope, &[base.as_str()], 0)? {
+            Callee::Function(base) => self.member(&base, member, hops + 1),
+            _ => None,
+        })
+    }
+
+    /// The module `import` imports from, made absolute when it's relative to the importing module's package.
+    fn absolute(&self, module: &str, import: &ImportFrom) -> String {
+        if import.level == 0 {
+            return import.module.clone();
+        }
+        let package = self.modules.get(module).map_or("", |info| info.package.as_str());
+        let mut parts: Vec<&str> = package.split('.').filter(|p| !p.is_empty()).collect();
+        for _ in 1..import.level {
+            parts.pop();
+        }
+        if !import.module.is_empty() {
+            parts.push(&import.module);
+        }
+        parts.join(".")
+    }
+}
+
+/// The parts of a dotted name like `os.path.join`, if that's what `expr` is.
+fn dotted(expr: &ExprType) -> Option<Vec<&str>> {
+    match expr {
+        ExprType::Name(name) => Some(vec![name.id.as_str()]),
+        ExprType::Attribute(attribute) => {
+            let mut chain = dotted(&attribute.value)?;
+            chain.push(&attribute.attr);
+            Some(chain)
+        }
+        _ => None,
+    }
+}
+
+/// A short description of a callee that isn't a dotted name, such as `handlers[]` or `make().run`.
+fn describe(expr: &ExprType) -> String {
+    match expr {
+        ExprType::Name(name) => name.id.clone(),
+        ExprType::Attribute(attribute) => format!("{}.{}", describe(&attribute.value), attribute.attr),
+        ExprType::Call(call) => format!("{}()", describe(&call.func)),
+        ExprType::Subscript(subscript) => format!("{}[]", describe(&subscript.value)),
+        ExprType::Lambda(_) => "lambda".to_string(),
+        _ => "<expression>".to_string(),
+    }
+}
+
+/// The line and 0-based column of the name a callee starts with, since calls don't carry positions.
+fn leftmost_name(expr: &ExprType) -> Option<(usize, usize)> {
+    match expr {
+        ExprType::Name(name) => Some((name.lineno?, name.col_offset?)),
+        ExprType::Attribute(attribute) => leftmost_name(&attribute.value),
+        ExprType::Call(call) => leftmost_name(&call.func),
+        ExprType::Subscript(subscript) => leftmost_name(&subscript.value),
+        _ => None,
+    }
+}
+
+/// The calls in a body, each with the innermost statement it's in, leaving out the bodies of nested
+/// functions and classes. Also collects the variables loops, comprehensions, `with`, `except`, lambdas and
+/// `:=` bind, which the symbol tables leave out.
+#[derive(Default)]
+struct CallSites<'a> {
+    calls: Vec<(&'a Call, &'a Statement)>,
+    statement: Option<&'a Statement>,
+    bound: Vec<&'a str>,
+}
+
+impl<'a> CallSites<'a> {
+    fn bind(&mut self, target: &'a ExprType) {
+        match target {
+            ExprType::Name(name) => self.bound.push(&name.id),
+            ExprType::Tuple(tuple) => tuple.elts.iter().for_each(|e| self.bind(e)),
+            ExprType::List(elts) => elts.iter().for_each(|e| self.bind(e)),
+            ExprType::Starred(starred) => self.bind(&starred.value),
+            _ => {}
+        }
+    }
+}
+
+impl<'a> Visitor<'a> for CallSites<'a> {
+    fn visit_stmt(&mut self, stmt: &'a Statement) {
+        let outer = self.statement.replace(stmt);
+        match &stmt.statement {
+            StatementType::FunctionDef(f) | StatementType::AsyncFunctionDef(f) => {
+                for decorator in &f.decorator_list {
+                    self.visit_expr(decorator);
+                }
+                self.visit_arguments(&f.args);
+            }
+            StatementType::ClassDef(c) => {
+                for decorator in &c.decorator_list {
+                    self.visit_expr(decorator);
+                }
+            }
+            StatementType::Call(call) => {
+                self.calls.push((call, stmt));
+                walk_stmt(self, stmt);
+            }
+            StatementType::For(f) => {
+                self.bind(&f.target);
+                walk_stmt(self, stmt);
+            }
+            StatementType::AsyncFor(f) => {
+                self.bind(&f.target);
+                walk_stmt(self, stmt);
+            }
+            _ => walk_stmt(self, stmt),
+        }
+        self.statement = outer;
+    }
+
+    fn visit_expr(&mut self, expr: &'a ExprType) {
+        match expr {
+            ExprType::Call(call) => self.calls.extend(self.statement.map(|statement| (call, statement))),
+            ExprType::NamedExpr(named) => self.bind(&named.target),
+            ExprType::Lambda(lambda) => {
+                let args = &lambda.args;
+                let parameters = args.posonlyargs.iter().chain(&args.args).chain(&args.vararg).chain(&args.kwonlyargs).chain(&args.kwarg);
+                self.bound.extend(parameters.map(|p| p.arg.as_str()));
+            }
+            _ => {}
+        }
+        walk_expr(self, expr);
+    }
+
+    fn visit_comprehension(&mut self, comprehension: &'a Comprehension) {
+        self.bind(&comprehension.target);
+        walk_comprehension(self, comprehension);
+    }
+
+    fn visit_except_handler(&mut self, handler: &'a ExceptHandler) {
+        self.bound.extend(handler.name.as_deref());
+        walk_except_handler(self, handler);
+    }
+
+    fn visit_with_item(&mut self, item: &'a WithItem) {
+        if let Some(vars) = &item.optional_vars {
+            self.bind(vars);
+        }
+        self.visit_expr(&item.context_expr);
+        if let Some(vars) = &item.optional_vars {
+            self.visit_expr(vars);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn graph(files: &[(&str, &str)]) -> CallGraph {
+        let modules: Vec<Module> = files.iter().map(|(filename, source)| parse(source, filename).unwrap()).collect();
+        CallGraph::new(&modules)
+    }
+
+    fn callees<'a>(graph: &'a CallGraph, caller: &'a str) -> Vec<&'a Callee> {
+        graph.calls_from(caller).map(|call| &call.callee).collect()
+    }
+
+    #[test]
+    fn test_functions_and_builtins() {
+        let graph = graph(&[(
+            "app.py",
+            "def helper():\n    return 1\n\ndef main():\n    print(helper())\n\nmain()\n",
+        )]);
+        assert_eq!(
+            callees(&graph, "app.main"),
+            vec![&Callee::External("builtins.print".into()), &Callee::Function("app.helper".into())]
+        );
+        assert_eq!(callees(&graph, "app"), vec![&Callee::Function("app.main".into())]);
+        let call = graph.calls_to("app.helper").next().unwrap();
+        assert_eq!((call.location.line, call.location.column), (Some(5), Some(11)));
+    }
+
+    #[test]
+    fn test_methods_through_self_and_bases() {
+        let graph = graph(&[(
+            "shapes.py",
+            "class Base:\n    def __init__(self):\n        self.setup()\n    def setup(self):\n        pass\n\n\
+             class Square(Base):\n    def area(self):\n        return self.side()\n    def side(self):\n        return 2\n\n\
+             s = Square()\n",
+        )]);
+        assert_eq!(callees(&graph, "shapes.Base.__init__"), vec![&Callee::Function("shapes.Base.setup".into())]);
+        assert_eq!(callees(&graph, "shapes.Square.area"), vec![&Callee::Function("shapes.Square.side".into())]);
+        // Square inherits its constructor.
+        assert_eq!(callees(&graph, "shapes"), vec![&Callee::Function("shapes.Base.__init__".into())]);
+    }
+
+    #[test]
+    fn test_imports_across_modules() {
+        let graph = graph(&[
+            ("pkg/__init__.py", "from . import util\nfrom .util import tidy as clean\n"),
+            ("pkg/util.py", "import os.path\n\ndef tidy(p):\n    return os.path.normpath(p)\n"),
+            ("main.py", "import pkg\nfrom pkg import util\nfrom pkg.util import tidy\n\ndef run():\n    pkg.clean('a')\n    util.tidy('b')\n    tidy('c')\n    pkg.util.tidy('d')\n"),
+        ]);
+        let tidy = Callee::Function("pkg.util.tidy".into());
+        assert_eq!(callees(&graph, "main.run"), vec![&tidy; 4]);
+        assert_eq!(callees(&graph, "pkg.util.tidy"), vec![&Callee::External("os.path.normpath".into())]);
+    }
+
+    #[test]
+    fn test_dynamic_and_unresolved_calls() {
+        let graph = graph(&[(
+            "m.py",
+            "import json\n\ndef f(callback, items):\n    callback()\n    items[0]()\n    missing()\n    json.loads('1')\n    \
+             return [g() for g in items]\n",
+        )]);
+        assert_eq!(
+            callees(&graph, "m.f"),
+            vec![
+                &Callee::Dynamic("callback".into()),
+                &Callee::Dynamic("items[]".into()),
+                &Callee::Unresolved("missing".into()),
+                &Callee::External("json.loads".into()),
+                &Callee::Dynamic("g".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dead_functions_and_impact() {
+        let graph = graph(&[(
+            "m.py",
+            "def a():\n    b()\n\ndef b():\n    c()\n\ndef c():\n    pass\n\ndef unused():\n    c()\n\na()\n",
+        )]);
+        assert_eq!(graph.unreachable_functions(&["m"]), vec!["m.unused"]);
+        let impact: Vec<String> = graph.impact("m.c").into_iter().collect();
+        assert_eq!(impact, vec!["m", "m.a", "m.b", "m.unused"]);
+        assert!(graph.to_dot().contains("\"m.a\" -> \"m.b\";"));
+    }
+}
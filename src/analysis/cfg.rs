@@ -0,0 +1,710 @@
+//! Control-flow graphs of module and function bodies.
+//!
+//! A [`Cfg`] splits a body into basic blocks, runs of statements that always execute together, joined by an
+//! edge for each way control can pass from one to another. A compound statement ends the block that runs its
+//! header, such as an `if` test or the next item of a `for`, and its branches start blocks of their own.
+//! `finally` bodies are copied onto each path that leaves through them, as CPython compiles them, so a
+//! `return` inside a `try` is followed through the `finally` to the exit.
+//!
+//! Exceptions are drawn only inside a `try`, from each block that can raise to the handlers or `finally`, and
+//! for an explicit `raise`. A `with` is entered and left in order; exceptions its context manager might
+//! suppress aren't followed. Definitions are statements like any other: a function's body has its own graph.
+
+use std::fmt::{self, Write};
+
+use super::names::always_true;
+use crate::{ExceptHandler, ExprType, FunctionDef, Module, Statement, StatementType, WithItem};
+
+/// The index of a block in [`Cfg::blocks`].
+pub type BlockId = usize;
+
+/// How control passes along an edge.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// Falling through to the next block, or back to the head of a loop.
+    Normal,
+    /// An `if` or `while` test holding, or a `for` loop taking another item.
+    True,
+    /// The test failing, or the loop running out of items.
+    False,
+    Break,
+    Continue,
+    Return,
+    /// An explicit `raise`, or an exception no `except` clause matched, passed on.
+    Raise,
+    /// An exception from inside a `try`, on its way to the handlers or `finally`.
+    Exception,
+}
+
+impl fmt::Display for EdgeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EdgeKind::Normal => "normal",
+            EdgeKind::True => "true",
+            EdgeKind::False => "false",
+            EdgeKind::Break => "break",
+            EdgeKind::Continue => "continue",
+            EdgeKind::Return => "return",
+            EdgeKind::Raise => "raise",
+            EdgeKind::Exception => "exception",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Edge {
+    pub from: BlockId,
+    pub to: BlockId,
+    pub kind: EdgeKind,
+}
+
+/// Statements that run one after another, without branching in between.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BasicBlock<'a> {
+    pub statements: Vec<&'a Statement>,
+    /// The `except` clause the block starts, if it's the start of a handler.
+    pub handler: Option<&'a ExceptHandler>,
+}
+
+/// The control-flow graph of a body of statements.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cfg<'a> {
+    /// The blocks, starting with the empty [`Cfg::ENTRY`] and [`Cfg::EXIT`].
+    pub blocks: Vec<BasicBlock<'a>>,
+    pub edges: Vec<Edge>,
+}
+
+impl<'a> Cfg<'a> {
+    /// Where control enters the body.
+    pub const ENTRY: BlockId = 0;
+    /// Where it leaves, by returning, raising, or running off the end.
+    pub const EXIT: BlockId = 1;
+
+    pub fn from_body(body: &'a [Statement]) -> Self {
+        let blocks = vec![BasicBlock::default(), BasicBlock::default()];
+        let mut builder = Builder { blocks, edges: Vec::new(), frames: Vec::new() };
+        let start = builder.follow(Self::ENTRY, EdgeKind::Normal);
+        if let Some(end) = builder.body(body, start) {
+            builder.edge(end, Self::EXIT, EdgeKind::Normal);
+        }
+        Cfg { blocks: builder.blocks, edges: builder.edges }
+    }
+
+    pub fn from_function(function: &'a FunctionDef) -> Self {
+        Self::from_body(&function.body)
+    }
+
+    pub fn from_module(module: &'a Module) -> Self {
+        Self::from_body(&module.raw.body)
+    }
+
+    pub fn successors(&self, block: BlockId) -> impl Iterator<Item = (BlockId, EdgeKind)> + '_ {
+        self.edges.iter().filter(move |e| e.from == block).map(|e| (e.to, e.kind))
+    }
+
+    pub fn predecessors(&self, block: BlockId) -> impl Iterator<Item = (BlockId, EdgeKind)> + '_ {
+        self.edges.iter().filter(move |e| e.to == block).map(|e| (e.from, e.kind))
+    }
+
+    /// The blocks reachable from the entry, in reverse postorder: each block comes before its successors,
+    /// except along the edges back to a loop's head.
+    pub fn reverse_postorder(&self) -> Vec<BlockId> {
+        let mut visited = vec![false; self.blocks.len()];
+        let mut order = Vec::new();
+        // Each entry is a block and how many of its successors have been visited.
+        let mut stack = vec![(Self::ENTRY, 0)];
+        visited[Self::ENTRY] = true;
+        while let Some((block, next)) = stack.pop() {
+            match self.successors(block).nth(next) {
+                Some((successor, _)) => {
+                    stack.push((block, next + 1));
+                    if !visited[successor] {
+                        visited[successor] = true;
+                        stack.push((successor, 0));
+                    }
+                }
+                None => order.push(block),
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    /// Which block dominates which: those every path from the entry passes through first.
+    pub fn dominators(&self) -> Dominators {
+        let order = self.reverse_postorder();
+        let mut position = vec![usize::MAX; self.blocks.len()];
+        for (index, &block) in order.iter().enumerate() {
+            position[block] = index;
+        }
+
+        // Cooper, Harvey and Kennedy's iterative algorithm, walking up the tree found so far to meet.
+        let mut idom: Vec<Option<BlockId>> = vec![None; self.blocks.len()];
+        idom[Self::ENTRY] = Some(Self::ENTRY);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in order.iter().skip(1) {
+                let mut found: Option<BlockId> = None;
+                for (predecessor, _) in self.predecessors(block).filter(|(p, _)| idom[*p].is_some()) {
+                    found = Some(match found {
+                        None => predecessor,
+                        Some(mut other) => {
+                            let mut finger = predecessor;
+                            while finger != other {
+                                while position[finger] > position[other] {
+                                    finger = idom[finger].unwrap_or(Self::ENTRY);
+                                }
+                                while position[other] > position[finger] {
+                                    other = idom[other].unwrap_or(Self::ENTRY);
+                                }
+                            }
+                            finger
+                        }
+                    });
+                }
+                if found.is_some() && idom[block] != found {
+                    idom[block] = found;
+                    changed = true;
+                }
+            }
+        }
+        idom[Self::ENTRY] = None;
+        Dominators { idom }
+    }
+
+    /// The graph in Graphviz's DOT language, named `name`. Blocks are labelled with their statements' lines and
+    /// headers, and edges other than [`EdgeKind::Normal`] with their kind.
+    pub fn to_dot(&self, name: &str) -> String {
+        let mut dot = format!("digraph {} {{\n    node [shape=box, fontname=\"monospace\"];\n", quoted(name));
+        for (id, block) in self.blocks.iter().enumerate() {
+            let connected = self.edges.iter().any(|e| e.from == id || e.to == id);
+            let label = match id {
+                Self::ENTRY => "entry".to_string(),
+                Self::EXIT => "exit".to_string(),
+                _ if !connected => continue,
+                // `\l` ends a left-aligned line.
+                _ => block_lines(block).iter().map(|line| format!("{}\\l", escape(line))).collect(),
+            };
+            let _ = writeln!(dot, "    b{} [label=\"{}\"];", id, label);
+        }
+        for edge in &self.edges {
+            let _ = match edge.kind {
+                EdgeKind::Normal => writeln!(dot, "    b{} -> b{};", edge.from, edge.to),
+                kind => writeln!(dot, "    b{} -> b{} [label=\"{}\"];", edge.from, edge.to, kind),
+            };
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// The immediate dominator of each block, from [`Cfg::dominators`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dominators {
+    idom: Vec<Option<BlockId>>,
+}
+
+impl Dominators {
+    /// The closest block that dominates `block`, other than itself. The entry and unreachable blocks have none.
+    pub fn immediate_dominator(&self, block: BlockId) -> Option<BlockId> {
+        self.idom.get(block).copied().flatten()
+    }
+
+    /// The blocks that dominate `block`, from itself up to the entry. Empty if it's unreachable.
+    pub fn dominators(&self, block: BlockId) -> Vec<BlockId> {
+        if block != Cfg::ENTRY && self.immediate_dominator(block).is_none() {
+            return Vec::new();
+        }
+        let mut chain = vec![block];
+        while let Some(next) = self.immediate_dominator(*chain.last().unwrap_or(&block)) {
+            chain.push(next);
+        }
+        chain
+    }
+
+    /// Whether every path from the entry to `block` passes through `dominator`. A block dominates itself.
+    pub fn dominates(&self, dominator: BlockId, block: BlockId) -> bool {
+        self.dominators(block).contains(&dominator)
+    }
+}
+
+/// The graphs of `module`'s body and of every function in it, with nested functions and methods named by
+/// their path, like `Class.method`.
+pub fn control_flow_graphs(module: &Module) -> Vec<(String, Cfg<'_>)> {
+    let name = module.name.as_ref().map_or("<module>", |n| n.id.as_str());
+    let mut graphs = vec![(name.to_string(), Cfg::from_module(module))];
+    functions("", &module.raw.body, &mut graphs);
+    graphs
+}
+
+fn functions<'a>(prefix: &str, body: &'a [Statement], graphs: &mut Vec<(String, Cfg<'a>)>) {
+    for statement in body {
+        match &statement.statement {
+            StatementType::FunctionDef(f) | StatementType::AsyncFunctionDef(f) => {
+                let name = format!("{}{}", prefix, f.name);
+                graphs.push((name.clone(), Cfg::from_function(f)));
+                functions(&format!("{}.", name), &f.body, graphs);
+            }
+            StatementType::ClassDef(c) => functions(&format!("{}{}.", prefix, c.name), &c.body, graphs),
+            _ => nested_bodies(statement).into_iter().for_each(|nested| functions(prefix, nested, graphs)),
+        }
+    }
+}
+
+/// The blocks of a compound statement other than a definition.
+pub(crate) fn nested_bodies(statement: &Statement) -> Vec<&[Statement]> {
+    match &statement.statement {
+        StatementType::If(i) => vec![&i.body, &i.orelse],
+        StatementType::While(w) => vec![&w.body, &w.orelse],
+        StatementType::For(f) => vec![&f.body, &f.orelse],
+        StatementType::AsyncFor(f) => vec![&f.body, &f.orelse],
+        StatementType::With(w) => vec![&w.body],
+        StatementType::AsyncWith(w) => vec![&w.body],
+        StatementType::Try(t) => {
+            let mut bodies: Vec<&[Statement]> = vec![&t.body];
+            bodies.extend(t.handlers.iter().map(|h| h.body.as_slice()));
+            bodies.extend([t.orelse.as_slice(), &t.finalbody]);
+            bodies
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", escape(text))
+}
+
+/// A block's statements, each with its line number.
+fn block_lines(block: &BasicBlock) -> Vec<String> {
+    let numbered = |lineno: Option<usize>, text: String| lineno.map_or(text.clone(), |l| format!("{}: {}", l, text));
+    let handler = block.handler.map(|h| numbered(h.lineno, except_header(h)));
+    handler.into_iter().chain(block.statements.iter().map(|s| numbered(s.lineno, header(s)))).collect()
+}
+
+fn source(expr: &ExprType) -> String {
+    expr.to_python_source().unwrap_or_else(|_| "...".to_string())
+}
+
+fn with_items(items: &[WithItem]) -> String {
+    let items: Vec<_> = items
+        .iter()
+        .map(|item| match &item.optional_vars {
+            Some(vars) => format!("{} as {}", source(&item.context_expr), source(vars)),
+            None => source(&item.context_expr),
+        })
+        .collect();
+    items.join(", ")
+}
+
+fn except_header(handler: &ExceptHandler) -> String {
+    match (&handler.exception_type, &handler.name) {
+        (Some(t), Some(name)) => format!("except {} as {}", source(t), name),
+        (Some(t), None) => format!("except {}", source(t)),
+        _ => "except".to_string(),
+    }
+}
+
+/// A statement's first line: the whole of a simple statement, or the header of a compound one.
+fn header(statement: &Statement) -> String {
+    match &statement.statement {
+        StatementType::If(i) => format!("if {}", source(&i.test)),
+        StatementType::While(w) => format!("while {}", source(&w.test)),
+        StatementType::For(f) => format!("for {} in {}", source(&f.target), source(&f.iter)),
+        StatementType::AsyncFor(f) => format!("async for {} in {}", source(&f.target), source(&f.iter)),
+        StatementType::With(w) => format!("with {}", with_items(&w.items)),
+        StatementType::AsyncWith(w) => format!("async with {}", with_items(&w.items)),
+        StatementType::Try(_) => "try".to_string(),
+        StatementType::FunctionDef(f) => format!("def {}", f.name),
+        StatementType::AsyncFunctionDef(f) => format!("async def {}", f.name),
+        StatementType::ClassDef(c) => format!("class {}", c.name),
+        StatementType::Unimplemented(source) => source.lines().next().unwrap_or_default().to_string(),
+        _ => statement.to_python_source().map_or_else(
+            |_| format!("{:?}", statement.statement).chars().take(40).collect(),
+            |s| s.lines().next().unwrap_or_default().to_string(),
+        ),
+    }
+}
+
+/// What a `return`, `raise`, `break` or `continue` leaves for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Jump {
+    Return,
+    Raise,
+    Break,
+    Continue,
+}
+
+/// The statements around the one being added that change where a jump goes.
+enum Frame<'a> {
+    Loop { head: BlockId, after: BlockId },
+    /// A `try` body, whose exceptions go to its handlers. `raising` is every block started inside it.
+    Handlers { dispatch: BlockId, raising: Vec<BlockId> },
+    /// A `try` with a `finally`, which runs on the way out of everything inside it.
+    Finally { body: &'a [Statement], raising: Vec<BlockId> },
+}
+
+struct Builder<'a> {
+    blocks: Vec<BasicBlock<'a>>,
+    edges: Vec<Edge>,
+    frames: Vec<Frame<'a>>,
+}
+
+impl<'a> Builder<'a> {
+    fn block(&mut self) -> BlockId {
+        self.blocks.push(BasicBlock::default());
+        let id = self.blocks.len() - 1;
+        // Whatever the block runs may raise, into the innermost `try` around it.
+        for frame in self.frames.iter_mut().rev() {
+            if let Frame::Handlers { raising, .. } | Frame::Finally { raising, .. } = frame {
+                raising.push(id);
+                break;
+            }
+        }
+        id
+    }
+
+    fn edge(&mut self, from: BlockId, to: BlockId, kind: EdgeKind) {
+        self.edges.push(Edge { from, to, kind });
+    }
+
+    /// A new block that `from` leads to.
+    fn follow(&mut self, from: BlockId, kind: EdgeKind) -> BlockId {
+        let block = self.block();
+        self.edge(from, block, kind);
+        block
+    }
+
+    /// A block where the paths that reach their ends meet, if any do.
+    fn join(&mut self, ends: &[(Option<BlockId>, EdgeKind)]) -> Option<BlockId> {
+        let ends: Vec<_> = ends.iter().filter_map(|&(end, kind)| Some((end?, kind))).collect();
+        if ends.is_empty() {
+            return None;
+        }
+        let join = self.block();
+        for (end, kind) in ends {
+            self.edge(end, join, kind);
+        }
+        Some(join)
+    }
+
+    /// A block to start a loop at: `current` if nothing has been put in it yet, so loops don't add empty blocks.
+    fn loop_head(&mut self, current: BlockId) -> BlockId {
+        let block = &self.blocks[current];
+        if current != Cfg::ENTRY && block.statements.is_empty() && block.handler.is_none() {
+            current
+        } else {
+            self.follow(current, EdgeKind::Normal)
+        }
+    }
+
+    /// `block`, if anything leads to it.
+    fn reached(&self, block: BlockId) -> Option<BlockId> {
+        self.edges.iter().any(|e| e.to == block).then_some(block)
+    }
+
+    /// Adds `body` from `current`, returning the block it ends in, or `None` if it never ends normally.
+    fn body(&mut self, body: &'a [Statement], mut current: BlockId) -> Option<BlockId> {
+        for statement in body {
+            current = self.statement(statement, current)?;
+        }
+        Some(current)
+    }
+
+    fn statement(&mut self, statement: &'a Statement, current: BlockId) -> Option<BlockId> {
+        match &statement.statement {
+            StatementType::While(w) => {
+                let head = self.loop_head(current);
+                self.blocks[head].statements.push(statement);
+                let forever = always_true(&w.test);
+                self.repeat(head, &w.body, &w.orelse, !forever)
+            }
+            StatementType::For(f) => {
+                let head = self.loop_head(current);
+                self.blocks[head].statements.push(statement);
+                self.repeat(head, &f.body, &f.orelse, true)
+            }
+            StatementType::AsyncFor(f) => {
+                let head = self.loop_head(current);
+                self.blocks[head].statements.push(statement);
+                self.repeat(head, &f.body, &f.orelse, true)
+            }
+            _ => {
+                self.blocks[current].statements.push(statement);
+                self.simple_or_branch(statement, current)
+            }
+        }
+    }
+
+    fn simple_or_branch(&mut self, statement: &'a Statement, current: BlockId) -> Option<BlockId> {
+        let jump = match &statement.statement {
+            StatementType::Return(_) => Jump::Return,
+            StatementType::Raise(_) => Jump::Raise,
+            StatementType::Break => Jump::Break,
+            StatementType::Continue => Jump::Continue,
+            StatementType::If(i) => {
+                let then = self.follow(current, EdgeKind::True);
+                let then_end = self.body(&i.body, then);
+                let else_end = if i.orelse.is_empty() {
+                    (Some(current), EdgeKind::False)
+                } else {
+                    let orelse = self.follow(current, EdgeKind::False);
+                    (self.body(&i.orelse, orelse), EdgeKind::Normal)
+                };
+                return self.join(&[(then_end, EdgeKind::Normal), else_end]);
+            }
+            StatementType::With(w) => return self.with(&w.body, current),
+            StatementType::AsyncWith(w) => return self.with(&w.body, current),
+            StatementType::Try(t) => return self.try_block(&t.body, &t.handlers, &t.orelse, &t.finalbody, current),
+            _ => return Some(current),
+        };
+        self.jump(current, jump);
+        None
+    }
+
+    /// The body and `else` of a loop whose header is in `head`. The `else` runs when the header finds the loop
+    /// is done, which an infinite `while` never does.
+    fn repeat(&mut self, head: BlockId, body: &'a [Statement], orelse: &'a [Statement], ends: bool) -> Option<BlockId> {
+        let after = self.block();
+        let start = self.follow(head, EdgeKind::True);
+        self.frames.push(Frame::Loop { head, after });
+        let end = self.body(body, start);
+        self.frames.pop();
+        if let Some(end) = end {
+            self.edge(end, head, EdgeKind::Normal);
+        }
+        if ends {
+            if orelse.is_empty() {
+                self.edge(head, after, EdgeKind::False);
+            } else {
+                let start = self.follow(head, EdgeKind::False);
+                if let Some(end) = self.body(orelse, start) {
+                    self.edge(end, after, EdgeKind::Normal);
+                }
+            }
+        }
+        self.reached(after)
+    }
+
+    fn with(&mut self, body: &'a [Statement], current: BlockId) -> Option<BlockId> {
+        let start = self.follow(current, EdgeKind::Normal);
+        let end = self.body(body, start);
+        self.join(&[(end, EdgeKind::Normal)])
+    }
+
+    fn try_block(
+        &mut self,
+        body: &'a [Statement],
+        handlers: &'a [ExceptHandler],
+        orelse: &'a [Statement],
+        finalbody: &'a [Statement],
+        current: BlockId,
+    ) -> Option<BlockId> {
+        if !finalbody.is_empty() {
+            self.frames.push(Frame::Finally { body: finalbody, raising: Vec::new() });
+        }
+        let dispatch = (!handlers.is_empty()).then(|| self.block());
+        if let Some(dispatch) = dispatch {
+            self.frames.push(Frame::Handlers { dispatch, raising: Vec::new() });
+        }
+
+        let start = self.follow(current, EdgeKind::Normal);
+        let mut end = self.body(body, start);
+        if let Some(dispatch) = dispatch
+            && let Some(Frame::Handlers { raising, .. }) = self.frames.pop()
+        {
+            self.raise_from(raising, dispatch);
+        }
+        if !orelse.is_empty()
+            && let Some(body_end) = end
+        {
+            let start = self.follow(body_end, EdgeKind::Normal);
+            end = self.body(orelse, start);
+        }
+
+        let mut ends = vec![(end, EdgeKind::Normal)];
+        if let Some(dispatch) = dispatch {
+            for handler in handlers {
+                let start = self.follow(dispatch, EdgeKind::Exception);
+                self.blocks[start].handler = Some(handler);
+                ends.push((self.body(&handler.body, start), EdgeKind::Normal));
+            }
+            // Without a bare `except`, an exception none of them match carries on outwards.
+            if handlers.iter().all(|h| h.exception_type.is_some()) {
+                self.jump(dispatch, Jump::Raise);
+            }
+        }
+
+        if finalbody.is_empty() {
+            return self.join(&ends);
+        }
+        let Some(Frame::Finally { raising, .. }) = self.frames.pop() else {
+            return self.join(&ends);
+        };
+        let end = self.join(&ends).and_then(|join| self.body(finalbody, join));
+        // One copy of the `finally` for every exception raised under it, which then carries on outwards.
+        let raising: Vec<_> = raising.into_iter().filter(|&b| !self.blocks[b].statements.is_empty()).collect();
+        if !raising.is_empty() {
+            let start = self.block();
+            self.raise_from(raising, start);
+            if let Some(finally_end) = self.body(finalbody, start) {
+                self.jump(finally_end, Jump::Raise);
+            }
+        }
+        end
+    }
+
+    /// Adds exception edges to `target` from the blocks that run statements.
+    fn raise_from(&mut self, raising: Vec<BlockId>, target: BlockId) {
+        for block in raising {
+            if !self.blocks[block].statements.is_empty() {
+                self.edge(block, target, EdgeKind::Exception);
+            }
+        }
+    }
+
+    /// Leaves `from` by `jump`, through the `finally` bodies on the way.
+    fn jump(&mut self, mut from: BlockId, jump: Jump) {
+        let kind = match jump {
+            Jump::Return => EdgeKind::Return,
+            Jump::Raise => EdgeKind::Raise,
+            Jump::Break => EdgeKind::Break,
+            Jump::Continue => EdgeKind::Continue,
+        };
+        let mut index = self.frames.len();
+        while index > 0 {
+            index -= 1;
+            match self.frames[index] {
+                Frame::Loop { after, .. } if jump == Jump::Break => return self.edge(from, after, kind),
+                Frame::Loop { head, .. } if jump == Jump::Continue => return self.edge(from, head, kind),
+                Frame::Handlers { dispatch, .. } if jump == Jump::Raise => return self.edge(from, dispatch, kind),
+                Frame::Finally { body, .. } => {
+                    // The copy runs outside the `try`, so set aside what's inside it while it's added.
+                    let inside = self.frames.split_off(index);
+                    let start = self.follow(from, kind);
+                    let end = self.body(body, start);
+                    self.frames.extend(inside);
+                    match end {
+                        Some(end) => from = end,
+                        None => return,
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.edge(from, Cfg::EXIT, kind);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(source: &str) -> Module {
+        crate::parse(source, "test.py").unwrap()
+    }
+
+    fn graph(module: &Module) -> Cfg<'_> {
+        match &module.raw.body[0].statement {
+            StatementType::FunctionDef(f) => Cfg::from_function(f),
+            _ => Cfg::from_module(module),
+        }
+    }
+
+    /// The blocks that hold a statement on `line`, in the order they were made.
+    fn blocks_at(cfg: &Cfg, line: usize) -> Vec<BlockId> {
+        (0..cfg.blocks.len()).filter(|&b| cfg.blocks[b].statements.iter().any(|s| s.lineno == Some(line))).collect()
+    }
+
+    fn at(cfg: &Cfg, line: usize) -> BlockId {
+        blocks_at(cfg, line)[0]
+    }
+
+    fn edge(cfg: &Cfg, from: BlockId, to: BlockId) -> Option<EdgeKind> {
+        cfg.successors(from).find(|&(b, _)| b == to).map(|(_, kind)| kind)
+    }
+
+    #[test]
+    fn test_if_else() {
+        let module = function("def f(x):\n    y = 1\n    if x:\n        y = 2\n    else:\n        return y\n    print(y)\n");
+        let cfg = graph(&module);
+        let (test, then, orelse, after) = (at(&cfg, 2), at(&cfg, 4), at(&cfg, 6), at(&cfg, 7));
+        assert_eq!(test, at(&cfg, 3));
+        assert_eq!(edge(&cfg, test, then), Some(EdgeKind::True));
+        assert_eq!(edge(&cfg, test, orelse), Some(EdgeKind::False));
+        assert_eq!(edge(&cfg, orelse, Cfg::EXIT), Some(EdgeKind::Return));
+        assert_eq!(cfg.predecessors(after).collect::<Vec<_>>(), [(then, EdgeKind::Normal)]);
+        assert_eq!(edge(&cfg, after, Cfg::EXIT), Some(EdgeKind::Normal));
+
+        let dominators = cfg.dominators();
+        assert_eq!(dominators.immediate_dominator(after), Some(then));
+        assert_eq!(dominators.immediate_dominator(Cfg::EXIT), Some(test));
+        assert!(dominators.dominates(test, after) && !dominators.dominates(orelse, after));
+        assert_eq!(dominators.dominators(then), [then, test, Cfg::ENTRY]);
+    }
+
+    #[test]
+    fn test_loops() {
+        let source = "def f(xs):\n    for x in xs:\n        if x:\n            continue\n        if x > 1:\n            \
+                      break\n    else:\n        print('done')\n    while True:\n        pass\n";
+        let module = function(source);
+        let cfg = graph(&module);
+        let (head, orelse, forever) = (at(&cfg, 2), at(&cfg, 8), at(&cfg, 9));
+        assert_eq!(edge(&cfg, head, at(&cfg, 3)), Some(EdgeKind::True));
+        assert_eq!(edge(&cfg, head, orelse), Some(EdgeKind::False));
+        assert_eq!(edge(&cfg, at(&cfg, 4), head), Some(EdgeKind::Continue));
+        // `break` skips the `else`, meeting it after the loop.
+        assert_eq!(edge(&cfg, at(&cfg, 6), forever), Some(EdgeKind::Break));
+        assert_eq!(edge(&cfg, orelse, forever), Some(EdgeKind::Normal));
+        // `while True` never ends, so nothing reaches the exit.
+        assert_eq!(edge(&cfg, forever, at(&cfg, 10)), Some(EdgeKind::True));
+        assert_eq!(edge(&cfg, at(&cfg, 10), forever), Some(EdgeKind::Normal));
+        assert_eq!(cfg.predecessors(Cfg::EXIT).count(), 0);
+        assert!(cfg.dominators().dominates(head, forever));
+    }
+
+    #[test]
+    fn test_try() {
+        let source = "def f():\n    try:\n        x = g()\n    except ValueError:\n        return 0\n    else:\n        \
+                      x += 1\n    finally:\n        close()\n    return x\n";
+        let module = function(source);
+        let cfg = graph(&module);
+        let (body, handler) = (at(&cfg, 3), at(&cfg, 5));
+        assert!(cfg.blocks[handler].handler.is_some());
+        let dispatch = cfg.successors(body).find(|&(_, kind)| kind == EdgeKind::Exception).map(|(b, _)| b).unwrap();
+        assert_eq!(edge(&cfg, dispatch, handler), Some(EdgeKind::Exception));
+        assert_eq!(edge(&cfg, body, at(&cfg, 7)), Some(EdgeKind::Normal));
+
+        // The `finally` is copied onto each way out: the handler's `return`, an exception no handler matches,
+        // falling through, and an exception from the handler or the `else`.
+        let finally = blocks_at(&cfg, 9);
+        assert_eq!(finally.len(), 4, "{}", cfg.to_dot("f"));
+        assert_eq!(edge(&cfg, handler, finally[0]), Some(EdgeKind::Return));
+        assert_eq!(edge(&cfg, finally[0], Cfg::EXIT), Some(EdgeKind::Return));
+        assert_eq!(edge(&cfg, dispatch, finally[1]), Some(EdgeKind::Raise));
+        assert_eq!(edge(&cfg, finally[2], at(&cfg, 10)), None, "`return x` follows on in the same block");
+        assert_eq!(finally[2], at(&cfg, 10));
+        assert_eq!(edge(&cfg, at(&cfg, 7), finally[3]), Some(EdgeKind::Exception));
+        assert_eq!(edge(&cfg, handler, finally[3]), Some(EdgeKind::Exception));
+        assert_eq!(edge(&cfg, finally[3], Cfg::EXIT), Some(EdgeKind::Raise));
+        assert_eq!(edge(&cfg, finally[2], Cfg::EXIT), Some(EdgeKind::Return));
+    }
+
+    #[test]
+    fn test_dot() {
+        let module = function("if x:\n    y = 1\n");
+        let dot = graph(&module).to_dot("test");
+        assert!(dot.starts_with("digraph \"test\" {\n"), "{}", dot);
+        assert!(dot.contains("b2 [label=\"1: if x\\l\"];"), "{}", dot);
+        assert!(dot.contains("b2 -> b3 [label=\"true\"];"), "{}", dot);
+        assert!(dot.contains("b2 -> b4 [label=\"false\"];"), "{}", dot);
+
+        let module = function("class C:\n    def m(self):\n        def inner():\n            pass\n");
+        let names: Vec<_> = control_flow_graphs(&module).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["test", "C.m", "C.m.inner"]);
+    }
+}
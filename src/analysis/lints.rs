@@ -0,0 +1,485 @@
+//! Lints for code that does nothing: imports and variables nothing reads, parameters a function ignores, and
+//! statements that can't run.
+//!
+//! The rules share [`check_names`](crate::check_names)'s scopes, so a read counts for the binding Python would
+//! find for it. [`LintOptions`] chooses the rules and the severity each reports at, and a `# noqa` comment
+//! silences what's reported on its line: everything, or with `# noqa: unused-import, unused-variable` just
+//! those codes.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    str::FromStr,
+    sync::LazyLock,
+};
+
+use litrs::Literal;
+use regex::Regex;
+
+use super::names::{BindingKind, MODULE, ScopeKind, Scopes, always_true, key};
+use crate::{
+    Constant, Diagnostic, ExprType, FunctionDef, Module, Severity, SourceLocation, Statement, StatementType, locate,
+    visit::{Visitor, walk_stmt},
+};
+
+/// A lint rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Rule {
+    /// An imported name that nothing reads.
+    UnusedImport,
+    /// A local variable that's assigned but never read.
+    UnusedVariable,
+    /// A function parameter the body never reads.
+    UnusedParameter,
+    /// A statement after one that always leaves the block, such as `return`.
+    UnreachableCode,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 4] = [Rule::UnusedImport, Rule::UnusedVariable, Rule::UnusedParameter, Rule::UnreachableCode];
+
+    /// The rule's name in diagnostics, options and `# noqa` comments.
+    pub fn code(self) -> &'static str {
+        match self {
+            Rule::UnusedImport => "unused-import",
+            Rule::UnusedVariable => "unused-variable",
+            Rule::UnusedParameter => "unused-parameter",
+            Rule::UnreachableCode => "unreachable-code",
+        }
+    }
+
+    /// The severity the rule reports at unless the options say otherwise.
+    pub fn default_severity(self) -> Severity {
+        match self {
+            Rule::UnusedParameter => Severity::Info,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Rule::ALL.into_iter().find(|rule| rule.code() == s).ok_or_else(|| {
+            let codes: Vec<_> = Rule::ALL.iter().map(|rule| rule.code()).collect();
+            format!("unknown lint rule `{}`; expected one of {}", s, codes.join(", "))
+        })
+    }
+}
+
+/// Which rules run, and how serious the problems each finds are.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LintOptions {
+    /// The rules that run, with the severity each reports at.
+    pub rules: BTreeMap<Rule, Severity>,
+}
+
+impl Default for LintOptions {
+    /// Every rule but `unused-parameter`, at its default severity. Methods and callbacks often have to accept
+    /// parameters they don't need, so that rule is opt-in.
+    fn default() -> Self {
+        let rules = Rule::ALL.into_iter().filter(|rule| *rule != Rule::UnusedParameter);
+        Self { rules: rules.map(|rule| (rule, rule.default_severity())).collect() }
+    }
+}
+
+impl LintOptions {
+    /// Runs `rule`, at `severity` or else its default.
+    pub fn enable(mut self, rule: Rule, severity: Option<Severity>) -> Self {
+        self.rules.insert(rule, severity.unwrap_or(rule.default_severity()));
+        self
+    }
+
+    pub fn disable(mut self, rule: Rule) -> Self {
+        self.rules.remove(&rule);
+        self
+    }
+}
+
+/// A problem a lint rule found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lint {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub message: String,
+    pub location: SourceLocation,
+}
+
+impl Lint {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic {
+            code: self.rule.code(),
+            severity: self.severity,
+            message: self.message.clone(),
+            location: self.location.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_diagnostic().fmt(f)
+    }
+}
+
+/// Runs the rules `options` enables over `module`, returning what they find in source order, less what
+/// `# noqa` comments suppress.
+pub fn lint(module: &Module, options: &LintOptions) -> Vec<Lint> {
+    let filename = module.filename.as_deref().unwrap_or("<unknown>");
+    let scopes = Scopes::collect(filename, &module.raw.body);
+    let mut found = unused(module, &scopes);
+    unreachable(filename, &module.raw.body, &mut found);
+
+    let suppressions = Suppressions::from_module(module);
+    let mut lints: Vec<Lint> = found
+        .into_iter()
+        .filter_map(|(rule, message, location)| {
+            let severity = *options.rules.get(&rule)?;
+            Some(Lint { rule, severity, message, location })
+        })
+        .filter(|lint| !suppressions.suppresses(&lint.to_diagnostic()))
+        .collect();
+    lints.sort_by_key(|lint| (lint.location.line, lint.location.column, lint.rule));
+    lints
+}
+
+/// The `# noqa` comments in a module, by line. A comment without codes suppresses everything on its line.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Suppressions(HashMap<usize, Option<HashSet<String>>>);
+
+static NOQA: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(?:^|#)\s*noqa\b(?:\s*:\s*([\w-]+(?:[\s,]+[\w-]+)*))?").unwrap());
+
+impl Suppressions {
+    pub fn from_module(module: &Module) -> Self {
+        let mut comments = Comments(module.comments.iter().collect());
+        module.raw.body.iter().for_each(|statement| comments.visit_stmt(statement));
+
+        let mut suppressions = Suppressions::default();
+        for comment in comments.0 {
+            if let Some(captures) = NOQA.captures(&comment.text) {
+                let codes = captures.get(1).map(|codes| {
+                    codes.as_str().split(|c: char| c == ',' || c.is_whitespace()).filter(|c| !c.is_empty()).map(String::from).collect()
+                });
+                suppressions.0.insert(comment.lineno, codes);
+            }
+        }
+        suppressions
+    }
+
+    /// Whether a `# noqa` comment on the line `diagnostic` is reported at covers it.
+    pub fn suppresses(&self, diagnostic: &Diagnostic) -> bool {
+        match diagnostic.location.line.and_then(|line| self.0.get(&line)) {
+            Some(None) => true,
+            Some(Some(codes)) => codes.contains(diagnostic.code),
+            None => false,
+        }
+    }
+}
+
+/// Every comment attached to the statements of a module.
+struct Comments<'a>(Vec<&'a crate::Comment>);
+
+impl<'a> Visitor<'a> for Comments<'a> {
+    fn visit_stmt(&mut self, statement: &'a Statement) {
+        self.0.extend(statement.trivia.leading.iter().chain(&statement.trivia.trailing));
+        walk_stmt(self, statement);
+    }
+}
+
+type Found = (Rule, String, SourceLocation);
+
+/// The imports, variables and parameters that no read finds.
+fn unused(module: &Module, scopes: &Scopes) -> Vec<Found> {
+    let mut used: HashSet<(usize, &str)> = HashSet::new();
+    for (index, scope) in scopes.scopes.iter().enumerate() {
+        for name in &scope.reads {
+            if let Some(owner) = scopes.resolve(index, name) {
+                used.insert((owner, name));
+            }
+        }
+    }
+    let exports = exports(module);
+    used.extend(exports.iter().map(|name| (MODULE, name.as_str())));
+
+    // A package's `__init__.py` imports names so they can be imported from the package.
+    let package = module.filename.as_deref().is_some_and(|f| f.ends_with("__init__.py"));
+    let mut found = Vec::new();
+    for (index, scope) in scopes.scopes.iter().enumerate() {
+        // A function that calls `locals()` may read any of its variables through it.
+        let reads_locals = scope.reads.contains("locals") && scopes.resolve(index, "locals").is_none();
+        for binding in &scope.definitions {
+            if used.contains(&(index, binding.name.as_str())) {
+                continue;
+            }
+            let name = &binding.name;
+            let exported = (package && index == MODULE) || dunder(name);
+            let lint = match binding.kind {
+                BindingKind::Import if !exported => {
+                    (Rule::UnusedImport, format!("`{}` is imported but never used", name))
+                }
+                BindingKind::Variable if scope.kind == ScopeKind::Function && !name.starts_with('_') && !reads_locals => {
+                    (Rule::UnusedVariable, format!("local variable `{}` is assigned but never read", name))
+                }
+                _ => continue,
+            };
+            found.push((lint.0, lint.1, binding.location.clone()));
+        }
+    }
+
+    let mut parameters = UnusedParameters { scopes, used: &used, found: &mut found };
+    module.raw.body.iter().for_each(|statement| parameters.visit_stmt(statement));
+    found
+}
+
+/// The names a module lists in `__all__`, which other modules may import from it.
+fn exports(module: &Module) -> Vec<String> {
+    let mut names = Vec::new();
+    for statement in &module.raw.body {
+        let (target, value) = match &statement.statement {
+            StatementType::Assign(a) if a.targets.len() == 1 => (&a.targets[0], &a.value),
+            StatementType::AugAssign(a) => (&a.target, &a.value),
+            _ => continue,
+        };
+        let elements = match value {
+            ExprType::List(elements) => elements,
+            ExprType::Tuple(t) => &t.elts,
+            _ => continue,
+        };
+        if matches!(target, ExprType::Name(n) if n.id == "__all__") {
+            for element in elements {
+                if let ExprType::Constant(Constant(Some(Literal::String(s)))) = element {
+                    names.push(s.value().to_string());
+                }
+            }
+        }
+    }
+    names
+}
+
+/// Finds the parameters of each `def` that its body never reads.
+struct UnusedParameters<'s, 'u> {
+    scopes: &'s Scopes<'s>,
+    used: &'u HashSet<(usize, &'s str)>,
+    found: &'u mut Vec<Found>,
+}
+
+impl<'a> Visitor<'a> for UnusedParameters<'_, '_> {
+    fn visit_stmt(&mut self, statement: &'a Statement) {
+        if let StatementType::FunctionDef(f) | StatementType::AsyncFunctionDef(f) = &statement.statement
+            && let Some(&function) = self.scopes.statements.get(&key(statement))
+            && !exempt(f)
+        {
+            let scope = &self.scopes.scopes[function];
+            let method = scope.parent.is_some_and(|parent| self.scopes.scopes[parent].kind == ScopeKind::Class);
+            // The instance or class a method is called on is passed whether it's needed or not.
+            let receiver = f.args.posonlyargs.first().or(f.args.args.first()).filter(|_| method && !decorated(f, "staticmethod"));
+            for binding in scope.definitions.iter().filter(|b| b.kind == BindingKind::Parameter) {
+                let name = binding.name.as_str();
+                if name.starts_with('_') || receiver.is_some_and(|r| r.arg == name) || self.used.contains(&(function, name)) {
+                    continue;
+                }
+                self.found.push((Rule::UnusedParameter, format!("parameter `{}` is never used", name), binding.location.clone()));
+            }
+        }
+        walk_stmt(self, statement);
+    }
+}
+
+/// Whether a function's signature is fixed by something else, so its parameters needn't all be used: a stub
+/// whose body only documents or raises, an overload or abstract method, or a special method like `__exit__`.
+fn exempt(f: &FunctionDef) -> bool {
+    let stub = f.body.iter().all(|s| {
+        matches!(&s.statement, StatementType::Pass | StatementType::Raise(_))
+            || matches!(&s.statement, StatementType::Expr(e) if matches!(e.value, ExprType::Constant(_)))
+    });
+    stub || dunder(&f.name) || ["overload", "abstractmethod", "override"].iter().any(|d| decorated(f, d))
+}
+
+/// Whether `name` is a special name like `__doc__`, which is imported or defined for Python itself to use.
+fn dunder(name: &str) -> bool {
+    name.len() > 4 && name.starts_with("__") && name.ends_with("__")
+}
+
+/// Whether `f` has a decorator called `name`, however it's imported.
+fn decorated(f: &FunctionDef, name: &str) -> bool {
+    f.decorator_list.iter().any(|decorator| {
+        let decorator = match decorator {
+            ExprType::Call(call) => call.func.as_ref(),
+            other => other,
+        };
+        match decorator {
+            ExprType::Name(n) => n.id == name,
+            ExprType::Attribute(a) => a.attr == name,
+            _ => false,
+        }
+    })
+}
+
+/// Reports the first statement after one that always leaves its block, in `body` and every block in it.
+fn unreachable(filename: &str, body: &[Statement], found: &mut Vec<Found>) {
+    for (index, statement) in body.iter().enumerate() {
+        for nested in bodies(statement) {
+            unreachable(filename, nested, found);
+        }
+        if let Some(cause) = leaves(statement) {
+            if let Some(next) = body.get(index + 1) {
+                let line = statement.lineno.map_or_else(String::new, |line| format!(" on line {}", line));
+                found.push((Rule::UnreachableCode, format!("unreachable code after {}{}", cause, line), locate(filename, next)));
+            }
+            return;
+        }
+    }
+}
+
+/// The blocks directly inside a statement.
+fn bodies(statement: &Statement) -> Vec<&[Statement]> {
+    match &statement.statement {
+        StatementType::FunctionDef(f) | StatementType::AsyncFunctionDef(f) => vec![&f.body],
+        StatementType::ClassDef(c) => vec![&c.body],
+        StatementType::If(i) => vec![&i.body, &i.orelse],
+        StatementType::While(w) => vec![&w.body, &w.orelse],
+        StatementType::For(f) => vec![&f.body, &f.orelse],
+        StatementType::AsyncFor(f) => vec![&f.body, &f.orelse],
+        StatementType::With(w) => vec![&w.body],
+        StatementType::AsyncWith(w) => vec![&w.body],
+        StatementType::Try(t) => {
+            let mut bodies: Vec<&[Statement]> = vec![&t.body];
+            bodies.extend(t.handlers.iter().map(|h| h.body.as_slice()));
+            bodies.extend([t.orelse.as_slice(), &t.finalbody]);
+            bodies
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// If `statement` never lets the block carry on after it, what it is.
+fn leaves(statement: &Statement) -> Option<&'static str> {
+    let ends = |body: &[Statement]| body.iter().any(|s| leaves(s).is_some());
+    match &statement.statement {
+        StatementType::Return(_) => Some("`return`"),
+        StatementType::Raise(_) => Some("`raise`"),
+        StatementType::Break => Some("`break`"),
+        StatementType::Continue => Some("`continue`"),
+        StatementType::If(i) if !i.orelse.is_empty() && ends(&i.body) && ends(&i.orelse) => {
+            Some("an `if` that leaves the block on every branch")
+        }
+        StatementType::Try(t)
+            if ends(&t.finalbody) || ((ends(&t.body) || ends(&t.orelse)) && t.handlers.iter().all(|h| ends(&h.body))) =>
+        {
+            Some("a `try` that leaves the block on every path")
+        }
+        StatementType::While(w) if always_true(&w.test) && !breaks(&w.body) => Some("a `while` loop that never ends"),
+        _ => None,
+    }
+}
+
+/// Whether a `break` in `body` leaves the loop it belongs to, rather than one nested in it.
+fn breaks(body: &[Statement]) -> bool {
+    body.iter().any(|statement| match &statement.statement {
+        StatementType::Break => true,
+        StatementType::If(i) => breaks(&i.body) || breaks(&i.orelse),
+        StatementType::With(w) => breaks(&w.body),
+        StatementType::AsyncWith(w) => breaks(&w.body),
+        StatementType::Try(t) => {
+            breaks(&t.body) || t.handlers.iter().any(|h| breaks(&h.body)) || breaks(&t.orelse) || breaks(&t.finalbody)
+        }
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lints(source: &str, options: &LintOptions) -> Vec<(Rule, Option<usize>, String)> {
+        let module = crate::parse(source, "test.py").unwrap();
+        lint(&module, options).into_iter().map(|l| (l.rule, l.location.line, l.message)).collect()
+    }
+
+    fn rules(source: &str) -> Vec<(Rule, Option<usize>)> {
+        lints(source, &LintOptions::default().enable(Rule::UnusedParameter, None)).into_iter().map(|(r, l, _)| (r, l)).collect()
+    }
+
+    #[test]
+    fn test_unused_imports() {
+        let source = "import os\nimport os.path\nimport sys\nfrom json import dumps, loads\nfrom typing import Any as Any\n\
+                      def f():\n    import re\n    return sys.argv, loads\n";
+        assert_eq!(
+            lints(source, &LintOptions::default()),
+            [
+                (Rule::UnusedImport, Some(1), "`os` is imported but never used".to_string()),
+                (Rule::UnusedImport, Some(2), "`os` is imported but never used".to_string()),
+                (Rule::UnusedImport, Some(4), "`dumps` is imported but never used".to_string()),
+                (Rule::UnusedImport, Some(7), "`re` is imported but never used".to_string()),
+            ]
+        );
+        // Names listed in `__all__` are exported, and a package's imports are its interface.
+        assert!(rules("from a import b\n__all__ = ['b']\n").is_empty());
+        let module = crate::parse("from a import b\n", "__init__.py").unwrap();
+        assert!(lint(&module, &LintOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_unused_variables() {
+        let source = "total = 0\ndef f(items):\n    count = 0\n    _ignored = 1\n    a, b = items\n    try:\n        \
+                      pass\n    except ValueError as e:\n        pass\n    def g():\n        return items\n    return g\n";
+        assert_eq!(rules(source), [(Rule::UnusedVariable, Some(3)), (Rule::UnusedVariable, Some(8))]);
+        // Reads from a nested function, through `locals()`, or an augmented assignment count.
+        assert!(rules("def f():\n    x = 1\n    def g():\n        return x\n    return g\n").is_empty());
+        assert!(rules("def f():\n    x = 1\n    return locals()\n").is_empty());
+        assert!(rules("def f():\n    n = 0\n    n += 1\n").is_empty());
+    }
+
+    #[test]
+    fn test_unused_parameters() {
+        let source = "class C:\n    def m(self, used, unused, _skip):\n        return used\n    \
+                      @staticmethod\n    def s(x):\n        pass\n    def __exit__(self, kind, value, tb):\n        \
+                      return False\n    @staticmethod\n    def t(y):\n        return 1\n";
+        assert_eq!(
+            lints(source, &LintOptions::default().enable(Rule::UnusedParameter, Some(Severity::Warning))),
+            [
+                (Rule::UnusedParameter, Some(2), "parameter `unused` is never used".to_string()),
+                (Rule::UnusedParameter, Some(10), "parameter `y` is never used".to_string()),
+            ]
+        );
+        assert!(lints(source, &LintOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_unreachable_code() {
+        let source = "def f(x):\n    while True:\n        if x:\n            break\n        else:\n            continue\n        \
+                      x += 1\n    if x:\n        return 1\n    else:\n        raise ValueError(x)\n    print(x)\n    print(x)\n";
+        assert_eq!(
+            lints(source, &LintOptions::default()),
+            [
+                (Rule::UnreachableCode, Some(7), "unreachable code after an `if` that leaves the block on every branch on line 3".to_string()),
+                (Rule::UnreachableCode, Some(12), "unreachable code after an `if` that leaves the block on every branch on line 8".to_string()),
+            ]
+        );
+        assert_eq!(rules("def f():\n    while True:\n        pass\n    return 1\n"), [(Rule::UnreachableCode, Some(4))]);
+        assert!(rules("def f():\n    while True:\n        if g():\n            break\n    return 1\n").is_empty());
+    }
+
+    #[test]
+    fn test_options_and_suppression() {
+        let source = "import os  # noqa\nimport re  # noqa: unused-variable\nimport sys  # NOQA:unused-import\n";
+        assert_eq!(rules(source), [(Rule::UnusedImport, Some(2))]);
+
+        let options = LintOptions::default().disable(Rule::UnusedImport).enable(Rule::UnreachableCode, Some(Severity::Error));
+        let module = crate::parse("import os\ndef f():\n    return\n    pass\n", "test.py").unwrap();
+        let found = lint(&module, &options);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].to_string(), "test.py:4:5-9: error[unreachable-code]: unreachable code after `return` on line 3");
+
+        assert_eq!("unused-import".parse::<Rule>(), Ok(Rule::UnusedImport));
+        assert!("unused".parse::<Rule>().is_err());
+    }
+}
@@ -0,0 +1,238 @@
+//! Finds what is at a position in a module, for editor features such as hover, go-to-definition and expanding a
+//! selection.
+//!
+//! Positions use Python's conventions, as [`Span`] does: lines count from 1 and columns are byte offsets from 0.
+//! The lookup works from the positions the parser already extracted, so it can only be as precise as they are:
+//! calls, attributes and the other expressions without positions of their own cover just their children, and
+//! constants can't be found at all.
+
+use std::collections::BTreeMap;
+
+use crate::{Arena, ExprType, Module, NodeId, NodeRef, SourceLocation, Span, locate};
+
+use super::names::{MODULE, ScopeKind, Scopes, key};
+
+/// A scope a position is in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScopeAt {
+    pub kind: ScopeKind,
+    /// The module, `def`, `class`, lambda or comprehension that opens the scope.
+    pub node: NodeId,
+    /// The names bound in the scope, with where each is first bound.
+    pub bindings: BTreeMap<String, SourceLocation>,
+}
+
+/// What is at a position in a module.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeAt {
+    /// The innermost node covering the position.
+    pub node: NodeId,
+    /// The nodes around it, innermost first and ending with the module. Their spans give the ranges to grow a
+    /// selection through.
+    pub enclosing: Vec<NodeId>,
+    /// The scopes the position is in, innermost first and ending with the module. A name read there is looked up
+    /// in these in order, except that a class's names are only visible directly in its body.
+    pub scopes: Vec<ScopeAt>,
+}
+
+/// An index of a module's nodes and scopes, built once and then queried by position.
+pub struct PositionIndex<'a> {
+    filename: &'a str,
+    arena: Arena<'a>,
+    scopes: Scopes<'a>,
+}
+
+impl<'a> PositionIndex<'a> {
+    /// Index the module's nodes and the scopes they are in.
+    pub fn new(module: &'a Module) -> Self {
+        let filename = module.filename.as_deref().unwrap_or("<unknown>");
+        Self { filename, arena: Arena::new(module), scopes: Scopes::collect(filename, &module.raw.body) }
+    }
+
+    /// The arena the index's node ids refer to.
+    pub fn arena(&self) -> &Arena<'a> {
+        &self.arena
+    }
+
+    /// The innermost node at the position, the nodes around it and the scopes it is in.
+    pub fn at(&self, lineno: usize, col_offset: usize) -> Option<NodeAt> {
+        let node = self.arena.node_at(lineno, col_offset)?;
+        let scopes = self
+            .scope_chain(node)
+            .into_iter()
+            .rev()
+            .map(|(scope, opener)| ScopeAt {
+                kind: self.scopes.scopes[scope].kind,
+                node: opener,
+                bindings: self.scopes.scopes[scope].bindings.iter().map(|(n, l)| (n.clone(), l.clone())).collect(),
+            })
+            .collect();
+        Some(NodeAt { node, enclosing: self.arena.ancestors(node).collect(), scopes })
+    }
+
+    /// The span of the node at the position and of each node around it that covers more, innermost first, for
+    /// growing a selection one step at a time.
+    pub fn selection_ranges(&self, lineno: usize, col_offset: usize) -> Vec<Span> {
+        let Some(node) = self.arena.node_at(lineno, col_offset) else {
+            return Vec::new();
+        };
+        let mut ranges: Vec<Span> = Vec::new();
+        for span in std::iter::once(node).chain(self.arena.ancestors(node)).filter_map(|id| self.arena.span(id)) {
+            if span.contains(lineno, col_offset) && ranges.last().is_none_or(|last| *last != span) {
+                ranges.push(span);
+            }
+        }
+        ranges
+    }
+
+    /// Where the name a node reads or binds is first bound, for a name or a parameter. Builtins, and names bound
+    /// nowhere, have no definition.
+    pub fn definition(&self, node: NodeId) -> Option<SourceLocation> {
+        let name = match self.arena.node(node) {
+            NodeRef::Expr(ExprType::Name(n)) => &n.id,
+            NodeRef::Parameter(p) => &p.arg,
+            _ => return None,
+        };
+        let (scope, _) = *self.scope_chain(node).last()?;
+        let found = self.scopes.resolve(scope, name)?;
+        self.scopes.scopes[found].bindings.get(name).cloned()
+    }
+
+    /// Where the node itself is, as a [`SourceLocation`] in the module's file.
+    pub fn location(&self, node: NodeId) -> Option<SourceLocation> {
+        let span = self.arena.span(node)?;
+        Some(locate(self.filename, &span))
+    }
+
+    /// The scopes around a node, outermost first, each with the node that opens it.
+    fn scope_chain(&self, node: NodeId) -> Vec<(usize, NodeId)> {
+        let mut path: Vec<NodeId> = self.arena.ancestors(node).collect();
+        path.reverse();
+        path.push(node);
+
+        let mut chain = vec![(MODULE, self.arena.root())];
+        for (i, &opener) in path.iter().enumerate() {
+            let Some(scope) = self.opened(opener) else {
+                continue;
+            };
+            // The node that opens a scope is itself in the scope around it.
+            let Some(&child) = path.get(i + 1) else {
+                break;
+            };
+            // A parameter's name is bound in the function, but its annotation and default are evaluated outside.
+            let parameter = || path.len() == i + 3 && matches!(self.arena.node(path[i + 2]), NodeRef::Parameter(_));
+            let inside = match (self.arena.node(opener), self.arena.node(child)) {
+                (_, NodeRef::Arguments(_)) => parameter(),
+                // A function or class body, but not its decorators.
+                (NodeRef::Statement(_), child) => matches!(child, NodeRef::Statement(_)),
+                // The first iterable of a comprehension is evaluated where the comprehension is.
+                (NodeRef::Expr(_), NodeRef::Comprehension(_)) => {
+                    let first = self.arena.children(opener).first() == Some(&child);
+                    !(first && path.get(i + 2) == self.arena.children(child).get(1))
+                }
+                _ => true,
+            };
+            if inside {
+                chain.push((scope, opener));
+            }
+        }
+        chain
+    }
+
+    /// The scope a `def`, `class`, lambda or comprehension opens.
+    fn opened(&self, node: NodeId) -> Option<usize> {
+        match self.arena.node(node) {
+            NodeRef::Statement(s) => self.scopes.statements.get(&key(s)).copied(),
+            NodeRef::Expr(e) => self.scopes.expressions.get(&key(e)).copied(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    const SOURCE: &str = "\
+import os
+
+@cache
+def load(path, mode=MODE):
+    names = [os.path.join(path, n) for n in os.listdir(path)]
+    return open(names[0], mode)
+";
+
+    fn source(index: &PositionIndex, node: NodeId) -> String {
+        match index.arena().node(node) {
+            NodeRef::Expr(e) => e.to_python_source().unwrap(),
+            NodeRef::Parameter(p) => p.arg.clone(),
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_innermost_node() {
+        let module = parse(SOURCE, "load.py").unwrap();
+        let index = PositionIndex::new(&module);
+
+        // The `n` in `join(path, n)`.
+        let found = index.at(5, 32).unwrap();
+        assert_eq!(source(&index, found.node), "n");
+        assert!(matches!(index.arena().node(found.enclosing[0]), NodeRef::Expr(ExprType::Call(_))));
+        assert_eq!(found.enclosing.last(), Some(&index.arena().root()));
+
+        // A decorator comes before the `def` it belongs to.
+        let found = index.at(3, 2).unwrap();
+        assert_eq!(source(&index, found.node), "cache");
+        assert!(matches!(index.arena().node(found.enclosing[0]), NodeRef::Statement(_)));
+    }
+
+    #[test]
+    fn test_scopes() {
+        let module = parse(SOURCE, "load.py").unwrap();
+        let index = PositionIndex::new(&module);
+        let kinds = |line, column| index.at(line, column).unwrap().scopes.iter().map(|s| s.kind).collect::<Vec<_>>();
+
+        assert_eq!(kinds(3, 1), [ScopeKind::Module]);
+        // The default is evaluated outside the function, but the parameter is bound inside it.
+        assert_eq!(kinds(4, 20), [ScopeKind::Module]);
+        assert_eq!(kinds(4, 9), [ScopeKind::Function, ScopeKind::Module]);
+        assert_eq!(kinds(5, 32), [ScopeKind::Comprehension, ScopeKind::Function, ScopeKind::Module]);
+        // The first iterable is evaluated in the function.
+        assert_eq!(kinds(5, 55), [ScopeKind::Function, ScopeKind::Module]);
+
+        let scopes = index.at(6, 11).unwrap().scopes;
+        assert_eq!(scopes[0].bindings.keys().collect::<Vec<_>>(), ["mode", "names", "path"]);
+        assert!(scopes[1].bindings.contains_key("load"));
+    }
+
+    #[test]
+    fn test_definition() {
+        let module = parse(SOURCE, "load.py").unwrap();
+        let index = PositionIndex::new(&module);
+        let definition = |line, column| {
+            let found = index.at(line, column).unwrap();
+            index.definition(found.node).map(|l| (l.line, l.column))
+        };
+
+        // `path` in the call goes to the parameter, `os` to the import and `n` to the comprehension's target.
+        assert_eq!(definition(5, 27), Some((Some(4), Some(10))));
+        assert_eq!(definition(5, 14), Some((Some(1), Some(1))));
+        assert_eq!(definition(5, 32), Some((Some(5), Some(40))));
+        // `open` is a builtin.
+        assert_eq!(definition(6, 11), None);
+    }
+
+    #[test]
+    fn test_selection_ranges() {
+        let module = parse(SOURCE, "load.py").unwrap();
+        let index = PositionIndex::new(&module);
+
+        let ranges = index.selection_ranges(6, 17);
+        let lines: Vec<_> = ranges.iter().map(|s| (s.lineno, s.col_offset, s.end_col_offset)).collect();
+        assert_eq!(lines[0], (6, 16, 21));
+        assert_eq!(lines.last(), Some(&(1, 0, 31)));
+        assert!(ranges.windows(2).all(|w| w[1].contains(w[0].lineno, w[0].col_offset)));
+    }
+}
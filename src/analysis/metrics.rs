@@ -0,0 +1,482 @@
+//! Size and complexity metrics for a module, and each class and function in it.
+//!
+//! Each unit is measured over everything inside it, so a class's figures include its methods', and the
+//! module's include everything in the file:
+//!
+//! * statements counts every statement, including compound ones and those in nested blocks;
+//! * cyclomatic complexity is one plus a decision point for each `if`, `elif`, loop, `except`, `and`/`or`,
+//!   conditional expression and comprehension `for` or `if`;
+//! * nesting is the deepest the control-flow blocks go, where an `elif` is as deep as its `if`;
+//! * the Halstead measures take operators to be the arithmetic, comparison and boolean operators,
+//!   assignment, attribute access, calls, subscripts and keywords, and operands to be names, attribute names
+//!   and constants;
+//! * the maintainability index is the normalized `171 - 5.2 ln V - 0.23 G - 16.2 ln L`, from 0 to 100, where
+//!   `V` is the Halstead volume, `G` the complexity and `L` the lines the unit spans.
+
+use std::{collections::HashMap, fmt};
+
+use serde_json::{Value, json};
+
+use super::cfg::nested_bodies;
+use crate::{
+    Comprehension, ExceptHandler, ExprType, Module, SourceLocation, Statement, StatementType, locate,
+    visit::{Visitor, walk_body, walk_comprehension, walk_except_handler, walk_expr, walk_stmt},
+};
+
+/// What a set of metrics measures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnitKind {
+    Module,
+    Class,
+    Function,
+}
+
+impl fmt::Display for UnitKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UnitKind::Module => "module",
+            UnitKind::Class => "class",
+            UnitKind::Function => "function",
+        })
+    }
+}
+
+/// Halstead's counts of operators and operands, and the measures derived from them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Halstead {
+    pub distinct_operators: usize,
+    pub distinct_operands: usize,
+    pub operators: usize,
+    pub operands: usize,
+}
+
+impl Halstead {
+    pub fn vocabulary(&self) -> usize {
+        self.distinct_operators + self.distinct_operands
+    }
+
+    pub fn length(&self) -> usize {
+        self.operators + self.operands
+    }
+
+    pub fn volume(&self) -> f64 {
+        match self.vocabulary() {
+            0 => 0.0,
+            vocabulary => self.length() as f64 * (vocabulary as f64).log2(),
+        }
+    }
+
+    pub fn difficulty(&self) -> f64 {
+        match self.distinct_operands {
+            0 => 0.0,
+            distinct => self.distinct_operators as f64 / 2.0 * self.operands as f64 / distinct as f64,
+        }
+    }
+
+    pub fn effort(&self) -> f64 {
+        self.difficulty() * self.volume()
+    }
+}
+
+/// The metrics of a module, class or function.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Metrics {
+    /// The unit's name, qualified by the classes and functions it's in, as in `Parser.parse`.
+    pub name: String,
+    pub kind: UnitKind,
+    pub location: SourceLocation,
+    /// The lines the unit spans.
+    pub lines: usize,
+    pub statements: usize,
+    pub complexity: usize,
+    pub nesting: usize,
+    /// A function's parameters, including `self`, `*args` and `**kwargs`.
+    pub parameters: Option<usize>,
+    pub halstead: Halstead,
+}
+
+impl Metrics {
+    pub fn maintainability_index(&self) -> f64 {
+        let ln = |x: f64| if x > 0.0 { x.ln() } else { 0.0 };
+        let raw = 171.0 - 5.2 * ln(self.halstead.volume()) - 0.23 * self.complexity as f64 - 16.2 * ln(self.lines as f64);
+        (raw * 100.0 / 171.0).clamp(0.0, 100.0)
+    }
+
+    fn to_json(&self) -> Value {
+        let halstead = &self.halstead;
+        json!({
+            "name": self.name,
+            "kind": self.kind.to_string(),
+            "file": self.location.filename,
+            "line": self.location.line,
+            "column": self.location.column,
+            "end_line": self.location.end_line,
+            "end_column": self.location.end_column,
+            "lines": self.lines,
+            "statements": self.statements,
+            "complexity": self.complexity,
+            "nesting": self.nesting,
+            "parameters": self.parameters,
+            "maintainability_index": rounded(self.maintainability_index()),
+            "halstead": {
+                "distinct_operators": halstead.distinct_operators,
+                "distinct_operands": halstead.distinct_operands,
+                "operators": halstead.operators,
+                "operands": halstead.operands,
+                "vocabulary": halstead.vocabulary(),
+                "length": halstead.length(),
+                "volume": rounded(halstead.volume()),
+                "difficulty": rounded(halstead.difficulty()),
+                "effort": rounded(halstead.effort()),
+            },
+        })
+    }
+}
+
+/// The metrics of a module, then of each class and function in it, in source order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MetricsReport {
+    pub units: Vec<Metrics>,
+}
+
+impl MetricsReport {
+    /// The report as a JSON array, one object per unit.
+    pub fn to_json(&self) -> String {
+        let units: Vec<Value> = self.units.iter().map(Metrics::to_json).collect();
+        serde_json::to_string_pretty(&units).expect("serializing metrics")
+    }
+
+    /// The report as a plain-text table, one row per unit.
+    pub fn to_table(&self) -> String {
+        let header = ["name", "kind", "line", "lines", "stmts", "cc", "nest", "params", "mi", "volume"];
+        let mut rows = vec![header.map(String::from).to_vec()];
+        for unit in self.units.iter() {
+            rows.push(vec![
+                unit.name.clone(),
+                unit.kind.to_string(),
+                unit.location.line.map_or("-".to_string(), |l| l.to_string()),
+                unit.lines.to_string(),
+                unit.statements.to_string(),
+                unit.complexity.to_string(),
+                unit.nesting.to_string(),
+                unit.parameters.map_or("-".to_string(), |p| p.to_string()),
+                format!("{:.1}", unit.maintainability_index()),
+                format!("{:.1}", unit.halstead.volume()),
+            ]);
+        }
+        let widths: Vec<usize> = (0..header.len()).map(|i| rows.iter().map(|row| row[i].len()).max().unwrap_or(0)).collect();
+        let mut table = String::new();
+        for row in rows {
+            // Names and kinds line up on the left, numbers on the right.
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(i, (cell, &width))| if i < 2 { format!("{:<width$}", cell) } else { format!("{:>width$}", cell) })
+                .collect();
+            table.push_str(cells.join("  ").trim_end());
+            table.push('\n');
+        }
+        table
+    }
+}
+
+/// Measures a module, and each class and function in it.
+pub fn metrics(module: &Module) -> MetricsReport {
+    let filename = module.filename.as_deref().unwrap_or("<unknown>");
+    let name = module.name.as_ref().map_or("<module>", |n| n.id.as_str());
+    let body = &module.raw.body;
+    let lines = body.iter().filter_map(|s| s.end_lineno).max().unwrap_or(0);
+    let location = SourceLocation::with_span(filename, Some(1), Some(1), Some(lines.max(1)), None);
+    let mut report = MetricsReport { units: vec![measure(name, UnitKind::Module, location, lines, None, body)] };
+    units(filename, "", body, &mut report.units);
+    report
+}
+
+fn units(filename: &str, prefix: &str, body: &[Statement], found: &mut Vec<Metrics>) {
+    for statement in body {
+        let lines = match (statement.lineno, statement.end_lineno) {
+            (Some(start), Some(end)) => end + 1 - start,
+            _ => 0,
+        };
+        match &statement.statement {
+            StatementType::FunctionDef(f) | StatementType::AsyncFunctionDef(f) => {
+                let name = format!("{}{}", prefix, f.name);
+                let args = &f.args;
+                let parameters = args.posonlyargs.len() + args.args.len() + args.kwonlyargs.len()
+                    + usize::from(args.vararg.is_some()) + usize::from(args.kwarg.is_some());
+                let location = locate(filename, statement);
+                found.push(measure(&name, UnitKind::Function, location, lines, Some(parameters), &f.body));
+                units(filename, &format!("{}.", name), &f.body, found);
+            }
+            StatementType::ClassDef(c) => {
+                let name = format!("{}{}", prefix, c.name);
+                found.push(measure(&name, UnitKind::Class, locate(filename, statement), lines, None, &c.body));
+                units(filename, &format!("{}.", name), &c.body, found);
+            }
+            _ => nested_bodies(statement).into_iter().for_each(|block| units(filename, prefix, block, found)),
+        }
+    }
+}
+
+fn measure(
+    name: &str,
+    kind: UnitKind,
+    location: SourceLocation,
+    lines: usize,
+    parameters: Option<usize>,
+    body: &[Statement],
+) -> Metrics {
+    let mut counter = Counter::default();
+    walk_body(&mut counter, body);
+    Metrics {
+        name: name.to_string(),
+        kind,
+        location,
+        lines,
+        statements: counter.statements,
+        complexity: 1 + counter.decisions,
+        nesting: counter.max_depth,
+        parameters,
+        halstead: Halstead {
+            distinct_operators: counter.operators.len(),
+            distinct_operands: counter.operands.len(),
+            operators: counter.operators.values().sum(),
+            operands: counter.operands.values().sum(),
+        },
+    }
+}
+
+fn rounded(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+#[derive(Default)]
+struct Counter {
+    statements: usize,
+    decisions: usize,
+    depth: usize,
+    max_depth: usize,
+    operators: HashMap<String, usize>,
+    operands: HashMap<String, usize>,
+}
+
+impl Counter {
+    fn operator(&mut self, operator: impl Into<String>) {
+        *self.operators.entry(operator.into()).or_default() += 1;
+    }
+
+    fn operand(&mut self, operand: impl Into<String>) {
+        *self.operands.entry(operand.into()).or_default() += 1;
+    }
+
+    fn nested(&mut self, visit: impl FnOnce(&mut Self)) {
+        self.depth += 1;
+        self.max_depth = self.max_depth.max(self.depth);
+        visit(self);
+        self.depth -= 1;
+    }
+}
+
+impl<'a> Visitor<'a> for Counter {
+    fn visit_stmt(&mut self, stmt: &'a Statement) {
+        self.statements += 1;
+        match &stmt.statement {
+            StatementType::If(i) => {
+                self.decisions += 1;
+                self.operator("if");
+                self.visit_expr(&i.test);
+                self.nested(|counter| walk_body(counter, &i.body));
+                match i.orelse.as_slice() {
+                    // An `elif` is the `if` of an `else` block, starting where its parent does.
+                    [elif] if matches!(elif.statement, StatementType::If(_)) && elif.col_offset == stmt.col_offset => {
+                        self.visit_stmt(elif)
+                    }
+                    orelse => self.nested(|counter| walk_body(counter, orelse)),
+                }
+                return;
+            }
+            StatementType::For(_) | StatementType::AsyncFor(_) => {
+                self.decisions += 1;
+                self.operator("for");
+            }
+            StatementType::While(_) => {
+                self.decisions += 1;
+                self.operator("while");
+            }
+            StatementType::Try(_) => self.operator("try"),
+            StatementType::With(_) | StatementType::AsyncWith(_) => self.operator("with"),
+            StatementType::FunctionDef(f) | StatementType::AsyncFunctionDef(f) => {
+                self.operator("def");
+                self.operand(f.name.clone());
+                let args = &f.args;
+                let parameters = args.posonlyargs.iter().chain(&args.args).chain(&args.vararg).chain(&args.kwonlyargs).chain(&args.kwarg);
+                parameters.for_each(|p| self.operand(p.arg.clone()));
+            }
+            StatementType::ClassDef(c) => {
+                self.operator("class");
+                self.operand(c.name.clone());
+            }
+            StatementType::Assign(a) => a.targets.iter().for_each(|_| self.operator("=")),
+            StatementType::AugAssign(a) => self.operator(format!("{:?}=", a.op)),
+            StatementType::Return(_) => self.operator("return"),
+            StatementType::Raise(_) => self.operator("raise"),
+            StatementType::Break => self.operator("break"),
+            StatementType::Continue => self.operator("continue"),
+            StatementType::Pass => self.operator("pass"),
+            StatementType::Import(i) => {
+                self.operator("import");
+                i.names.iter().for_each(|a| self.operand(a.asname.clone().unwrap_or_else(|| a.name.clone())));
+            }
+            StatementType::ImportFrom(i) => {
+                self.operator("import");
+                i.names.iter().for_each(|a| self.operand(a.asname.clone().unwrap_or_else(|| a.name.clone())));
+            }
+            StatementType::Call(_) | StatementType::Expr(_) | StatementType::Unimplemented(_) => (),
+        }
+        let nests = matches!(
+            stmt.statement,
+            StatementType::For(_)
+                | StatementType::AsyncFor(_)
+                | StatementType::While(_)
+                | StatementType::Try(_)
+                | StatementType::With(_)
+                | StatementType::AsyncWith(_)
+        );
+        if nests {
+            self.nested(|counter| walk_stmt(counter, stmt));
+        } else {
+            walk_stmt(self, stmt);
+        }
+    }
+
+    fn visit_except_handler(&mut self, handler: &'a ExceptHandler) {
+        self.decisions += 1;
+        self.operator("except");
+        walk_except_handler(self, handler);
+    }
+
+    fn visit_comprehension(&mut self, comprehension: &'a Comprehension) {
+        self.decisions += 1 + comprehension.ifs.len();
+        self.operator("for");
+        comprehension.ifs.iter().for_each(|_| self.operator("if"));
+        walk_comprehension(self, comprehension);
+    }
+
+    fn visit_expr(&mut self, expr: &'a ExprType) {
+        match expr {
+            ExprType::BoolOp(b) => {
+                self.decisions += 1;
+                self.operator(format!("{:?}", b.op));
+            }
+            ExprType::IfExp(_) => {
+                self.decisions += 1;
+                self.operator("if");
+            }
+            ExprType::BinOp(b) => self.operator(format!("{:?}", b.op)),
+            ExprType::UnaryOp(u) => self.operator(format!("{:?}", u.op)),
+            ExprType::Compare(c) => c.ops.iter().for_each(|op| self.operator(format!("{:?}", op))),
+            ExprType::NamedExpr(_) => self.operator(":="),
+            ExprType::Lambda(_) => self.operator("lambda"),
+            ExprType::Await(_) => self.operator("await"),
+            ExprType::Yield(_) | ExprType::YieldFrom(_) => self.operator("yield"),
+            ExprType::Call(_) => self.operator("()"),
+            ExprType::Subscript(_) => self.operator("[]"),
+            ExprType::Starred(_) => self.operator("*"),
+            ExprType::Attribute(a) => {
+                self.operator(".");
+                self.operand(a.attr.clone());
+            }
+            ExprType::Name(n) => self.operand(n.id.clone()),
+            ExprType::Constant(c) | ExprType::NoneType(c) => self.operand(c.to_string()),
+            _ => (),
+        }
+        walk_expr(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn report(code: &str) -> MetricsReport {
+        metrics(&parse(code, "test.py").unwrap())
+    }
+
+    fn unit<'a>(report: &'a MetricsReport, name: &str) -> &'a Metrics {
+        report.units.iter().find(|unit| unit.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_units_are_qualified() {
+        let report = report("class C:\n    def m(self, x, *args):\n        def inner():\n            pass\n\ndef f():\n    pass\n");
+        let units: Vec<_> = report.units.iter().map(|u| (u.name.as_str(), u.kind, u.parameters)).collect();
+        assert_eq!(
+            units,
+            vec![
+                ("test", UnitKind::Module, None),
+                ("C", UnitKind::Class, None),
+                ("C.m", UnitKind::Function, Some(3)),
+                ("C.m.inner", UnitKind::Function, Some(0)),
+                ("f", UnitKind::Function, Some(0)),
+            ]
+        );
+        let m = unit(&report, "C.m");
+        assert_eq!((m.location.line, m.location.column, m.lines), (Some(2), Some(5), 3));
+    }
+
+    #[test]
+    fn test_complexity_and_nesting() {
+        let code = "\
+def f(xs):
+    for x in xs:
+        if x > 0 and x < 10:
+            return x
+        elif x == 0:
+            continue
+        else:
+            try:
+                g(x)
+            except ValueError:
+                pass
+    return [y for y in xs if y]
+";
+        let report = report(code);
+        let f = unit(&report, "f");
+        // for, if, and, elif, except, and the comprehension's for and if.
+        assert_eq!(f.complexity, 8);
+        // The `try` in the `else` is three blocks deep, and the `elif` no deeper than the `if`.
+        assert_eq!(f.nesting, 3);
+        assert_eq!(f.statements, 9);
+        // The module is measured over everything in it.
+        assert_eq!(unit(&report, "test").complexity, 8);
+    }
+
+    #[test]
+    fn test_halstead() {
+        let report = report("def f(a, b):\n    return a + b * a\n");
+        let halstead = &unit(&report, "f").halstead;
+        // `return`, `+` and `*`; `a` twice and `b`.
+        assert_eq!((halstead.distinct_operators, halstead.operators), (3, 3));
+        assert_eq!((halstead.distinct_operands, halstead.operands), (2, 3));
+        assert_eq!(halstead.volume(), 6.0 * 5f64.log2());
+        assert_eq!(halstead.difficulty(), 3.0 / 2.0 * 3.0 / 2.0);
+    }
+
+    #[test]
+    fn test_output() {
+        let report = report("def f(a):\n    if a:\n        return 1\n    return 2\n");
+        let json: Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json[1]["name"], "f");
+        assert_eq!(json[1]["complexity"], 2);
+        assert_eq!(json[1]["parameters"], 1);
+        assert_eq!(json[0]["parameters"], Value::Null);
+        assert!(json[1]["maintainability_index"].as_f64().unwrap() > 50.0);
+
+        let table = report.to_table();
+        let lines: Vec<_> = table.lines().collect();
+        assert!(lines[0].starts_with("name  kind      line  lines  stmts  cc  nest  params"));
+        assert!(lines[2].starts_with("f     function     1      4      3   2     1       1"));
+    }
+}
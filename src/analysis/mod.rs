@@ -0,0 +1,86 @@
+//! Static analyses over parsed modules, for finding problems in the Python itself rather than transpiling it.
+//!
+//! Each analysis has its own result types, and turns them into [`Diagnostic`]s so they can be reported
+//! together.
+
+use std::{fmt, str::FromStr};
+
+use crate::{Node, SourceLocation};
+
+pub mod call_graph;
+pub use call_graph::*;
+
+pub mod cfg;
+pub use cfg::*;
+
+pub mod lints;
+pub use lints::*;
+
+pub mod lookup;
+pub use lookup::*;
+
+pub mod metrics;
+pub use metrics::*;
+
+pub mod names;
+pub use names::*;
+
+/// How serious a problem is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    /// The code fails, or would if it ran.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "info" => Ok(Severity::Info),
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            _ => Err(format!("unknown severity `{}`; expected info, warning or error", s)),
+        }
+    }
+}
+
+/// A problem found by an analysis, displayed as `file:line:column: severity[code]: message`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// A short name for the kind of problem, such as `undefined-name`.
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub location: SourceLocation,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}[{}]: {}", self.location, self.severity, self.code, self.message)
+    }
+}
+
+/// Where `node` is in `filename`. Columns are 1-based, like the parser's errors.
+pub(crate) fn locate(filename: &str, node: &impl Node) -> SourceLocation {
+    let column = |offset: Option<usize>| offset.map(|c| c + 1);
+    SourceLocation::with_span(
+        filename,
+        node.lineno(),
+        column(node.col_offset()),
+        node.end_lineno(),
+        column(node.end_col_offset()),
+    )
+}
@@ -0,0 +1,1167 @@
+//! Checks that the names a module reads are bound where they're read.
+//!
+//! Python decides which scope a name belongs to from the whole block: a name bound anywhere in a function is
+//! local to all of it, so reading it before the assignment fails rather than finding a global. The checks here
+//! follow those rules, which codegen's symbol table doesn't, and then follow each block's control flow to find
+//! reads that an assignment doesn't reach on every path.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::OnceLock,
+};
+
+use litrs::Literal;
+use pyo3::prelude::*;
+
+use crate::{
+    Arguments, Call, Comprehension, Constant, Diagnostic, ExprType, Module, Node, Severity, SourceLocation,
+    Statement, StatementType, Try, boundary_names, deleted_names, locate,
+    visit::{Visitor, walk_arguments, walk_expr},
+};
+
+/// What's wrong with a name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NameIssueKind {
+    /// Read, but bound in no scope the read can see, and not a builtin.
+    Undefined,
+    /// Read where no assignment to it can have run yet.
+    UsedBeforeAssignment,
+    /// Read where an assignment to it has run on some paths but not others.
+    PossiblyUsedBeforeAssignment,
+    /// Bound over a builtin, such as a variable named `list`.
+    ShadowsBuiltin,
+    /// A function's variable or parameter with the name of one in a scope around the function.
+    ShadowsOuter,
+}
+
+impl NameIssueKind {
+    /// The code for the issue's [`Diagnostic`].
+    pub fn code(self) -> &'static str {
+        match self {
+            NameIssueKind::Undefined => "undefined-name",
+            NameIssueKind::UsedBeforeAssignment => "used-before-assignment",
+            NameIssueKind::PossiblyUsedBeforeAssignment => "possibly-used-before-assignment",
+            NameIssueKind::ShadowsBuiltin => "shadowed-builtin",
+            NameIssueKind::ShadowsOuter => "shadowed-name",
+        }
+    }
+
+    pub fn severity(self) -> Severity {
+        match self {
+            NameIssueKind::Undefined | NameIssueKind::UsedBeforeAssignment => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+/// A problem with a name, found by [`check_names`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct NameIssue {
+    pub kind: NameIssueKind,
+    pub name: String,
+    /// Where the name is read, or for shadowing, where it's bound.
+    pub location: SourceLocation,
+    /// For [`NameIssueKind::ShadowsOuter`], where the shadowed name is bound.
+    pub shadowed: Option<SourceLocation>,
+}
+
+impl NameIssue {
+    pub fn message(&self) -> String {
+        let name = &self.name;
+        match self.kind {
+            NameIssueKind::Undefined => format!("`{}` is not defined", name),
+            NameIssueKind::UsedBeforeAssignment => format!("`{}` is used before it's assigned", name),
+            NameIssueKind::PossiblyUsedBeforeAssignment => format!("`{}` may be used before it's assigned", name),
+            NameIssueKind::ShadowsBuiltin => format!("`{}` shadows the builtin of the same name", name),
+            NameIssueKind::ShadowsOuter => match self.shadowed.as_ref().and_then(|outer| outer.line) {
+                Some(line) => format!("`{}` shadows the `{}` bound on line {}", name, name, line),
+                None => format!("`{}` shadows a name from an enclosing scope", name),
+            },
+        }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic {
+            code: self.kind.code(),
+            severity: self.kind.severity(),
+            message: self.message(),
+            location: self.location.clone(),
+        }
+    }
+}
+
+impl fmt::Display for NameIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_diagnostic().fmt(f)
+    }
+}
+
+/// Checks the names `module` reads and binds, returning the issues in source order.
+pub fn check_names(module: &Module) -> Vec<NameIssue> {
+    let filename = module.filename.as_deref().unwrap_or("<unknown>");
+    let scopes = Scopes::collect(filename, &module.raw.body);
+    let mut checker = Checker {
+        scopes: &scopes,
+        lazy_annotations: lazy_annotations(module),
+        issues: Vec::new(),
+        quiet: 0,
+        deferred: 0,
+        guarded: 0,
+        loops: Vec::new(),
+        assigned: Vec::new(),
+        statement: SourceLocation::new(filename),
+    };
+    checker.block(MODULE, &module.raw.body, Some(Flow::default()));
+
+    let mut issues = checker.issues;
+    issues.extend(scopes.shadowing());
+    // A `finally` block is walked twice, so it can report the same read twice.
+    issues.sort_by(|a, b| {
+        let key = |i: &NameIssue| (i.location.line, i.location.column, i.kind);
+        key(a).cmp(&key(b)).then_with(|| a.name.cmp(&b.name))
+    });
+    issues.dedup();
+    issues
+}
+
+/// The names every module can read without binding them: the builtins, and what Python gives each module.
+pub(super) fn builtins() -> &'static HashSet<String> {
+    static BUILTINS: OnceLock<HashSet<String>> = OnceLock::new();
+    BUILTINS.get_or_init(|| {
+        let names: PyResult<Vec<String>> = Python::with_gil(|py| py.import("builtins")?.dir()?.extract());
+        let mut names: HashSet<String> = names.unwrap_or_default().into_iter().collect();
+        names.extend(["__file__", "__builtins__", "__cached__", "__annotations__", "__path__"].map(String::from));
+        names
+    })
+}
+
+/// Whether binding `name` hides a builtin worth warning about. Dunder names are module attributes, and the
+/// helpers `site` adds for the interactive prompt, like `help`, are fine to reuse.
+fn shadows_builtin(name: &str) -> bool {
+    let dunder = name.len() > 4 && name.starts_with("__") && name.ends_with("__");
+    let interactive = matches!(name, "help" | "copyright" | "credits" | "license" | "exit" | "quit");
+    builtins().contains(name) && !dunder && !interactive
+}
+
+/// Whether the module has `from __future__ import annotations`, which leaves annotations unevaluated.
+fn lazy_annotations(module: &Module) -> bool {
+    module.raw.body.iter().any(|s| match &s.statement {
+        StatementType::ImportFrom(i) => i.module == "__future__" && i.names.iter().any(|a| a.name == "annotations"),
+        _ => false,
+    })
+}
+
+/// The names in a `global` or `nonlocal` statement, which can't be transpiled, so only its source is kept.
+/// The flag is whether they're global.
+fn declaration(source: &str) -> Option<(bool, Vec<String>)> {
+    let (keyword, names) = source.split_once(char::is_whitespace)?;
+    let global = match keyword {
+        "global" => true,
+        "nonlocal" => false,
+        _ => return None,
+    };
+    Some((global, names.split(',').map(|name| name.trim().to_string()).collect()))
+}
+
+/// The name an `import` binds for `name as asname`: the alias, or else the first part of a dotted name.
+fn import_binding(name: &str, asname: Option<&String>) -> String {
+    asname.cloned().unwrap_or_else(|| name.split('.').next().unwrap_or(name).to_string())
+}
+
+/// How an import binds its name. `import a as a` and `from m import a as a` mark the name as re-exported, so
+/// they're never unused.
+fn import_kind(alias: &crate::Alias) -> BindingKind {
+    if alias.asname.as_ref() == Some(&alias.name) { BindingKind::Other } else { BindingKind::Import }
+}
+
+/// Where a `def` or `class` statement's name is, after its keyword.
+fn name_after(filename: &str, statement: &Statement, keyword: &str, name: &str) -> SourceLocation {
+    let column = statement.col_offset.map(|c| c + keyword.len() + 1);
+    SourceLocation::with_span(filename, statement.lineno, column, statement.lineno, column.map(|c| c + name.len()))
+}
+
+fn parameters(args: &Arguments) -> impl Iterator<Item = &crate::Parameter> {
+    args.posonlyargs.iter().chain(&args.args).chain(&args.vararg).chain(&args.kwonlyargs).chain(&args.kwarg)
+}
+
+fn call_parts(call: &Call) -> impl Iterator<Item = &ExprType> {
+    std::iter::once(call.func.as_ref()).chain(&call.args).chain(call.keywords.iter().map(|k| &k.value))
+}
+
+/// An expression's direct subexpressions.
+#[derive(Default)]
+struct Children<'a>(Vec<&'a ExprType>);
+
+impl<'a> Visitor<'a> for Children<'a> {
+    fn visit_expr(&mut self, expr: &'a ExprType) {
+        self.0.push(expr);
+    }
+}
+
+fn children(expr: &ExprType) -> Vec<&ExprType> {
+    let mut children = Children::default();
+    walk_expr(&mut children, expr);
+    children.0
+}
+
+/// The generators of a comprehension, and the expressions it produces.
+fn comprehension(expr: &ExprType) -> Option<(&[Comprehension], Vec<&ExprType>)> {
+    match expr {
+        ExprType::ListComp(c) => Some((&c.generators, vec![&c.elt])),
+        ExprType::SetComp(c) => Some((&c.generators, vec![&c.elt])),
+        ExprType::GeneratorExp(c) => Some((&c.generators, vec![&c.elt])),
+        ExprType::DictComp(c) => Some((&c.generators, vec![&c.key, &c.value])),
+        _ => None,
+    }
+}
+
+/// Whether a loop condition is always true, as in `while True:`.
+pub(super) fn always_true(test: &ExprType) -> bool {
+    match test {
+        ExprType::Constant(Constant(Some(Literal::Bool(b)))) => b.value(),
+        ExprType::Constant(Constant(Some(Literal::Integer(i)))) => i.value::<u64>().is_some_and(|v| v != 0),
+        _ => false,
+    }
+}
+
+/// Identifies a node by its address, which is stable while the tree is borrowed.
+pub(super) fn key<T>(node: &T) -> usize {
+    node as *const T as usize
+}
+
+pub(super) const MODULE: usize = 0;
+
+/// The kinds of scope Python looks names up in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ScopeKind {
+    Module,
+    Class,
+    /// A function or lambda.
+    Function,
+    Comprehension,
+}
+
+/// How a statement binds a name, for the lints that look for bindings nothing reads.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum BindingKind {
+    Import,
+    /// A plain assignment to the name, or an `except ... as` name.
+    Variable,
+    /// A parameter of a `def`.
+    Parameter,
+    /// Anything else, such as a loop target, part of an unpacking, or a definition.
+    Other,
+}
+
+/// A place a scope binds a name.
+#[derive(Clone, Debug)]
+pub(super) struct Binding {
+    pub(super) name: String,
+    pub(super) kind: BindingKind,
+    pub(super) location: SourceLocation,
+}
+
+#[derive(Debug)]
+pub(super) struct Scope {
+    pub(super) kind: ScopeKind,
+    pub(super) parent: Option<usize>,
+    /// The names bound in the scope, with where each is first bound.
+    pub(super) bindings: HashMap<String, SourceLocation>,
+    /// Every binding in the scope, in source order.
+    pub(super) definitions: Vec<Binding>,
+    /// The names read in the scope, wherever they're bound.
+    pub(super) reads: HashSet<String>,
+    globals: HashSet<String>,
+    nonlocals: HashSet<String>,
+    /// Names a nested function assigns through `global` or `nonlocal`, which can't be followed here.
+    external: HashSet<String>,
+    /// Whether a `from module import *` binds names that can't be known.
+    star_import: bool,
+}
+
+/// Every scope in a module, and the names bound in each.
+pub(super) struct Scopes<'a> {
+    filename: &'a str,
+    pub(super) scopes: Vec<Scope>,
+    /// The scope each `def` and `class` opens, by the statement.
+    pub(super) statements: HashMap<usize, usize>,
+    /// The scope each lambda and comprehension opens, by the expression.
+    pub(super) expressions: HashMap<usize, usize>,
+}
+
+impl<'a> Scopes<'a> {
+    pub(super) fn collect(filename: &'a str, body: &[Statement]) -> Self {
+        let mut scopes = Scopes { filename, scopes: Vec::new(), statements: HashMap::new(), expressions: HashMap::new() };
+        scopes.open(ScopeKind::Module, None);
+        scopes.body(MODULE, body);
+
+        // A `nonlocal` name is bound in the nearest function around that binds it.
+        for index in 0..scopes.scopes.len() {
+            for name in scopes.scopes[index].nonlocals.clone() {
+                let mut parent = scopes.scopes[index].parent;
+                while let Some(outer) = parent {
+                    let scope = &mut scopes.scopes[outer];
+                    if scope.kind == ScopeKind::Function && scope.bindings.contains_key(&name) {
+                        scope.external.insert(name);
+                        break;
+                    }
+                    parent = scope.parent;
+                }
+            }
+        }
+        scopes
+    }
+
+    fn open(&mut self, kind: ScopeKind, parent: Option<usize>) -> usize {
+        self.scopes.push(Scope {
+            kind,
+            parent,
+            bindings: HashMap::new(),
+            definitions: Vec::new(),
+            reads: HashSet::new(),
+            globals: HashSet::new(),
+            nonlocals: HashSet::new(),
+            external: HashSet::new(),
+            star_import: false,
+        });
+        self.scopes.len() - 1
+    }
+
+    /// The scope an assignment in `scope` binds in, which for a comprehension is the one around it.
+    fn owner(&self, mut scope: usize) -> usize {
+        while self.scopes[scope].kind == ScopeKind::Comprehension {
+            scope = self.scopes[scope].parent.unwrap_or(MODULE);
+        }
+        scope
+    }
+
+    fn bind(&mut self, scope: usize, name: &str, location: SourceLocation) {
+        self.bind_as(scope, name, location, BindingKind::Other);
+    }
+
+    fn bind_as(&mut self, scope: usize, name: &str, location: SourceLocation, kind: BindingKind) {
+        let declared = &self.scopes[scope];
+        let scope = if declared.globals.contains(name) {
+            self.scopes[MODULE].external.insert(name.to_string());
+            MODULE
+        } else if declared.nonlocals.contains(name) {
+            return;
+        } else {
+            scope
+        };
+        self.scopes[scope].definitions.push(Binding { name: name.to_string(), kind, location: location.clone() });
+        self.scopes[scope].bindings.entry(name.to_string()).or_insert(location);
+    }
+
+    /// The scope a read of `name` in `scope` finds it bound in, if any.
+    pub(super) fn resolve(&self, scope: usize, name: &str) -> Option<usize> {
+        let here = &self.scopes[scope];
+        if here.globals.contains(name) {
+            return Some(MODULE);
+        }
+        if here.bindings.contains_key(name) && !here.nonlocals.contains(name) {
+            return Some(scope);
+        }
+        let mut parent = here.parent;
+        while let Some(index) = parent {
+            let outer = &self.scopes[index];
+            if outer.kind != ScopeKind::Class && outer.bindings.contains_key(name) {
+                return Some(index);
+            }
+            parent = outer.parent;
+        }
+        None
+    }
+
+    fn body(&mut self, scope: usize, body: &[Statement]) {
+        for statement in body {
+            self.statement(scope, statement);
+        }
+    }
+
+    fn statement(&mut self, scope: usize, statement: &Statement) {
+        let here = locate(self.filename, statement);
+        match &statement.statement {
+            StatementType::FunctionDef(f) | StatementType::AsyncFunctionDef(f) => {
+                let keyword = if matches!(statement.statement, StatementType::AsyncFunctionDef(_)) { "async def " } else { "def " };
+                self.bind(scope, &f.name, name_after(self.filename, statement, keyword, &f.name));
+                f.decorator_list.iter().for_each(|d| self.expression(scope, d));
+                self.signature(scope, &f.args);
+                if let Some(returns) = &f.returns {
+                    self.expression(scope, returns);
+                }
+                let function = self.open(ScopeKind::Function, Some(scope));
+                self.statements.insert(key(statement), function);
+                for parameter in parameters(&f.args) {
+                    self.bind_as(function, &parameter.arg, locate(self.filename, parameter), BindingKind::Parameter);
+                }
+                self.body(function, &f.body);
+            }
+            StatementType::ClassDef(c) => {
+                self.bind(scope, &c.name, name_after(self.filename, statement, "class ", &c.name));
+                c.decorator_list.iter().for_each(|d| self.expression(scope, d));
+                self.scopes[scope].reads.extend(c.bases.iter().map(|base| base.id.clone()));
+                c.keywords.iter().for_each(|k| self.expression(scope, &k.value));
+                let class = self.open(ScopeKind::Class, Some(scope));
+                self.statements.insert(key(statement), class);
+                self.body(class, &c.body);
+            }
+            StatementType::Assign(a) => {
+                for target in &a.targets {
+                    match target {
+                        ExprType::Name(n) => self.bind_as(scope, &n.id, locate(self.filename, n), BindingKind::Variable),
+                        _ => self.target(scope, target),
+                    }
+                }
+                self.expression(scope, &a.value);
+            }
+            StatementType::AugAssign(a) => {
+                // `x += 1` reads `x` as well as binding it.
+                self.expression(scope, &a.target);
+                self.target(scope, &a.target);
+                self.expression(scope, &a.value);
+            }
+            StatementType::For(f) => self.for_loop(scope, &f.target, &f.iter, &f.body, &f.orelse),
+            StatementType::AsyncFor(f) => self.for_loop(scope, &f.target, &f.iter, &f.body, &f.orelse),
+            StatementType::While(w) => {
+                self.expression(scope, &w.test);
+                self.body(scope, &w.body);
+                self.body(scope, &w.orelse);
+            }
+            StatementType::If(i) => {
+                self.expression(scope, &i.test);
+                self.body(scope, &i.body);
+                self.body(scope, &i.orelse);
+            }
+            StatementType::With(w) => self.with(scope, &w.items, &w.body),
+            StatementType::AsyncWith(w) => self.with(scope, &w.items, &w.body),
+            StatementType::Try(t) => {
+                self.body(scope, &t.body);
+                for handler in &t.handlers {
+                    if let Some(exception_type) = &handler.exception_type {
+                        self.expression(scope, exception_type);
+                    }
+                    if let Some(name) = &handler.name {
+                        self.bind_as(scope, name, locate(self.filename, handler), BindingKind::Variable);
+                    }
+                    self.body(scope, &handler.body);
+                }
+                self.body(scope, &t.orelse);
+                self.body(scope, &t.finalbody);
+            }
+            StatementType::Import(i) => {
+                for alias in &i.names {
+                    let kind = import_kind(alias);
+                    self.bind_as(scope, &import_binding(&alias.name, alias.asname.as_ref()), here.clone(), kind);
+                }
+            }
+            StatementType::ImportFrom(i) => {
+                for alias in &i.names {
+                    if alias.name == "*" {
+                        self.scopes[scope].star_import = true;
+                    } else {
+                        let kind = if i.module == "__future__" { BindingKind::Other } else { import_kind(alias) };
+                        self.bind_as(scope, alias.asname.as_ref().unwrap_or(&alias.name), here.clone(), kind);
+                    }
+                }
+            }
+            StatementType::Return(Some(e)) | StatementType::Expr(e) => self.expression(scope, &e.value),
+            StatementType::Call(c) => call_parts(c).for_each(|e| self.expression(scope, e)),
+            StatementType::Raise(r) => r.exc.iter().chain(&r.cause).for_each(|e| self.expression(scope, e)),
+            StatementType::Unimplemented(source) => match declaration(source) {
+                Some((true, names)) => self.scopes[scope].globals.extend(names),
+                Some((false, names)) => self.scopes[scope].nonlocals.extend(names),
+                None => {
+                    let (loaded, stored) = boundary_names(source, "exec", false).unwrap_or_default();
+                    self.scopes[scope].reads.extend(loaded);
+                    for name in stored {
+                        self.bind(scope, &name, here.clone());
+                    }
+                }
+            },
+            StatementType::Return(None) | StatementType::Break | StatementType::Continue | StatementType::Pass => {}
+        }
+    }
+
+    fn for_loop(&mut self, scope: usize, target: &ExprType, iter: &ExprType, body: &[Statement], orelse: &[Statement]) {
+        self.target(scope, target);
+        self.expression(scope, iter);
+        self.body(scope, body);
+        self.body(scope, orelse);
+    }
+
+    fn with(&mut self, scope: usize, items: &[crate::WithItem], body: &[Statement]) {
+        for item in items {
+            self.expression(scope, &item.context_expr);
+            if let Some(vars) = &item.optional_vars {
+                self.target(scope, vars);
+            }
+        }
+        self.body(scope, body);
+    }
+
+    /// The defaults and annotations of a function's parameters, which belong to the scope around it.
+    fn signature(&mut self, scope: usize, args: &Arguments) {
+        let mut parts = Children::default();
+        walk_arguments(&mut parts, args);
+        parts.0.into_iter().for_each(|e| self.expression(scope, e));
+    }
+
+    fn target(&mut self, scope: usize, target: &ExprType) {
+        match target {
+            ExprType::Name(n) => self.bind(scope, &n.id, locate(self.filename, n)),
+            ExprType::Tuple(t) => t.elts.iter().for_each(|e| self.target(scope, e)),
+            ExprType::List(elts) => elts.iter().for_each(|e| self.target(scope, e)),
+            ExprType::Starred(s) => self.target(scope, &s.value),
+            _ => self.expression(scope, target),
+        }
+    }
+
+    fn expression(&mut self, scope: usize, expr: &ExprType) {
+        if let Some((generators, results)) = comprehension(expr) {
+            // The first iterable is evaluated where the comprehension is; the rest is its own scope.
+            if let Some(first) = generators.first() {
+                self.expression(scope, &first.iter);
+            }
+            let inner = self.open(ScopeKind::Comprehension, Some(scope));
+            self.expressions.insert(key(expr), inner);
+            for (i, generator) in generators.iter().enumerate() {
+                if i > 0 {
+                    self.expression(inner, &generator.iter);
+                }
+                self.target(inner, &generator.target);
+                generator.ifs.iter().for_each(|e| self.expression(inner, e));
+            }
+            results.into_iter().for_each(|e| self.expression(inner, e));
+            return;
+        }
+        match expr {
+            ExprType::Name(n) => {
+                self.scopes[scope].reads.insert(n.id.clone());
+            }
+            ExprType::NamedExpr(n) => {
+                self.expression(scope, &n.value);
+                match n.target.as_ref() {
+                    ExprType::Name(name) => {
+                        let location = locate(self.filename, name);
+                        self.bind_as(self.owner(scope), &name.id, location, BindingKind::Variable);
+                    }
+                    target => self.target(self.owner(scope), target),
+                }
+            }
+            ExprType::Lambda(l) => {
+                self.signature(scope, &l.args);
+                let lambda = self.open(ScopeKind::Function, Some(scope));
+                self.expressions.insert(key(expr), lambda);
+                for parameter in parameters(&l.args) {
+                    self.bind(lambda, &parameter.arg, locate(self.filename, parameter));
+                }
+                self.expression(lambda, &l.body);
+            }
+            _ => children(expr).into_iter().for_each(|e| self.expression(scope, e)),
+        }
+    }
+
+    /// Bindings that hide a builtin, or a function's bindings that hide one from around the function.
+    fn shadowing(&self) -> Vec<NameIssue> {
+        let mut issues = Vec::new();
+        for (index, scope) in self.scopes.iter().enumerate() {
+            for (name, location) in &scope.bindings {
+                if name == "_" {
+                    continue;
+                }
+                let issue = |kind, shadowed| NameIssue { kind, name: name.clone(), location: location.clone(), shadowed };
+                // Class attributes are read through the class, so they hide nothing.
+                if scope.kind != ScopeKind::Class && shadows_builtin(name) {
+                    issues.push(issue(NameIssueKind::ShadowsBuiltin, None));
+                } else if scope.kind == ScopeKind::Function
+                    && let Some(outer) = self.outer_binding(index, name)
+                {
+                    issues.push(issue(NameIssueKind::ShadowsOuter, Some(outer.clone())));
+                }
+            }
+        }
+        issues
+    }
+
+    /// Where `name` is bound in the scopes a read in `scope` would look in after its own.
+    fn outer_binding(&self, scope: usize, name: &str) -> Option<&SourceLocation> {
+        let mut parent = self.scopes[scope].parent;
+        while let Some(index) = parent {
+            let outer = &self.scopes[index];
+            // A class's names aren't visible to the functions in it.
+            if outer.kind != ScopeKind::Class
+                && let Some(location) = outer.bindings.get(name)
+            {
+                return Some(location);
+            }
+            parent = outer.parent;
+        }
+        None
+    }
+
+    /// Whether a read of `name` in `scope` that doesn't find it there finds it further out.
+    fn visible_outside(&self, scope: usize, name: &str) -> bool {
+        if self.outer_binding(scope, name).is_some() || builtins().contains(name) || self.scopes[MODULE].star_import {
+            return true;
+        }
+        // Methods can read `__class__`, which `super()` uses.
+        let mut parent = self.scopes[scope].parent;
+        while let Some(index) = parent {
+            if self.scopes[index].kind == ScopeKind::Class && name == "__class__" {
+                return true;
+            }
+            parent = self.scopes[index].parent;
+        }
+        false
+    }
+}
+
+/// What has been assigned at a point in a block.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Flow {
+    /// Assigned on every path to the point.
+    definite: HashSet<String>,
+    /// Assigned on at least one path to it.
+    maybe: HashSet<String>,
+}
+
+/// The flow at a point, or `None` if nothing reaches it.
+type State = Option<Flow>;
+
+/// The flow where two paths meet.
+fn join(a: State, b: State) -> State {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(Flow {
+            definite: a.definite.intersection(&b.definite).cloned().collect(),
+            maybe: a.maybe.union(&b.maybe).cloned().collect(),
+        }),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// Where a loop's `break` and `continue` statements leave from.
+#[derive(Default)]
+struct Loop {
+    breaks: State,
+    continues: State,
+}
+
+/// Follows each block's control flow, checking each read against the scopes and what has been assigned.
+struct Checker<'a, 's> {
+    scopes: &'s Scopes<'a>,
+    lazy_annotations: bool,
+    issues: Vec<NameIssue>,
+    /// While above zero, issues aren't kept. A loop is walked until what reaches its start settles, and only
+    /// the last walk reports.
+    quiet: usize,
+    /// While above zero, reads aren't checked against the flow, as in annotations that aren't evaluated.
+    deferred: usize,
+    /// While above zero, reads of unbound names aren't reported, as in the body of a `try` that catches the
+    /// `NameError` they'd raise.
+    guarded: usize,
+    loops: Vec<Loop>,
+    /// Each assignment so far, with its scope, so a `try` can tell what its body may have assigned before it
+    /// failed.
+    assigned: Vec<(usize, String)>,
+    /// The statement being checked, for reads that have no position of their own.
+    statement: SourceLocation,
+}
+
+impl Checker<'_, '_> {
+    fn report(&mut self, kind: NameIssueKind, name: &str, location: SourceLocation) {
+        if self.quiet == 0 && self.guarded == 0 {
+            self.issues.push(NameIssue { kind, name: name.to_string(), location, shadowed: None });
+        }
+    }
+
+    fn locate(&self, node: &impl Node) -> SourceLocation {
+        match node.lineno() {
+            Some(_) => locate(self.scopes.filename, node),
+            None => self.statement.clone(),
+        }
+    }
+
+    fn assign(&mut self, scope: usize, state: &mut State, name: &str) {
+        if let Some(flow) = state {
+            flow.definite.insert(name.to_string());
+            flow.maybe.insert(name.to_string());
+            self.assigned.push((self.scopes.owner(scope), name.to_string()));
+        }
+    }
+
+    fn unbind(&mut self, state: &mut State, name: &str) {
+        if let Some(flow) = state {
+            flow.definite.remove(name);
+            flow.maybe.remove(name);
+        }
+    }
+
+    fn read(&mut self, scope: usize, name: &str, location: SourceLocation, state: &State) {
+        let Some(flow) = state else { return };
+        let info = &self.scopes.scopes[scope];
+        if info.kind == ScopeKind::Comprehension && !info.bindings.contains_key(name) {
+            // A comprehension runs where it is, so other names are read as if there.
+            return self.read(info.parent.unwrap_or(MODULE), name, location, state);
+        }
+        let implicit = info.kind == ScopeKind::Class && matches!(name, "__module__" | "__qualname__");
+        if info.globals.contains(name) || info.nonlocals.contains(name) || implicit {
+            return;
+        }
+        if info.bindings.contains_key(name) {
+            let assigned = flow.definite.contains(name) || info.external.contains(name) || self.deferred > 0;
+            // A class body or module that hasn't assigned a name yet reads it from further out instead.
+            let falls_back = matches!(info.kind, ScopeKind::Class | ScopeKind::Module) && self.scopes.visible_outside(scope, name);
+            if assigned || falls_back || info.kind == ScopeKind::Comprehension {
+                return;
+            }
+            let kind = if flow.maybe.contains(name) {
+                NameIssueKind::PossiblyUsedBeforeAssignment
+            } else {
+                NameIssueKind::UsedBeforeAssignment
+            };
+            self.report(kind, name, location);
+        } else if !self.scopes.visible_outside(scope, name) {
+            self.report(NameIssueKind::Undefined, name, location);
+        }
+    }
+
+    fn block(&mut self, scope: usize, body: &[Statement], mut state: State) -> State {
+        for statement in body {
+            state = self.statement(scope, statement, state);
+        }
+        state
+    }
+
+    fn statement(&mut self, scope: usize, statement: &Statement, mut state: State) -> State {
+        // Code nothing reaches is left alone.
+        state.as_ref()?;
+        self.statement = locate(self.scopes.filename, statement);
+        match &statement.statement {
+            StatementType::FunctionDef(f) | StatementType::AsyncFunctionDef(f) => {
+                f.decorator_list.iter().for_each(|d| self.expression(scope, d, &mut state));
+                self.signature(scope, &f.args, f.returns.as_deref(), &mut state);
+                let function = self.scopes.statements[&key(statement)];
+                self.function(function, &f.args, |checker, state| checker.block(function, &f.body, state));
+                self.assign(scope, &mut state, &f.name);
+            }
+            StatementType::ClassDef(c) => {
+                c.decorator_list.iter().for_each(|d| self.expression(scope, d, &mut state));
+                for base in &c.bases {
+                    let location = self.locate(base);
+                    self.read(scope, &base.id, location, &state);
+                }
+                c.keywords.iter().for_each(|k| self.expression(scope, &k.value, &mut state));
+                let class = self.scopes.statements[&key(statement)];
+                let loops = std::mem::take(&mut self.loops);
+                self.block(class, &c.body, Some(Flow::default()));
+                self.loops = loops;
+                self.assign(scope, &mut state, &c.name);
+            }
+            StatementType::Assign(a) => {
+                self.expression(scope, &a.value, &mut state);
+                a.targets.iter().for_each(|t| self.target(scope, t, &mut state));
+            }
+            StatementType::AugAssign(a) => {
+                self.expression(scope, &a.target, &mut state);
+                self.expression(scope, &a.value, &mut state);
+                self.target(scope, &a.target, &mut state);
+            }
+            StatementType::For(f) => return self.for_loop(scope, &f.target, &f.iter, &f.body, &f.orelse, state),
+            StatementType::AsyncFor(f) => return self.for_loop(scope, &f.target, &f.iter, &f.body, &f.orelse, state),
+            StatementType::While(w) => return self.while_loop(scope, &w.test, &w.body, &w.orelse, state),
+            StatementType::If(i) => {
+                self.expression(scope, &i.test, &mut state);
+                let body = self.block(scope, &i.body, state.clone());
+                let orelse = self.block(scope, &i.orelse, state);
+                return join(body, orelse);
+            }
+            StatementType::With(w) => return self.with(scope, &w.items, &w.body, state),
+            StatementType::AsyncWith(w) => return self.with(scope, &w.items, &w.body, state),
+            StatementType::Try(t) => return self.try_block(scope, t, state),
+            StatementType::Return(value) => {
+                if let Some(e) = value {
+                    self.expression(scope, &e.value, &mut state);
+                }
+                return None;
+            }
+            StatementType::Raise(r) => {
+                r.exc.iter().chain(&r.cause).for_each(|e| self.expression(scope, e, &mut state));
+                return None;
+            }
+            StatementType::Break | StatementType::Continue => {
+                if let Some(exits) = self.loops.last_mut() {
+                    let exit = match statement.statement {
+                        StatementType::Break => &mut exits.breaks,
+                        _ => &mut exits.continues,
+                    };
+                    *exit = join(exit.take(), state);
+                }
+                return None;
+            }
+            StatementType::Import(i) => {
+                for alias in &i.names {
+                    self.assign(scope, &mut state, &import_binding(&alias.name, alias.asname.as_ref()));
+                }
+            }
+            StatementType::ImportFrom(i) => {
+                for alias in i.names.iter().filter(|a| a.name != "*") {
+                    self.assign(scope, &mut state, alias.asname.as_ref().unwrap_or(&alias.name));
+                }
+            }
+            StatementType::Expr(e) => self.expression(scope, &e.value, &mut state),
+            StatementType::Call(c) => call_parts(c).for_each(|e| self.expression(scope, e, &mut state)),
+            StatementType::Unimplemented(source) => {
+                // Its names are only known from the source, so they're placed where it starts. A compound
+                // statement such as `match` may bind a name before reading it, so those aren't checked.
+                if let (None, Ok((loaded, stored))) = (declaration(source), boundary_names(source, "exec", false)) {
+                    let start = &self.statement;
+                    let location = SourceLocation::with_position(start.filename.clone(), start.line, start.column);
+                    for name in loaded.iter().filter(|name| !stored.contains(name)) {
+                        self.read(scope, name, location.clone(), &state);
+                    }
+                    // `del` needs the name bound, and leaves it unbound.
+                    for name in deleted_names(source).unwrap_or_default() {
+                        self.read(scope, &name, location.clone(), &state);
+                        self.unbind(&mut state, &name);
+                    }
+                    for name in stored {
+                        self.assign(scope, &mut state, &name);
+                    }
+                }
+            }
+            StatementType::Pass => {}
+        }
+        state
+    }
+
+    /// Checks a function or lambda body, which starts with only its parameters assigned.
+    fn function(&mut self, function: usize, args: &Arguments, body: impl FnOnce(&mut Self, State) -> State) {
+        let mut state = Some(Flow::default());
+        for parameter in parameters(args) {
+            self.assign(function, &mut state, &parameter.arg);
+        }
+        let loops = std::mem::take(&mut self.loops);
+        let guarded = std::mem::take(&mut self.guarded);
+        body(self, state);
+        self.loops = loops;
+        self.guarded = guarded;
+    }
+
+    fn signature(&mut self, scope: usize, args: &Arguments, returns: Option<&ExprType>, state: &mut State) {
+        let defaults = args.defaults.iter().map(|d| d.as_ref()).chain(args.kw_defaults.iter().flatten().map(|d| d.as_ref()));
+        defaults.for_each(|d| self.expression(scope, d, state));
+        let annotations = parameters(args).filter_map(|p| p.annotation.as_deref()).chain(returns);
+        self.deferred += usize::from(self.lazy_annotations);
+        annotations.for_each(|a| self.expression(scope, a, state));
+        self.deferred -= usize::from(self.lazy_annotations);
+    }
+
+    /// What reaches the start of a loop: what enters it, and what comes round again. The body is walked, without
+    /// reporting, until that settles.
+    fn loop_head(&mut self, entry: State, mut body: impl FnMut(&mut Self, State) -> State) -> State {
+        self.quiet += 1;
+        let mut head = entry.clone();
+        loop {
+            self.loops.push(Loop::default());
+            let end = body(self, head.clone());
+            let exits = self.loops.pop().unwrap_or_default();
+            let next = join(entry.clone(), join(end, exits.continues));
+            if next == head {
+                break;
+            }
+            head = next;
+        }
+        self.quiet -= 1;
+        head
+    }
+
+    fn for_loop(
+        &mut self,
+        scope: usize,
+        target: &ExprType,
+        iter: &ExprType,
+        body: &[Statement],
+        orelse: &[Statement],
+        mut state: State,
+    ) -> State {
+        self.expression(scope, iter, &mut state);
+        let run = |checker: &mut Self, mut state: State| {
+            checker.target(scope, target, &mut state);
+            checker.block(scope, body, state)
+        };
+        let head = self.loop_head(state, run);
+        self.loops.push(Loop::default());
+        run(self, head.clone());
+        let exits = self.loops.pop().unwrap_or_default();
+        // The `else` runs once the iterator is exhausted, which may be straight away.
+        join(self.block(scope, orelse, head), exits.breaks)
+    }
+
+    fn while_loop(&mut self, scope: usize, test: &ExprType, body: &[Statement], orelse: &[Statement], state: State) -> State {
+        let run = |checker: &mut Self, mut state: State| {
+            checker.expression(scope, test, &mut state);
+            checker.block(scope, body, state)
+        };
+        let mut head = self.loop_head(state, run);
+        self.loops.push(Loop::default());
+        run(self, head.clone());
+        let exits = self.loops.pop().unwrap_or_default();
+        self.quiet += 1;
+        self.expression(scope, test, &mut head);
+        self.quiet -= 1;
+        let finished = if always_true(test) { None } else { self.block(scope, orelse, head) };
+        join(finished, exits.breaks)
+    }
+
+    fn with(&mut self, scope: usize, items: &[crate::WithItem], body: &[Statement], mut state: State) -> State {
+        for item in items {
+            self.expression(scope, &item.context_expr, &mut state);
+            if let Some(vars) = &item.optional_vars {
+                self.target(scope, vars, &mut state);
+            }
+        }
+        self.block(scope, body, state)
+    }
+
+    /// `state`, but with everything assigned in `scope` since the `start`th assignment as possibly assigned.
+    fn attempted(&self, scope: usize, start: usize, state: &State) -> State {
+        let mut state = state.clone();
+        if let Some(flow) = &mut state {
+            let owner = self.scopes.owner(scope);
+            flow.maybe.extend(self.assigned[start..].iter().filter(|(s, _)| *s == owner).map(|(_, name)| name.clone()));
+        }
+        state
+    }
+
+    fn try_block(&mut self, scope: usize, t: &Try, state: State) -> State {
+        let start = self.assigned.len();
+        // `try: name` / `except NameError:` checks whether a name exists.
+        let probe = t.handlers.iter().any(|h| match &h.exception_type {
+            Some(ExprType::Name(n)) => n.id == "NameError",
+            Some(_) => false,
+            None => true,
+        });
+        self.guarded += usize::from(probe);
+        let body = self.block(scope, &t.body, state.clone());
+        self.guarded -= usize::from(probe);
+        // An exception can come from anywhere in the body, so a handler may see any of its assignments, but
+        // can only count on what came before it.
+        let failed = self.attempted(scope, start, &state);
+        let mut normal = self.block(scope, &t.orelse, body);
+        for handler in &t.handlers {
+            let mut entry = failed.clone();
+            if let Some(exception_type) = &handler.exception_type {
+                self.expression(scope, exception_type, &mut entry);
+            }
+            if let Some(name) = &handler.name {
+                self.assign(scope, &mut entry, name);
+            }
+            normal = join(normal, self.block(scope, &handler.body, entry));
+        }
+        if t.finalbody.is_empty() {
+            return normal;
+        }
+        // `finally` also runs when the rest stopped part way, so it's checked against that, then walked again
+        // for what carries on after it.
+        let interrupted = self.attempted(scope, start, &state);
+        self.block(scope, &t.finalbody, join(interrupted, normal.clone()));
+        self.quiet += 1;
+        let after = self.block(scope, &t.finalbody, normal);
+        self.quiet -= 1;
+        after
+    }
+
+    fn target(&mut self, scope: usize, target: &ExprType, state: &mut State) {
+        match target {
+            ExprType::Name(n) => self.assign(scope, state, &n.id),
+            ExprType::Tuple(t) => t.elts.iter().for_each(|e| self.target(scope, e, state)),
+            ExprType::List(elts) => elts.iter().for_each(|e| self.target(scope, e, state)),
+            ExprType::Starred(s) => self.target(scope, &s.value, state),
+            _ => self.expression(scope, target, state),
+        }
+    }
+
+    fn expression(&mut self, scope: usize, expr: &ExprType, state: &mut State) {
+        if let Some((generators, results)) = comprehension(expr) {
+            let inner = self.scopes.expressions[&key(expr)];
+            if let Some(first) = generators.first() {
+                self.expression(scope, &first.iter, state);
+            }
+            for (i, generator) in generators.iter().enumerate() {
+                if i > 0 {
+                    self.expression(inner, &generator.iter, state);
+                }
+                // The comprehension's own variables aren't assigned in the flow around it.
+                self.target(inner, &generator.target, &mut state.clone());
+                generator.ifs.iter().for_each(|e| self.expression(inner, e, state));
+            }
+            results.into_iter().for_each(|e| self.expression(inner, e, state));
+            return;
+        }
+        match expr {
+            ExprType::Name(n) => {
+                let location = self.locate(n);
+                self.read(scope, &n.id, location, state);
+            }
+            ExprType::NamedExpr(n) => {
+                self.expression(scope, &n.value, state);
+                self.target(scope, &n.target, state);
+            }
+            ExprType::Lambda(l) => {
+                self.signature(scope, &l.args, None, state);
+                let lambda = self.scopes.expressions[&key(expr)];
+                self.function(lambda, &l.args, |checker, mut state| {
+                    checker.expression(lambda, &l.body, &mut state);
+                    state
+                });
+            }
+            _ => children(expr).into_iter().for_each(|e| self.expression(scope, e, state)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issues(source: &str) -> Vec<(NameIssueKind, String, Option<usize>, Option<usize>)> {
+        let module = crate::parse(source, "test.py").unwrap();
+        check_names(&module).into_iter().map(|i| (i.kind, i.name, i.location.line, i.location.column)).collect()
+    }
+
+    fn kinds(source: &str) -> Vec<(NameIssueKind, String)> {
+        issues(source).into_iter().map(|(kind, name, _, _)| (kind, name)).collect()
+    }
+
+    #[test]
+    fn test_undefined_names() {
+        let source = "import os\ndef f(a):\n    return os.path.join(a, b, len(c))\nclass C:\n    x = 1\n    def m(self):\n        return x\n";
+        assert_eq!(
+            issues(source),
+            [
+                (NameIssueKind::Undefined, "b".to_string(), Some(3), Some(28)),
+                (NameIssueKind::Undefined, "c".to_string(), Some(3), Some(35)),
+                // Methods can't see the class's names.
+                (NameIssueKind::Undefined, "x".to_string(), Some(7), Some(16)),
+            ]
+        );
+        // Functions can read globals bound after them, and anything a star import might bind.
+        assert!(issues("def f():\n    return g()\ndef g():\n    return h\nfrom m import *\n").is_empty());
+    }
+
+    #[test]
+    fn test_use_before_assignment() {
+        let source = "def f(flag):\n    print(total)\n    if flag:\n        value = 1\n    print(value)\n    total = 0\n";
+        assert_eq!(
+            kinds(source),
+            [
+                (NameIssueKind::UsedBeforeAssignment, "total".to_string()),
+                (NameIssueKind::PossiblyUsedBeforeAssignment, "value".to_string()),
+            ]
+        );
+        // Every path assigns, or the ones that don't leave first.
+        let source = "def f(xs):\n    if xs:\n        y = 1\n    else:\n        return\n    try:\n        z = g()\n    except ValueError:\n        z = None\n    return y, z\ndef g():\n    pass\n";
+        assert!(issues(source).is_empty(), "{:?}", issues(source));
+    }
+
+    #[test]
+    fn test_loops() {
+        // Assigned in an earlier iteration, or never if the loop doesn't run.
+        let source = "def f(xs):\n    for x in xs:\n        if x:\n            print(last)\n        last = x\n    return last\n";
+        assert_eq!(
+            kinds(source),
+            [
+                (NameIssueKind::PossiblyUsedBeforeAssignment, "last".to_string()),
+                (NameIssueKind::PossiblyUsedBeforeAssignment, "last".to_string()),
+            ]
+        );
+        // `while True` only leaves through its `break`.
+        let source = "def f():\n    while True:\n        line = input()\n        if line:\n            break\n    return line\n";
+        assert!(issues(source).is_empty());
+    }
+
+    #[test]
+    fn test_try_and_finally() {
+        let source = "def f():\n    try:\n        handle = open('x')\n        data = handle.read()\n    except OSError:\n        print(data)\n        raise\n    finally:\n        handle.close()\n    return data\n";
+        assert_eq!(
+            kinds(source),
+            [
+                (NameIssueKind::PossiblyUsedBeforeAssignment, "data".to_string()),
+                (NameIssueKind::PossiblyUsedBeforeAssignment, "handle".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scopes() {
+        // Comprehensions, lambdas, nested functions, `global` and `nonlocal`.
+        let source = "\
+def outer():
+    count = 0
+    def bump():
+        nonlocal count
+        count += 1
+    bump()
+    squares = [n * n for n in range(count) if n]
+    key = lambda item: item[0]
+    return squares, key, count
+def configure():
+    global settings
+    settings = {}
+configure()
+print(settings, __name__, __file__)
+";
+        assert!(issues(source).is_empty(), "{:?}", issues(source));
+        assert_eq!(kinds("print([n for n in range(3)], n)\n"), [(NameIssueKind::Undefined, "n".to_string())]);
+        // Reading a local before assigning it doesn't fall back to the global.
+        assert_eq!(
+            kinds("x = 1\ndef f():\n    print(x)\n    x = 2\n"),
+            [
+                (NameIssueKind::UsedBeforeAssignment, "x".to_string()),
+                (NameIssueKind::ShadowsOuter, "x".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let source = "import json\ndef load(json, list=None):\n    id = 1\n    return json, list, id\nclass Row:\n    id = 0\n";
+        let found = check_names(&crate::parse(source, "test.py").unwrap());
+        let summary: Vec<_> = found.iter().map(|i| (i.kind, i.name.as_str(), i.location.line, i.location.column)).collect();
+        assert_eq!(
+            summary,
+            [
+                (NameIssueKind::ShadowsOuter, "json", Some(2), Some(10)),
+                (NameIssueKind::ShadowsBuiltin, "list", Some(2), Some(16)),
+                (NameIssueKind::ShadowsBuiltin, "id", Some(3), Some(5)),
+            ]
+        );
+        assert_eq!(found[0].shadowed.as_ref().and_then(|l| l.line), Some(1));
+        assert_eq!(found[0].to_string(), "test.py:2:10-14: warning[shadowed-name]: `json` shadows the `json` bound on line 1");
+    }
+
+    #[test]
+    fn test_unsupported_statements() {
+        // `assert`, `del` and annotated assignments are only kept as source.
+        let source = "def f(x):\n    assert x, message\n    total: int = x\n    return total\n";
+        assert_eq!(issues(source), [(NameIssueKind::Undefined, "message".to_string(), Some(2), Some(5))]);
+    }
+
+    #[test]
+    fn test_del_unbinds() {
+        let source = "def f(flag):\n    a = 1\n    b = 2\n    del a\n    if flag:\n        del b\n    print(a, b)\n    del a\n";
+        assert_eq!(
+            kinds(source),
+            [
+                (NameIssueKind::UsedBeforeAssignment, "a".to_string()),
+                (NameIssueKind::PossiblyUsedBeforeAssignment, "b".to_string()),
+                (NameIssueKind::UsedBeforeAssignment, "a".to_string()),
+            ]
+        );
+        // Assigning it again binds it again.
+        assert!(issues("def f():\n    a = 1\n    del a\n    a = 2\n    return a\n").is_empty());
+    }
+}
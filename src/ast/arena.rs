@@ -0,0 +1,472 @@
+//! A flat, indexed view of a parsed module.
+//!
+//! The tree in [`crate::tree`] is owned and nested: a statement holds its expressions, which hold theirs, and
+//! nothing points back up. That suits code generation, but analysis passes often need to ask where a node sits —
+//! which statement an expression belongs to, which function a statement is in — or to refer to a node without
+//! holding a borrow of it. [`Arena`] numbers every node of a [`Module`] in pre-order and records its parent,
+//! children and span, so those questions become index lookups.
+//!
+//! The arena borrows the module rather than copying it, so building one does not clone any part of the tree.
+
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    Node,
+    tree::{Alias, Arguments, Comprehension, ExceptHandler, ExprType, Keyword, Module, Parameter, Statement, WithItem},
+    visit::{Visitor, walk_body, walk_comprehension, walk_except_handler, walk_expr, walk_stmt},
+};
+
+/// The identifier of a node in an [`Arena`]. Identifiers are assigned in pre-order starting from the module at 0,
+/// so a node's descendants always have larger identifiers than the node itself, and they are stable for as long
+/// as the arena lives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// The position of the node in the arena's pre-order.
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// A borrowed node of the original tree.
+///
+/// A call written as a statement on its own is a [`StatementType::Call`](crate::StatementType::Call) in this
+/// crate rather than an expression inside an `Expr` statement, so it appears in the arena as a single
+/// `Statement` node whose children are the callee and the arguments.
+#[derive(Clone, Copy, Debug)]
+pub enum NodeRef<'a> {
+    Module(&'a Module),
+    Statement(&'a Statement),
+    Expr(&'a ExprType),
+    Arguments(&'a Arguments),
+    Parameter(&'a Parameter),
+    Keyword(&'a Keyword),
+    Comprehension(&'a Comprehension),
+    ExceptHandler(&'a ExceptHandler),
+    WithItem(&'a WithItem),
+    Alias(&'a Alias),
+}
+
+impl NodeRef<'_> {
+    /// The kind and address of the node, which identify it within the tree it was borrowed from. The kind is
+    /// needed as well because a node and its first field can share an address.
+    fn key(&self) -> (u8, usize) {
+        fn address<T>(node: &T) -> usize {
+            node as *const T as usize
+        }
+        match self {
+            NodeRef::Module(n) => (0, address(*n)),
+            NodeRef::Statement(n) => (1, address(*n)),
+            NodeRef::Expr(n) => (2, address(*n)),
+            NodeRef::Arguments(n) => (3, address(*n)),
+            NodeRef::Parameter(n) => (4, address(*n)),
+            NodeRef::Keyword(n) => (5, address(*n)),
+            NodeRef::Comprehension(n) => (6, address(*n)),
+            NodeRef::ExceptHandler(n) => (7, address(*n)),
+            NodeRef::WithItem(n) => (8, address(*n)),
+            NodeRef::Alias(n) => (9, address(*n)),
+        }
+    }
+
+    /// The span the node records for itself, if it records one.
+    fn own_span(&self) -> Option<Span> {
+        match self {
+            NodeRef::Statement(n) => Span::of(*n),
+            NodeRef::Expr(n) => Span::of(*n),
+            NodeRef::Parameter(n) => Span::of(*n),
+            NodeRef::Keyword(n) => Span::of(*n),
+            NodeRef::ExceptHandler(n) => Span::of(*n),
+            _ => None,
+        }
+    }
+}
+
+/// The source range a node covers, with 1-based lines and 0-based columns as in Python's `ast`. The end is
+/// exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Span {
+    pub lineno: usize,
+    pub col_offset: usize,
+    pub end_lineno: usize,
+    pub end_col_offset: usize,
+}
+
+impl Span {
+    fn of(node: &impl Node) -> Option<Self> {
+        let lineno = node.lineno()?;
+        let col_offset = node.col_offset()?;
+        Some(Self {
+            lineno,
+            col_offset,
+            end_lineno: node.end_lineno().unwrap_or(lineno),
+            end_col_offset: node.end_col_offset().unwrap_or(col_offset),
+        })
+    }
+
+    /// Whether the position, with a 1-based line and 0-based column, is inside the span.
+    pub fn contains(&self, lineno: usize, col_offset: usize) -> bool {
+        let position = (lineno, col_offset);
+        (self.lineno, self.col_offset) <= position && position < (self.end_lineno, self.end_col_offset)
+    }
+
+    /// The smallest span covering both spans.
+    pub fn union(self, other: Span) -> Span {
+        let (lineno, col_offset) = (self.lineno, self.col_offset).min((other.lineno, other.col_offset));
+        let (end_lineno, end_col_offset) =
+            (self.end_lineno, self.end_col_offset).max((other.end_lineno, other.end_col_offset));
+        Span { lineno, col_offset, end_lineno, end_col_offset }
+    }
+}
+
+impl Node for Span {
+    fn lineno(&self) -> Option<usize> {
+        Some(self.lineno)
+    }
+
+    fn col_offset(&self) -> Option<usize> {
+        Some(self.col_offset)
+    }
+
+    fn end_lineno(&self) -> Option<usize> {
+        Some(self.end_lineno)
+    }
+
+    fn end_col_offset(&self) -> Option<usize> {
+        Some(self.end_col_offset)
+    }
+}
+
+#[derive(Debug)]
+struct Entry<'a> {
+    node: NodeRef<'a>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    /// One past the last descendant, so the subtree is the contiguous range of identifiers up to here.
+    end: usize,
+    span: Option<Span>,
+}
+
+/// Every node of a [`Module`], indexed by [`NodeId`], with links to parents and children.
+///
+/// Nodes that carry positions in the original tree keep them. Nodes that do not, such as calls, attributes and
+/// argument lists, are given the smallest span covering their children, so every node with any positioned
+/// descendant has a span.
+#[derive(Debug)]
+pub struct Arena<'a> {
+    entries: Vec<Entry<'a>>,
+    ids: HashMap<(u8, usize), NodeId>,
+}
+
+impl<'a> Arena<'a> {
+    /// Index every node of the module.
+    pub fn new(module: &'a Module) -> Self {
+        let mut builder = Builder::default();
+        let root = builder.open(NodeRef::Module(module));
+        walk_body(&mut builder, &module.raw.body);
+        builder.close(root);
+
+        let mut entries = builder.entries;
+        for index in (0..entries.len()).rev() {
+            if entries[index].span.is_none() {
+                entries[index].span = entries[index]
+                    .children
+                    .iter()
+                    .filter_map(|child| entries[child.0].span)
+                    .reduce(Span::union);
+            }
+        }
+        let ids = entries.iter().enumerate().map(|(index, entry)| (entry.node.key(), NodeId(index))).collect();
+        Self { entries, ids }
+    }
+
+    /// The module itself.
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    /// The number of nodes in the arena.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the arena has no nodes. An arena built from a module always has at least the module.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The node with the given identifier. Panics if the identifier came from a different arena and is out of
+    /// range; use [`Arena::get`] when that is possible.
+    pub fn node(&self, id: NodeId) -> NodeRef<'a> {
+        self.entries[id.0].node
+    }
+
+    /// The node with the given identifier, if there is one.
+    pub fn get(&self, id: NodeId) -> Option<NodeRef<'a>> {
+        self.entries.get(id.0).map(|entry| entry.node)
+    }
+
+    /// The identifier of a node borrowed from the module the arena was built from.
+    pub fn id_of(&self, node: NodeRef<'_>) -> Option<NodeId> {
+        self.ids.get(&node.key()).copied()
+    }
+
+    /// The node's parent, or `None` for the module.
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.entries[id.0].parent
+    }
+
+    /// The node's direct children, in the order they are visited.
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.entries[id.0].children
+    }
+
+    /// The node's parent, its parent's parent, and so on up to the module.
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(self.parent(id), |&id| self.parent(id))
+    }
+
+    /// Every node below this one, in pre-order.
+    pub fn descendants(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        (id.0 + 1..self.entries[id.0].end).map(NodeId)
+    }
+
+    /// Whether `descendant` is `ancestor` or somewhere below it.
+    pub fn contains(&self, ancestor: NodeId, descendant: NodeId) -> bool {
+        (ancestor.0..self.entries[ancestor.0].end).contains(&descendant.0)
+    }
+
+    /// The source range the node covers, if it or any of its descendants has a position.
+    pub fn span(&self, id: NodeId) -> Option<Span> {
+        self.entries[id.0].span
+    }
+
+    /// The innermost node whose span contains the position, with a 1-based line and 0-based column as in
+    /// [`Span`]. Nodes without a span can't be found, so a position on a constant finds the node around it.
+    ///
+    /// A node's span doesn't always cover its children: a function's decorators come before its `def`. The node
+    /// found is the deepest one containing the position, rather than the end of a walk down from the module.
+    pub fn node_at(&self, lineno: usize, col_offset: usize) -> Option<NodeId> {
+        let mut found: Option<(usize, NodeId)> = None;
+        for (id, _) in self.iter() {
+            if self.span(id).is_some_and(|span| span.contains(lineno, col_offset)) {
+                let depth = self.ancestors(id).count();
+                if found.is_none_or(|(deepest, _)| depth >= deepest) {
+                    found = Some((depth, id));
+                }
+            }
+        }
+        found.map(|(_, id)| id)
+    }
+
+    /// Every node with its identifier, in pre-order.
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, NodeRef<'a>)> + '_ {
+        self.entries.iter().enumerate().map(|(index, entry)| (NodeId(index), entry.node))
+    }
+}
+
+impl<'a> From<&'a Module> for Arena<'a> {
+    fn from(module: &'a Module) -> Self {
+        Self::new(module)
+    }
+}
+
+/// Walks the module, appending each node as it is entered and keeping the chain of open nodes as the parents of
+/// whatever is visited next.
+#[derive(Default)]
+struct Builder<'a> {
+    entries: Vec<Entry<'a>>,
+    open: Vec<NodeId>,
+}
+
+impl<'a> Builder<'a> {
+    fn open(&mut self, node: NodeRef<'a>) -> NodeId {
+        let id = NodeId(self.entries.len());
+        let parent = self.open.last().copied();
+        if let Some(parent) = parent {
+            self.entries[parent.0].children.push(id);
+        }
+        self.entries.push(Entry { node, parent, children: Vec::new(), end: id.0 + 1, span: node.own_span() });
+        self.open.push(id);
+        id
+    }
+
+    fn close(&mut self, id: NodeId) {
+        self.open.pop();
+        self.entries[id.0].end = self.entries.len();
+    }
+
+    fn leaf(&mut self, node: NodeRef<'a>) {
+        let id = self.open(node);
+        self.close(id);
+    }
+
+    fn parameter(&mut self, parameter: &'a Parameter) {
+        let id = self.open(NodeRef::Parameter(parameter));
+        if let Some(annotation) = &parameter.annotation {
+            self.visit_expr(annotation);
+        }
+        self.close(id);
+    }
+}
+
+impl<'a> Visitor<'a> for Builder<'a> {
+    fn visit_stmt(&mut self, stmt: &'a Statement) {
+        let id = self.open(NodeRef::Statement(stmt));
+        walk_stmt(self, stmt);
+        self.close(id);
+    }
+
+    fn visit_expr(&mut self, expr: &'a ExprType) {
+        let id = self.open(NodeRef::Expr(expr));
+        walk_expr(self, expr);
+        self.close(id);
+    }
+
+    /// Parameters become nodes of their own, each followed by its default, so the arena keeps source order
+    /// where [`walk_arguments`](crate::walk_arguments) visits all the defaults first.
+    fn visit_arguments(&mut self, arguments: &'a Arguments) {
+        let id = self.open(NodeRef::Arguments(arguments));
+        let positional: Vec<_> = arguments.posonlyargs.iter().chain(&arguments.args).collect();
+        let first_default = positional.len().saturating_sub(arguments.defaults.len());
+        for (index, parameter) in positional.into_iter().enumerate() {
+            self.parameter(parameter);
+            if let Some(default) = index.checked_sub(first_default).and_then(|i| arguments.defaults.get(i)) {
+                self.visit_expr(default);
+            }
+        }
+        if let Some(vararg) = &arguments.vararg {
+            self.parameter(vararg);
+        }
+        for (index, parameter) in arguments.kwonlyargs.iter().enumerate() {
+            self.parameter(parameter);
+            if let Some(Some(default)) = arguments.kw_defaults.get(index) {
+                self.visit_expr(default);
+            }
+        }
+        if let Some(kwarg) = &arguments.kwarg {
+            self.parameter(kwarg);
+        }
+        self.close(id);
+    }
+
+    fn visit_keyword(&mut self, keyword: &'a Keyword) {
+        let id = self.open(NodeRef::Keyword(keyword));
+        self.visit_expr(&keyword.value);
+        self.close(id);
+    }
+
+    fn visit_comprehension(&mut self, comprehension: &'a Comprehension) {
+        let id = self.open(NodeRef::Comprehension(comprehension));
+        walk_comprehension(self, comprehension);
+        self.close(id);
+    }
+
+    fn visit_except_handler(&mut self, handler: &'a ExceptHandler) {
+        let id = self.open(NodeRef::ExceptHandler(handler));
+        walk_except_handler(self, handler);
+        self.close(id);
+    }
+
+    fn visit_with_item(&mut self, item: &'a WithItem) {
+        let id = self.open(NodeRef::WithItem(item));
+        self.visit_expr(&item.context_expr);
+        if let Some(vars) = &item.optional_vars {
+            self.visit_expr(vars);
+        }
+        self.close(id);
+    }
+
+    fn visit_alias(&mut self, alias: &'a Alias) {
+        self.leaf(NodeRef::Alias(alias));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{StatementType, parse};
+
+    fn find<'a>(arena: &Arena<'a>, source: &str) -> NodeId {
+        arena
+            .iter()
+            .find(|(_, node)| matches!(node, NodeRef::Expr(e) if e.to_python_source().is_ok_and(|s| s == source)))
+            .map(|(id, _)| id)
+            .unwrap_or_else(|| panic!("no expression {source}"))
+    }
+
+    #[test]
+    fn test_parents_and_children() {
+        let module = parse("x = 1\nif x:\n    y = f(x)\n", "test.py").unwrap();
+        let arena = Arena::new(&module);
+
+        assert!(matches!(arena.node(arena.root()), NodeRef::Module(_)));
+        assert_eq!(arena.parent(arena.root()), None);
+        assert_eq!(arena.children(arena.root()).len(), 2);
+
+        let call = find(&arena, "f(x)");
+        let assign = arena.parent(call).unwrap();
+        assert!(matches!(arena.node(assign), NodeRef::Statement(s) if matches!(s.statement, StatementType::Assign(_))));
+        let chain: Vec<_> = arena.ancestors(call).collect();
+        assert_eq!(chain.len(), 3);
+        assert_eq!(chain.last(), Some(&arena.root()));
+        for &child in arena.children(assign) {
+            assert_eq!(arena.parent(child), Some(assign));
+        }
+    }
+
+    #[test]
+    fn test_spans_are_derived_for_unpositioned_nodes() {
+        let module = parse("value = compute(first, second)\n", "test.py").unwrap();
+        let arena = Arena::new(&module);
+
+        let call = find(&arena, "compute(first, second)");
+        assert_eq!(arena.span(call), Some(Span { lineno: 1, col_offset: 8, end_lineno: 1, end_col_offset: 29 }));
+        assert_eq!(arena.span(arena.root()), Some(Span { lineno: 1, col_offset: 0, end_lineno: 1, end_col_offset: 30 }));
+    }
+
+    #[test]
+    fn test_descendants_are_the_subtree() {
+        let module = parse("def f(a, b=g(1), *, c: int = 2):\n    return a\nz = 3\n", "test.py").unwrap();
+        let arena = Arena::new(&module);
+
+        let function = arena.children(arena.root())[0];
+        let descendants: Vec<_> = arena.descendants(function).collect();
+        assert!(descendants.iter().all(|&d| arena.contains(function, d)));
+        assert!(descendants.iter().all(|&d| arena.ancestors(d).any(|a| a == function)));
+        let z = find(&arena, "z");
+        assert!(!arena.contains(function, z));
+
+        // Each default follows its parameter.
+        let arguments = arena.children(function)[0];
+        let order: Vec<_> = arena
+            .children(arguments)
+            .iter()
+            .map(|&id| match arena.node(id) {
+                NodeRef::Parameter(p) => p.arg.clone(),
+                NodeRef::Expr(e) => e.to_python_source().unwrap(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(order, ["a", "b", "g(1)", "c", "2"]);
+    }
+
+    #[test]
+    fn test_id_of_round_trips() {
+        let module = parse("import os\ntry:\n    os.remove(path)\nexcept OSError as e:\n    pass\n", "test.py").unwrap();
+        let arena = Arena::from(&module);
+
+        for (id, node) in arena.iter() {
+            assert_eq!(arena.id_of(node), Some(id));
+        }
+        assert_eq!(arena.id_of(NodeRef::Module(&module)), Some(arena.root()));
+        let other = parse("import os\n", "other.py").unwrap();
+        assert_eq!(arena.id_of(NodeRef::Module(&other)), None);
+    }
+}
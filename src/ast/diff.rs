@@ -0,0 +1,572 @@
+//! Structural diffs: the edits that turn one module into another, described in terms of the code rather than
+//! its lines, such as "function `parse` changed the default of `strict` from `False` to `True`".
+//!
+//! Nodes are compared with [`StructuralEq`], so code that only moved to other lines, or whose comments changed,
+//! is the same. Definitions are matched by name within each body, and other statements by an alignment of the
+//! bodies they're in; a statement that takes the place of one of the same kind is an update, and what is left
+//! over was inserted or deleted. An insertion of what was deleted elsewhere is a move.
+
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::{
+    ClassDef, ExprType, FunctionDef, Keyword, Module, Parameter, SourceLocation, Statement, StatementType, ToAstJson,
+    locate, nested_bodies,
+};
+
+/// Equality that ignores where nodes are, and the comments around them, for comparing code across edits.
+/// Nodes that are `==` are always structurally equal.
+pub trait StructuralEq {
+    fn structurally_eq(&self, other: &Self) -> bool;
+}
+
+impl<T: ToAstJson + PartialEq> StructuralEq for T {
+    fn structurally_eq(&self, other: &Self) -> bool {
+        self == other || without_positions(self.to_ast_json()) == without_positions(other.to_ast_json())
+    }
+}
+
+/// The JSON of a node without its positions, comments or other extras.
+fn without_positions(value: Value) -> Value {
+    const POSITIONS: [&str; 4] = ["lineno", "col_offset", "end_lineno", "end_col_offset"];
+    match value {
+        Value::Object(node) => node
+            .into_iter()
+            .filter(|(key, _)| !POSITIONS.contains(&key.as_str()) && (key == "_type" || !key.starts_with('_')))
+            .map(|(key, value)| (key, without_positions(value)))
+            .collect(),
+        Value::Array(items) => items.into_iter().map(without_positions).collect(),
+        value => value,
+    }
+}
+
+/// What an [`Edit`] did.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditKind {
+    Inserted,
+    Deleted,
+    Moved,
+    Updated,
+}
+
+impl fmt::Display for EditKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EditKind::Inserted => "inserted",
+            EditKind::Deleted => "deleted",
+            EditKind::Moved => "moved",
+            EditKind::Updated => "updated",
+        })
+    }
+}
+
+/// One change between two modules.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edit {
+    pub kind: EditKind,
+    /// The qualified name of the definition changed, or the one the changed statement is in, such as
+    /// `Parser.parse`. Empty for statements at module level.
+    pub path: String,
+    /// The change as a sentence, such as "function `parse` added parameter `strict`".
+    pub description: String,
+    /// Where the changed code was, unless it was inserted.
+    pub old: Option<SourceLocation>,
+    /// Where the changed code is, unless it was deleted.
+    pub new: Option<SourceLocation>,
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line = |location: &Option<SourceLocation>| location.as_ref().and_then(|l| l.line);
+        match (line(&self.old), line(&self.new)) {
+            (Some(old), Some(new)) => write!(f, "{} -> {}: ", old, new)?,
+            (Some(old), None) => write!(f, "{} -> _: ", old)?,
+            (None, Some(new)) => write!(f, "_ -> {}: ", new)?,
+            (None, None) => {}
+        }
+        write!(f, "{}: {}", self.kind, self.description)
+    }
+}
+
+/// The edits that turn `old` into `new`, ordered by the line they're on in `new`, or for deletions, in `old`.
+pub fn structural_diff(old: &Module, new: &Module) -> Vec<Edit> {
+    let mut differ = Differ {
+        old_file: old.filename.as_deref().unwrap_or("<old>"),
+        new_file: new.filename.as_deref().unwrap_or("<new>"),
+        edits: Vec::new(),
+    };
+    let module = Scope { path: String::new(), description: "the module".to_string() };
+    differ.bodies(&module, &module, &old.raw.body, &new.raw.body);
+    differ.find_moves();
+    let mut edits: Vec<Edit> = differ.edits.into_iter().map(|pending| pending.edit).collect();
+    edits.sort_by_key(|edit| edit.new.as_ref().or(edit.old.as_ref()).and_then(|location| location.line));
+    edits
+}
+
+/// The definition, or module, that code is in.
+struct Scope {
+    path: String,
+    /// Such as "function `Parser.parse`".
+    description: String,
+}
+
+impl Scope {
+    fn enter(&self, name: &str, is_class: bool) -> Scope {
+        let path = if self.path.is_empty() { name.to_string() } else { format!("{}.{}", self.path, name) };
+        let kind = if is_class { "class" } else { "function" };
+        Scope { description: format!("{} `{}`", kind, path), path }
+    }
+}
+
+/// An edit, with what an insertion or deletion needs to be recognized as half of a move.
+struct Pending {
+    edit: Edit,
+    /// The inserted or deleted statement, without positions.
+    node: Option<Value>,
+    /// What was inserted or deleted, as in "function `parse`", and the description of where.
+    subject: String,
+    scope: String,
+}
+
+struct Differ<'a> {
+    old_file: &'a str,
+    new_file: &'a str,
+    edits: Vec<Pending>,
+}
+
+/// A definition's kind and name, which it's matched by.
+fn definition(statement: &Statement) -> Option<(bool, &str)> {
+    match &statement.statement {
+        StatementType::FunctionDef(f) | StatementType::AsyncFunctionDef(f) => Some((false, &f.name)),
+        StatementType::ClassDef(c) => Some((true, &c.name)),
+        _ => None,
+    }
+}
+
+/// Whether two statements are the same one for aligning bodies: definitions of the same name, or other
+/// statements that are structurally equal.
+fn corresponds(old: &Statement, new: &Statement) -> bool {
+    match (definition(old), definition(new)) {
+        (Some(old), Some(new)) => old == new,
+        (None, None) => old.structurally_eq(new),
+        _ => false,
+    }
+}
+
+/// The pairs of positions of the longest common subsequence of two bodies.
+fn align(old: &[Statement], new: &[Statement]) -> Vec<(usize, usize)> {
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if corresponds(&old[i], &new[j]) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j, mut pairs) = (0, 0, Vec::new());
+    while i < old.len() && j < new.len() {
+        if corresponds(&old[i], &new[j]) {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// The first line of the source of a statement, like `if ready:`.
+fn header(statement: &Statement) -> String {
+    statement.to_python_source().ok().and_then(|s| s.lines().next().map(str::to_string)).unwrap_or_else(|| "…".to_string())
+}
+
+fn source(expr: &ExprType) -> String {
+    expr.to_python_source().unwrap_or_else(|_| "…".to_string())
+}
+
+fn is_docstring(statement: &Statement) -> bool {
+    let json = statement.to_ast_json();
+    json["_type"] == "Expr" && json["value"]["_type"] == "Constant" && json["value"]["value"].is_string()
+}
+
+/// A parameter, with the kind of parameter it is and its default.
+struct Param<'a> {
+    parameter: &'a Parameter,
+    kind: &'static str,
+    default: Option<&'a ExprType>,
+}
+
+impl<'a> Param<'a> {
+    fn annotation(&self) -> Option<&'a ExprType> {
+        self.parameter.annotation.as_deref()
+    }
+}
+
+fn parameters(function: &FunctionDef) -> Vec<Param<'_>> {
+    let args = &function.args;
+    let positional: Vec<_> = args.posonlyargs.iter().map(|p| (p, "positional-only")).chain(args.args.iter().map(|p| (p, "positional"))).collect();
+    // The defaults belong to the last positional parameters.
+    let first_default = positional.len().saturating_sub(args.defaults.len());
+    let mut params: Vec<Param<'_>> = positional
+        .into_iter()
+        .enumerate()
+        .map(|(i, (parameter, kind))| Param { parameter, kind, default: i.checked_sub(first_default).map(|d| args.defaults[d].as_ref()) })
+        .collect();
+    params.extend(args.vararg.iter().map(|parameter| Param { parameter, kind: "variadic", default: None }));
+    params.extend(args.kwonlyargs.iter().enumerate().map(|(i, parameter)| Param {
+        parameter,
+        kind: "keyword-only",
+        default: args.kw_defaults.get(i).and_then(|d| d.as_deref()),
+    }));
+    params.extend(args.kwarg.iter().map(|parameter| Param { parameter, kind: "variadic keyword", default: None }));
+    params
+}
+
+/// Describes a change to an optional expression, such as a default or an annotation, of a parameter or, when
+/// `of` is `None`, of the definition itself.
+fn changed_expression(old: Option<&ExprType>, new: Option<&ExprType>, what: &str, of: Option<&str>) -> Option<String> {
+    match (old, new, of) {
+        (None, None, _) => None,
+        (Some(old), Some(new), _) if old.structurally_eq(new) => None,
+        (Some(old), Some(new), Some(of)) => Some(format!("changed the {} of {} from `{}` to `{}`", what, of, source(old), source(new))),
+        (Some(old), Some(new), None) => Some(format!("changed its {} from `{}` to `{}`", what, source(old), source(new))),
+        (None, Some(new), Some(of)) => Some(format!("gave {} the {} `{}`", of, what, source(new))),
+        (None, Some(new), None) => Some(format!("added the {} `{}`", what, source(new))),
+        (Some(_), None, Some(of)) => Some(format!("removed the {} of {}", what, of)),
+        (Some(_), None, None) => Some(format!("removed its {}", what)),
+    }
+}
+
+impl Differ<'_> {
+    fn old_location(&self, statement: &Statement) -> Option<SourceLocation> {
+        Some(locate(self.old_file, statement))
+    }
+
+    fn new_location(&self, statement: &Statement) -> Option<SourceLocation> {
+        Some(locate(self.new_file, statement))
+    }
+
+    fn update(&mut self, scope: &Scope, description: String, old: Option<SourceLocation>, new: Option<SourceLocation>) {
+        let edit = Edit { kind: EditKind::Updated, path: scope.path.clone(), description, old, new };
+        self.edits.push(Pending { edit, node: None, subject: String::new(), scope: String::new() });
+    }
+
+    /// Records that `statement`, in `scope`, was inserted or deleted.
+    fn added_or_removed(&mut self, kind: EditKind, scope: &Scope, statement: &Statement) {
+        let (subject, path) = match definition(statement) {
+            Some((is_class, name)) => {
+                let inner = scope.enter(name, is_class);
+                (format!("{} `{}`", if is_class { "class" } else { "function" }, name), inner.path)
+            }
+            None => (format!("`{}`", header(statement)), scope.path.clone()),
+        };
+        let (verb, location) = match kind {
+            EditKind::Inserted => ("added to", (None, self.new_location(statement))),
+            _ => ("removed from", (self.old_location(statement), None)),
+        };
+        let edit = Edit {
+            kind,
+            path,
+            description: format!("{} was {} {}", subject, verb, scope.description),
+            old: location.0,
+            new: location.1,
+        };
+        let node = Some(without_positions(statement.to_ast_json()));
+        self.edits.push(Pending { edit, node, subject, scope: scope.description.clone() });
+    }
+
+    fn bodies(&mut self, old_scope: &Scope, new_scope: &Scope, old: &[Statement], new: &[Statement]) {
+        let pairs = align(old, new);
+        let mut deleted = Vec::new();
+        let mut inserted = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        for &(next_i, next_j) in pairs.iter().chain(&[(old.len(), new.len())]) {
+            self.gap(old_scope, new_scope, &old[i..next_i], &new[j..next_j], &mut deleted, &mut inserted);
+            if next_i < old.len() {
+                self.statements(old_scope, new_scope, &old[next_i], &new[next_j]);
+            }
+            (i, j) = (next_i + 1, next_j + 1);
+        }
+        // Definitions that were reordered weren't aligned, but are still the same definitions.
+        let mut remaining_inserted = Vec::new();
+        for statement in inserted {
+            let key = definition(statement);
+            match deleted.iter().position(|d| key.is_some() && definition(d) == key) {
+                Some(index) => {
+                    let before = deleted.remove(index);
+                    let (is_class, name) = key.unwrap_or_default();
+                    let subject = format!("{} `{}`", if is_class { "class" } else { "function" }, name);
+                    let edit = Edit {
+                        kind: EditKind::Moved,
+                        path: new_scope.enter(name, is_class).path,
+                        description: format!("{} moved within {}", subject, new_scope.description),
+                        old: self.old_location(before),
+                        new: self.new_location(statement),
+                    };
+                    self.edits.push(Pending { edit, node: None, subject, scope: String::new() });
+                    self.statements(old_scope, new_scope, before, statement);
+                }
+                None => remaining_inserted.push(statement),
+            }
+        }
+        for statement in deleted {
+            self.added_or_removed(EditKind::Deleted, old_scope, statement);
+        }
+        for statement in remaining_inserted {
+            self.added_or_removed(EditKind::Inserted, new_scope, statement);
+        }
+    }
+
+    /// Pairs up the statements between two aligned ones that took each other's places, and leaves the rest
+    /// to be inserted or deleted.
+    fn gap<'a>(
+        &mut self,
+        old_scope: &Scope,
+        new_scope: &Scope,
+        old: &'a [Statement],
+        new: &'a [Statement],
+        deleted: &mut Vec<&'a Statement>,
+        inserted: &mut Vec<&'a Statement>,
+    ) {
+        let mut new_left: Vec<&Statement> = new.iter().collect();
+        for statement in old {
+            let kind = std::mem::discriminant(&statement.statement);
+            let replacement = new_left
+                .iter()
+                .position(|n| definition(statement).is_none() && definition(n).is_none() && std::mem::discriminant(&n.statement) == kind);
+            match replacement {
+                Some(index) => {
+                    let replacement = new_left.remove(index);
+                    self.statements(old_scope, new_scope, statement, replacement);
+                }
+                None => deleted.push(statement),
+            }
+        }
+        inserted.extend(new_left);
+    }
+
+    /// Compares two statements that are in the same place, or definitions of the same name.
+    fn statements(&mut self, old_scope: &Scope, new_scope: &Scope, old: &Statement, new: &Statement) {
+        if old.structurally_eq(new) {
+            return;
+        }
+        match (&old.statement, &new.statement) {
+            (
+                StatementType::FunctionDef(a) | StatementType::AsyncFunctionDef(a),
+                StatementType::FunctionDef(b) | StatementType::AsyncFunctionDef(b),
+            ) => {
+                let was_async = matches!(old.statement, StatementType::AsyncFunctionDef(_));
+                let is_async = matches!(new.statement, StatementType::AsyncFunctionDef(_));
+                self.functions(old_scope.enter(&a.name, false), new_scope.enter(&b.name, false), (old, a), (new, b), (was_async, is_async));
+            }
+            (StatementType::ClassDef(a), StatementType::ClassDef(b)) => {
+                self.classes(old_scope.enter(&a.name, true), new_scope.enter(&b.name, true), (old, a), (new, b));
+            }
+            _ => {
+                let (old_blocks, new_blocks) = (nested_bodies(old), nested_bodies(new));
+                let locations = (self.old_location(old), self.new_location(new));
+                if old_blocks.is_empty() || old_blocks.len() != new_blocks.len() {
+                    let description = match is_docstring(old) && is_docstring(new) {
+                        true => format!("{} changed its docstring", new_scope.description),
+                        false => format!("{} changed `{}` to `{}`", new_scope.description, header(old), header(new)),
+                    };
+                    self.update(new_scope, description, locations.0, locations.1);
+                    return;
+                }
+                let (old_header, new_header) = (header(old), header(new));
+                if old_header != new_header {
+                    let description = format!("{} changed `{}` to `{}`", new_scope.description, old_header, new_header);
+                    self.update(new_scope, description, locations.0, locations.1);
+                }
+                for (old_block, new_block) in old_blocks.into_iter().zip(new_blocks) {
+                    self.bodies(old_scope, new_scope, old_block, new_block);
+                }
+            }
+        }
+    }
+
+    fn functions(
+        &mut self,
+        old_scope: Scope,
+        scope: Scope,
+        (old_statement, old): (&Statement, &FunctionDef),
+        (new_statement, new): (&Statement, &FunctionDef),
+        (was_async, is_async): (bool, bool),
+    ) {
+        let (old_location, new_location) = (self.old_location(old_statement), self.new_location(new_statement));
+        let mut changes = Vec::new();
+        if was_async != is_async {
+            changes.push(if is_async { "became async".to_string() } else { "is no longer async".to_string() });
+        }
+        changes.extend(decorators(&old.decorator_list, &new.decorator_list));
+        let (old_params, new_params) = (parameters(old), parameters(new));
+        for param in new_params.iter() {
+            let name = &param.parameter.arg;
+            match old_params.iter().find(|p| &p.parameter.arg == name) {
+                None => changes.push(format!("added parameter `{}`", name)),
+                Some(before) => {
+                    let of = format!("`{}`", name);
+                    if before.kind != param.kind {
+                        changes.push(format!("made {} {} instead of {}", of, param.kind, before.kind));
+                    }
+                    changes.extend(changed_expression(before.default, param.default, "default", Some(&of)));
+                    changes.extend(changed_expression(before.annotation(), param.annotation(), "annotation", Some(&of)));
+                }
+            }
+        }
+        for param in old_params.iter().filter(|p| !new_params.iter().any(|n| n.parameter.arg == p.parameter.arg)) {
+            changes.push(format!("removed parameter `{}`", param.parameter.arg));
+        }
+        let order = |params: &[Param], others: &[Param]| -> Vec<String> {
+            params.iter().map(|p| p.parameter.arg.clone()).filter(|name| others.iter().any(|o| &o.parameter.arg == name)).collect()
+        };
+        if order(&old_params, &new_params) != order(&new_params, &old_params) {
+            changes.push("reordered its parameters".to_string());
+        }
+        changes.extend(changed_expression(old.returns.as_deref(), new.returns.as_deref(), "return annotation", None));
+        for change in changes {
+            self.update(&scope, format!("{} {}", scope.description, change), old_location.clone(), new_location.clone());
+        }
+        self.bodies(&old_scope, &scope, &old.body, &new.body);
+    }
+
+    fn classes(
+        &mut self,
+        old_scope: Scope,
+        scope: Scope,
+        (old_statement, old): (&Statement, &ClassDef),
+        (new_statement, new): (&Statement, &ClassDef),
+    ) {
+        let mut changes = decorators(&old.decorator_list, &new.decorator_list);
+        let keyword = |k: &Keyword| match &k.arg {
+            Some(arg) => format!("{}={}", arg, source(&k.value)),
+            None => format!("**{}", source(&k.value)),
+        };
+        let bases = |class: &ClassDef| class.bases.iter().map(|b| b.id.clone()).chain(class.keywords.iter().map(keyword)).collect::<Vec<_>>();
+        if bases(old) != bases(new) {
+            changes.push(format!("changed its bases from `({})` to `({})`", bases(old).join(", "), bases(new).join(", ")));
+        }
+        for change in changes {
+            let (old_location, new_location) = (self.old_location(old_statement), self.new_location(new_statement));
+            self.update(&scope, format!("{} {}", scope.description, change), old_location, new_location);
+        }
+        self.bodies(&old_scope, &scope, &old.body, &new.body);
+    }
+
+    /// Pairs the deletions and insertions of the same code, wherever they are, into moves.
+    fn find_moves(&mut self) {
+        let mut index = 0;
+        while index < self.edits.len() {
+            let pending = &self.edits[index];
+            if pending.edit.kind != EditKind::Deleted {
+                index += 1;
+                continue;
+            }
+            let same = |other: &Pending| other.edit.kind == EditKind::Inserted && other.node == pending.node;
+            let Some(insertion) = self.edits.iter().position(same) else {
+                index += 1;
+                continue;
+            };
+            let inserted = self.edits.remove(insertion);
+            let index = if insertion < index { index - 1 } else { index };
+            let deleted = &mut self.edits[index];
+            let description = match deleted.scope == inserted.scope {
+                true => format!("{} moved within {}", deleted.subject, deleted.scope),
+                false => format!("{} moved from {} to {}", deleted.subject, deleted.scope, inserted.scope),
+            };
+            deleted.edit = Edit {
+                kind: EditKind::Moved,
+                path: inserted.edit.path,
+                description,
+                old: deleted.edit.old.take(),
+                new: inserted.edit.new,
+            };
+            deleted.node = None;
+        }
+    }
+}
+
+/// The decorators added and removed, as changes to the definition.
+fn decorators(old: &[ExprType], new: &[ExprType]) -> Vec<String> {
+    let removed = old.iter().filter(|d| !new.iter().any(|n| n.structurally_eq(d))).map(|d| format!("removed decorator `@{}`", source(d)));
+    let added = new.iter().filter(|d| !old.iter().any(|o| o.structurally_eq(d))).map(|d| format!("added decorator `@{}`", source(d)));
+    removed.chain(added).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn diff(old: &str, new: &str) -> Vec<String> {
+        let old = parse(old, "old.py").unwrap();
+        let new = parse(new, "new.py").unwrap();
+        structural_diff(&old, &new).iter().map(|edit| format!("{}: {}", edit.kind, edit.description)).collect()
+    }
+
+    #[test]
+    fn test_ignores_positions_and_comments() {
+        let old = "def f(x):\n    return x\n";
+        let new = "# A comment.\n\n\ndef f(x):\n    return x  # Still x.\n";
+        assert!(diff(old, new).is_empty());
+        let a = parse(old, "a.py").unwrap();
+        let b = parse(new, "b.py").unwrap();
+        assert!(a.raw.body[0].structurally_eq(&b.raw.body[0]));
+        assert_ne!(a.raw.body[0], b.raw.body[0]);
+    }
+
+    #[test]
+    fn test_signature_changes() {
+        let old = "def foo(a, b=1, *, c: int = 2):\n    pass\n";
+        let new = "@cache\nasync def foo(a, b=2, *, c: str = 2, d=None) -> int:\n    pass\n";
+        assert_eq!(
+            diff(old, new),
+            vec![
+                "updated: function `foo` became async",
+                "updated: function `foo` added decorator `@cache`",
+                "updated: function `foo` changed the default of `b` from `1` to `2`",
+                "updated: function `foo` changed the annotation of `c` from `int` to `str`",
+                "updated: function `foo` added parameter `d`",
+                "updated: function `foo` added the return annotation `int`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_statements_in_bodies() {
+        let old = "class C:\n    def m(self):\n        x = 1\n        if x:\n            print(x)\n        return x\n";
+        let new = "class C(Base, metaclass=Meta):\n    def m(self):\n        x = 2\n        if x:\n            log(x)\n            print(x)\n";
+        assert_eq!(
+            diff(old, new),
+            vec![
+                "updated: class `C` changed its bases from `()` to `(Base, metaclass=Meta)`",
+                "updated: function `C.m` changed `x = 1` to `x = 2`",
+                "inserted: `log(x)` was added to function `C.m`",
+                "deleted: `return x` was removed from function `C.m`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_moves() {
+        let old = "def a():\n    pass\n\ndef b():\n    pass\n\nclass C:\n    y = 1\n";
+        let new = "def b():\n    pass\n\ndef a():\n    pass\n\nclass C:\n    y = 1\n\n    def helper(self):\n        pass\n";
+        assert_eq!(diff(old, new), vec!["moved: function `a` moved within the module", "inserted: function `helper` was added to class `C`"]);
+
+        let old = "def helper():\n    return 1\n\nclass C:\n    y = 1\n";
+        let new = "class C:\n    y = 1\n\n    def helper():\n        return 1\n";
+        let old = parse(old, "old.py").unwrap();
+        let new = parse(new, "new.py").unwrap();
+        let edits = structural_diff(&old, &new);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].description, "function `helper` moved from the module to class `C`");
+        assert_eq!(edits[0].path, "C.helper");
+        assert_eq!((edits[0].old.as_ref().unwrap().line, edits[0].new.as_ref().unwrap().line), (Some(1), Some(4)));
+    }
+}
@@ -17,3 +17,8 @@ pub fn dump(o: &Bound<'_, PyAny>, indent: Option<u8>) -> PyResult<String> {
         t.call1(args)?.extract()
     })
 }
+
+/// A wrapper for the Python ast.unparse function, which turns a node back into source.
+pub fn unparse(o: &Bound<'_, PyAny>) -> PyResult<String> {
+    o.py().import("ast")?.getattr("unparse")?.call1((o,))?.extract()
+}
@@ -0,0 +1,94 @@
+import ast
+import json
+import math
+
+# Keep in step with AST_JSON_SCHEMA_VERSION in mod.rs.
+SCHEMA_VERSION = 1
+
+# Fields that older trees may leave out but that ast.unparse expects to be lists.
+LIST_FIELDS = {"type_params", "decorator_list", "keywords", "type_ignores"}
+
+# Tagged numbers, and how to read their text back.
+NUMBER_TYPES = {"int": lambda text: int(text, 0), "float": float, "complex": complex}
+
+# Convert an ast node to plain data in the JSON layout.
+def to_data(node, positions=True):
+    if isinstance(node, ast.AST):
+        data = {"_type": type(node).__name__}
+        for field in node._fields:
+            data[field] = to_data(getattr(node, field, None), positions)
+        for attribute in node._attributes if positions else ():
+            if hasattr(node, attribute):
+                data[attribute] = getattr(node, attribute)
+        return data
+    if isinstance(node, list):
+        return [to_data(item, positions) for item in node]
+    if isinstance(node, bytes):
+        return {"_type": "bytes", "value": node.decode("latin-1")}
+    # Numbers a reader can't hold as a 64-bit JSON number, and complex numbers, are kept as text.
+    if isinstance(node, int) and not isinstance(node, bool) and not -2**63 <= node < 2**64:
+        return {"_type": "int", "value": str(node)}
+    if isinstance(node, float) and not math.isfinite(node):
+        return {"_type": "float", "value": repr(node)}
+    if isinstance(node, complex):
+        return {"_type": "complex", "value": repr(node)}
+    return node
+
+# Convert plain data in the JSON layout back to ast nodes.
+def from_data(data):
+    if isinstance(data, list):
+        return [from_data(item) for item in data]
+    if not isinstance(data, dict):
+        return data
+    if data["_type"] == "bytes":
+        return data["value"].encode("latin-1")
+    if data["_type"] in NUMBER_TYPES:
+        return NUMBER_TYPES[data["_type"]](data["value"])
+    cls = getattr(ast, data["_type"])
+    node = cls()
+    for field in cls._fields:
+        if field in data:
+            setattr(node, field, from_data(data[field]))
+        else:
+            setattr(node, field, [] if field in LIST_FIELDS else None)
+    for attribute in cls._attributes:
+        if attribute in data:
+            setattr(node, attribute, data[attribute])
+    return node
+
+def to_json(source, filename="<unknown>"):
+    data = to_data(ast.parse(source, filename=filename))
+    data["_schema_version"] = SCHEMA_VERSION
+    return json.dumps(data)
+
+def from_json(text):
+    data = json.loads(text)
+    if data.get("_schema_version", SCHEMA_VERSION) > SCHEMA_VERSION:
+        raise ValueError(f"AST JSON schema version {data['_schema_version']} is not supported")
+    return ast.fix_missing_locations(from_data(data))
+
+def unparse_json(text):
+    return ast.unparse(from_json(text))
+
+def unparse_node(text):
+    return ast.unparse(ast.fix_missing_locations(from_data(json.loads(text))))
+
+# The Rust tree keeps constructs it doesn't model, such as `del` or a slice, as their source. These convert
+# between that source and the CPython node, so the JSON only ever holds real nodes.
+def source_data(source, kind):
+    if kind == "stmt":
+        node = ast.parse(source).body[0]
+    else:
+        try:
+            node = ast.parse(source, mode="eval").body
+        except SyntaxError:
+            # A slice is only an expression inside a subscript.
+            node = ast.parse(f"_[{source}]", mode="eval").body.slice
+    # Its positions are within the source alone, so they're left for the reader to fill in.
+    return json.dumps(to_data(node, positions=False))
+
+def node_source(text, kind):
+    node = from_data(json.loads(text))
+    if not isinstance(node, ast.stmt if kind == "stmt" else ast.expr):
+        raise ValueError(f"{type(node).__name__} isn't an ast.{kind}")
+    return ast.unparse(ast.fix_missing_locations(node))
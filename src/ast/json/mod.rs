@@ -0,0 +1,1264 @@
+//! A JSON form of the AST that mirrors CPython's `ast` module.
+//!
+//! Every node is an object whose `_type` is the CPython class name, with the CPython field names and the
+//! `lineno`/`col_offset` attributes where we track them, e.g. `{"_type": "Assign", "targets": [...], "value": ...}`.
+//! The root `Module` also carries `_schema_version` so readers can reject trees written by a newer format.
+//! Comments, which CPython doesn't keep, ride along as `_trivia` on statements and `_comments` on the module.
+//! Constructs the tree only keeps as source, such as `del` or slices, are written as the CPython nodes for that
+//! source, and read back as source.
+//!
+//! The `__init__.py` next to this file is the Python side of the format: it converts between this JSON and real
+//! `ast` trees.
+
+// Readers return the crate-wide `Error`, whose size is shared with the rest of the crate.
+#![allow(clippy::result_large_err)]
+
+use std::ffi::CString;
+
+use litrs::Literal;
+use pyo3::{prelude::*, sync::GILOnceCell};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value, json};
+
+use crate::{
+    Alias, Arguments, Assign, AsyncFor, AsyncWith, Attribute, AugAssign, Await, BinOp, BinOps, BoolOp, BoolOps,
+    Call, ClassDef, Compare, Compares, Comprehension, Constant, Dict, DictComp, Error, ExceptHandler, Expr,
+    ExprType, For, FormattedValue, FunctionDef, GeneratorExp, If, IfExp, Import, ImportFrom, JoinedStr, Keyword,
+    Lambda, ListComp, Module, Name, NamedExpr, Node, Ops, Parameter, Raise, RawModule, Result, Set, SetComp,
+    SourceLocation, Starred, Statement, StatementType, Subscript, Try, Tuple, Type, UnaryOp, While, With,
+    WithItem, Yield, YieldFrom, float_literal,
+};
+
+/// The version of the JSON layout written by [`Module::to_json`]. Bump it whenever the layout changes in a way
+/// older readers would misinterpret.
+pub const AST_JSON_SCHEMA_VERSION: u64 = 1;
+
+/// Converts a node to its CPython-shaped JSON.
+pub trait ToAstJson {
+    fn to_ast_json(&self) -> Value;
+}
+
+/// Reads a node back from its CPython-shaped JSON.
+pub trait FromAstJson: Sized {
+    fn from_ast_json(value: &Value) -> Result<Self>;
+}
+
+impl Module {
+    /// Serializes the module in the CPython-compatible JSON layout.
+    pub fn to_json(&self) -> String {
+        self.to_ast_json().to_string()
+    }
+
+    /// Reads a module written by [`Module::to_json`] or by the Python side of the format.
+    pub fn from_json(json: &str) -> Result<Module> {
+        let value: Value =
+            serde_json::from_str(json).map_err(|e| invalid(format!("malformed AST JSON: {}", e)))?;
+        Module::from_ast_json(&value)
+    }
+}
+
+impl Statement {
+    /// The statement as Python source, by way of CPython's `ast.unparse`.
+    pub fn to_python_source(&self) -> Result<String> {
+        unparse(&self.to_ast_json())
+    }
+}
+
+impl ExprType {
+    /// The expression as Python source, by way of CPython's `ast.unparse`.
+    pub fn to_python_source(&self) -> Result<String> {
+        unparse(&self.to_ast_json())
+    }
+}
+
+/// The Python side of the format, so it is only built once per interpreter.
+static AST_JSON_MODULE: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+
+fn helpers(py: Python<'_>) -> PyResult<&Py<PyModule>> {
+    AST_JSON_MODULE.get_or_try_init(py, || -> PyResult<_> {
+        let code = CString::new(include_str!("__init__.py"))?;
+        Ok(PyModule::from_code(py, &code, c"ast_json.py", c"ast_json")?.unbind())
+    })
+}
+
+fn unparse(value: &Value) -> Result<String> {
+    Python::with_gil(|py| -> PyResult<String> {
+        helpers(py)?.bind(py).getattr("unparse_node")?.call1((value.to_string(),))?.extract()
+    })
+    .map_err(|e| invalid(format!("can't unparse the tree: {}", e)))
+}
+
+/// Whether a kept-as-source construct is a statement or an expression.
+#[derive(Clone, Copy)]
+enum SourceKind {
+    Stmt,
+    Expr,
+}
+
+impl SourceKind {
+    /// The name of CPython's base class for the kind.
+    fn name(self) -> &'static str {
+        match self {
+            SourceKind::Stmt => "stmt",
+            SourceKind::Expr => "expr",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            SourceKind::Stmt => "statement",
+            SourceKind::Expr => "expression",
+        }
+    }
+}
+
+/// The CPython node for a construct the tree only keeps as source, such as `del x` or the slice in `a[1:2]`.
+/// Source that CPython can't parse, which only a tree built by hand holds, is written as `null`.
+fn source_to_json(source: &str, kind: SourceKind) -> Value {
+    Python::with_gil(|py| -> PyResult<String> {
+        helpers(py)?.bind(py).getattr("source_data")?.call1((source, kind.name()))?.extract()
+    })
+    .ok()
+    .and_then(|json| serde_json::from_str(&json).ok())
+    .unwrap_or(Value::Null)
+}
+
+/// The source of a CPython node the tree doesn't model, to keep it as.
+fn source_from_json(value: &Value, kind: SourceKind) -> Result<String> {
+    Python::with_gil(|py| -> PyResult<String> {
+        helpers(py)?.bind(py).getattr("node_source")?.call1((value.to_string(), kind.name()))?.extract()
+    })
+    .map_err(|e| invalid(format!("unsupported {} type {}: {}", kind.description(), value["_type"], e)))
+}
+
+/// Any node's JSON as Python source, for callers that only have the JSON.
+pub(crate) fn unparse_json(value: &Value) -> Result<String> {
+    unparse(value)
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::parsing_error(
+        SourceLocation::new("<json>"),
+        message,
+        "The JSON must use CPython's ast node and field names, as written by Module::to_json.",
+    )
+}
+
+/// Adds a node's source positions to its JSON object.
+fn located(mut value: Value, node: &impl Node) -> Value {
+    if let Value::Object(map) = &mut value {
+        let positions = [
+            ("lineno", node.lineno()),
+            ("col_offset", node.col_offset()),
+            ("end_lineno", node.end_lineno()),
+            ("end_col_offset", node.end_col_offset()),
+        ];
+        for (key, position) in positions {
+            if let Some(position) = position {
+                map.insert(key.to_string(), position.into());
+            }
+        }
+    }
+    value
+}
+
+fn list<T: ToAstJson>(items: &[T]) -> Value {
+    Value::Array(items.iter().map(ToAstJson::to_ast_json).collect())
+}
+
+fn optional<T: ToAstJson>(item: Option<&T>) -> Value {
+    item.map_or(Value::Null, ToAstJson::to_ast_json)
+}
+
+fn op(name: impl std::fmt::Debug) -> Value {
+    json!({ "_type": format!("{:?}", name) })
+}
+
+impl<T: ToAstJson> ToAstJson for Box<T> {
+    fn to_ast_json(&self) -> Value {
+        self.as_ref().to_ast_json()
+    }
+}
+
+impl<T: FromAstJson> FromAstJson for Box<T> {
+    fn from_ast_json(value: &Value) -> Result<Self> {
+        T::from_ast_json(value).map(Box::new)
+    }
+}
+
+/// The `ctx` CPython records on names and other assignable expressions.
+#[derive(Clone, Copy)]
+enum Ctx {
+    Load,
+    Store,
+}
+
+impl Ctx {
+    fn to_ast_json(self) -> Value {
+        match self {
+            Ctx::Load => json!({ "_type": "Load" }),
+            Ctx::Store => json!({ "_type": "Store" }),
+        }
+    }
+}
+
+/// An assignment target, which CPython marks with a `Store` context.
+struct Target<'a>(&'a ExprType);
+
+impl ToAstJson for Target<'_> {
+    fn to_ast_json(&self) -> Value {
+        expr_to_json(self.0, Ctx::Store)
+    }
+}
+
+fn expr_to_json(expr: &ExprType, ctx: Ctx) -> Value {
+    let elts = |elts: &[ExprType]| Value::Array(elts.iter().map(|e| expr_to_json(e, ctx)).collect());
+    match expr {
+        ExprType::BoolOp(b) => json!({
+            "_type": "BoolOp",
+            "op": op(&b.op),
+            "values": [b.left.to_ast_json(), b.right.to_ast_json()],
+        }),
+        ExprType::NamedExpr(n) => located(
+            json!({ "_type": "NamedExpr", "target": Target(&n.target).to_ast_json(), "value": n.value.to_ast_json() }),
+            n,
+        ),
+        ExprType::BinOp(b) => json!({
+            "_type": "BinOp",
+            "left": b.left.to_ast_json(),
+            "op": op(&b.op),
+            "right": b.right.to_ast_json(),
+        }),
+        ExprType::UnaryOp(u) => json!({ "_type": "UnaryOp", "op": op(&u.op), "operand": u.operand.to_ast_json() }),
+        ExprType::Lambda(l) => located(
+            json!({ "_type": "Lambda", "args": l.args.to_ast_json(), "body": l.body.to_ast_json() }),
+            l,
+        ),
+        ExprType::IfExp(i) => located(
+            json!({
+                "_type": "IfExp",
+                "test": i.test.to_ast_json(),
+                "body": i.body.to_ast_json(),
+                "orelse": i.orelse.to_ast_json(),
+            }),
+            i,
+        ),
+        ExprType::Dict(d) => located(
+            json!({
+                "_type": "Dict",
+                "keys": d.keys.iter().map(|k| optional(k.as_ref())).collect::<Vec<_>>(),
+                "values": list(&d.values),
+            }),
+            d,
+        ),
+        ExprType::Set(s) => located(json!({ "_type": "Set", "elts": list(&s.elts) }), s),
+        ExprType::ListComp(c) => located(
+            json!({ "_type": "ListComp", "elt": c.elt.to_ast_json(), "generators": list(&c.generators) }),
+            c,
+        ),
+        ExprType::SetComp(c) => located(
+            json!({ "_type": "SetComp", "elt": c.elt.to_ast_json(), "generators": list(&c.generators) }),
+            c,
+        ),
+        ExprType::GeneratorExp(c) => located(
+            json!({ "_type": "GeneratorExp", "elt": c.elt.to_ast_json(), "generators": list(&c.generators) }),
+            c,
+        ),
+        ExprType::DictComp(c) => located(
+            json!({
+                "_type": "DictComp",
+                "key": c.key.to_ast_json(),
+                "value": c.value.to_ast_json(),
+                "generators": list(&c.generators),
+            }),
+            c,
+        ),
+        ExprType::Await(a) => json!({ "_type": "Await", "value": a.value.to_ast_json() }),
+        ExprType::Yield(y) => located(json!({ "_type": "Yield", "value": optional(y.value.as_ref()) }), y),
+        ExprType::YieldFrom(y) => located(json!({ "_type": "YieldFrom", "value": y.value.to_ast_json() }), y),
+        ExprType::Compare(c) => json!({
+            "_type": "Compare",
+            "left": c.left.to_ast_json(),
+            "ops": c.ops.iter().map(op).collect::<Vec<_>>(),
+            "comparators": list(&c.comparators),
+        }),
+        ExprType::Call(c) => json!({
+            "_type": "Call",
+            "func": c.func.to_ast_json(),
+            "args": list(&c.args),
+            "keywords": list(&c.keywords),
+        }),
+        ExprType::FormattedValue(f) => located(
+            json!({
+                "_type": "FormattedValue",
+                "value": f.value.to_ast_json(),
+                "conversion": f.conversion.unwrap_or(-1),
+                "format_spec": optional(f.format_spec.as_ref()),
+            }),
+            f,
+        ),
+        ExprType::JoinedStr(j) => located(json!({ "_type": "JoinedStr", "values": list(&j.values) }), j),
+        ExprType::Constant(c) => json!({ "_type": "Constant", "value": constant_to_json(c), "kind": null }),
+        ExprType::Attribute(a) => json!({
+            "_type": "Attribute",
+            "value": a.value.to_ast_json(),
+            "attr": a.attr,
+            "ctx": { "_type": a.ctx },
+        }),
+        ExprType::Subscript(s) => located(
+            json!({
+                "_type": "Subscript",
+                "value": s.value.to_ast_json(),
+                "slice": s.slice.to_ast_json(),
+                "ctx": ctx.to_ast_json(),
+            }),
+            s,
+        ),
+        ExprType::Starred(s) => located(
+            json!({
+                "_type": "Starred",
+                "value": expr_to_json(&s.value, ctx),
+                "ctx": s.ctx.as_ref().map_or_else(|| ctx.to_ast_json(), |c| json!({ "_type": c })),
+            }),
+            s,
+        ),
+        ExprType::Name(n) => located(json!({ "_type": "Name", "id": n.id, "ctx": ctx.to_ast_json() }), n),
+        ExprType::List(l) => json!({ "_type": "List", "elts": elts(l), "ctx": ctx.to_ast_json() }),
+        ExprType::Tuple(t) => located(json!({ "_type": "Tuple", "elts": elts(&t.elts), "ctx": ctx.to_ast_json() }), t),
+        ExprType::NoneType(_) => Value::Null,
+        ExprType::Unimplemented(s) => source_to_json(s, SourceKind::Expr),
+        ExprType::Unknown => json!({ "_type": "Unknown" }),
+    }
+}
+
+impl ToAstJson for ExprType {
+    fn to_ast_json(&self) -> Value {
+        expr_to_json(self, Ctx::Load)
+    }
+}
+
+// JSON numbers are read as 64-bit values, so numbers outside that range, and the imaginary numbers JSON has no
+// form for, are written as their text with a `_type` tag, in the same way as bytes.
+fn tagged(kind: &str, text: impl Into<String>) -> Value {
+    json!({ "_type": kind, "value": text.into() })
+}
+
+fn constant_to_json(constant: &Constant) -> Value {
+    match &constant.0 {
+        None => Value::Null,
+        Some(Literal::Bool(b)) => b.value().into(),
+        Some(Literal::Integer(i)) if i.suffix() == "j" => tagged("complex", i.raw_input()),
+        Some(Literal::Float(f)) if f.suffix() == "j" => tagged("complex", f.raw_input()),
+        Some(Literal::Integer(i)) => match i.value::<u64>() {
+            Some(n) => n.into(),
+            None => tagged("int", format!("{}{}", i.base().prefix(), i.raw_main_part())),
+        },
+        Some(Literal::Float(f)) => match f.number_part().parse::<f64>() {
+            Ok(n) if n.is_finite() => n.into(),
+            _ => tagged("float", "inf"),
+        },
+        Some(Literal::String(s)) => s.value().into(),
+        // JSON has no bytes, so they are written as a string of latin-1 characters, one per byte.
+        Some(Literal::ByteString(b)) => json!({
+            "_type": "bytes",
+            "value": b.value().iter().map(|&b| b as char).collect::<String>(),
+        }),
+        Some(other) => other.to_string().into(),
+    }
+}
+
+fn constant_from_json(value: &Value) -> Result<Constant> {
+    let unsupported = || invalid(format!("unsupported constant {}", value));
+    let literal = match value {
+        Value::Null => return Ok(Constant(None)),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => match n.as_u64() {
+            Some(n) => n.to_string(),
+            None => n.as_f64().and_then(float_literal).ok_or_else(unsupported)?,
+        },
+        Value::String(s) => format!("{:?}", s),
+        Value::Object(o) => {
+            let text = o.get("value").and_then(Value::as_str).unwrap_or_default();
+            match o.get("_type").and_then(Value::as_str) {
+                Some("bytes") => {
+                    let mut literal = String::from("b\"");
+                    for c in text.chars() {
+                        match c {
+                            ' '..='~' if c != '"' && c != '\\' => literal.push(c),
+                            _ => literal.push_str(&format!("\\x{:02x}", c as u32 & 0xff)),
+                        }
+                    }
+                    literal.push('"');
+                    literal
+                }
+                Some("int") => text.to_string(),
+                Some("float") => text.parse().ok().and_then(float_literal).ok_or_else(unsupported)?,
+                // Written the way extraction writes it; only imaginary numbers have a literal form.
+                Some("complex") => text
+                    .strip_suffix('j')
+                    .and_then(|imag| imag.parse().ok())
+                    .and_then(float_literal)
+                    .map(|imag| imag + "j")
+                    .ok_or_else(unsupported)?,
+                _ => return Err(unsupported()),
+            }
+        }
+        _ => return Err(unsupported()),
+    };
+    Literal::parse(literal)
+        .map(|l| Constant(Some(l)))
+        .map_err(|e| invalid(format!("invalid constant {}: {}", value, e)))
+}
+
+impl ToAstJson for Comprehension {
+    fn to_ast_json(&self) -> Value {
+        json!({
+            "_type": "comprehension",
+            "target": Target(&self.target).to_ast_json(),
+            "iter": self.iter.to_ast_json(),
+            "ifs": list(&self.ifs),
+            "is_async": self.is_async as u8,
+        })
+    }
+}
+
+impl ToAstJson for Keyword {
+    fn to_ast_json(&self) -> Value {
+        located(json!({ "_type": "keyword", "arg": self.arg, "value": self.value.to_ast_json() }), self)
+    }
+}
+
+impl ToAstJson for Parameter {
+    fn to_ast_json(&self) -> Value {
+        located(
+            json!({
+                "_type": "arg",
+                "arg": self.arg,
+                "annotation": optional(self.annotation.as_ref()),
+                "type_comment": self.type_comment,
+            }),
+            self,
+        )
+    }
+}
+
+impl ToAstJson for Arguments {
+    fn to_ast_json(&self) -> Value {
+        json!({
+            "_type": "arguments",
+            "posonlyargs": list(&self.posonlyargs),
+            "args": list(&self.args),
+            "vararg": optional(self.vararg.as_ref()),
+            "kwonlyargs": list(&self.kwonlyargs),
+            "kw_defaults": self.kw_defaults.iter().map(|d| optional(d.as_ref())).collect::<Vec<_>>(),
+            "kwarg": optional(self.kwarg.as_ref()),
+            "defaults": list(&self.defaults),
+        })
+    }
+}
+
+impl ToAstJson for Alias {
+    fn to_ast_json(&self) -> Value {
+        json!({ "_type": "alias", "name": self.name, "asname": self.asname })
+    }
+}
+
+impl ToAstJson for WithItem {
+    fn to_ast_json(&self) -> Value {
+        json!({
+            "_type": "withitem",
+            "context_expr": self.context_expr.to_ast_json(),
+            "optional_vars": self.optional_vars.as_ref().map_or(Value::Null, |v| Target(v).to_ast_json()),
+        })
+    }
+}
+
+impl ToAstJson for ExceptHandler {
+    fn to_ast_json(&self) -> Value {
+        located(
+            json!({
+                "_type": "ExceptHandler",
+                "type": optional(self.exception_type.as_ref()),
+                "name": self.name,
+                "body": list(&self.body),
+            }),
+            self,
+        )
+    }
+}
+
+fn function_to_json(kind: &str, f: &FunctionDef) -> Value {
+    json!({
+        "_type": kind,
+        "name": f.name,
+        "args": f.args.to_ast_json(),
+        "body": list(&f.body),
+        "decorator_list": list(&f.decorator_list),
+        "returns": optional(f.returns.as_ref()),
+        "type_comment": null,
+    })
+}
+
+fn targets(targets: &[ExprType]) -> Value {
+    Value::Array(targets.iter().map(|t| Target(t).to_ast_json()).collect())
+}
+
+impl ToAstJson for Statement {
+    fn to_ast_json(&self) -> Value {
+        let value = match &self.statement {
+            StatementType::FunctionDef(f) => function_to_json("FunctionDef", f),
+            StatementType::AsyncFunctionDef(f) => function_to_json("AsyncFunctionDef", f),
+            StatementType::ClassDef(c) => json!({
+                "_type": "ClassDef",
+                "name": c.name,
+                "bases": c.bases.iter().map(|b| ExprType::Name(b.clone()).to_ast_json()).collect::<Vec<_>>(),
+                "keywords": list(&c.keywords),
+                "body": list(&c.body),
+                "decorator_list": list(&c.decorator_list),
+            }),
+            StatementType::Assign(a) => json!({
+                "_type": "Assign",
+                "targets": targets(&a.targets),
+                "value": a.value.to_ast_json(),
+                "type_comment": a.type_comment,
+            }),
+            StatementType::AugAssign(a) => json!({
+                "_type": "AugAssign",
+                "target": Target(&a.target).to_ast_json(),
+                "op": op(&a.op),
+                "value": a.value.to_ast_json(),
+            }),
+            StatementType::Break => json!({ "_type": "Break" }),
+            StatementType::Continue => json!({ "_type": "Continue" }),
+            StatementType::Pass => json!({ "_type": "Pass" }),
+            StatementType::Call(c) => json!({ "_type": "Expr", "value": ExprType::Call(c.clone()).to_ast_json() }),
+            StatementType::Return(value) => {
+                json!({ "_type": "Return", "value": value.as_ref().map_or(Value::Null, |e| e.value.to_ast_json()) })
+            }
+            StatementType::Import(i) => json!({ "_type": "Import", "names": list(&i.names) }),
+            StatementType::ImportFrom(i) => json!({
+                "_type": "ImportFrom",
+                "module": if i.module.is_empty() { Value::Null } else { i.module.clone().into() },
+                "names": list(&i.names),
+                "level": i.level,
+            }),
+            StatementType::Expr(e) => json!({ "_type": "Expr", "value": e.value.to_ast_json() }),
+            StatementType::If(i) => json!({
+                "_type": "If",
+                "test": i.test.to_ast_json(),
+                "body": list(&i.body),
+                "orelse": list(&i.orelse),
+            }),
+            StatementType::While(w) => json!({
+                "_type": "While",
+                "test": w.test.to_ast_json(),
+                "body": list(&w.body),
+                "orelse": list(&w.orelse),
+            }),
+            StatementType::For(f) => json!({
+                "_type": "For",
+                "target": Target(&f.target).to_ast_json(),
+                "iter": f.iter.to_ast_json(),
+                "body": list(&f.body),
+                "orelse": list(&f.orelse),
+                "type_comment": null,
+            }),
+            StatementType::AsyncFor(f) => json!({
+                "_type": "AsyncFor",
+                "target": Target(&f.target).to_ast_json(),
+                "iter": f.iter.to_ast_json(),
+                "body": list(&f.body),
+                "orelse": list(&f.orelse),
+                "type_comment": null,
+            }),
+            StatementType::Try(t) => json!({
+                "_type": "Try",
+                "body": list(&t.body),
+                "handlers": list(&t.handlers),
+                "orelse": list(&t.orelse),
+                "finalbody": list(&t.finalbody),
+            }),
+            StatementType::Raise(r) => json!({
+                "_type": "Raise",
+                "exc": optional(r.exc.as_ref()),
+                "cause": optional(r.cause.as_ref()),
+            }),
+            StatementType::With(w) => json!({
+                "_type": "With",
+                "items": list(&w.items),
+                "body": list(&w.body),
+                "type_comment": null,
+            }),
+            StatementType::AsyncWith(w) => json!({
+                "_type": "AsyncWith",
+                "items": list(&w.items),
+                "body": list(&w.body),
+                "type_comment": null,
+            }),
+            StatementType::Unimplemented(s) => source_to_json(s, SourceKind::Stmt),
+        };
+        let mut value = located(value, self);
+        if !self.trivia.is_empty() {
+            value["_trivia"] = serde_json::to_value(&self.trivia).unwrap_or_default();
+        }
+        value
+    }
+}
+
+impl ToAstJson for Module {
+    fn to_ast_json(&self) -> Value {
+        let mut value = json!({
+            "_type": "Module",
+            "_schema_version": AST_JSON_SCHEMA_VERSION,
+            "body": list(&self.raw.body),
+            "type_ignores": self.raw.type_ignores.iter().map(|_| json!({ "_type": "TypeIgnore" })).collect::<Vec<_>>(),
+        });
+        if !self.comments.is_empty() {
+            value["_comments"] = serde_json::to_value(&self.comments).unwrap_or_default();
+        }
+        value
+    }
+}
+
+/// The fields of one node object, with typed accessors that report which field was wrong.
+struct Fields<'a> {
+    ty: &'a str,
+    map: &'a Map<String, Value>,
+}
+
+impl<'a> Fields<'a> {
+    fn of(value: &'a Value) -> Result<Self> {
+        let map = value
+            .as_object()
+            .ok_or_else(|| invalid(format!("expected an AST node, found {}", value)))?;
+        let ty = map
+            .get("_type")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid(format!("AST node without a _type: {}", value)))?;
+        Ok(Self { ty, map })
+    }
+
+    /// A field's value, treating `null` the same as a missing field.
+    fn get(&self, key: &str) -> Option<&'a Value> {
+        self.map.get(key).filter(|v| !v.is_null())
+    }
+
+    fn node<T: FromAstJson>(&self, key: &str) -> Result<T> {
+        match self.get(key) {
+            Some(value) => T::from_ast_json(value),
+            None => Err(invalid(format!("{} is missing its {} field", self.ty, key))),
+        }
+    }
+
+    fn optional<T: FromAstJson>(&self, key: &str) -> Result<Option<T>> {
+        self.get(key).map(T::from_ast_json).transpose()
+    }
+
+    fn list<T: FromAstJson>(&self, key: &str) -> Result<Vec<T>> {
+        match self.get(key) {
+            None => Ok(Vec::new()),
+            Some(Value::Array(items)) => items.iter().map(T::from_ast_json).collect(),
+            Some(other) => Err(invalid(format!("{}.{} should be a list, found {}", self.ty, key, other))),
+        }
+    }
+
+    /// A list whose entries may be `null`, such as `Dict.keys`.
+    fn optional_list<T: FromAstJson>(&self, key: &str) -> Result<Vec<Option<T>>> {
+        match self.get(key) {
+            None => Ok(Vec::new()),
+            Some(Value::Array(items)) => items
+                .iter()
+                .map(|item| if item.is_null() { Ok(None) } else { T::from_ast_json(item).map(Some) })
+                .collect(),
+            Some(other) => Err(invalid(format!("{}.{} should be a list, found {}", self.ty, key, other))),
+        }
+    }
+
+    fn string(&self, key: &str) -> Result<String> {
+        self.optional_string(key)?
+            .ok_or_else(|| invalid(format!("{} is missing its {} field", self.ty, key)))
+    }
+
+    fn optional_string(&self, key: &str) -> Result<Option<String>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::String(s)) => Ok(Some(s.clone())),
+            Some(other) => Err(invalid(format!("{}.{} should be a string, found {}", self.ty, key, other))),
+        }
+    }
+
+    fn number(&self, key: &str) -> Option<usize> {
+        self.get(key).and_then(Value::as_u64).map(|n| n as usize)
+    }
+
+    /// One of our own underscored fields, such as `_trivia`, which CPython ignores.
+    fn extension<T: DeserializeOwned + Default>(&self, key: &str) -> Result<T> {
+        match self.get(key) {
+            None => Ok(T::default()),
+            Some(value) => serde_json::from_value(value.clone())
+                .map_err(|e| invalid(format!("{}.{} is malformed: {}", self.ty, key, e))),
+        }
+    }
+
+    /// The `_type` of a nested marker object such as an operator or `ctx`.
+    fn kind(&self, key: &str) -> Result<&'a str> {
+        match self.get(key) {
+            Some(value) => Fields::of(value).map(|f| f.ty),
+            None => Err(invalid(format!("{} is missing its {} field", self.ty, key))),
+        }
+    }
+
+    fn positions(&self) -> (Option<usize>, Option<usize>, Option<usize>, Option<usize>) {
+        (
+            self.number("lineno"),
+            self.number("col_offset"),
+            self.number("end_lineno"),
+            self.number("end_col_offset"),
+        )
+    }
+}
+
+/// Fills in the position fields of a struct from a node's JSON.
+macro_rules! with_positions {
+    ($fields:expr, $type_name:ident { $($field:ident: $value:expr),* $(,)? }) => {{
+        let (lineno, col_offset, end_lineno, end_col_offset) = $fields.positions();
+        $type_name { $($field: $value,)* lineno, col_offset, end_lineno, end_col_offset }
+    }};
+}
+
+fn bin_op(name: &str) -> Result<BinOps> {
+    Ok(match name {
+        "Add" => BinOps::Add,
+        "Sub" => BinOps::Sub,
+        "Mult" => BinOps::Mult,
+        "Div" => BinOps::Div,
+        "FloorDiv" => BinOps::FloorDiv,
+        "Mod" => BinOps::Mod,
+        "Pow" => BinOps::Pow,
+        "LShift" => BinOps::LShift,
+        "RShift" => BinOps::RShift,
+        "BitOr" => BinOps::BitOr,
+        "BitXor" => BinOps::BitXor,
+        "BitAnd" => BinOps::BitAnd,
+        "MatMult" => BinOps::MatMult,
+        other => return Err(invalid(format!("unknown binary operator {}", other))),
+    })
+}
+
+fn compare_op(value: &Value) -> Result<Compares> {
+    Ok(match Fields::of(value)?.ty {
+        "Eq" => Compares::Eq,
+        "NotEq" => Compares::NotEq,
+        "Lt" => Compares::Lt,
+        "LtE" => Compares::LtE,
+        "Gt" => Compares::Gt,
+        "GtE" => Compares::GtE,
+        "Is" => Compares::Is,
+        "IsNot" => Compares::IsNot,
+        "In" => Compares::In,
+        "NotIn" => Compares::NotIn,
+        other => return Err(invalid(format!("unknown comparison operator {}", other))),
+    })
+}
+
+impl FromAstJson for ExprType {
+    fn from_ast_json(value: &Value) -> Result<Self> {
+        if value.is_null() {
+            return Ok(ExprType::NoneType(Constant(None)));
+        }
+        let f = Fields::of(value)?;
+        Ok(match f.ty {
+            "BoolOp" => {
+                let op = match f.kind("op")? {
+                    "And" => BoolOps::And,
+                    "Or" => BoolOps::Or,
+                    other => return Err(invalid(format!("unknown boolean operator {}", other))),
+                };
+                // Our BoolOp is binary, so `a and b and c` nests to the left.
+                let mut values = f.list::<ExprType>("values")?.into_iter();
+                let first = values.next().ok_or_else(|| invalid("BoolOp without values"))?;
+                values.try_fold(first, |left, right| {
+                    Ok::<_, Error>(ExprType::BoolOp(BoolOp { op: op.clone(), left: Box::new(left), right: Box::new(right) }))
+                })?
+            }
+            "NamedExpr" => ExprType::NamedExpr(with_positions!(f, NamedExpr {
+                target: f.node("target")?,
+                value: f.node("value")?,
+            })),
+            "BinOp" => ExprType::BinOp(BinOp {
+                left: f.node("left")?,
+                op: bin_op(f.kind("op")?)?,
+                right: f.node("right")?,
+            }),
+            "UnaryOp" => ExprType::UnaryOp(UnaryOp {
+                op: match f.kind("op")? {
+                    "Invert" => Ops::Invert,
+                    "Not" => Ops::Not,
+                    "UAdd" => Ops::UAdd,
+                    "USub" => Ops::USub,
+                    other => return Err(invalid(format!("unknown unary operator {}", other))),
+                },
+                operand: f.node("operand")?,
+            }),
+            "Lambda" => ExprType::Lambda(with_positions!(f, Lambda { args: f.node("args")?, body: f.node("body")? })),
+            "IfExp" => ExprType::IfExp(with_positions!(f, IfExp {
+                test: f.node("test")?,
+                body: f.node("body")?,
+                orelse: f.node("orelse")?,
+            })),
+            "Dict" => ExprType::Dict(with_positions!(f, Dict {
+                keys: f.optional_list("keys")?,
+                values: f.list("values")?,
+            })),
+            "Set" => ExprType::Set(with_positions!(f, Set { elts: f.list("elts")? })),
+            "ListComp" => ExprType::ListComp(with_positions!(f, ListComp {
+                elt: f.node("elt")?,
+                generators: f.list("generators")?,
+            })),
+            "SetComp" => ExprType::SetComp(with_positions!(f, SetComp {
+                elt: f.node("elt")?,
+                generators: f.list("generators")?,
+            })),
+            "GeneratorExp" => ExprType::GeneratorExp(with_positions!(f, GeneratorExp {
+                elt: f.node("elt")?,
+                generators: f.list("generators")?,
+            })),
+            "DictComp" => ExprType::DictComp(with_positions!(f, DictComp {
+                key: f.node("key")?,
+                value: f.node("value")?,
+                generators: f.list("generators")?,
+            })),
+            "Await" => ExprType::Await(Await { value: f.node("value")? }),
+            "Yield" => ExprType::Yield(with_positions!(f, Yield { value: f.optional("value")? })),
+            "YieldFrom" => ExprType::YieldFrom(with_positions!(f, YieldFrom { value: f.node("value")? })),
+            "Compare" => ExprType::Compare(Compare {
+                ops: match f.get("ops") {
+                    Some(Value::Array(ops)) => ops.iter().map(compare_op).collect::<Result<_>>()?,
+                    _ => return Err(invalid("Compare is missing its ops field")),
+                },
+                left: f.node("left")?,
+                comparators: f.list("comparators")?,
+            }),
+            "Call" => ExprType::Call(Call {
+                func: f.node("func")?,
+                args: f.list("args")?,
+                keywords: f.list("keywords")?,
+            }),
+            "FormattedValue" => ExprType::FormattedValue(with_positions!(f, FormattedValue {
+                value: f.node("value")?,
+                conversion: f.get("conversion").and_then(Value::as_i64).map(|c| c as i32).filter(|&c| c != -1),
+                format_spec: f.optional("format_spec")?,
+            })),
+            "JoinedStr" => ExprType::JoinedStr(with_positions!(f, JoinedStr { values: f.list("values")? })),
+            "Constant" => ExprType::Constant(constant_from_json(f.map.get("value").unwrap_or(&Value::Null))?),
+            "Attribute" => ExprType::Attribute(Attribute {
+                value: f.node("value")?,
+                attr: f.string("attr")?,
+                ctx: f.kind("ctx").unwrap_or("Load").to_string(),
+            }),
+            "Subscript" => ExprType::Subscript(with_positions!(f, Subscript {
+                value: f.node("value")?,
+                slice: f.node("slice")?,
+            })),
+            "Starred" => ExprType::Starred(with_positions!(f, Starred {
+                value: f.node("value")?,
+                ctx: f.kind("ctx").ok().map(str::to_string),
+            })),
+            "Name" => ExprType::Name(with_positions!(f, Name { id: f.string("id")? })),
+            "List" => ExprType::List(f.list("elts")?),
+            "Tuple" => ExprType::Tuple(with_positions!(f, Tuple { elts: f.list("elts")? })),
+            "Unknown" => ExprType::Unknown,
+            // Anything else CPython has is kept as source, as when it's extracted from Python.
+            _ => ExprType::Unimplemented(source_from_json(value, SourceKind::Expr)?),
+        })
+    }
+}
+
+impl FromAstJson for Comprehension {
+    fn from_ast_json(value: &Value) -> Result<Self> {
+        let f = Fields::of(value)?;
+        Ok(Comprehension {
+            target: f.node("target")?,
+            iter: f.node("iter")?,
+            ifs: f.list("ifs")?,
+            is_async: f.number("is_async").unwrap_or(0) != 0,
+        })
+    }
+}
+
+impl FromAstJson for Keyword {
+    fn from_ast_json(value: &Value) -> Result<Self> {
+        let f = Fields::of(value)?;
+        Ok(with_positions!(f, Keyword { arg: f.optional_string("arg")?, value: f.node("value")? }))
+    }
+}
+
+impl FromAstJson for Parameter {
+    fn from_ast_json(value: &Value) -> Result<Self> {
+        let f = Fields::of(value)?;
+        Ok(with_positions!(f, Parameter {
+            arg: f.string("arg")?,
+            annotation: f.optional("annotation")?,
+            type_comment: f.optional_string("type_comment")?,
+        }))
+    }
+}
+
+impl FromAstJson for Arguments {
+    fn from_ast_json(value: &Value) -> Result<Self> {
+        let f = Fields::of(value)?;
+        Ok(Arguments {
+            posonlyargs: f.list("posonlyargs")?,
+            args: f.list("args")?,
+            vararg: f.optional("vararg")?,
+            kwonlyargs: f.list("kwonlyargs")?,
+            kw_defaults: f.optional_list("kw_defaults")?,
+            kwarg: f.optional("kwarg")?,
+            defaults: f.list("defaults")?,
+        })
+    }
+}
+
+impl FromAstJson for Alias {
+    fn from_ast_json(value: &Value) -> Result<Self> {
+        let f = Fields::of(value)?;
+        Ok(Alias { name: f.string("name")?, asname: f.optional_string("asname")? })
+    }
+}
+
+impl FromAstJson for WithItem {
+    fn from_ast_json(value: &Value) -> Result<Self> {
+        let f = Fields::of(value)?;
+        Ok(WithItem { context_expr: f.node("context_expr")?, optional_vars: f.optional("optional_vars")? })
+    }
+}
+
+impl FromAstJson for ExceptHandler {
+    fn from_ast_json(value: &Value) -> Result<Self> {
+        let f = Fields::of(value)?;
+        Ok(with_positions!(f, ExceptHandler {
+            exception_type: f.optional("type")?,
+            name: f.optional_string("name")?,
+            body: f.list("body")?,
+        }))
+    }
+}
+
+fn function_from_json(f: &Fields) -> Result<FunctionDef> {
+    Ok(FunctionDef {
+        name: f.string("name")?,
+        args: f.node("args")?,
+        body: f.list("body")?,
+        decorator_list: f.list("decorator_list")?,
+        returns: f.optional("returns")?,
+    })
+}
+
+impl FromAstJson for Statement {
+    fn from_ast_json(value: &Value) -> Result<Self> {
+        let f = Fields::of(value)?;
+        let (lineno, col_offset, end_lineno, end_col_offset) = f.positions();
+        // Expression statements keep the statement's positions, as they do when extracted from Python.
+        let expr = |value: ExprType| Expr { value, ctx: None, lineno, col_offset, end_lineno, end_col_offset };
+
+        let statement = match f.ty {
+            "FunctionDef" => StatementType::FunctionDef(function_from_json(&f)?),
+            "AsyncFunctionDef" => StatementType::AsyncFunctionDef(function_from_json(&f)?),
+            "ClassDef" => StatementType::ClassDef(ClassDef {
+                name: f.string("name")?,
+                bases: f
+                    .list::<ExprType>("bases")?
+                    .into_iter()
+                    .map(|base| match base {
+                        ExprType::Name(name) => Ok(name),
+                        other => Err(invalid(format!("unsupported base class {:?}", other))),
+                    })
+                    .collect::<Result<_>>()?,
+                keywords: f.list("keywords")?,
+                body: f.list("body")?,
+                decorator_list: f.list("decorator_list")?,
+            }),
+            "Assign" => StatementType::Assign(Assign {
+                targets: f.list("targets")?,
+                value: f.node("value")?,
+                type_comment: f.optional_string("type_comment")?,
+            }),
+            "AugAssign" => StatementType::AugAssign(with_positions!(f, AugAssign {
+                target: f.node("target")?,
+                op: bin_op(f.kind("op")?)?,
+                value: f.node("value")?,
+            })),
+            "Break" => StatementType::Break,
+            "Continue" => StatementType::Continue,
+            "Pass" => StatementType::Pass,
+            "Return" => StatementType::Return(Some(expr(f.node("value").unwrap_or(ExprType::NoneType(Constant(None)))))),
+            "Import" => StatementType::Import(Import { names: f.list("names")? }),
+            "ImportFrom" => StatementType::ImportFrom(ImportFrom {
+                module: f.optional_string("module")?.unwrap_or_default(),
+                names: f.list("names")?,
+                level: f.number("level").unwrap_or(0),
+            }),
+            "Expr" => StatementType::Expr(expr(f.node("value")?)),
+            "If" => StatementType::If(with_positions!(f, If {
+                test: f.node("test")?,
+                body: f.list("body")?,
+                orelse: f.list("orelse")?,
+            })),
+            "While" => StatementType::While(with_positions!(f, While {
+                test: f.node("test")?,
+                body: f.list("body")?,
+                orelse: f.list("orelse")?,
+            })),
+            "For" => StatementType::For(with_positions!(f, For {
+                target: f.node("target")?,
+                iter: f.node("iter")?,
+                body: f.list("body")?,
+                orelse: f.list("orelse")?,
+            })),
+            "AsyncFor" => StatementType::AsyncFor(with_positions!(f, AsyncFor {
+                target: f.node("target")?,
+                iter: f.node("iter")?,
+                body: f.list("body")?,
+                orelse: f.list("orelse")?,
+            })),
+            "Try" => StatementType::Try(with_positions!(f, Try {
+                body: f.list("body")?,
+                handlers: f.list("handlers")?,
+                orelse: f.list("orelse")?,
+                finalbody: f.list("finalbody")?,
+            })),
+            "Raise" => StatementType::Raise(with_positions!(f, Raise {
+                exc: f.optional("exc")?,
+                cause: f.optional("cause")?,
+            })),
+            "With" => StatementType::With(with_positions!(f, With { items: f.list("items")?, body: f.list("body")? })),
+            "AsyncWith" => StatementType::AsyncWith(with_positions!(f, AsyncWith {
+                items: f.list("items")?,
+                body: f.list("body")?,
+            })),
+            _ => StatementType::Unimplemented(source_from_json(value, SourceKind::Stmt)?),
+        };
+
+        Ok(Statement { lineno, col_offset, end_lineno, end_col_offset, statement, trivia: f.extension("_trivia")? })
+    }
+}
+
+impl FromAstJson for Module {
+    fn from_ast_json(value: &Value) -> Result<Self> {
+        let f = Fields::of(value)?;
+        if f.ty != "Module" {
+            return Err(invalid(format!("expected a Module, found {}", f.ty)));
+        }
+        if let Some(version) = f.number("_schema_version")
+            && version as u64 > AST_JSON_SCHEMA_VERSION
+        {
+            return Err(invalid(format!(
+                "AST JSON schema version {} is newer than the supported version {}",
+                version, AST_JSON_SCHEMA_VERSION
+            )));
+        }
+        let type_ignores = match f.get("type_ignores") {
+            Some(Value::Array(ignores)) => ignores.iter().map(|_| Type::Unimplemented).collect(),
+            _ => Vec::new(),
+        };
+        Ok(Module {
+            raw: RawModule { body: f.list("body")?, type_ignores },
+            comments: f.extension("_comments")?,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use pyo3::{prelude::*, types::PyModule};
+
+    use super::*;
+
+    const SOURCE: &str = r#"
+import os.path as p
+from os import sep
+
+def f(a, /, b: int = 1, *args, c, d=None, **kw):
+    """Doc."""
+    x = [i * 2 for i in range(10) if i % 2 == 0]
+    y = {k: v for k, v in kw.items()}
+    while (n := len(args)) > 3:
+        args = args[1:]
+    for i, j in zip(a, b):
+        if i and j or not i:
+            continue
+        elif i is None:
+            break
+    try:
+        raise ValueError("bad") from None
+    except (ValueError, TypeError) as e:
+        pass
+    finally:
+        print(f"{x!r:>10} and {y}", sep="")
+    with open(p.join("a", "b")) as fh, lock:
+        data = fh.read()
+    del data, x[::2, 0]
+    return lambda q: q if q else -q
+
+class C(Base, metaclass=Meta):
+    attr = b"bytes"
+
+    async def g(self):
+        async for item in self.stream():
+            yield item
+        await other()
+        return {1, 2.5, True, "s"}
+"#;
+
+    fn python_helpers(py: Python<'_>) -> Bound<'_, PyModule> {
+        let code = CString::new(include_str!("__init__.py")).unwrap();
+        PyModule::from_code(py, &code, c"ast_json.py", c"ast_json").unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let module = crate::parse(SOURCE, "test.py").unwrap();
+        let restored = Module::from_json(&module.to_json()).unwrap();
+        assert_eq!(module.raw.body, restored.raw.body);
+    }
+
+    #[test]
+    fn test_round_trip_keeps_comments() {
+        let module = crate::parse("# setup\nx = 1  # one\n\ny = 2\n# done\n", "test.py").unwrap();
+        let restored = Module::from_json(&module.to_json()).unwrap();
+        assert_eq!(module.raw.body, restored.raw.body);
+        assert_eq!(restored.raw.body[0].trivia.leading[0].text, " setup");
+        assert_eq!(restored.comments, module.comments);
+    }
+
+    #[test]
+    fn test_cpython_layout() {
+        let module = crate::parse("x = y + 1", "test.py").unwrap();
+        let value = module.to_ast_json();
+        assert_eq!(value["_schema_version"], AST_JSON_SCHEMA_VERSION);
+        assert_eq!(
+            value["body"][0],
+            json!({
+                "_type": "Assign",
+                "targets": [{
+                    "_type": "Name", "id": "x", "ctx": { "_type": "Store" },
+                    "lineno": 1, "col_offset": 0, "end_lineno": 1, "end_col_offset": 1,
+                }],
+                "value": {
+                    "_type": "BinOp",
+                    "left": {
+                        "_type": "Name", "id": "y", "ctx": { "_type": "Load" },
+                        "lineno": 1, "col_offset": 4, "end_lineno": 1, "end_col_offset": 5,
+                    },
+                    "op": { "_type": "Add" },
+                    "right": { "_type": "Constant", "value": 1, "kind": null },
+                },
+                "type_comment": null,
+                "lineno": 1,
+                "col_offset": 0,
+                "end_lineno": 1,
+                "end_col_offset": 9,
+            })
+        );
+    }
+
+    #[test]
+    fn test_reads_cpython_output() {
+        let json: String = Python::with_gil(|py| {
+            python_helpers(py).getattr("to_json").unwrap().call1((SOURCE,)).unwrap().extract().unwrap()
+        });
+        let mut from_python = Module::from_json(&json).unwrap();
+        // CPython's trees carry no trivia, so take the blank lines from the source as the parser does.
+        crate::attach_trivia(&mut from_python, SOURCE, Vec::new());
+        let extracted = crate::parse(SOURCE, "test.py").unwrap();
+        assert_eq!(from_python.raw.body, extracted.raw.body);
+    }
+
+    #[test]
+    fn test_cpython_reads_our_output() {
+        let module = crate::parse(SOURCE, "test.py").unwrap();
+        Python::with_gil(|py| {
+            let helpers = python_helpers(py);
+            let ours: String = helpers.getattr("unparse_json").unwrap().call1((module.to_json(),)).unwrap().extract().unwrap();
+            let ast = py.import("ast").unwrap();
+            let tree = ast.getattr("parse").unwrap().call1((SOURCE,)).unwrap();
+            let expected: String = ast.getattr("unparse").unwrap().call1((tree,)).unwrap().extract().unwrap();
+            assert_eq!(ours, expected);
+        });
+    }
+
+    #[test]
+    fn test_rejects_newer_schema() {
+        let json = json!({ "_type": "Module", "_schema_version": AST_JSON_SCHEMA_VERSION + 1, "body": [] });
+        assert!(Module::from_json(&json.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_reports_unknown_nodes() {
+        let json = json!({ "_type": "Module", "body": [{ "_type": "Frobnicate" }] });
+        let err = Module::from_json(&json.to_string()).unwrap_err();
+        assert!(err.to_string().contains("unsupported statement type \"Frobnicate\""), "{}", err);
+        let json = json!({ "_type": "Module", "body": [{ "_type": "Expr", "value": { "_type": "Assert", "test": null } }] });
+        let err = Module::from_json(&json.to_string()).unwrap_err();
+        assert!(err.to_string().contains("Assert isn't an ast.expr"), "{}", err);
+    }
+
+    #[test]
+    fn test_unmodelled_nodes_are_cpython_nodes() {
+        let module = crate::parse("del a[1:2]\n", "test.py").unwrap();
+        let value = module.to_ast_json();
+        assert_eq!(value["body"][0]["_type"], "Delete");
+        assert_eq!(value["body"][0]["targets"][0]["slice"]["_type"], "Slice");
+        assert_eq!(value["body"][0]["lineno"], 1);
+
+        // The slice on its own, as the tree has it.
+        let crate::StatementType::Unimplemented(source) = &module.raw.body[0].statement else { unreachable!() };
+        assert_eq!(source, "del a[1:2]");
+        let slice = ExprType::Unimplemented("1:2".to_string());
+        assert_eq!(slice.to_ast_json()["_type"], "Slice");
+        let subscript = crate::parse("a[1:2]", "test.py").unwrap().raw.body.remove(0);
+        assert_eq!(subscript.to_python_source().unwrap(), "a[1:2]");
+        assert_eq!(ExprType::from_ast_json(&slice.to_ast_json()).unwrap(), slice);
+    }
+
+    #[test]
+    fn test_round_trips_numbers_json_cant_hold() {
+        let source = "a = 123456789012345678901234567890\nb = 1e999\nc = 2j\nd = 1.5e999j\ne = 5.0\n";
+        let module = crate::parse(source, "test.py").unwrap();
+        let value = module.to_ast_json();
+        let values: Vec<_> = (0..5).map(|i| value["body"][i]["value"]["value"].clone()).collect();
+        assert_eq!(
+            values,
+            [
+                json!({ "_type": "int", "value": "123456789012345678901234567890" }),
+                json!({ "_type": "float", "value": "inf" }),
+                json!({ "_type": "complex", "value": "2.0j" }),
+                json!({ "_type": "complex", "value": "1e309j" }),
+                json!(5.0),
+            ]
+        );
+        assert_eq!(Module::from_json(&module.to_json()).unwrap().raw.body, module.raw.body);
+
+        Python::with_gil(|py| {
+            let helpers = python_helpers(py);
+            let json: String = helpers.getattr("to_json").unwrap().call1((source,)).unwrap().extract().unwrap();
+            let mut from_python = Module::from_json(&json).unwrap();
+            crate::attach_trivia(&mut from_python, source, Vec::new());
+            assert_eq!(from_python.raw.body, module.raw.body);
+
+            let ours: String = helpers.getattr("unparse_json").unwrap().call1((module.to_json(),)).unwrap().extract().unwrap();
+            assert_eq!(ours, "a = 123456789012345678901234567890\nb = 1e309\nc = 2j\nd = 1e309j\ne = 5.0");
+
+            // NaN never comes out of the parser, but a tree built in Python can hold it.
+            let nan = r#"{"_type": "Constant", "value": {"_type": "float", "value": "nan"}}"#;
+            let unparsed: String = helpers.getattr("unparse_node").unwrap().call1((nan,)).unwrap().extract().unwrap();
+            assert_eq!(unparsed, "(1e309-1e309)");
+        });
+        let nan = json!({ "_type": "Constant", "value": { "_type": "float", "value": "nan" } });
+        let err = ExprType::from_ast_json(&nan).unwrap_err();
+        assert!(err.to_string().contains("unsupported constant"), "{}", err);
+    }
+}
@@ -10,3 +10,21 @@ pub use node::*;
 
 pub mod dump;
 pub use dump::*;
+
+pub mod visit;
+pub use visit::*;
+
+pub mod json;
+pub use json::*;
+
+pub mod query;
+pub use query::*;
+
+pub mod diff;
+pub use diff::*;
+
+pub mod arena;
+pub use arena::*;
+
+pub mod trivia;
+pub use trivia::*;
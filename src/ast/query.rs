@@ -0,0 +1,618 @@
+//! Structural queries: finding the nodes of a tree that match a pattern, such as
+//! `Call(func=Attribute(value=Name('os'), attr='system'))`.
+//!
+//! Patterns are written like the reprs of CPython's `ast` nodes, and are matched against the [`json`](super::json)
+//! form of the tree, so the node types and field names are CPython's:
+//!
+//! * `Type(...)` matches a node of that type whose fields match. Positional patterns go to the fields in the
+//!   order of the class's `_fields`, and fields left out match anything, so a bare `Type` matches any node of
+//!   the type. An abstract type like `expr` or `stmt` matches any of its subclasses;
+//! * `_` matches anything, including a field that is missing or `None`;
+//! * strings in quotes, numbers, `True`, `False` and `None` match equal values, and `/regex/` matches a string
+//!   the regular expression finds a match in;
+//! * `[a, b]` matches a list of exactly those elements, where `...` stands for any number of them, so
+//!   `[..., Name('route'), ...]` matches a list with `route` in it anywhere;
+//! * `a | b` matches what either does, `!a` what `a` doesn't, and parentheses group;
+//! * `$name` matches anything and captures it, and `$name:pattern` captures what `pattern` matches. A name
+//!   captured twice has to match the same code both times, wherever it is, so `Compare(left=$x,
+//!   comparators=[$x])` finds `a == a`.
+
+// Parsing returns the crate-wide `Error`, whose size is shared with the rest of the crate.
+#![allow(clippy::result_large_err)]
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+use pyo3::{prelude::*, types::PyType};
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::{Error, Module, Result, SourceLocation, ToAstJson, json::unparse_json};
+
+/// A structural pattern. [`Query::parse`] reads one from the pattern language, or one can be built directly:
+///
+/// ```
+/// use python_ast::{Pattern, Query};
+///
+/// let pattern = Pattern::node("Call").field("func", Pattern::node("Name").field("id", Pattern::literal("eval")));
+/// let query = Query::new(pattern);
+/// ```
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    /// Matches anything.
+    Any,
+    /// A node of type `kind`, or a subclass of it, whose fields match.
+    Node { kind: String, fields: Vec<(String, Pattern)> },
+    /// A string, number, boolean or null.
+    Literal(Value),
+    /// A string the expression finds a match in.
+    Regex(Regex),
+    /// A list, where [`Element::Rest`] matches any number of elements.
+    List(Vec<Element>),
+    Or(Vec<Pattern>),
+    Not(Box<Pattern>),
+    /// Captures what the pattern matches under the name.
+    Capture(String, Box<Pattern>),
+}
+
+/// An element of a [`Pattern::List`].
+#[derive(Clone, Debug)]
+pub enum Element {
+    Pattern(Pattern),
+    /// `...`, for any number of elements.
+    Rest,
+}
+
+impl Pattern {
+    /// Matches any node of type `kind`; add the fields to match with [`Pattern::field`].
+    pub fn node(kind: impl Into<String>) -> Self {
+        Pattern::Node { kind: kind.into(), fields: Vec::new() }
+    }
+
+    /// Adds a field to a node pattern. Other patterns are returned unchanged.
+    pub fn field(mut self, name: impl Into<String>, pattern: Pattern) -> Self {
+        if let Pattern::Node { fields, .. } = &mut self {
+            fields.push((name.into(), pattern));
+        }
+        self
+    }
+
+    pub fn literal(value: impl Into<Value>) -> Self {
+        Pattern::Literal(value.into())
+    }
+
+    pub fn capture(name: impl Into<String>, pattern: Pattern) -> Self {
+        Pattern::Capture(name.into(), Box::new(pattern))
+    }
+
+    fn matches(&self, value: &Value, captures: &mut Captures) -> bool {
+        match self {
+            Pattern::Any => true,
+            Pattern::Node { kind, fields } => {
+                let Some(node) = value.as_object() else {
+                    return false;
+                };
+                let is_kind = node.get("_type").and_then(Value::as_str).is_some_and(|t| is_subclass(t, kind));
+                is_kind && fields.iter().all(|(name, pattern)| pattern.matches(node.get(name).unwrap_or(&Value::Null), captures))
+            }
+            Pattern::Literal(literal) => match (literal.as_f64(), value.as_f64()) {
+                (Some(a), Some(b)) => a == b,
+                _ => literal == value,
+            },
+            Pattern::Regex(regex) => value.as_str().is_some_and(|s| regex.is_match(s)),
+            Pattern::List(elements) => value.as_array().is_some_and(|values| match_list(elements, values, captures)),
+            Pattern::Or(patterns) => patterns.iter().any(|pattern| attempt(captures, |trial| pattern.matches(value, trial))),
+            // What a negated pattern captures is thrown away, since it didn't match.
+            Pattern::Not(pattern) => !pattern.matches(value, &mut captures.clone()),
+            Pattern::Capture(name, pattern) => {
+                if !pattern.matches(value, captures) {
+                    return false;
+                }
+                match captures.get(name) {
+                    Some(captured) => same_code(captured, value),
+                    None => {
+                        captures.insert(name.clone(), value.clone());
+                        true
+                    }
+                }
+            }
+        }
+    }
+}
+
+type Captures = BTreeMap<String, Value>;
+
+/// Runs `f` on a copy of the captures, and keeps what it captured only if it matched.
+fn attempt(captures: &mut Captures, f: impl FnOnce(&mut Captures) -> bool) -> bool {
+    let mut trial = captures.clone();
+    let matched = f(&mut trial);
+    if matched {
+        *captures = trial;
+    }
+    matched
+}
+
+fn match_list(elements: &[Element], values: &[Value], captures: &mut Captures) -> bool {
+    match elements.split_first() {
+        None => values.is_empty(),
+        Some((Element::Rest, rest)) => {
+            (0..=values.len()).any(|skip| attempt(captures, |trial| match_list(rest, &values[skip..], trial)))
+        }
+        Some((Element::Pattern(pattern), rest)) => match values.split_first() {
+            Some((value, others)) => {
+                attempt(captures, |trial| pattern.matches(value, trial) && match_list(rest, others, trial))
+            }
+            None => false,
+        },
+    }
+}
+
+/// Whether two trees are the same code: equal but for where they are, and whether names are read or written.
+fn same_code(a: &Value, b: &Value) -> bool {
+    const IGNORED: [&str; 5] = ["lineno", "col_offset", "end_lineno", "end_col_offset", "ctx"];
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            let keys = |map: &Map<String, Value>| map.keys().filter(|k| !IGNORED.contains(&k.as_str())).cloned().collect::<Vec<_>>();
+            keys(a) == keys(b) && keys(a).iter().all(|key| same_code(&a[key], &b[key]))
+        }
+        (Value::Array(a), Value::Array(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_code(a, b)),
+        _ => a == b,
+    }
+}
+
+/// What the pattern language needs to know about a CPython `ast` class.
+struct AstClass {
+    fields: Vec<String>,
+    attributes: Vec<String>,
+    /// The `ast` classes it derives from, itself included.
+    ancestors: Vec<String>,
+}
+
+/// The classes of CPython's `ast` module, by name.
+fn ast_classes() -> &'static HashMap<String, AstClass> {
+    static CLASSES: OnceLock<HashMap<String, AstClass>> = OnceLock::new();
+    CLASSES.get_or_init(|| {
+        let classes: PyResult<HashMap<String, AstClass>> = Python::with_gil(|py| {
+            let ast = py.import("ast")?;
+            let base = ast.getattr("AST")?;
+            let mut classes = HashMap::new();
+            for name in ast.dir()?.extract::<Vec<String>>()? {
+                let Ok(class) = ast.getattr(name.as_str())?.downcast_into::<PyType>() else {
+                    continue;
+                };
+                if !class.is_subclass(&base)? {
+                    continue;
+                }
+                let mro: Vec<Bound<'_, PyType>> = class.getattr("__mro__")?.extract()?;
+                let ancestors = mro.iter().filter(|c| c.is_subclass(&base).unwrap_or(false)).map(|c| c.name().map(|n| n.to_string())).collect::<PyResult<_>>()?;
+                let fields = class.getattr("_fields")?.extract()?;
+                let attributes = class.getattr("_attributes").and_then(|a| a.extract()).unwrap_or_default();
+                classes.insert(name, AstClass { fields, attributes, ancestors });
+            }
+            Ok(classes)
+        });
+        classes.unwrap_or_default()
+    })
+}
+
+/// Whether a node of type `kind` is a `pattern`, as a class or one of its subclasses.
+fn is_subclass(kind: &str, pattern: &str) -> bool {
+    kind == pattern || ast_classes().get(kind).is_some_and(|class| class.ancestors.iter().any(|a| a == pattern))
+}
+
+/// A node a query matched.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryMatch {
+    /// The node, in the [`json`](super::json) form.
+    pub node: Value,
+    /// Where the node is. Nodes without positions of their own, like calls, are placed at the first of the
+    /// nodes in them that has one.
+    pub location: SourceLocation,
+    /// What each `$name` in the pattern matched.
+    pub captures: BTreeMap<String, Value>,
+}
+
+impl QueryMatch {
+    /// The CPython type of the node, such as `Call`.
+    pub fn node_type(&self) -> &str {
+        self.node.get("_type").and_then(Value::as_str).unwrap_or_default()
+    }
+
+    /// The node as Python source, by way of CPython's `ast.unparse`.
+    pub fn to_python_source(&self) -> Result<String> {
+        unparse_json(&self.node)
+    }
+}
+
+/// A compiled pattern, to search trees with.
+#[derive(Clone, Debug)]
+pub struct Query {
+    pattern: Pattern,
+}
+
+impl Query {
+    pub fn new(pattern: Pattern) -> Self {
+        Query { pattern }
+    }
+
+    /// Reads a pattern in the pattern language, checking its node types and field names against CPython's.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut parser = PatternParser { chars: text.chars().collect(), position: 0 };
+        let pattern = parser.alternatives()?;
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(Query { pattern }),
+            Some(c) => Err(parser.error(format!("unexpected `{}` after the pattern", c))),
+        }
+    }
+
+    pub fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    /// The nodes of `module` the pattern matches, in source order.
+    pub fn find(&self, module: &Module) -> Vec<QueryMatch> {
+        let filename = module.filename.as_deref().unwrap_or("<unknown>");
+        let mut found = Vec::new();
+        self.search(&module.to_ast_json(), filename, None, &mut found);
+        // The JSON's fields are in alphabetical order, not the order they're written in.
+        found.sort_by_key(|m| (m.location.line, m.location.column));
+        found
+    }
+
+    /// Whether the pattern matches `node` itself, rather than something in it.
+    pub fn matches(&self, node: &impl ToAstJson) -> bool {
+        self.pattern.matches(&node.to_ast_json(), &mut Captures::new())
+    }
+
+    fn search(&self, value: &Value, filename: &str, enclosing: Option<(usize, usize)>, found: &mut Vec<QueryMatch>) {
+        match value {
+            Value::Object(node) => {
+                let start = first_position(value).or(enclosing);
+                if node.contains_key("_type") {
+                    let mut captures = Captures::new();
+                    if self.pattern.matches(value, &mut captures) {
+                        let column = |offset: Option<usize>| offset.map(|c| c + 1);
+                        let own_end = || (position(node, "end_lineno"), column(position(node, "end_col_offset")));
+                        let (end_line, end_column) = if position(node, "lineno").is_some() { own_end() } else { (None, None) };
+                        let location = SourceLocation::with_span(
+                            filename,
+                            start.map(|(line, _)| line),
+                            start.map(|(_, col)| col + 1),
+                            end_line,
+                            end_column,
+                        );
+                        found.push(QueryMatch { node: value.clone(), location, captures });
+                    }
+                }
+                for (key, child) in node.iter() {
+                    // Comments aren't nodes.
+                    if !key.starts_with('_') {
+                        self.search(child, filename, start, found);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter().for_each(|item| self.search(item, filename, enclosing, found)),
+            _ => {}
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        Query::parse(text)
+    }
+}
+
+fn position(node: &Map<String, Value>, key: &str) -> Option<usize> {
+    node.get(key).and_then(Value::as_u64).map(|p| p as usize)
+}
+
+/// The node's own line and 0-based column, or else the first of those of the nodes in it.
+fn first_position(value: &Value) -> Option<(usize, usize)> {
+    match value {
+        Value::Object(node) => match (position(node, "lineno"), position(node, "col_offset")) {
+            (Some(line), Some(column)) => Some((line, column)),
+            _ => node.iter().filter(|(key, _)| !key.starts_with('_')).filter_map(|(_, child)| first_position(child)).min(),
+        },
+        Value::Array(items) => items.iter().filter_map(first_position).min(),
+        _ => None,
+    }
+}
+
+/// A recursive-descent parser for the pattern language.
+struct PatternParser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl PatternParser {
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error::parsing_error(
+            SourceLocation::with_span("<query>", Some(1), Some(self.position + 1), None, None),
+            message,
+            "Patterns are written like CPython's ast reprs, e.g. Call(func=Attribute(value=Name('os'), attr='system')).",
+        )
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    /// Skips whitespace and then `expected`, if it's next.
+    fn eat(&mut self, expected: &str) -> bool {
+        self.skip_whitespace();
+        let matches = expected.chars().enumerate().all(|(i, c)| self.chars.get(self.position + i) == Some(&c));
+        if matches {
+            self.position += expected.chars().count();
+        }
+        matches
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<()> {
+        match self.eat(expected) {
+            true => Ok(()),
+            false => Err(self.error(format!("expected `{}`", expected))),
+        }
+    }
+
+    fn identifier(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            self.position += 1;
+        }
+        let identifier: String = self.chars[start..self.position].iter().collect();
+        match identifier.chars().next() {
+            Some(c) if !c.is_numeric() => Some(identifier),
+            _ => {
+                self.position = start;
+                None
+            }
+        }
+    }
+
+    fn alternatives(&mut self) -> Result<Pattern> {
+        let mut patterns = vec![self.term()?];
+        while self.eat("|") {
+            patterns.push(self.term()?);
+        }
+        Ok(if patterns.len() == 1 { patterns.remove(0) } else { Pattern::Or(patterns) })
+    }
+
+    fn term(&mut self) -> Result<Pattern> {
+        if self.eat("!") {
+            return Ok(Pattern::Not(Box::new(self.term()?)));
+        }
+        if self.eat("$") {
+            let name = self.identifier().ok_or_else(|| self.error("expected a name to capture after `$`"))?;
+            let pattern = if self.eat(":") { self.term()? } else { Pattern::Any };
+            return Ok(Pattern::capture(name, pattern));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Pattern> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('(') => {
+                self.position += 1;
+                let pattern = self.alternatives()?;
+                self.expect(")")?;
+                Ok(pattern)
+            }
+            Some('[') => {
+                self.position += 1;
+                self.list()
+            }
+            Some(quote @ ('\'' | '"')) => Ok(Pattern::Literal(Value::String(self.delimited(quote)?))),
+            Some('/') => {
+                let start = self.position;
+                let source = self.delimited('/')?;
+                Regex::new(&source).map(Pattern::Regex).map_err(|e| {
+                    self.position = start;
+                    self.error(format!("invalid regular expression: {}", e))
+                })
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' => self.number(),
+            Some(_) => {
+                let start = self.position;
+                let Some(name) = self.identifier() else {
+                    return Err(self.error("expected a pattern"));
+                };
+                match name.as_str() {
+                    "_" => Ok(Pattern::Any),
+                    "True" => Ok(Pattern::literal(true)),
+                    "False" => Ok(Pattern::literal(false)),
+                    "None" => Ok(Pattern::Literal(Value::Null)),
+                    _ => {
+                        let Some(class) = ast_classes().get(&name) else {
+                            self.position = start;
+                            return Err(self.error(format!("`{}` isn't a node type of Python's ast module", name)));
+                        };
+                        let fields = if self.eat("(") { self.arguments(&name, class)? } else { Vec::new() };
+                        Ok(Pattern::Node { kind: name, fields })
+                    }
+                }
+            }
+            None => Err(self.error("expected a pattern, but the query ended")),
+        }
+    }
+
+    fn arguments(&mut self, kind: &str, class: &AstClass) -> Result<Vec<(String, Pattern)>> {
+        let mut fields = Vec::new();
+        let mut named = false;
+        while !self.eat(")") {
+            if !fields.is_empty() {
+                self.expect(",")?;
+                if self.eat(")") {
+                    break;
+                }
+            }
+            let start = self.position;
+            // A keyword is a name followed by `=`; anything else is positional.
+            let keyword = self.identifier().filter(|_| self.eat("="));
+            match keyword {
+                Some(field) => {
+                    if !class.fields.contains(&field) && !class.attributes.contains(&field) {
+                        self.position = start;
+                        let known = class.fields.join(", ");
+                        return Err(self.error(format!("`{}` has no field `{}`; its fields are {}", kind, field, known)));
+                    }
+                    named = true;
+                    fields.push((field, self.alternatives()?));
+                }
+                None => {
+                    self.position = start;
+                    if named {
+                        return Err(self.error("positional patterns have to come before the named ones"));
+                    }
+                    let Some(field) = class.fields.get(fields.len()).cloned() else {
+                        return Err(self.error(format!("`{}` has only {} fields", kind, class.fields.len())));
+                    };
+                    fields.push((field, self.alternatives()?));
+                }
+            }
+        }
+        Ok(fields)
+    }
+
+    fn list(&mut self) -> Result<Pattern> {
+        let mut elements = Vec::new();
+        while !self.eat("]") {
+            if !elements.is_empty() {
+                self.expect(",")?;
+                if self.eat("]") {
+                    break;
+                }
+            }
+            let element = if self.eat("...") { Element::Rest } else { Element::Pattern(self.alternatives()?) };
+            elements.push(element);
+        }
+        Ok(Pattern::List(elements))
+    }
+
+    /// The text up to the closing `delimiter`, where a backslash escapes the delimiter and itself.
+    fn delimited(&mut self, delimiter: char) -> Result<String> {
+        let start = self.position;
+        self.position += 1;
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                Some(c) if c == delimiter => {
+                    self.position += 1;
+                    return Ok(text);
+                }
+                Some('\\') if matches!(self.chars.get(self.position + 1), Some(&c) if c == delimiter || c == '\\') => {
+                    // Regular expressions keep their own escapes.
+                    if delimiter == '/' && self.chars[self.position + 1] == '\\' {
+                        text.push('\\');
+                    }
+                    text.push(self.chars[self.position + 1]);
+                    self.position += 2;
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.position += 1;
+                }
+                None => {
+                    self.position = start;
+                    return Err(self.error(format!("unterminated `{}`", delimiter)));
+                }
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Pattern> {
+        let start = self.position;
+        self.position += 1;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_') {
+            self.position += 1;
+        }
+        let text: String = self.chars[start..self.position].iter().filter(|&&c| c != '_').collect();
+        if let Ok(integer) = text.parse::<i64>() {
+            return Ok(Pattern::literal(integer));
+        }
+        match text.parse::<f64>() {
+            Ok(float) => Ok(Pattern::literal(float)),
+            Err(_) => {
+                self.position = start;
+                Err(self.error(format!("`{}` isn't a number", text)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn find(pattern: &str, source: &str) -> Vec<QueryMatch> {
+        let module = parse(source, "test.py").unwrap();
+        Query::parse(pattern).unwrap().find(&module)
+    }
+
+    fn sources(matches: &[QueryMatch]) -> Vec<String> {
+        matches.iter().map(|m| m.to_python_source().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_finds_calls_by_structure() {
+        let source = "import os\nos.system('ls')\nos.path.join('a')\nx = os.system(cmd)\n";
+        let found = find("Call(func=Attribute(value=Name('os'), attr='system'))", source);
+        assert_eq!(sources(&found), vec!["os.system('ls')", "os.system(cmd)"]);
+        // Calls have no positions, so they're placed at their first name.
+        assert_eq!((found[1].location.line, found[1].location.column), (Some(4), Some(5)));
+        assert_eq!(found[0].node_type(), "Call");
+    }
+
+    #[test]
+    fn test_lists_and_alternatives() {
+        let source = "@app.route('/')\ndef index():\n    pass\n\n@route\ndef about():\n    pass\n\n@cache\ndef other():\n    pass\n";
+        let pattern = "FunctionDef(decorator_list=[..., Name('route') | Call(func=Attribute(attr='route')), ...])";
+        let names: Vec<_> = find(pattern, source).iter().map(|m| m.node["name"].clone()).collect();
+        assert_eq!(names, vec!["index", "about"]);
+        assert_eq!(find("FunctionDef(name=/^ot/, decorator_list=[_])", source).len(), 1);
+        assert_eq!(find("FunctionDef(name=!'other')", source).len(), 2);
+    }
+
+    #[test]
+    fn test_captures_and_abstract_types() {
+        let found = find("Compare(left=$x, comparators=[$x])", "if a == a:\n    pass\nif a == b:\n    pass\n");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].captures["x"]["id"], "a");
+        let found = find("Assign(targets=[$target], value=$value:Constant)", "x = 1\ny = f()\n");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].captures["value"]["value"], 1);
+        assert_eq!(find("Return(value=expr)", "def f():\n    return 1\n\ndef g():\n    return\n").len(), 1);
+    }
+
+    #[test]
+    fn test_builder() {
+        let pattern = Pattern::node("Call").field("func", Pattern::node("Name").field("id", Pattern::literal("eval")));
+        let module = parse("eval(x)\nprint(x)\n", "test.py").unwrap();
+        assert_eq!(Query::new(pattern).find(&module).len(), 1);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let message = |pattern: &str| Query::parse(pattern).unwrap_err().to_string();
+        assert!(message("Cal(func=_)").contains("`Cal` isn't a node type"));
+        assert!(message("Name(idd='x')").contains("`Name` has no field `idd`; its fields are id, ctx"));
+        assert!(message("Name('x', 'y', 'z')").contains("`Name` has only 2 fields"));
+        assert!(message("Call(func=_").contains("expected `,`"));
+        assert!(message("Name('x").contains("unterminated `'`"));
+    }
+}
@@ -548,9 +548,7 @@ def func(a, *, b, c=3):
     #[test]
     fn test_arg_with_name() {
         // Test that Arg (now ExprType) works with name expressions
-        let name_expr = ExprType::Name(crate::Name {
-            id: "variable".to_string(),
-        });
+        let name_expr = ExprType::Name(crate::Name::new("variable"));
         let arg: Arg = name_expr;
         
         let options = PythonOptions::default();
@@ -1,10 +1,10 @@
 use proc_macro2::TokenStream;
 use pyo3::{Bound, FromPyObject, PyAny, PyResult, prelude::PyAnyMethods};
-use quote::quote;
+use quote::{format_ident, quote};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    CodeGen, CodeGenContext, ExprType, Node, PythonOptions, SymbolTableNode,
+    declares, CodeGen, CodeGenContext, ExprType, Node, PythonOptions, SymbolTableNode,
     SymbolTableScopes,
 };
 
@@ -54,16 +54,15 @@ impl<'a> CodeGen for Assign {
         let mut position = 0;
         for target in self.targets {
             // Only add symbols for Name assignments, not for Attribute assignments
-            if let ExprType::Name(name) = target {
+            for name in target_names(&target) {
                 symbols.insert(
-                    name.id,
+                    name,
                     SymbolTableNode::Assign {
                         position: position,
                         value: self.value.clone(),
                     },
                 );
             }
-            // Could also handle other target types here if needed
             position += 1;
         }
         symbols
@@ -75,28 +74,97 @@ impl<'a> CodeGen for Assign {
         options: Self::Options,
         symbols: Self::SymbolTable,
     ) -> Result<TokenStream, Box<dyn std::error::Error>> {
-        let mut target_streams = Vec::new();
-        
-        // Convert each target to Rust code
-        for target in self.targets {
-            let target_code = target.to_rust(ctx.clone(), options.clone(), symbols.clone())?;
-            target_streams.push(target_code);
+        let value = self.value.to_rust(ctx.clone(), options.clone(), symbols.clone())?;
+
+        // Each target is either declared here, with `let`, or was declared already (see `Declarations`).
+        let mut assignments = Vec::new();
+        let count = self.targets.len();
+        for (index, target) in self.targets.into_iter().enumerate() {
+            // `a = b = value` evaluates the value once, and gives each target a copy.
+            let value = if count == 1 { value.clone() } else if index + 1 == count { quote!(__value) } else { quote!(__value.clone()) };
+            match declaration_pattern(&target, &symbols) {
+                Some(pattern) => assignments.push(quote!(let #pattern = #value;)),
+                None => {
+                    let target = target.to_rust(ctx.clone(), options.clone(), symbols.clone())?;
+                    assignments.push(quote!(#target = #value;));
+                }
+            }
         }
-        
-        let value = self.value.to_rust(ctx, options, symbols)?;
-        
-        // For single target assignment
-        if target_streams.len() == 1 {
-            let target = &target_streams[0];
-            // Check if this is a new variable declaration or reassignment
-            // For now, we'll use `let` for new declarations
-            Ok(quote!(let #target = #value;))
+
+        if count == 1 {
+            Ok(quote!(#(#assignments)*))
         } else {
-            // For multiple assignment targets like: a, b = 1, 2
-            // Use tuple destructuring in Rust
-            Ok(quote! {
-                let (#(#target_streams),*) = #value;
-            })
+            Ok(quote!(let __value = #value; #(#assignments)*))
         }
     }
 }
+
+/// The names an assignment target binds, including those unpacked from a tuple or list.
+fn target_names(target: &ExprType) -> Vec<String> {
+    match target {
+        ExprType::Name(name) => vec![name.id.clone()],
+        ExprType::Tuple(t) => t.elts.iter().flat_map(target_names).collect(),
+        ExprType::List(elts) => elts.iter().flat_map(target_names).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The pattern a `let` binds for an assignment that declares every name in `target`, e.g. `(mut a, b)`.
+fn declaration_pattern(target: &ExprType, symbols: &SymbolTableScopes) -> Option<TokenStream> {
+    let elts = match target {
+        ExprType::Name(name) => {
+            let mutable = declares(symbols, &name.id)?;
+            let name = format_ident!("{}", name.id);
+            return Some(if mutable { quote!(mut #name) } else { quote!(#name) });
+        }
+        ExprType::Tuple(t) => &t.elts,
+        ExprType::List(elts) => elts,
+        _ => return None,
+    };
+    let patterns = elts.iter().map(|e| declaration_pattern(e, symbols)).collect::<Option<Vec<_>>>()?;
+    match patterns.as_slice() {
+        [pattern] => Some(quote!((#pattern,))),
+        _ => Some(quote!((#(#patterns),*))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::macros::rust_for;
+
+    #[test]
+    fn test_reassignment_in_loop() {
+        let code = rust_for("def f(n):\n    total = 0\n    for i in range(n):\n        total = total + i\n    return total");
+        assert_eq!(
+            code,
+            "pub fn f (n : impl Into < PyObject >) { let mut total = 0 ; for i in range (n) { total = (total) + (i) ; } return total ; }"
+        );
+    }
+
+    #[test]
+    fn test_branch_assignments_are_hoisted() {
+        let code = rust_for("def f(c):\n    if c:\n        label = 'big'\n    else:\n        label = 'small'\n    return label");
+        assert_eq!(
+            code,
+            "pub fn f (c : impl Into < PyObject >) { let label : & str ; if c { label = \"big\" ; } else { label = \"small\" ; } return label ; }"
+        );
+    }
+
+    #[test]
+    fn test_mut_only_when_reassigned() {
+        let code = rust_for("def f():\n    a, b = 1, 2\n    a = 3\n    c = a + b\n    return c");
+        assert_eq!(code, "pub fn f () { let (mut a , b) = (1 , 2) ; a = 3 ; let c = (a) + (b) ; return c ; }");
+    }
+
+    #[test]
+    fn test_chained_assignment() {
+        let code = rust_for("def f():\n    x = y = g()\n    return x + y");
+        assert_eq!(code, "pub fn f () { let __value = g () ; let x = __value . clone () ; let y = __value ; return (x) + (y) ; }");
+    }
+
+    #[test]
+    fn test_attribute_target_is_assigned() {
+        let code = rust_for("def f(p):\n    p.x = 1");
+        assert_eq!(code, "pub fn f (p : impl Into < PyObject >) { p . x = 1 ; }");
+    }
+}
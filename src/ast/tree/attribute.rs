@@ -9,9 +9,9 @@ use serde::{Deserialize, Serialize};
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 //#[pyo3(transparent)]
 pub struct Attribute {
-    value: Box<ExprType>,
-    attr: String,
-    ctx: String,
+    pub value: Box<ExprType>,
+    pub attr: String,
+    pub ctx: String,
 }
 
 impl<'a> FromPyObject<'a> for Attribute {
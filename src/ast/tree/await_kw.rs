@@ -32,10 +32,11 @@ impl<'a> CodeGen for Await {
         _options: Self::Options,
         _symbols: Self::SymbolTable,
     ) -> Result<TokenStream, Box<dyn std::error::Error>> {
-        let value = self
-            .value
-            .to_rust(_ctx, _options, _symbols)
-            .expect("Failed to convert async function to rust");
+        let value = match *self.value {
+            // Left to itself, a call to an async function adds its own `.await`.
+            ExprType::Call(call) => call.awaited_rust(_ctx, _options, _symbols)?,
+            value => value.to_rust(_ctx, _options, _symbols)?,
+        };
         Ok(quote!(#value.await))
     }
 }
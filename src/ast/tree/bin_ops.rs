@@ -4,8 +4,8 @@ use quote::quote;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    dump, CodeGen, CodeGenContext, Error, ExprType, Node, PythonOptions, SymbolTableScopes,
-    PythonOperator, BinaryOperation, FromPythonString, PyAttributeExtractor,
+    dump, percent_format, CodeGen, CodeGenContext, Error, ExprType, Node, PercentArgs, PythonOptions,
+    SymbolTableScopes, PythonOperator, BinaryOperation, FromPythonString, PyAttributeExtractor,
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -69,8 +69,8 @@ impl PythonOperator for BinOps {
             BinOps::BitAnd => Ok(quote!(&)),
             _ => Err(Error::BinOpNotYetImplemented(BinOp { 
                 op: self.clone(), 
-                left: Box::new(ExprType::Name(crate::Name { id: "unknown".to_string() })),
-                right: Box::new(ExprType::Name(crate::Name { id: "unknown".to_string() })),
+                left: Box::new(ExprType::Name(crate::Name::new("unknown"))),
+                right: Box::new(ExprType::Name(crate::Name::new("unknown"))),
             }).into()),
         }
     }
@@ -104,9 +104,9 @@ impl<'a> FromPyObject<'a> for BinOps {
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct BinOp {
-    op: BinOps,
-    left: Box<ExprType>,
-    right: Box<ExprType>,
+    pub op: BinOps,
+    pub left: Box<ExprType>,
+    pub right: Box<ExprType>,
 }
 
 impl BinaryOperation for BinOp {
@@ -164,6 +164,32 @@ impl CodeGen for BinOp {
         options: Self::Options,
         symbols: Self::SymbolTable,
     ) -> std::result::Result<TokenStream, Box<dyn std::error::Error>> {
+        // A string on the left of % is printf-style interpolation, not modulo.
+        if matches!(self.op, BinOps::Mod)
+            && let Some(template) = self.left.as_ref().string_value()
+        {
+            let args = match *self.right.clone() {
+                ExprType::Tuple(t) => PercentArgs::Positional(
+                    t.elts
+                        .into_iter()
+                        .map(|e| e.to_rust(ctx.clone(), options.clone(), symbols.clone()))
+                        .collect::<Result<_, _>>()?,
+                ),
+                ExprType::Dict(d) if d.keys.iter().all(|k| k.as_ref().and_then(|k| k.string_value()).is_some()) => {
+                    let mut entries = Vec::new();
+                    for (key, value) in d.keys.into_iter().zip(d.values) {
+                        let key = key.as_ref().and_then(|k| k.string_value()).unwrap_or_default().to_string();
+                        entries.push((key, value.to_rust(ctx.clone(), options.clone(), symbols.clone())?));
+                    }
+                    PercentArgs::Mapping(entries)
+                }
+                other => PercentArgs::Positional(vec![other.to_rust(ctx.clone(), options.clone(), symbols.clone())?]),
+            };
+            if let Some(tokens) = percent_format(template, args) {
+                return Ok(tokens);
+            }
+        }
+
         // Special handling for Pow operator which needs different syntax
         if matches!(self.op, BinOps::Pow) {
             let left = self.left.clone().to_rust(ctx.clone(), options.clone(), symbols.clone())?;
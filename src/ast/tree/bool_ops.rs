@@ -40,9 +40,9 @@ impl<'a> FromPyObject<'a> for BoolOps {
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct BoolOp {
-    op: BoolOps,
-    left: Box<ExprType>,
-    right: Box<ExprType>,
+    pub op: BoolOps,
+    pub left: Box<ExprType>,
+    pub right: Box<ExprType>,
 }
 
 impl<'a> FromPyObject<'a> for BoolOp {
@@ -68,9 +68,11 @@ impl<'a> FromPyObject<'a> for BoolOp {
 
         log::debug!("BoolOps values: {}", dump(&values, None)?);
 
-        let value: Vec<ExprType> = values.extract().expect("getting values from BoolOp");
-        let left = value[0].clone();
-        let right = value[1].clone();
+        let mut value: Vec<ExprType> = values.extract().expect("getting values from BoolOp");
+        // Python keeps every operand of `a or b or c` in one node; ours is binary, so it nests to the left.
+        let right = value.pop().expect("BoolOp without values");
+        let mut operands = value.into_iter();
+        let first = operands.next().expect("BoolOp with one value");
 
         let op_type_str: String = op_type.extract()?;
         let op = match op_type_str.as_str() {
@@ -83,6 +85,10 @@ impl<'a> FromPyObject<'a> for BoolOp {
             }
         };
 
+        let left = operands.fold(first, |left, right| {
+            ExprType::BoolOp(BoolOp { op: op.clone(), left: Box::new(left), right: Box::new(right) })
+        });
+
         log::debug!(
             "left: {:?}, right: {:?}, op: {:?}/{:?}",
             left,
@@ -183,4 +189,17 @@ mod tests {
             .unwrap();
         log::info!("module: {:?}", code);
     }
+
+    #[test]
+    fn test_chain_keeps_every_operand() {
+        let result = crate::parse("a or b or c", "test_case.py").unwrap();
+        let name = |expr: &ExprType| match expr {
+            ExprType::Name(n) => n.id.clone(),
+            other => panic!("expected a name, got {:?}", other),
+        };
+        let crate::StatementType::Expr(expr) = &result.raw.body[0].statement else { panic!("expected an expression") };
+        let ExprType::BoolOp(outer) = &expr.value else { panic!("expected a BoolOp, got {:?}", expr.value) };
+        let ExprType::BoolOp(inner) = outer.left.as_ref() else { panic!("expected a nested BoolOp, got {:?}", outer.left) };
+        assert_eq!([name(&inner.left), name(&inner.right), name(&outer.right)], ["a", "b", "c"]);
+    }
 }
@@ -3,7 +3,10 @@ use pyo3::{Bound, FromPyObject, PyAny, PyResult};
 use quote::quote;
 use serde::{Deserialize, Serialize};
 
-use crate::{CodeGen, CodeGenContext, ExprType, Keyword, PythonOptions, SymbolTableScopes, extract_required_attr};
+use crate::{
+    str_format, CodeGen, CodeGenContext, ExprType, Keyword, PythonOptions, SymbolTableNode, SymbolTableScopes,
+    extract_required_attr,
+};
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
 pub struct Call {
@@ -37,6 +40,57 @@ impl<'a> CodeGen for Call {
         options: Self::Options,
         symbols: Self::SymbolTable,
     ) -> Result<TokenStream, Box<dyn std::error::Error>> {
+        self.generate(ctx, options, symbols, false)
+    }
+}
+
+impl Call {
+    /// Generates the call as the operand of an `await`, which adds the `.await` itself.
+    pub(crate) fn awaited_rust(
+        self,
+        ctx: CodeGenContext,
+        options: PythonOptions,
+        symbols: SymbolTableScopes,
+    ) -> Result<TokenStream, Box<dyn std::error::Error>> {
+        self.generate(ctx, options, symbols, true)
+    }
+
+    fn generate(
+        self,
+        ctx: CodeGenContext,
+        options: PythonOptions,
+        symbols: SymbolTableScopes,
+        awaited: bool,
+    ) -> Result<TokenStream, Box<dyn std::error::Error>> {
+        // "...".format(...) becomes a single format! call when the arguments can be mapped statically.
+        if let ExprType::Attribute(attribute) = self.func.as_ref() {
+            let template = attribute.value.string_value();
+            let unpacks = self.args.iter().any(|a| matches!(a, ExprType::Starred(_)))
+                || self.keywords.iter().any(|k| k.arg.is_none());
+            if let (Some(template), "format", false) = (template, attribute.attr.as_str(), unpacks) {
+                let args = self
+                    .args
+                    .iter()
+                    .map(|a| a.clone().to_rust(ctx.clone(), options.clone(), symbols.clone()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut keywords = Vec::new();
+                for keyword in &self.keywords {
+                    let value = keyword.value.clone().to_rust(ctx.clone(), options.clone(), symbols.clone())?;
+                    keywords.push((keyword.arg.clone().unwrap_or_default(), value));
+                }
+                if let Some(tokens) = str_format(template, &args, &keywords) {
+                    return Ok(tokens);
+                }
+            }
+        }
+
+        // What the symbol table knows about the callee, from the module or from a stub.
+        let known = dotted_name(&self.func).and_then(|callee| symbols.get(&callee).cloned());
+        let keywords = match known.as_ref().and_then(SymbolTableNode::parameters) {
+            Some(parameters) => in_parameter_order(self.keywords, self.args.len(), &parameters),
+            None => self.keywords,
+        };
+
         let name = self.func.to_rust(ctx.clone(), options.clone(), symbols.clone())?;
         
         let mut all_args = Vec::new();
@@ -48,22 +102,19 @@ impl<'a> CodeGen for Call {
         }
         
         // Add keyword arguments
-        for keyword in self.keywords {
+        for keyword in keywords {
             let rust_kw = keyword.to_rust(ctx.clone(), options.clone(), symbols.clone())?;
             all_args.push(rust_kw);
         }
         
-        // Check if we're in an async context and if the function being called is async
-        let call_expr = quote!(#name(#(#all_args),*));
-        
-        // Check if this function returns a Result that should be unwrapped
+        // A stub says whether the callee returns a `Result` to unwrap.
+        let unwrap = known
+            .as_ref()
+            .and_then(SymbolTableNode::returns_result)
+            .unwrap_or(false)
+            .then(|| quote!(.unwrap()));
         let name_str = format!("{}", name);
-        let needs_unwrap = matches!(name_str.as_str(), 
-            "subprocess :: run" | "subprocess :: run_with_env" | "subprocess :: check_call" | 
-            "subprocess :: check_output" | "os :: getcwd" | "os :: chdir" | "os :: execv" |
-            "os :: path :: abspath"
-        );
-        
+
         // Special handling for subprocess.run and os.execv with fallback for compatibility
         let final_call = if name_str == "subprocess :: run" {
             // Try mixed_args version first, fallback to regular version
@@ -75,14 +126,14 @@ impl<'a> CodeGen for Call {
                     let args_owned: Vec<String> = #args_param;
                     let args_vec: Vec<&str> = args_owned.iter().map(|s| s.as_str()).collect();
                     let cwd_str = #cwd_param;
-                    subprocess::run(args_vec, Some(&cwd_str)).unwrap()
+                    subprocess::run(args_vec, Some(&cwd_str))#unwrap
                 })
             } else {
                 let args_param = &all_args[0];
                 quote!({
                     let args_owned: Vec<String> = #args_param;
                     let args_vec: Vec<&str> = args_owned.iter().map(|s| s.as_str()).collect();
-                    subprocess::run(args_vec, None).unwrap()
+                    subprocess::run(args_vec, None)#unwrap
                 })
             }
         } else if name_str == "os :: execv" {
@@ -93,26 +144,20 @@ impl<'a> CodeGen for Call {
                 let program_str: String = (#program_param).clone();
                 let args_owned: Vec<String> = #args_param;
                 let args_vec: Vec<&str> = args_owned.iter().map(|s| s.as_str()).collect();
-                os::execv(&program_str, args_vec).unwrap()
+                os::execv(&program_str, args_vec)#unwrap
             })
-        } else if needs_unwrap {
-            quote!(#call_expr.unwrap())
         } else {
-            call_expr
+            quote!(#name(#(#all_args),*)#unwrap)
         };
-        
+
         match ctx {
-            CodeGenContext::Async(_) => {
-                // In async context, we assume Python async functions need .await
-                // We'll check if the function name suggests it's async
-                if name_str.contains("async") || 
-                   name_str.starts_with("a") || // Common async function naming
-                   // TODO: Better async function detection based on symbol table
-                   false {
+            // Inside an `await`, the `Await` adds the `.await`.
+            CodeGenContext::Async(_) if !awaited => {
+                // A stub says whether the callee is async. Otherwise, guess from the name.
+                let is_async = known.as_ref().and_then(SymbolTableNode::is_async);
+                if is_async.unwrap_or_else(|| name_str.contains("async") || name_str.starts_with("a")) {
                     Ok(quote!(#final_call.await))
                 } else {
-                    // For now, just return the regular call
-                    // In a full implementation, we'd track which functions are async
                     Ok(final_call)
                 }
             },
@@ -121,9 +166,58 @@ impl<'a> CodeGen for Call {
     }
 }
 
+/// The dotted name a callee is called by, such as `os.path.join`, if it is one.
+fn dotted_name(func: &ExprType) -> Option<String> {
+    match func {
+        ExprType::Name(name) => Some(name.id.clone()),
+        ExprType::Attribute(attribute) => Some(format!("{}.{}", dotted_name(&attribute.value)?, attribute.attr)),
+        _ => None,
+    }
+}
+
+/// Sorts keyword arguments into the order of the callee's parameters, since they are passed positionally,
+/// passing the default for any parameter skipped before the last one given. When a keyword can't be matched
+/// to a parameter after the positional arguments, or a skipped parameter's default isn't known, the call's
+/// order is kept.
+fn in_parameter_order(
+    keywords: Vec<Keyword>,
+    positional: usize,
+    parameters: &[(String, Option<ExprType>)],
+) -> Vec<Keyword> {
+    let positions: Option<Vec<usize>> = keywords
+        .iter()
+        .map(|keyword| {
+            let name = keyword.arg.as_ref()?;
+            parameters.iter().position(|(p, _)| p == name).filter(|&index| index >= positional)
+        })
+        .collect();
+    let Some(positions) = positions else {
+        return keywords;
+    };
+    let Some(&last) = positions.iter().max() else {
+        return keywords;
+    };
+
+    let mut given: Vec<_> = positions.into_iter().zip(keywords.iter().cloned()).collect();
+    given.sort_by_key(|(index, _)| *index);
+    let mut given = given.into_iter().peekable();
+    let mut ordered = Vec::new();
+    for (index, (name, default)) in parameters.iter().enumerate().take(last + 1).skip(positional) {
+        match given.next_if(|(i, _)| *i == index) {
+            Some((_, keyword)) => ordered.push(keyword),
+            None => match default {
+                Some(default) => ordered.push(Keyword { arg: Some(name.clone()), value: default.clone(), ..Default::default() }),
+                None => return keywords,
+            },
+        }
+    }
+    ordered
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::macros::rust_for;
 
     #[test]
     fn test_lookup_of_function() {
@@ -144,4 +238,45 @@ foo(b=9)",
             )
             .unwrap();
     }
+
+    #[test]
+    fn test_keywords_follow_parameter_order() {
+        let code = rust_for("def area(width, height, scale=1):\n    pass\n\narea(2, scale=3, height=4)\n");
+        assert!(code.contains("area (2 , 4 , 3)"), "{}", code);
+
+        // From the builtins stub: sorted(iterable, /, *, key=None, reverse=False).
+        let code = rust_for("xs = []\nsorted(xs, reverse=True, key=len)\n");
+        assert!(code.contains("sorted (xs , len , true)"), "{}", code);
+
+        // Skipped parameters get their defaults, from the module or a stub.
+        let code = rust_for("def f(a, b=2, c=4):\n    pass\n\nf(x, c=3)\n");
+        assert!(code.contains("f (x , 2 , 3)"), "{}", code);
+        let code = rust_for("xs = []\nsorted(xs, reverse=True)\n");
+        assert!(code.contains("sorted (xs , None , true)"), "{}", code);
+
+        // `b` is already filled positionally, so the call is left as written.
+        let code = rust_for("def f(a, b):\n    pass\n\nf(1, 2, a=3)\n");
+        assert!(code.contains("f (1 , 2 , 3)"), "{}", code);
+    }
+
+    #[test]
+    fn test_unknown_default_keeps_call_order() {
+        // As for a stub that leaves `b`'s default out as `...`.
+        let parameters = ["a", "b", "c", "d"].map(|name| (name.to_string(), None));
+        let keyword = |arg: &str| Keyword { arg: Some(arg.to_string()), ..Default::default() };
+        let ordered = in_parameter_order(vec![keyword("d"), keyword("c")], 1, &parameters);
+        assert_eq!(ordered, vec![keyword("d"), keyword("c")]);
+    }
+
+    #[test]
+    fn test_awaits_async_stub_functions() {
+        let code = rust_for("import asyncio\n\nasync def main():\n    await asyncio.sleep(1)\n    asyncio.sleep(2)\n    abs(-1)\n");
+        assert!(code.contains("{ asyncio :: sleep (1) . await ; asyncio :: sleep (2) . await ; abs (- 1) ; }"), "{}", code);
+    }
+
+    #[test]
+    fn test_unwraps_stub_results() {
+        let code = rust_for("import os\nfrom os.path import abspath\n\ndef f(d):\n    os.getcwd()\n    os.getpid()\n    abspath(d)\n");
+        assert!(code.contains("{ os :: getcwd () . unwrap () ; os :: getpid () ; abspath (d) . unwrap () ; }"), "{}", code);
+    }
 }
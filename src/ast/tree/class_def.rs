@@ -28,7 +28,7 @@ use pyo3::FromPyObject;
 use quote::{format_ident, quote};
 
 use crate::{
-    CodeGen, CodeGenContext, ExprType, Name, PythonOptions, Statement, StatementType,
+    CodeGen, CodeGenContext, ExprType, Keyword, Name, PythonOptions, Statement, StatementType,
     SymbolTableNode, SymbolTableScopes,
 };
 
@@ -40,8 +40,11 @@ use serde::{Deserialize, Serialize};
 pub struct ClassDef {
     pub name: String,
     pub bases: Vec<Name>,
-    pub keywords: Vec<String>,
+    /// Keywords passed to the class's construction, such as `metaclass=ABCMeta`.
+    pub keywords: Vec<Keyword>,
     pub body: Vec<Statement>,
+    #[serde(default)]
+    pub decorator_list: Vec<ExprType>,
 }
 
 impl CodeGen for ClassDef {
@@ -89,11 +92,7 @@ impl CodeGen for ClassDef {
         }
 
         for s in self.body.clone() {
-            streams.extend(
-                s.clone()
-                    .to_rust(CodeGenContext::Class, options.clone(), symbols.clone())
-                    .expect(format!("Failed to parse statement {:?}", s).as_str()),
-            );
+            streams.extend(s.clone().to_rust(CodeGenContext::Class, options.clone(), symbols.clone())?);
         }
 
         let class = if let Some(docstring) = self.get_docstring() {
@@ -34,9 +34,9 @@ impl<'a> FromPyObject<'a> for Compares {
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Compare {
-    ops: Vec<Compares>,
-    left: Box<ExprType>,
-    comparators: Vec<ExprType>,
+    pub ops: Vec<Compares>,
+    pub left: Box<ExprType>,
+    pub comparators: Vec<ExprType>,
 }
 
 impl<'a> FromPyObject<'a> for Compare {
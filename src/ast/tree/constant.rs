@@ -4,7 +4,12 @@ use encoding::{all::ISO_8859_6, DecoderTrap, Encoding};
 use litrs::Literal;
 use log::debug;
 use proc_macro2::*;
-use pyo3::{Bound, FromPyObject, PyAny, PyResult, prelude::PyAnyMethods};
+use pyo3::{
+    exceptions::PyValueError,
+    prelude::PyAnyMethods,
+    types::{PyComplex, PyComplexMethods, PyInt},
+    Bound, FromPyObject, PyAny, PyResult,
+};
 use quote::quote;
 
 use crate::{CodeGen, CodeGenContext, Node, PythonOptions, SymbolTableScopes};
@@ -19,6 +24,16 @@ pub trait PyConstantTrait: Clone + Debug + PartialEq {
 #[repr(transparent)]
 pub struct Constant(pub Option<Literal<String>>);
 
+impl Constant {
+    /// The value of a string constant, with escapes resolved.
+    pub fn string_value(&self) -> Option<&str> {
+        match &self.0 {
+            Some(Literal::String(s)) => Some(s.value()),
+            _ => None,
+        }
+    }
+}
+
 impl Serialize for Constant {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
@@ -34,7 +49,11 @@ impl<'de> Deserialize<'de> for Constant {
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        let l = Literal::parse(s).expect("[3] Parsing the literal");
+        // Mirrors `to_string`, which writes a missing value as `None`.
+        if s == "None" {
+            return Ok(Self(None));
+        }
+        let l = Literal::parse(s).map_err(serde::de::Error::custom)?;
         Ok(Self(Some(l)))
     }
 }
@@ -50,7 +69,8 @@ impl std::string::ToString for Constant {
 
 pub fn try_string(value: &Bound<PyAny>) -> PyResult<Option<Literal<String>>> {
     let v: String = value.extract()?;
-    let l = Literal::parse(format!("\"{}\"", v)).expect("[4] Parsing the literal");
+    // Debug formatting escapes quotes and backslashes, so any string makes a valid literal.
+    let l = Literal::parse(format!("{:?}", v)).expect("[4] Parsing the literal");
 
     Ok(Some(l))
 }
@@ -69,15 +89,40 @@ pub fn try_bytes(value: &Bound<PyAny>) -> PyResult<Option<Literal<String>>> {
 }
 
 pub fn try_int(value: &Bound<PyAny>) -> PyResult<Option<Literal<String>>> {
-    let v: isize = value.extract()?;
-    let l = Literal::parse(format!("{}", v)).expect("[4] Parsing the literal");
+    // Python ints have no size limit, so the digits are taken as text rather than through a Rust integer.
+    let v = value.downcast::<PyInt>()?.str()?;
+    let l = Literal::parse(v.to_string()).expect("[4] Parsing the literal");
 
     Ok(Some(l))
 }
 
+/// The literal text of a float, with infinity written as `1e309` like `ast.unparse` does. NaN and
+/// negative infinity never appear in a parsed tree and have no literal form.
+pub fn float_literal(v: f64) -> Option<String> {
+    match v {
+        f64::INFINITY => Some("1e309".to_string()),
+        // Debug formatting keeps the fractional part, so `5.0` stays a float.
+        v if v.is_finite() => Some(format!("{:?}", v)),
+        _ => None,
+    }
+}
+
 pub fn try_float(value: &Bound<PyAny>) -> PyResult<Option<Literal<String>>> {
     let v: f64 = value.extract()?;
-    let l = Literal::parse(format!("{}", v)).expect("[4] Parsing the literal");
+    let text = float_literal(v).ok_or_else(|| PyValueError::new_err(format!("no literal for {}", v)))?;
+    let l = Literal::parse(text).expect("[4] Parsing the literal");
+
+    Ok(Some(l))
+}
+
+// The parser only produces imaginary numbers, which are kept with Python's `j` suffix.
+pub fn try_complex(value: &Bound<PyAny>) -> PyResult<Option<Literal<String>>> {
+    let v = value.downcast::<PyComplex>()?;
+    let text = match float_literal(v.imag()) {
+        Some(imag) if v.real() == 0.0 => imag + "j",
+        _ => return Err(PyValueError::new_err(format!("no literal for {}", v))),
+    };
+    let l = Literal::parse(text).expect("[4] Parsing the literal");
 
     Ok(Some(l))
 }
@@ -121,9 +166,11 @@ impl<'a> FromPyObject<'a> for Constant {
         // We have to evaluaet bool before int because if a bool is evaluated as it, it will be cooerced to an in.
         } else if let Ok(l) = try_bool(&value) {
             l
+        } else if let Ok(l) = try_int(&value) {
+            l
         } else if let Ok(l) = try_float(&value) {
             l
-        } else if let Ok(l) = try_int(&value) {
+        } else if let Ok(l) = try_complex(&value) {
             l
         } else if let Ok(l) = try_option(&value) {
             l
@@ -178,7 +225,7 @@ mod tests {
             .unwrap();
         debug!("ast: {}", ast.to_string());
 
-        assert_eq!("use stdpython :: * ; \"I ate a bug\"", ast.to_string());
+        assert_eq!("use stdpython :: * ; \"I ate a bug\" ;", ast.to_string());
     }
 
     #[test]
@@ -192,7 +239,7 @@ mod tests {
             )
             .unwrap();
 
-        assert_eq!("use stdpython :: * ; b\"I ate a bug\"", ast.to_string());
+        assert_eq!("use stdpython :: * ; b\"I ate a bug\" ;", ast.to_string());
     }
 
     #[test]
@@ -206,7 +253,7 @@ mod tests {
             )
             .unwrap();
 
-        assert_eq!("use stdpython :: * ; 871234234", ast.to_string());
+        assert_eq!("use stdpython :: * ; 871234234 ;", ast.to_string());
     }
 
     #[test]
@@ -220,7 +267,7 @@ mod tests {
             )
             .unwrap();
 
-        assert_eq!("use stdpython :: * ; - 871234234", ast.to_string());
+        assert_eq!("use stdpython :: * ; - 871234234 ;", ast.to_string());
     }
 
     #[test]
@@ -234,7 +281,23 @@ mod tests {
             )
             .unwrap();
 
-        assert_eq!("use stdpython :: * ; 87123.4234", ast.to_string());
+        assert_eq!("use stdpython :: * ; 87123.4234 ;", ast.to_string());
+    }
+
+    #[test]
+    fn parse_number_exact() {
+        for number in ["123456789012345678901234567890", "5.0"] {
+            let s = crate::parse(number, "test.py").unwrap();
+            let ast = s
+                .to_rust(
+                    crate::CodeGenContext::Module("test".to_string()),
+                    crate::PythonOptions::default(),
+                    SymbolTableScopes::new(),
+                )
+                .unwrap();
+
+            assert_eq!(format!("use stdpython :: * ; {} ;", number), ast.to_string());
+        }
     }
 
     #[test]
@@ -248,7 +311,7 @@ mod tests {
             )
             .unwrap();
 
-        assert_eq!("use stdpython :: * ; true", ast.to_string());
+        assert_eq!("use stdpython :: * ; true ;", ast.to_string());
     }
 
     #[test]
@@ -262,6 +325,6 @@ mod tests {
             )
             .unwrap();
 
-        assert_eq!("use stdpython :: * ; None", ast.to_string());
+        assert_eq!("use stdpython :: * ; None ;", ast.to_string());
     }
 }
@@ -4,7 +4,7 @@ use quote::quote;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    dump, Attribute, Await, BinOp, BoolOp, Call, CodeGen, CodeGenContext, Compare, Constant, Error,
+    dump, expression_fallback, unparse, Attribute, Await, BinOp, BoolOp, Call, CodeGen, CodeGenContext, Compare, Constant, Error,
     Name, NamedExpr, Node, PythonOptions, SymbolTableScopes, UnaryOp, Lambda, IfExp, Dict, Set, Tuple, Subscript, Starred, ListComp, DictComp, SetComp, GeneratorExp, Yield, YieldFrom, JoinedStr, FormattedValue,
 };
 
@@ -65,6 +65,16 @@ pub enum ExprType {
     Unknown,
 }
 
+impl ExprType {
+    /// The value of a string constant expression, if this is one.
+    pub fn string_value(&self) -> Option<&str> {
+        match self {
+            ExprType::Constant(c) => c.string_value(),
+            _ => None,
+        }
+    }
+}
+
 impl<'a> FromPyObject<'a> for ExprType {
     fn extract_bound(ob: &Bound<'_, PyAny>) -> PyResult<Self> {
         log::debug!("exprtype ob: {}", dump(ob, Some(4))?);
@@ -100,6 +110,16 @@ impl<'a> FromPyObject<'a> for ExprType {
                 );
                 Ok(Self::Await(a))
             }
+            "NamedExpr" => {
+                let n = ob.extract().expect(
+                    ob.error_message(
+                        "<unknown>",
+                        format!("extracting NamedExpr in expression {}", dump(ob, None)?),
+                    )
+                    .as_str(),
+                );
+                Ok(Self::NamedExpr(n))
+            }
             "BoolOp" => {
                 let b = ob.extract().expect(
                     ob.error_message(
@@ -338,16 +358,8 @@ impl<'a> FromPyObject<'a> for ExprType {
                 );
                 Ok(Self::FormattedValue(fv))
             }
-            _ => {
-                let err_msg = format!(
-                    "Unimplemented expression type {}, {}",
-                    expr_type,
-                    dump(ob, None)?
-                );
-                Err(pyo3::exceptions::PyValueError::new_err(
-                    ob.error_message("<unknown>", err_msg.as_str()),
-                ))
-            }
+            // Kept as source, so codegen can report it or hand it to the Python fallback.
+            _ => Ok(Self::Unimplemented(unparse(ob)?)),
         };
         r
     }
@@ -358,6 +370,22 @@ impl<'a> CodeGen for ExprType {
     type Options = PythonOptions;
     type SymbolTable = SymbolTableScopes;
 
+    fn find_symbols(self, symbols: Self::SymbolTable) -> Self::SymbolTable {
+        match self {
+            ExprType::NamedExpr(n) => n.find_symbols(symbols),
+            ExprType::ListComp(c) => c.find_symbols(symbols),
+            ExprType::DictComp(c) => c.find_symbols(symbols),
+            ExprType::SetComp(c) => c.find_symbols(symbols),
+            ExprType::GeneratorExp(g) => g.find_symbols(symbols),
+            ExprType::Yield(y) => y.find_symbols(symbols),
+            ExprType::YieldFrom(y) => y.find_symbols(symbols),
+            ExprType::FormattedValue(f) => f.find_symbols(symbols),
+            ExprType::JoinedStr(j) => j.find_symbols(symbols),
+            ExprType::Starred(s) => s.find_symbols(symbols),
+            _ => symbols,
+        }
+    }
+
     fn to_rust(
         self,
         ctx: Self::Context,
@@ -376,6 +404,7 @@ impl<'a> CodeGen for ExprType {
             ExprType::IfExp(i) => i.to_rust(ctx, options, symbols),
             ExprType::Dict(d) => d.to_rust(ctx, options, symbols),
             ExprType::Set(s) => s.to_rust(ctx, options, symbols),
+            ExprType::NamedExpr(n) => n.to_rust(ctx, options, symbols),
             ExprType::ListComp(lc) => lc.to_rust(ctx, options, symbols),
             ExprType::DictComp(dc) => dc.to_rust(ctx, options, symbols),
             ExprType::SetComp(sc) => sc.to_rust(ctx, options, symbols),
@@ -465,6 +494,9 @@ impl<'a> CodeGen for ExprType {
             ExprType::Name(name) => name.to_rust(ctx, options, symbols),
             ExprType::NoneType(c) => c.to_rust(ctx, options, symbols),
             ExprType::UnaryOp(operand) => operand.to_rust(ctx, options, symbols),
+            ExprType::Unimplemented(source) if options.python_fallback.is_some() => {
+                expression_fallback(&source, options.python_fallback.as_ref().unwrap(), &symbols)
+            }
 
             _ => {
                 let error = Error::ExprTypeNotYetImplemented(self);
@@ -474,6 +506,80 @@ impl<'a> CodeGen for ExprType {
     }
 }
 
+/// The position of an expression, for the variants that record one. Calls, attributes, comparisons and the other
+/// variants without positions return `None` from every method.
+impl Node for ExprType {
+    fn lineno(&self) -> Option<usize> {
+        self.positions()?.lineno()
+    }
+
+    fn col_offset(&self) -> Option<usize> {
+        self.positions()?.col_offset()
+    }
+
+    fn end_lineno(&self) -> Option<usize> {
+        self.positions()?.end_lineno()
+    }
+
+    fn end_col_offset(&self) -> Option<usize> {
+        self.positions()?.end_col_offset()
+    }
+}
+
+/// The four position fields of an expression variant, copied out so [`ExprType`] can implement [`Node`] without
+/// matching on every variant in every method.
+#[derive(Clone, Copy)]
+struct Positions(Option<usize>, Option<usize>, Option<usize>, Option<usize>);
+
+impl Positions {
+    fn of(node: &impl Node) -> Option<Self> {
+        Some(Self(node.lineno(), node.col_offset(), node.end_lineno(), node.end_col_offset()))
+    }
+}
+
+impl Node for Positions {
+    fn lineno(&self) -> Option<usize> {
+        self.0
+    }
+
+    fn col_offset(&self) -> Option<usize> {
+        self.1
+    }
+
+    fn end_lineno(&self) -> Option<usize> {
+        self.2
+    }
+
+    fn end_col_offset(&self) -> Option<usize> {
+        self.3
+    }
+}
+
+impl ExprType {
+    fn positions(&self) -> Option<Positions> {
+        match self {
+            ExprType::NamedExpr(n) => Positions::of(n),
+            ExprType::Lambda(l) => Positions::of(l),
+            ExprType::IfExp(i) => Positions::of(i),
+            ExprType::Dict(d) => Positions::of(d),
+            ExprType::Set(s) => Positions::of(s),
+            ExprType::ListComp(c) => Positions::of(c),
+            ExprType::DictComp(c) => Positions::of(c),
+            ExprType::SetComp(c) => Positions::of(c),
+            ExprType::GeneratorExp(c) => Positions::of(c),
+            ExprType::Yield(y) => Positions::of(y),
+            ExprType::YieldFrom(y) => Positions::of(y),
+            ExprType::FormattedValue(f) => Positions::of(f),
+            ExprType::JoinedStr(j) => Positions::of(j),
+            ExprType::Subscript(s) => Positions::of(s),
+            ExprType::Starred(s) => Positions::of(s),
+            ExprType::Name(n) => Positions::of(n),
+            ExprType::Tuple(t) => Positions::of(t),
+            _ => None,
+        }
+    }
+}
+
 /// An Expr only contains a single value key, which leads to the actual expression,
 /// which is one of several types.
 #[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
@@ -790,6 +896,20 @@ impl<'a> FromPyObject<'a> for Expr {
                 r.value = ExprType::FormattedValue(fv);
                 Ok(r)
             }
+            "NamedExpr" => {
+                let n = ob_value.extract().expect(
+                    ob.error_message(
+                        "<unknown>",
+                        format!(
+                            "extracting NamedExpr in expression {:?}",
+                            dump(&ob_value, None)?
+                        ),
+                    )
+                    .as_str(),
+                );
+                r.value = ExprType::NamedExpr(n);
+                Ok(r)
+            }
             "GeneratorExp" => {
                 let ge = ob_value.extract().expect(
                     ob.error_message(
@@ -804,20 +924,57 @@ impl<'a> FromPyObject<'a> for Expr {
                 r.value = ExprType::GeneratorExp(ge);
                 Ok(r)
             }
+            "ListComp" => {
+                let lc = ob_value.extract().expect(
+                    ob.error_message(
+                        "<unknown>",
+                        format!(
+                            "extracting ListComp in expression {:?}",
+                            dump(&ob_value, None)?
+                        ),
+                    )
+                    .as_str(),
+                );
+                r.value = ExprType::ListComp(lc);
+                Ok(r)
+            }
+            "SetComp" => {
+                let sc = ob_value.extract().expect(
+                    ob.error_message(
+                        "<unknown>",
+                        format!(
+                            "extracting SetComp in expression {:?}",
+                            dump(&ob_value, None)?
+                        ),
+                    )
+                    .as_str(),
+                );
+                r.value = ExprType::SetComp(sc);
+                Ok(r)
+            }
+            "DictComp" => {
+                let dc = ob_value.extract().expect(
+                    ob.error_message(
+                        "<unknown>",
+                        format!(
+                            "extracting DictComp in expression {:?}",
+                            dump(&ob_value, None)?
+                        ),
+                    )
+                    .as_str(),
+                );
+                r.value = ExprType::DictComp(dc);
+                Ok(r)
+            }
             // In sitations where an expression is optional, we may see a NoneType expressions.
             "NoneType" => {
                 r.value = ExprType::NoneType(Constant(None));
                 Ok(r)
             }
+            // Kept as source, so codegen can report it or hand it to the Python fallback.
             _ => {
-                let err_msg = format!(
-                    "Unimplemented expression type {}, {}",
-                    expr_type,
-                    dump(ob, None)?
-                );
-                Err(pyo3::exceptions::PyValueError::new_err(
-                    ob.error_message("<unknown>", err_msg.as_str()),
-                ))
+                r.value = ExprType::Unimplemented(unparse(&ob_value)?);
+                Ok(r)
             }
         }
     }
@@ -828,6 +985,10 @@ impl CodeGen for Expr {
     type Options = PythonOptions;
     type SymbolTable = SymbolTableScopes;
 
+    fn find_symbols(self, symbols: Self::SymbolTable) -> Self::SymbolTable {
+        self.value.find_symbols(symbols)
+    }
+
     fn to_rust(
         self,
         ctx: Self::Context,
@@ -850,6 +1011,10 @@ impl CodeGen for Expr {
             ExprType::IfExp(i) => i.to_rust(ctx, options, symbols),
             ExprType::Dict(d) => d.to_rust(ctx, options, symbols),
             ExprType::Set(s) => s.to_rust(ctx, options, symbols),
+            ExprType::NamedExpr(n) => n.to_rust(ctx, options, symbols),
+            ExprType::ListComp(lc) => lc.to_rust(ctx, options, symbols),
+            ExprType::SetComp(sc) => sc.to_rust(ctx, options, symbols),
+            ExprType::DictComp(dc) => dc.to_rust(ctx, options, symbols),
             ExprType::GeneratorExp(ge) => ge.to_rust(ctx, options, symbols),
             ExprType::Tuple(t) => t.to_rust(ctx, options, symbols),
             ExprType::Subscript(s) => s.to_rust(ctx, options, symbols),
@@ -866,6 +1031,9 @@ impl CodeGen for Expr {
             ExprType::FormattedValue(fv) => fv.to_rust(ctx, options, symbols),
             // NoneType expressions generate no code.
             ExprType::NoneType(_c) => Ok(quote!()),
+            ExprType::Unimplemented(source) if options.python_fallback.is_some() => {
+                expression_fallback(&source, options.python_fallback.as_ref().unwrap(), &symbols)
+            }
             _ => {
                 let error = Error::ExprTypeNotYetImplemented(self.value);
                 Err(error.into())
@@ -906,6 +1074,6 @@ mod tests {
             .clone()
             .to_rust(CodeGenContext::Module("test".to_string()), options, symbols)
             .unwrap();
-        assert_eq!(tokens.to_string(), "fn __module_init__ () { test () } fn main () { __module_init__ () ; }");
+        assert_eq!(tokens.to_string(), "fn __module_init__ () { test () ; } fn main () { __module_init__ () ; }");
     }
 }
@@ -4,8 +4,8 @@ use quote::quote;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    CodeGen, CodeGenContext, ExprType, Node, PythonOptions, SymbolTableScopes,
-    extract_list,
+    CodeGen, CodeGenContext, Conversion, ExprType, FormatArgs, Node, PythonOptions, SpecToken,
+    SymbolTableScopes, extract_list,
 };
 
 /// Joined string (f-string, e.g., f"Hello {name}")
@@ -120,21 +120,78 @@ impl CodeGen for JoinedStr {
         options: Self::Options,
         symbols: Self::SymbolTable,
     ) -> Result<TokenStream, Box<dyn std::error::Error>> {
-        // Generate each part of the f-string
-        let part_tokens: Result<Vec<TokenStream>, Box<dyn std::error::Error>> = self.values.into_iter()
-            .map(|val| val.to_rust(ctx.clone(), options.clone(), symbols.clone()))
-            .collect();
-        let part_tokens = part_tokens?;
-
-        // For now, generate a simple format! macro call
-        // This is a simplified translation - real f-strings are more complex
-        if part_tokens.is_empty() {
-            Ok(quote! { String::new() })
-        } else {
-            Ok(quote! {
-                format!("{}", #(#part_tokens)+*)
-            })
+        if self.values.is_empty() {
+            return Ok(quote! { String::new() });
+        }
+
+        // Literal parts go into the format string and each replacement field
+        // becomes an argument, so the whole f-string is a single format! call.
+        let mut args = FormatArgs::new();
+        for value in self.values {
+            match value {
+                ExprType::Constant(c) if c.string_value().is_some() => {
+                    args.push_str(c.string_value().unwrap_or_default());
+                }
+                ExprType::FormattedValue(fv) => {
+                    fv.push_to(&mut args, ctx.clone(), options.clone(), symbols.clone())?;
+                }
+                other => {
+                    let value = other.to_rust(ctx.clone(), options.clone(), symbols.clone())?;
+                    args.push_value(value, Conversion::None, Vec::new());
+                }
+            }
+        }
+        Ok(args.into_tokens())
+    }
+}
+
+impl FormattedValue {
+    /// Adds this replacement field to a format! call under construction.
+    fn push_to(
+        self,
+        args: &mut FormatArgs,
+        ctx: CodeGenContext,
+        options: PythonOptions,
+        symbols: SymbolTableScopes,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let value = (*self.value).to_rust(ctx.clone(), options.clone(), symbols.clone())?;
+        let conversion = Conversion::from_code(self.conversion);
+
+        // The spec is itself a JoinedStr; nested fields such as the `{width}`
+        // in `{x:>{width}}` are kept as separate tokens.
+        let mut spec = Vec::new();
+        match self.format_spec.map(|s| *s) {
+            Some(ExprType::JoinedStr(joined)) => {
+                for part in joined.values {
+                    match part {
+                        ExprType::Constant(c) => {
+                            spec.extend(SpecToken::chars(c.string_value().unwrap_or_default()));
+                        }
+                        ExprType::FormattedValue(nested)
+                            if nested.conversion.is_none() && nested.format_spec.is_none() =>
+                        {
+                            let nested = (*nested.value).to_rust(ctx.clone(), options.clone(), symbols.clone())?;
+                            spec.push(SpecToken::Arg(nested));
+                        }
+                        other => {
+                            let nested = other.to_rust(ctx.clone(), options.clone(), symbols.clone())?;
+                            spec.push(SpecToken::Arg(nested));
+                        }
+                    }
+                }
+            }
+            Some(ExprType::Constant(c)) => {
+                spec.extend(SpecToken::chars(c.string_value().unwrap_or_default()));
+            }
+            Some(other) => {
+                let nested = other.to_rust(ctx, options, symbols)?;
+                spec.push(SpecToken::Arg(nested));
+            }
+            None => {}
         }
+
+        args.push_value(value, conversion, spec);
+        Ok(())
     }
 }
 
@@ -158,26 +215,67 @@ impl CodeGen for FormattedValue {
         options: Self::Options,
         symbols: Self::SymbolTable,
     ) -> Result<TokenStream, Box<dyn std::error::Error>> {
-        let value_tokens = (*self.value).to_rust(ctx.clone(), options.clone(), symbols.clone())?;
-        
-        if let Some(format_spec) = self.format_spec {
-            let _spec_tokens = (*format_spec).to_rust(ctx, options, symbols)?;
-            // For now, generate a simple format with the format specifier
-            // TODO: Properly handle format specifications
-            Ok(quote! {
-                format!("{}", #value_tokens)
-            })
-        } else {
-            // Simple case - just format with default formatting
-            Ok(quote! {
-                format!("{}", #value_tokens)
-            })
-        }
+        let mut args = FormatArgs::new();
+        self.push_to(&mut args, ctx, options, symbols)?;
+        Ok(args.into_tokens())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    // Tests would go here - currently commented out as they need full AST infrastructure
-    // create_parse_test!(test_simple_fstring, "f'Hello {name}'", "test.py");
+    use crate::macros::rust_for;
+
+    #[test]
+    fn test_simple_fstring() {
+        assert!(rust_for("f'Hello {name}!'").contains("format ! (\"Hello {}!\" , name)"));
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert!(rust_for("f'{a!r} {b!s} {c!a}'").contains("format ! (\"{:?} {} {}\" , a , b , ascii (c))"));
+    }
+
+    #[test]
+    fn test_nested_spec() {
+        assert!(rust_for("f'{x:>{width}.2f}'").contains("format ! (\"{:>1$.2}\" , (x) as f64 , (width) as usize)"));
+    }
+
+    #[test]
+    fn test_integer_precision() {
+        assert!(rust_for("f'{5:.2f}'").contains("format ! (\"{:.2}\" , (5) as f64)"));
+    }
+
+    #[test]
+    fn test_self_documenting() {
+        assert!(rust_for("f'{x=}'").contains("format ! (\"x={:?}\" , x)"));
+    }
+
+    #[test]
+    fn test_brace_escapes() {
+        assert!(rust_for("f'{{{x}}}'").contains("format ! (\"{{{}}}\" , x)"));
+    }
+
+    #[test]
+    fn test_runtime_spec() {
+        assert!(rust_for("f'{total:,}'").contains("format ! (\"{}\" , format (total , \",\"))"));
+    }
+
+    #[test]
+    fn test_percent_format() {
+        assert!(rust_for("'%s has %d items' % (name, n)").contains("format ! (\"{} has {} items\" , name , n)"));
+    }
+
+    #[test]
+    fn test_str_format() {
+        assert!(rust_for("'{} -> {key:>4}'.format(a, key=b)").contains("format ! (\"{} -> {:>4}\" , a , b)"));
+        assert!(rust_for("'{0} {0}'.format(g())").contains("format ! (\"{} {0}\" , g ())"));
+    }
+
+    #[test]
+    fn test_str_format_attribute_that_is_not_an_identifier() {
+        // Left as a method call rather than a field access that isn't Rust.
+        for code in ["'{0.1}'.format(x)", "'{0.}'.format(x)"] {
+            assert!(!rust_for(code).contains("format !"), "{}", code);
+        }
+    }
 }
\ No newline at end of file
@@ -6,7 +6,7 @@ use serde::{Deserialize, Serialize};
 use crate::ast::tree::statement::PyStatementTrait;
 
 use crate::{
-    CodeGen, CodeGenContext, ExprType, Object, ParameterList, PythonOptions, Statement,
+    CodeGen, CodeGenContext, Declarations, ExprType, Object, ParameterList, PythonOptions, Statement,
     StatementType, SymbolTableNode, SymbolTableScopes,
 };
 
@@ -16,6 +16,9 @@ pub struct FunctionDef {
     pub args: ParameterList,
     pub body: Vec<Statement>,
     pub decorator_list: Vec<ExprType>,
+    /// The return annotation.
+    #[serde(default)]
+    pub returns: Option<Box<ExprType>>,
 }
 
 impl<'a> FromPyObject<'a> for FunctionDef {
@@ -26,12 +29,16 @@ impl<'a> FromPyObject<'a> for FunctionDef {
         
         // Extract decorator_list as Vec<ExprType>
         let decorator_list: Vec<ExprType> = ob.getattr("decorator_list")?.extract().unwrap_or_default();
+
+        // An annotation we can't represent doesn't stop the function being read.
+        let returns: Option<ExprType> = ob.getattr("returns")?.extract().unwrap_or_default();
         
         Ok(FunctionDef {
             name,
             args,
             body,
             decorator_list,
+            returns: returns.map(Box::new),
         })
     }
 }
@@ -85,13 +92,21 @@ impl CodeGen for FunctionDef {
             .to_rust(ctx.clone(), options.clone(), symbols.clone())
             .expect(format!("parsing arguments {:?}", self.args).as_str());
 
+        // The function's variables: its parameters, then each statement's assignments as it's reached.
+        let mut symbols = symbols;
+        symbols.new_scope();
+        let args = &self.args;
+        let parameter_names: Vec<String> = args.posonlyargs.iter().chain(&args.args).chain(&args.vararg).chain(&args.kwonlyargs).chain(&args.kwarg).map(|p| p.arg.clone()).collect();
+        for p in parameter_names.iter() {
+            symbols.insert(p.clone(), SymbolTableNode::Assign { position: 0, value: ExprType::Name(crate::Name::new(p)) });
+        }
+        // Variables that outlive the block they're first assigned in are declared up front.
+        let declarations = Declarations::new(&self.body, &parameter_names);
+        symbols = declarations.register(symbols);
+        streams.extend(declarations.to_rust());
         for s in self.body.iter() {
-            streams.extend(
-                s.clone()
-                    .to_rust(ctx.clone(), options.clone(), symbols.clone())
-                    .expect(format!("parsing statement {:?}", s).as_str()),
-            );
-            streams.extend(quote!(;));
+            streams.extend(s.clone().to_rust(ctx.clone(), options.clone(), symbols.clone())?);
+            symbols = s.clone().find_symbols(symbols);
         }
 
         let function = if let Some(docstring) = self.get_docstring() {
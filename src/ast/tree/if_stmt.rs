@@ -1,11 +1,11 @@
 use proc_macro2::TokenStream;
 use pyo3::{Bound, FromPyObject, PyAny, PyResult, types::PyAnyMethods};
-use quote::quote;
+use quote::{format_ident, quote};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     CodeGen, CodeGenContext, ExprType, PythonOptions, SymbolTableScopes,
-    Node, impl_node_with_positions, PyAttributeExtractor, extract_list
+    Node, impl_node_with_positions, PyAttributeExtractor, extract_list, bare_walrus,
 };
 
 use super::Statement;
@@ -60,8 +60,15 @@ impl CodeGen for If {
         options: Self::Options,
         symbols: Self::SymbolTable,
     ) -> Result<TokenStream, Box<dyn std::error::Error>> {
-        // Regular if statement handling
-        let test = self.test.to_rust(ctx.clone(), options.clone(), symbols.clone())?;
+        // `if (m := f()):` keeps `m` bound after the test, so assign it first and test the name.
+        let (binding, test) = match bare_walrus(&self.test) {
+            Some((name, value)) => {
+                let name = format_ident!("{}", name);
+                let value = value.clone().to_rust(ctx.clone(), options.clone(), symbols.clone())?;
+                (quote!(#name = #value;), quote!(#name))
+            }
+            None => (quote!(), self.test.to_rust(ctx.clone(), options.clone(), symbols.clone())?),
+        };
         
         let body_stmts: Result<Vec<_>, _> = self.body
             .into_iter()
@@ -71,6 +78,7 @@ impl CodeGen for If {
         
         if self.orelse.is_empty() {
             Ok(quote! {
+                #binding
                 if #test {
                     #(#body_stmts)*
                 }
@@ -83,6 +91,7 @@ impl CodeGen for If {
             let else_stmts = else_stmts?;
             
             Ok(quote! {
+                #binding
                 if #test {
                     #(#body_stmts)*
                 } else {
@@ -1,6 +1,6 @@
 use log::debug;
 use proc_macro2::TokenStream;
-use pyo3::FromPyObject;
+use pyo3::{Bound, FromPyObject, PyAny, PyResult, types::PyAnyMethods};
 use quote::{format_ident, quote};
 use serde::{Deserialize, Serialize};
 
@@ -94,11 +94,18 @@ impl CodeGen for Import {
 
 #[derive(Clone, Debug, FromPyObject, Serialize, Deserialize, PartialEq)]
 pub struct ImportFrom {
+    /// The module imported from, empty in `from . import x`.
+    #[pyo3(from_py_with = module_name)]
     pub module: String,
     pub names: Vec<Alias>,
     pub level: usize,
 }
 
+/// Python leaves the module out of `from . import x`.
+fn module_name(module: &Bound<'_, PyAny>) -> PyResult<String> {
+    Ok(module.extract::<Option<String>>()?.unwrap_or_default())
+}
+
 impl CodeGen for ImportFrom {
     type Context = CodeGenContext;
     type Options = PythonOptions;
@@ -106,11 +113,10 @@ impl CodeGen for ImportFrom {
 
     fn find_symbols(self, symbols: Self::SymbolTable) -> Self::SymbolTable {
         let mut symbols = symbols;
+        // `from m import a as b` binds `b`.
         for alias in self.names.iter() {
-            symbols.insert(
-                alias.name.clone(),
-                SymbolTableNode::ImportFrom(self.clone()),
-            );
+            let bound = alias.asname.clone().unwrap_or_else(|| alias.name.clone());
+            symbols.insert(bound, SymbolTableNode::ImportFrom(self.clone()));
         }
         symbols
     }
@@ -5,7 +5,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{CodeGen, CodeGenContext, ExprType, PythonOptions, SymbolTableScopes, Node};
 
-/// A keyword argument in a function call.
+/// A keyword argument in a function call or class definition.
 #[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
 pub struct Keyword {
     /// Keyword name (None for **kwargs unpacking)
@@ -1,10 +1,10 @@
-use proc_macro2::TokenStream;
+use proc_macro2::{TokenStream, TokenTree};
 use pyo3::{Bound, FromPyObject, PyAny, PyResult, prelude::PyAnyMethods};
-use quote::quote;
+use quote::{format_ident, quote};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    CodeGen, CodeGenContext, ExprType, Node, PythonOptions, SymbolTableScopes,
+    CodeGen, CodeGenContext, Error, ExprType, Node, PythonOptions, SourceLocation, SymbolTableScopes, walrus_targets,
     PyAttributeExtractor, extract_list,
 };
 
@@ -177,8 +177,8 @@ impl<'a> FromPyObject<'a> for Comprehension {
         // Extract ifs (list of conditions)
         let ifs: Vec<ExprType> = extract_list(ob, "ifs", "comprehension conditions").unwrap_or_default();
         
-        // Extract is_async
-        let is_async: bool = ob.getattr("is_async")?.extract().unwrap_or(false);
+        // Extract is_async, which Python stores as an int
+        let is_async = ob.getattr("is_async")?.extract::<i64>().unwrap_or(0) != 0;
         
         Ok(Comprehension {
             target,
@@ -217,19 +217,285 @@ impl Node for DictComp {
     fn end_col_offset(&self) -> Option<usize> { self.end_col_offset }
 }
 
+/// The collection a comprehension gathers its elements into.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Collection {
+    List,
+    Set,
+    Dict,
+    /// Generator expressions stay lazy and are never collected.
+    Generator,
+}
+
+impl Collection {
+    fn collect(self) -> TokenStream {
+        match self {
+            Collection::List => quote!(.collect::<Vec<_>>()),
+            Collection::Set => quote!(.collect::<std::collections::HashSet<_>>()),
+            Collection::Dict => quote!(.collect::<std::collections::HashMap<_, _>>()),
+            Collection::Generator => quote!(),
+        }
+    }
+
+    fn empty(self) -> TokenStream {
+        match self {
+            Collection::List | Collection::Generator => quote!(Vec::new()),
+            Collection::Set => quote!(std::collections::HashSet::new()),
+            Collection::Dict => quote!(std::collections::HashMap::new()),
+        }
+    }
+}
+
+impl Comprehension {
+    /// The pattern that binds this generator's target, e.g. `x` or `(k, (a, b))`.
+    fn pattern(&self, position: Option<usize>) -> Result<TokenStream, Box<dyn std::error::Error>> {
+        fn pattern_for(target: &ExprType, position: Option<usize>) -> Result<TokenStream, Box<dyn std::error::Error>> {
+            let elements = |elts: &[ExprType]| -> Result<TokenStream, Box<dyn std::error::Error>> {
+                let patterns = elts
+                    .iter()
+                    .map(|e| pattern_for(e, position))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(quote!((#(#patterns),*)))
+            };
+            match target {
+                ExprType::Name(name) => {
+                    let ident = format_ident!("{}", name.id);
+                    Ok(quote!(#ident))
+                }
+                ExprType::Tuple(tuple) => elements(&tuple.elts),
+                ExprType::List(elts) => elements(elts),
+                other => Err(Error::unsupported_feature(
+                    SourceLocation::with_position("<unknown>", position, None),
+                    format!("comprehension target {:?}", other),
+                    "Only names and (nested) tuples of names can be comprehension targets",
+                )
+                .into()),
+            }
+        }
+        pattern_for(&self.target, position)
+    }
+
+    fn collect_symbols(self, symbols: SymbolTableScopes) -> SymbolTableScopes {
+        let symbols = self.iter.find_symbols(symbols);
+        self.ifs.into_iter().fold(symbols, |acc, if_expr| if_expr.find_symbols(acc))
+    }
+}
+
+/// Lowers a comprehension to an iterator chain: one `flat_map` per nested
+/// `for` clause, one `filter` per `if`, and a final `map` and `collect`.
+/// Comprehensions with an `async for` or a walrus become loops instead.
+fn lower_comprehension(
+    generators: &[Comprehension],
+    elts: Vec<ExprType>,
+    collection: Collection,
+    position: Option<usize>,
+    ctx: CodeGenContext,
+    options: PythonOptions,
+    symbols: SymbolTableScopes,
+) -> Result<TokenStream, Box<dyn std::error::Error>> {
+    let binds_names = !comprehension_walrus_targets(&elts, generators).is_empty();
+    let elts = elts
+        .into_iter()
+        .map(|e| e.to_rust(ctx.clone(), options.clone(), symbols.clone()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let elt = match elts.as_slice() {
+        [elt] => elt.clone(),
+        _ => quote!((#(#elts),*)),
+    };
+
+    if binds_names || generators.iter().any(|g| g.is_async) {
+        return comprehension_loop(generators, elt, collection, position, ctx, options, symbols);
+    }
+
+    let chain = iterator_chain(generators, elt, &[], position, ctx, options, symbols)?;
+    let collect = collection.collect();
+    Ok(quote!(#chain #collect))
+}
+
+/// Builds the chain for `generators`, where `outer` holds the names bound by
+/// the enclosing `for` clauses. An empty `outer` means this is the outermost clause.
+fn iterator_chain(
+    generators: &[Comprehension],
+    elt: TokenStream,
+    outer: &[String],
+    position: Option<usize>,
+    ctx: CodeGenContext,
+    options: PythonOptions,
+    symbols: SymbolTableScopes,
+) -> Result<TokenStream, Box<dyn std::error::Error>> {
+    let Some((generator, rest)) = generators.split_first() else {
+        return Err(Error::codegen_error(
+            SourceLocation::with_position("<unknown>", position, None),
+            "comprehension without a for clause",
+            "This is not valid Python; check the input",
+        )
+        .into());
+    };
+    let nested = !outer.is_empty();
+    let bound = target_names(&generator.target);
+
+    // Outer targets that a closure at this level refers to. The closure only
+    // borrows them, so it clones them before the body can consume them.
+    let captured = |body: &TokenStream| -> Vec<String> {
+        outer
+            .iter()
+            .filter(|n| !bound.contains(n) && n.as_str() != "_" && mentions(body, n))
+            .cloned()
+            .collect()
+    };
+
+    // Inner iterables are evaluated once per outer item, and may name the same
+    // collection as an outer one, so with several `for` clauses none is consumed.
+    let iter = generator.iter.clone().to_rust(ctx.clone(), options.clone(), symbols.clone())?;
+    let iter = if nested || !rest.is_empty() {
+        quote!((#iter).clone().into_iter())
+    } else {
+        quote!((#iter).into_iter())
+    };
+    let pattern = generator.pattern(position)?;
+
+    // Filters only see a reference to the item, so bind a copy of it to the
+    // target so the condition can use the names as the Python code does.
+    let mut filters = Vec::new();
+    for condition in &generator.ifs {
+        let condition = condition.clone().to_rust(ctx.clone(), options.clone(), symbols.clone())?;
+        let captures = captured(&condition);
+        let closure = closure(&captures, quote!(__item), quote!({ let #pattern = __item.clone(); #condition }));
+        filters.push(quote!(.filter(#closure)));
+    }
+
+    let tail = if rest.is_empty() {
+        let closure = closure(&captured(&elt), pattern, elt);
+        quote!(.map(#closure))
+    } else {
+        // The inner chain borrows this item and the outer targets, so it is
+        // collected before the closure returns rather than handed out lazily.
+        let outer = [outer, &bound].concat();
+        let inner = iterator_chain(rest, elt, &outer, position, ctx, options, symbols)?;
+        quote!(.flat_map(|#pattern| #inner.collect::<Vec<_>>()))
+    };
+
+    Ok(quote!(#iter #(#filters)* #tail))
+}
+
+/// A closure for an iterator adapter that clones the outer targets it uses on
+/// every call, since the adapter calls it repeatedly and the body may consume them.
+fn closure(captures: &[String], params: TokenStream, body: TokenStream) -> TokenStream {
+    if captures.is_empty() {
+        return quote!(|#params| #body);
+    }
+    let captures: Vec<_> = captures.iter().map(|n| format_ident!("{}", n)).collect();
+    quote!(|#params| { #(let #captures = #captures.clone();)* #body })
+}
+
+/// Whether `tokens` refer to `name` anywhere, including inside nested groups.
+fn mentions(tokens: &TokenStream, name: &str) -> bool {
+    tokens.clone().into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => ident == name,
+        TokenTree::Group(group) => mentions(&group.stream(), name),
+        _ => false,
+    })
+}
+
+/// The names a comprehension target binds.
+fn target_names(target: &ExprType) -> Vec<String> {
+    match target {
+        ExprType::Name(name) => vec![name.id.clone()],
+        ExprType::Tuple(tuple) => tuple.elts.iter().flat_map(target_names).collect(),
+        ExprType::List(elts) => elts.iter().flat_map(target_names).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Iterator adapters cannot `.await`, and a walrus inside a closure would
+/// assign a copy rather than the enclosing variable, so those comprehensions
+/// are lowered to nested loops that fill the collection.
+fn comprehension_loop(
+    generators: &[Comprehension],
+    elt: TokenStream,
+    collection: Collection,
+    position: Option<usize>,
+    ctx: CodeGenContext,
+    options: PythonOptions,
+    symbols: SymbolTableScopes,
+) -> Result<TokenStream, Box<dyn std::error::Error>> {
+    let mut body = quote!(__comprehension.extend(std::iter::once(#elt)););
+
+    for generator in generators.iter().rev() {
+        let iter = generator.iter.clone().to_rust(ctx.clone(), options.clone(), symbols.clone())?;
+        let pattern = generator.pattern(position)?;
+        let conditions = generator
+            .ifs
+            .iter()
+            .map(|c| c.clone().to_rust(ctx.clone(), options.clone(), symbols.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        body = if generator.is_async {
+            quote! {
+                let mut __stream = std::pin::pin!(#iter);
+                while let Some(#pattern) = futures::StreamExt::next(&mut __stream).await {
+                    #(if !(#conditions) { continue; })*
+                    #body
+                }
+            }
+        } else {
+            quote! {
+                for #pattern in #iter {
+                    #(if !(#conditions) { continue; })*
+                    #body
+                }
+            }
+        };
+    }
+
+    let empty = collection.empty();
+    let result = match collection {
+        Collection::Generator => quote!(__comprehension.into_iter()),
+        _ => quote!(__comprehension),
+    };
+    Ok(quote! {
+        {
+            let mut __comprehension = #empty;
+            #body
+            #result
+        }
+    })
+}
+
+fn comprehension_walrus_targets(elts: &[ExprType], generators: &[Comprehension]) -> Vec<String> {
+    let exprs = generators.iter().flat_map(|g| g.ifs.iter()).chain(elts);
+    exprs.flat_map(walrus_targets).map(|t| t.name).collect()
+}
+
+/// Comprehension targets are local to the comprehension, so the names they
+/// bind live in a scope of their own that is discarded afterwards. Names bound
+/// with a walrus belong to the enclosing scope, so they are carried over.
+fn comprehension_symbols(
+    elts: Vec<ExprType>,
+    generators: Vec<Comprehension>,
+    mut symbols: SymbolTableScopes,
+) -> SymbolTableScopes {
+    let walrus = comprehension_walrus_targets(&elts, &generators);
+    symbols.new_scope();
+    let symbols = generators.into_iter().fold(symbols, |acc, g| g.collect_symbols(acc));
+    let mut symbols = elts.into_iter().fold(symbols, |acc, elt| elt.find_symbols(acc));
+    if let Some(scope) = symbols.pop() {
+        for name in walrus {
+            if let Some(node) = scope.get(&name) {
+                symbols.insert(name, node.clone());
+            }
+        }
+    }
+    symbols
+}
+
 impl CodeGen for ListComp {
     type Context = CodeGenContext;
     type Options = PythonOptions;
     type SymbolTable = SymbolTableScopes;
 
     fn find_symbols(self, symbols: Self::SymbolTable) -> Self::SymbolTable {
-        // Process the element and generators
-        let symbols = (*self.elt).clone().find_symbols(symbols);
-        self.generators.into_iter().fold(symbols, |acc, generator| {
-            let acc = generator.target.find_symbols(acc);
-            let acc = generator.iter.find_symbols(acc);
-            generator.ifs.into_iter().fold(acc, |acc, if_expr| if_expr.find_symbols(acc))
-        })
+        comprehension_symbols(vec![*self.elt], self.generators, symbols)
     }
 
     fn to_rust(
@@ -238,38 +504,7 @@ impl CodeGen for ListComp {
         options: Self::Options,
         symbols: Self::SymbolTable,
     ) -> Result<TokenStream, Box<dyn std::error::Error>> {
-        // For now, generate a simple Vec collection since Rust doesn't have list comprehensions
-        // This is a simplified translation that doesn't handle all cases
-        if self.generators.len() == 1 {
-            let generator = &self.generators[0];
-            let elt = (*self.elt).clone().to_rust(ctx.clone(), options.clone(), symbols.clone())?;
-            let iter_expr = generator.iter.clone().to_rust(ctx.clone(), options.clone(), symbols.clone())?;
-            
-            if generator.ifs.is_empty() {
-                // Simple case: [expr for x in iter] -> iter.map(|x| expr).collect()
-                Ok(quote! {
-                    (#iter_expr).into_iter().map(|_item| #elt).collect::<Vec<_>>()
-                })
-            } else {
-                // With conditions: [expr for x in iter if cond] -> iter.filter(cond).map(expr).collect()
-                let conditions: Result<Vec<_>, _> = generator.ifs.iter()
-                    .map(|if_expr| if_expr.clone().to_rust(ctx.clone(), options.clone(), symbols.clone()))
-                    .collect();
-                let conditions = conditions?;
-                Ok(quote! {
-                    (#iter_expr).into_iter()
-                        .filter(|_item| { #(#conditions)&&* })
-                        .map(|_item| #elt)
-                        .collect::<Vec<_>>()
-                })
-            }
-        } else {
-            // Multiple generators would need nested iteration - this is complex
-            // For now, return a placeholder
-            Ok(quote! {
-                vec![] // Complex list comprehension with multiple generators not fully supported
-            })
-        }
+        lower_comprehension(&self.generators, vec![*self.elt], Collection::List, self.lineno, ctx, options, symbols)
     }
 }
 
@@ -279,13 +514,7 @@ impl CodeGen for SetComp {
     type SymbolTable = SymbolTableScopes;
 
     fn find_symbols(self, symbols: Self::SymbolTable) -> Self::SymbolTable {
-        // Process the element and generators
-        let symbols = (*self.elt).clone().find_symbols(symbols);
-        self.generators.into_iter().fold(symbols, |acc, generator| {
-            let acc = generator.target.find_symbols(acc);
-            let acc = generator.iter.find_symbols(acc);
-            generator.ifs.into_iter().fold(acc, |acc, if_expr| if_expr.find_symbols(acc))
-        })
+        comprehension_symbols(vec![*self.elt], self.generators, symbols)
     }
 
     fn to_rust(
@@ -294,38 +523,7 @@ impl CodeGen for SetComp {
         options: Self::Options,
         symbols: Self::SymbolTable,
     ) -> Result<TokenStream, Box<dyn std::error::Error>> {
-        // For now, generate a simple HashSet collection since Rust doesn't have set comprehensions
-        // This is a simplified translation that doesn't handle all cases
-        if self.generators.len() == 1 {
-            let generator = &self.generators[0];
-            let elt = (*self.elt).clone().to_rust(ctx.clone(), options.clone(), symbols.clone())?;
-            let iter_expr = generator.iter.clone().to_rust(ctx.clone(), options.clone(), symbols.clone())?;
-            
-            if generator.ifs.is_empty() {
-                // Simple case: {expr for x in iter} -> iter.map(|x| expr).collect()
-                Ok(quote! {
-                    (#iter_expr).into_iter().map(|_item| #elt).collect::<std::collections::HashSet<_>>()
-                })
-            } else {
-                // With conditions: {expr for x in iter if cond} -> iter.filter(cond).map(expr).collect()
-                let conditions: Result<Vec<_>, _> = generator.ifs.iter()
-                    .map(|if_expr| if_expr.clone().to_rust(ctx.clone(), options.clone(), symbols.clone()))
-                    .collect();
-                let conditions = conditions?;
-                Ok(quote! {
-                    (#iter_expr).into_iter()
-                        .filter(|_item| { #(#conditions)&&* })
-                        .map(|_item| #elt)
-                        .collect::<std::collections::HashSet<_>>()
-                })
-            }
-        } else {
-            // Multiple generators would need nested iteration - this is complex
-            // For now, return a placeholder
-            Ok(quote! {
-                std::collections::HashSet::new() // Complex set comprehension with multiple generators not fully supported
-            })
-        }
+        lower_comprehension(&self.generators, vec![*self.elt], Collection::Set, self.lineno, ctx, options, symbols)
     }
 }
 
@@ -335,13 +533,7 @@ impl CodeGen for GeneratorExp {
     type SymbolTable = SymbolTableScopes;
 
     fn find_symbols(self, symbols: Self::SymbolTable) -> Self::SymbolTable {
-        // Process the element and generators
-        let symbols = (*self.elt).clone().find_symbols(symbols);
-        self.generators.into_iter().fold(symbols, |acc, generator| {
-            let acc = generator.target.find_symbols(acc);
-            let acc = generator.iter.find_symbols(acc);
-            generator.ifs.into_iter().fold(acc, |acc, if_expr| if_expr.find_symbols(acc))
-        })
+        comprehension_symbols(vec![*self.elt], self.generators, symbols)
     }
 
     fn to_rust(
@@ -350,37 +542,7 @@ impl CodeGen for GeneratorExp {
         options: Self::Options,
         symbols: Self::SymbolTable,
     ) -> Result<TokenStream, Box<dyn std::error::Error>> {
-        // For now, generate a simple iterator since Rust doesn't have generator expressions
-        // This is a simplified translation that doesn't handle all cases
-        if self.generators.len() == 1 {
-            let generator = &self.generators[0];
-            let elt = (*self.elt).clone().to_rust(ctx.clone(), options.clone(), symbols.clone())?;
-            let iter_expr = generator.iter.clone().to_rust(ctx.clone(), options.clone(), symbols.clone())?;
-            
-            if generator.ifs.is_empty() {
-                // Simple case: (expr for x in iter) -> iter.map(|x| expr)
-                Ok(quote! {
-                    (#iter_expr).into_iter().map(|_item| #elt)
-                })
-            } else {
-                // With conditions: (expr for x in iter if cond) -> iter.filter(cond).map(expr)
-                let conditions: Result<Vec<_>, _> = generator.ifs.iter()
-                    .map(|if_expr| if_expr.clone().to_rust(ctx.clone(), options.clone(), symbols.clone()))
-                    .collect();
-                let conditions = conditions?;
-                Ok(quote! {
-                    (#iter_expr).into_iter()
-                        .filter(|_item| { #(#conditions)&&* })
-                        .map(|_item| #elt)
-                })
-            }
-        } else {
-            // Multiple generators would need nested iteration - this is complex
-            // For now, return a placeholder
-            Ok(quote! {
-                std::iter::empty() // Complex generator expression with multiple generators not fully supported
-            })
-        }
+        lower_comprehension(&self.generators, vec![*self.elt], Collection::Generator, self.lineno, ctx, options, symbols)
     }
 }
 
@@ -390,14 +552,7 @@ impl CodeGen for DictComp {
     type SymbolTable = SymbolTableScopes;
 
     fn find_symbols(self, symbols: Self::SymbolTable) -> Self::SymbolTable {
-        // Process the key, value and generators
-        let symbols = (*self.key).clone().find_symbols(symbols);
-        let symbols = (*self.value).clone().find_symbols(symbols);
-        self.generators.into_iter().fold(symbols, |acc, generator| {
-            let acc = generator.target.find_symbols(acc);
-            let acc = generator.iter.find_symbols(acc);
-            generator.ifs.into_iter().fold(acc, |acc, if_expr| if_expr.find_symbols(acc))
-        })
+        comprehension_symbols(vec![*self.key, *self.value], self.generators, symbols)
     }
 
     fn to_rust(
@@ -406,43 +561,119 @@ impl CodeGen for DictComp {
         options: Self::Options,
         symbols: Self::SymbolTable,
     ) -> Result<TokenStream, Box<dyn std::error::Error>> {
-        // For now, generate a simple HashMap collection since Rust doesn't have dict comprehensions
-        if self.generators.len() == 1 {
-            let generator = &self.generators[0];
-            let key = (*self.key).clone().to_rust(ctx.clone(), options.clone(), symbols.clone())?;
-            let value = (*self.value).clone().to_rust(ctx.clone(), options.clone(), symbols.clone())?;
-            let iter_expr = generator.iter.clone().to_rust(ctx.clone(), options.clone(), symbols.clone())?;
-            
-            if generator.ifs.is_empty() {
-                // Simple case: {k: v for x in iter} -> iter.map(|x| (k, v)).collect()
-                Ok(quote! {
-                    (#iter_expr).into_iter().map(|_item| (#key, #value)).collect::<std::collections::HashMap<_, _>>()
-                })
-            } else {
-                // With conditions: {k: v for x in iter if cond}
-                let conditions: Result<Vec<_>, _> = generator.ifs.iter()
-                    .map(|if_expr| if_expr.clone().to_rust(ctx.clone(), options.clone(), symbols.clone()))
-                    .collect();
-                let conditions = conditions?;
-                Ok(quote! {
-                    (#iter_expr).into_iter()
-                        .filter(|_item| { #(#conditions)&&* })
-                        .map(|_item| (#key, #value))
-                        .collect::<std::collections::HashMap<_, _>>()
-                })
-            }
-        } else {
-            // Multiple generators would need nested iteration - this is complex
-            Ok(quote! {
-                std::collections::HashMap::new() // Complex dict comprehension with multiple generators not fully supported
-            })
-        }
+        lower_comprehension(&self.generators, vec![*self.key, *self.value], Collection::Dict, self.lineno, ctx, options, symbols)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    // Note: These tests might need additional AST node implementations
-    // create_parse_test!(test_simple_listcomp, "[x for x in range(5)]", "test.py");
-    // create_parse_test!(test_listcomp_with_condition, "[x for x in range(10) if x % 2 == 0]", "test.py");
-}
\ No newline at end of file
+    use super::*;
+    use crate::macros::rust_for;
+
+    #[test]
+    fn test_simple_listcomp() {
+        assert!(rust_for("[x * 2 for x in items]")
+            .contains("(items) . into_iter () . map (| x | (x) * (2)) . collect :: < Vec < _ >> ()"));
+    }
+
+    #[test]
+    fn test_listcomp_with_conditions() {
+        let code = rust_for("[x for x in items if x > 0 if x < 10]");
+        assert_eq!(code.matches(". filter (| __item | { let x = __item . clone () ;").count(), 2);
+    }
+
+    /// The code generated for a single expression statement.
+    fn rust_expr(code: &str) -> String {
+        let module = crate::parse(code, "test.py").unwrap();
+        let crate::StatementType::Expr(expr) = &module.raw.body[0].statement else {
+            panic!("expected an expression statement");
+        };
+        expr.value
+            .clone()
+            .to_rust(CodeGenContext::Module("test".to_string()), PythonOptions::default(), SymbolTableScopes::new())
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_nested_generators() {
+        let code = rust_for("[(x, y) for x in xs for y in ys]");
+        assert!(code.contains(
+            "(xs) . clone () . into_iter () . flat_map (| x | (ys) . clone () . into_iter () \
+             . map (| y | { let x = x . clone () ; (x , y) }) . collect :: < Vec < _ >> ())"
+        ));
+    }
+
+    #[test]
+    fn test_nested_generators_compile() {
+        let program = format!(
+            "fn main() {{\n\
+                 let xs: Vec<i64> = vec![1, 2];\n\
+                 let words: Vec<String> = vec![\"a\".to_string(), \"b\".to_string()];\n\
+                 let rows: Vec<Vec<String>> = vec![words.clone(), vec![\"c\".to_string()]];\n\
+                 let sums: Vec<i64> = {};\n\
+                 let pairs: Vec<(String, String)> = {};\n\
+                 let cells: Vec<String> = {};\n\
+                 let triples: Vec<(String, String, i64)> = {};\n\
+                 assert_eq!(sums, vec![2, 3, 3, 4]);\n\
+                 assert_eq!(pairs, vec![(\"a\".to_string(), \"b\".to_string()), (\"b\".to_string(), \"a\".to_string())]);\n\
+                 assert_eq!(cells, vec![\"a\", \"b\", \"c\"]);\n\
+                 assert_eq!(triples.len(), 8);\n\
+                 let _ = (xs, words, rows);\n\
+             }}\n",
+            rust_expr("[a + b for a in xs for b in xs]"),
+            rust_expr("[(a, b) for a in words for b in words if a != b]"),
+            rust_expr("[cell for row in rows for cell in row]"),
+            rust_expr("[(a, b, n) for a in words for b in words for n in xs]"),
+        );
+
+        let dir = std::env::temp_dir().join(format!("python-ast-listcomp-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("main.rs");
+        std::fs::write(&source, &program).unwrap();
+        let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let output = std::process::Command::new(rustc)
+            .arg("--edition=2021")
+            .arg(&source)
+            .arg("-o")
+            .arg(dir.join("main"))
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}\n{}", program, String::from_utf8_lossy(&output.stderr));
+        let status = std::process::Command::new(dir.join("main")).status().unwrap();
+        assert!(status.success());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_tuple_target() {
+        assert!(rust_for("{k: v for (k, v) in pairs}")
+            .contains(". map (| (k , v) | (k , v)) . collect :: < std :: collections :: HashMap < _ , _ >> ()"));
+    }
+
+    #[test]
+    fn test_setcomp() {
+        assert!(rust_for("{x for x in items}").contains("collect :: < std :: collections :: HashSet < _ >> ()"));
+    }
+
+    #[test]
+    fn test_generator_is_lazy() {
+        let code = rust_for("total = sum(x for x in items)");
+        assert!(code.contains("sum ((items) . into_iter () . map (| x | x))"));
+    }
+
+    #[test]
+    fn test_async_comprehension() {
+        let code = rust_for("async def f():\n    return [x async for x in stream() if x]");
+        assert!(code.contains("while let Some (x) = futures :: StreamExt :: next (& mut __stream) . await"));
+    }
+
+    #[test]
+    fn test_targets_do_not_leak() {
+        let module = crate::parse("[x for x in items]", "test.py").unwrap();
+        let mut symbols = SymbolTableScopes::new();
+        symbols.new_scope();
+        let symbols = module.find_symbols(symbols);
+        assert!(symbols.get("x").is_none());
+    }
+}
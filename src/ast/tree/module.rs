@@ -6,7 +6,7 @@ use pyo3::{Bound, FromPyObject, PyAny, PyResult, prelude::PyAnyMethods};
 use quote::{format_ident, quote};
 use serde::{Deserialize, Serialize};
 
-use crate::{CodeGen, CodeGenContext, Name, Object, PythonOptions, Statement, StatementType, ExprType, SymbolTableScopes};
+use crate::{CodeGen, Comment, comment_marker, CodeGenContext, Declarations, Name, Object, PythonOptions, Statement, StatementType, ExprType, Stubs, SymbolTableScopes};
 
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -36,6 +36,9 @@ pub struct Module {
     pub doc: Option<String>,
     pub filename: Option<String>,
     pub attributes: HashMap<Name, String>,
+    /// Comments after the last statement.
+    #[serde(default)]
+    pub comments: Vec<Comment>,
 }
 
 impl<'a> FromPyObject<'a> for Module {
@@ -69,7 +72,23 @@ impl CodeGen for Module {
         options: Self::Options,
         symbols: Self::SymbolTable,
     ) -> Result<TokenStream, Box<dyn std::error::Error>> {
+        // The module's own definitions, with what stubs say about its imports over them. Variables are added
+        // as the statements assigning them are generated.
+        let mut symbols = symbols;
+        symbols.new_scope();
+        for s in self.raw.body.iter().filter(|s| Self::is_declaration_statement(&s.statement)) {
+            symbols = s.clone().find_symbols(symbols);
+        }
+        let mut symbols = Stubs::new(&options.python_path).register_imports(&self, symbols);
+        // The rest of the module's code runs in `__module_init__`, which declares its variables like a function.
+        let init_body: Vec<Statement> = self.raw.body.iter()
+            .filter(|s| !Self::is_declaration_statement(&s.statement) && !Self::is_main_guard(&s.statement))
+            .cloned()
+            .collect();
+        let init_declarations = Declarations::new(&init_body, &[]);
+        symbols = init_declarations.register(symbols);
         let mut stream = TokenStream::new();
+        let mut documented = false;
         
         // Add module-level documentation if available and not just an expression
         if let Some(docstring) = self.get_module_docstring() {
@@ -92,6 +111,7 @@ impl CodeGen for Module {
                 let generated_comment = format!("Generated from Python file: {}", 
                     self.filename.unwrap_or_else(|| "unknown.py".to_string()));
                 stream.extend(quote! { #![doc = #generated_comment] });
+                documented = true;
             }
         }
         
@@ -112,6 +132,12 @@ impl CodeGen for Module {
             stream.extend(quote!(use #runtime_import;));
         }
         
+        let trailing_comments: Vec<_> = if options.preserve_comments {
+            self.comments.iter().map(|c| comment_marker(&c.text)).collect()
+        } else {
+            Vec::new()
+        };
+
         let mut main_body_stmts = Vec::new();
         let mut has_main_code = false;
         let mut has_async_functions = false;
@@ -119,50 +145,55 @@ impl CodeGen for Module {
         let mut has_module_init_code = false;
         let mut is_simple_main_call_pattern = false;
         
-        for s in self.raw.body {
+        // The docstring is already the module's documentation, and a bare string isn't an item.
+        let skip = usize::from(documented);
+        for s in self.raw.body.into_iter().skip(skip) {
             // Check if this statement is an async function
             if let crate::StatementType::AsyncFunctionDef(_) = &s.statement {
                 has_async_functions = true;
             }
             
             // Check for if __name__ == "__main__" blocks at the AST level before generating code
-            if let crate::StatementType::If(if_stmt) = &s.statement {
-                let test_str = format!("{:?}", if_stmt.test);
-                if test_str.contains("__name__") && test_str.contains("__main__") {
-                    // Check if this is a simple main() call pattern
-                    let is_simple_main_call = Self::is_simple_main_call_block(&if_stmt.body);
-                    
-                    if is_simple_main_call {
-                        // For simple main() calls, we'll use the user's main function directly
-                        // Set a flag to indicate we should not rename the main function
-                        has_main_code = true;
-                        is_simple_main_call_pattern = true;
-                        // Don't collect the main body statements - we'll use user's main directly
-                    } else {
-                        // This is a complex __name__ == "__main__" block - collect its body for main function
-                        for body_stmt in &if_stmt.body {
-                            let stmt_token = body_stmt
-                                .clone()
-                                .to_rust(ctx.clone(), options.clone(), symbols.clone())
-                                .expect("parsing if __name__ body statement");
-                            if !stmt_token.to_string().trim().is_empty() {
-                                main_body_stmts.push(stmt_token);
-                                has_main_code = true;
-                            }
+            if let crate::StatementType::If(if_stmt) = &s.statement
+                && Self::is_main_guard(&s.statement)
+            {
+                // Check if this is a simple main() call pattern
+                let is_simple_main_call = Self::is_simple_main_call_block(&if_stmt.body);
+                
+                if is_simple_main_call {
+                    // For simple main() calls, we'll use the user's main function directly
+                    // Set a flag to indicate we should not rename the main function
+                    has_main_code = true;
+                    is_simple_main_call_pattern = true;
+                    // Don't collect the main body statements - we'll use user's main directly
+                } else {
+                    // This is a complex __name__ == "__main__" block - collect its body for main function,
+                    // which has variables of its own.
+                    let declarations = Declarations::new(&if_stmt.body, &[]);
+                    let mut main_symbols = symbols.clone();
+                    main_symbols.new_scope();
+                    main_symbols = declarations.register(main_symbols);
+                    main_body_stmts.push(declarations.to_rust());
+                    for body_stmt in &if_stmt.body {
+                        let stmt_token = body_stmt.clone().to_rust(ctx.clone(), options.clone(), main_symbols.clone())?;
+                        main_symbols = body_stmt.clone().find_symbols(main_symbols);
+                        if !stmt_token.to_string().trim().is_empty() {
+                            main_body_stmts.push(stmt_token);
+                            has_main_code = true;
                         }
                     }
-                    // Skip generating this if statement - we've processed its contents
-                    continue;
                 }
+                // Skip generating this if statement - we've processed its contents
+                continue;
             }
             
             // Categorize statements into declarations vs executable code
             let is_declaration = Self::is_declaration_statement(&s.statement);
             
-            let statement = s
-                .clone()
-                .to_rust(ctx.clone(), options.clone(), symbols.clone())
-                .expect(format!("parsing statement {:?} in module", s).as_str());
+            let statement = s.clone().to_rust(ctx.clone(), options.clone(), symbols.clone())?;
+            if !is_declaration {
+                symbols = s.clone().find_symbols(symbols);
+            }
             
             if statement.to_string() != "" {
                 if is_declaration {
@@ -178,8 +209,10 @@ impl CodeGen for Module {
         
         // Generate module initialization function if needed
         if has_module_init_code {
+            let declarations = init_declarations.to_rust();
             stream.extend(quote! {
                 fn __module_init__() {
+                    #declarations
                     #(#module_init_stmts)*
                 }
             });
@@ -317,6 +350,7 @@ impl CodeGen for Module {
                 }
             });
         }
+        stream.extend(quote!(#(#trailing_comments)*));
         Ok(stream)
     }
 }
@@ -366,6 +400,17 @@ impl Module {
         }
     }
     
+    /// Whether a statement is an `if __name__ == "__main__":` block.
+    fn is_main_guard(stmt_type: &crate::StatementType) -> bool {
+        match stmt_type {
+            crate::StatementType::If(if_stmt) => {
+                let test_str = format!("{:?}", if_stmt.test);
+                test_str.contains("__name__") && test_str.contains("__main__")
+            }
+            _ => false,
+        }
+    }
+
     /// Determine if a statement is a declaration (can stay at module level) or executable code (needs to go in init function)
     fn is_declaration_statement(stmt_type: &crate::StatementType) -> bool {
         use crate::StatementType::*;
@@ -1,8 +1,8 @@
 use proc_macro2::TokenStream;
-use pyo3::{FromPyObject, PyErr};
+use pyo3::{Bound, FromPyObject, PyAny, PyErr, PyResult, prelude::PyAnyMethods};
 use quote::{format_ident, quote};
 
-use crate::{CodeGen, CodeGenContext, IsIdentifier, PythonOptions, SymbolTableScopes};
+use crate::{CodeGen, CodeGenContext, IsIdentifier, Node, PythonOptions, SymbolTableScopes, impl_node_with_positions};
 
 use serde::{Deserialize, Serialize};
 
@@ -39,11 +39,57 @@ impl Into<String> for Identifier {
 }
 
 /// Names are Python identifiers, separated by '.'
-#[derive(Clone, Debug, Default, Eq, FromPyObject, Hash, PartialEq, Serialize, Deserialize)]
+///
+/// Two names are equal, and hash alike, when their identifiers are; where they appear doesn't matter.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Name {
     pub id: String,
+    /// Where the name appears. Names made up during codegen, and those from older caches, have none.
+    #[serde(default)]
+    pub lineno: Option<usize>,
+    #[serde(default)]
+    pub col_offset: Option<usize>,
+    #[serde(default)]
+    pub end_lineno: Option<usize>,
+    #[serde(default)]
+    pub end_col_offset: Option<usize>,
+}
+
+impl Name {
+    /// A name with no position.
+    pub fn new(id: impl Into<String>) -> Self {
+        Self { id: id.into(), ..Default::default() }
+    }
+}
+
+impl PartialEq for Name {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Name {}
+
+impl std::hash::Hash for Name {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<'a> FromPyObject<'a> for Name {
+    fn extract_bound(ob: &Bound<'a, PyAny>) -> PyResult<Self> {
+        Ok(Name {
+            id: ob.getattr("id")?.extract()?,
+            lineno: ob.lineno(),
+            col_offset: ob.col_offset(),
+            end_lineno: ob.end_lineno(),
+            end_col_offset: ob.end_col_offset(),
+        })
+    }
 }
 
+impl_node_with_positions!(Name { lineno, col_offset, end_lineno, end_col_offset });
+
 impl TryFrom<&str> for Name {
     type Error = PyErr;
 
@@ -57,7 +103,7 @@ impl TryFrom<&str> for Name {
             v.push(String::from(ident.as_ref()));
         }
 
-        Ok(Name { id: v.join(".") })
+        Ok(Name::new(v.join(".")))
     }
 }
 
@@ -111,4 +157,13 @@ mod tests {
         let name = Name::try_from("this.0symbol");
         assert!(name.is_err());
     }
+
+    #[test]
+    fn position_is_ignored() {
+        let placed = Name { lineno: Some(3), col_offset: Some(4), ..Name::new("x") };
+        assert_eq!(placed, Name::new("x"));
+        assert_ne!(placed, Name::new("y"));
+        let set: std::collections::HashSet<_> = [placed, Name::new("x")].into_iter().collect();
+        assert_eq!(set.len(), 1);
+    }
 }
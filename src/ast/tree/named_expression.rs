@@ -3,65 +3,171 @@ use pyo3::{Bound, PyAny, PyResult, FromPyObject, prelude::PyAnyMethods};
 use quote::quote;
 use serde::{Deserialize, Serialize};
 
-use crate::{CodeGen, CodeGenContext, ExprType, PythonOptions, SymbolTableScopes};
+use crate::{
+    CodeGen, CodeGenContext, ExprType, PythonOptions, SymbolTableNode, SymbolTableScopes,
+    Node, impl_node_with_positions, visit::{Visitor, walk_expr, walk_stmt},
+};
 
-/// A keyword argument, gnerally used in function calls.
+/// An assignment expression (the walrus operator), e.g. `(n := len(a))`.
 #[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
 pub struct NamedExpr {
-    left: Box<ExprType>,
-    right: Box<ExprType>,
+    /// The name being bound. Python only allows a plain name here.
+    pub target: Box<ExprType>,
+    pub value: Box<ExprType>,
+    pub lineno: Option<usize>,
+    pub col_offset: Option<usize>,
+    pub end_lineno: Option<usize>,
+    pub end_col_offset: Option<usize>,
 }
 
 impl<'a> FromPyObject<'a> for NamedExpr {
     fn extract_bound(ob: &Bound<'a, PyAny>) -> PyResult<Self> {
-        let left = ob.getattr("left")?.extract::<ExprType>()?;
-        let right = ob.getattr("right")?.extract::<ExprType>()?;
+        let target = ob.getattr("target")?.extract::<ExprType>()?;
+        let value = ob.getattr("value")?.extract::<ExprType>()?;
         Ok(NamedExpr {
-            left: Box::new(left),
-            right: Box::new(right),
+            target: Box::new(target),
+            value: Box::new(value),
+            lineno: ob.lineno(),
+            col_offset: ob.col_offset(),
+            end_lineno: ob.end_lineno(),
+            end_col_offset: ob.end_col_offset(),
         })
     }
 }
 
+impl_node_with_positions!(NamedExpr { lineno, col_offset, end_lineno, end_col_offset });
+
+impl NamedExpr {
+    /// The name this expression binds.
+    pub fn name(&self) -> Option<&str> {
+        match self.target.as_ref() {
+            ExprType::Name(name) => Some(name.id.as_str()),
+            _ => None,
+        }
+    }
+}
+
 impl CodeGen for NamedExpr {
     type Context = CodeGenContext;
     type Options = PythonOptions;
     type SymbolTable = SymbolTableScopes;
 
+    fn find_symbols(self, symbols: Self::SymbolTable) -> Self::SymbolTable {
+        let mut symbols = (*self.value).clone().find_symbols(symbols);
+        if let Some(name) = self.name() {
+            symbols.insert(
+                name.to_string(),
+                SymbolTableNode::Assign {
+                    position: 0,
+                    value: *self.value,
+                },
+            );
+        }
+        symbols
+    }
+
     fn to_rust(
         self,
         ctx: Self::Context,
         options: Self::Options,
         symbols: Self::SymbolTable,
     ) -> Result<TokenStream, Box<dyn std::error::Error>> {
-        let left = self
-            .left
-            .clone()
-            .to_rust(ctx.clone(), options.clone(), symbols.clone())
-            .expect(format!("parsing left side of named expression {:?}", self.left).as_str());
-        let right =
-            self.right.clone().to_rust(ctx, options, symbols).expect(
-                format!("parsing right side of named expression {:?}", self.right).as_str(),
-            );
-        Ok(quote!(#left = #right))
+        let target = self.target.to_rust(ctx.clone(), options.clone(), symbols.clone())?;
+        let value = self.value.to_rust(ctx, options, symbols)?;
+        // Rust has no binding expression, so the target is hoisted by the body's `Declarations` and
+        // this only assigns it and yields the value, as the Python expression does.
+        Ok(quote!({ #target = #value; #target.clone() }))
+    }
+}
+
+/// A name bound by a walrus operator somewhere inside a statement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WalrusTarget {
+    pub name: String,
+    /// Bound inside a comprehension. Python binds these in the enclosing scope, but the loop that
+    /// assigns them may not run, so they need an initial value.
+    pub in_comprehension: bool,
+}
+
+#[derive(Default)]
+struct WalrusTargets {
+    targets: Vec<WalrusTarget>,
+    comprehension_depth: usize,
+    entered_statement: bool,
+}
+
+impl<'a> Visitor<'a> for WalrusTargets {
+    fn visit_stmt(&mut self, stmt: &'a crate::Statement) {
+        // Nested blocks declare their own targets when they are generated.
+        if !self.entered_statement {
+            self.entered_statement = true;
+            walk_stmt(self, stmt);
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &'a ExprType) {
+        match expr {
+            // A lambda body is its own scope.
+            ExprType::Lambda(_) => {}
+            ExprType::NamedExpr(named) => {
+                if let Some(name) = named.name()
+                    && !self.targets.iter().any(|t| t.name == name)
+                {
+                    self.targets.push(WalrusTarget {
+                        name: name.to_string(),
+                        in_comprehension: self.comprehension_depth > 0,
+                    });
+                }
+                self.visit_expr(&named.value);
+            }
+            ExprType::ListComp(_) | ExprType::SetComp(_) | ExprType::DictComp(_) | ExprType::GeneratorExp(_) => {
+                self.comprehension_depth += 1;
+                walk_expr(self, expr);
+                self.comprehension_depth -= 1;
+            }
+            _ => walk_expr(self, expr),
+        }
+    }
+}
+
+/// Finds the names bound by walrus operators in an expression, outside of any lambda.
+pub fn walrus_targets(expr: &ExprType) -> Vec<WalrusTarget> {
+    let mut visitor = WalrusTargets::default();
+    visitor.visit_expr(expr);
+    visitor.targets
+}
+
+/// Finds the names bound by walrus operators in a statement's own expressions, leaving out the
+/// blocks nested in it.
+pub fn statement_walrus_targets(statement: &crate::Statement) -> Vec<WalrusTarget> {
+    let mut visitor = WalrusTargets::default();
+    visitor.visit_stmt(statement);
+    visitor.targets
+}
+
+/// The name and value of a condition that is nothing but a walrus, e.g. `if (m := f()):`.
+pub fn bare_walrus(test: &ExprType) -> Option<(&str, &ExprType)> {
+    match test {
+        ExprType::NamedExpr(named) => named.name().map(|name| (name, named.value.as_ref())),
+        _ => None,
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::macros::rust_for;
     use crate::{Constant, ExprType, Name};
     use litrs::*;
 
     #[test]
     fn test_named_expression() {
         let named_expression = NamedExpr {
-            left: Box::new(ExprType::Name(Name {
-                id: "a".to_string(),
-            })),
-            right: Box::new(ExprType::Constant(Constant(Some(Literal::Integer(
+            target: Box::new(ExprType::Name(Name::new("a"))),
+            value: Box::new(ExprType::Constant(Constant(Some(Literal::Integer(
                 IntegerLit::parse("1".to_string()).unwrap(),
             ))))),
+            ..Default::default()
         };
         let rust = named_expression
             .to_rust(
@@ -70,6 +176,50 @@ mod test {
                 SymbolTableScopes::new(),
             )
             .unwrap();
-        assert_eq!(rust.to_string(), "a = 1");
+        assert_eq!(rust.to_string(), "{ a = 1 ; a . clone () }");
+    }
+
+    #[test]
+    fn test_if_binds_before_test() {
+        let code = rust_for("def f(s):\n    if (m := match(s)):\n        print(m)");
+        assert!(code.contains("let m ; m = match (s) ; if m {"), "{}", code);
+    }
+
+    #[test]
+    fn test_while_loops_with_binding() {
+        let code = rust_for("def f(r):\n    while (line := r.readline()):\n        print(line)");
+        assert!(code.contains("let mut line ; loop { line = r . readline () ; if ! (line) { break ; }"), "{}", code);
+    }
+
+    #[test]
+    fn test_walrus_target_assigned_in_loop() {
+        let code = rust_for("def f(g):\n    while (line := g()):\n        line = line + 1\n        print(line)");
+        assert!(code.contains("let mut line ; loop { line = g () ; if ! (line) { break ; } line = (line) + (1) ;"), "{}", code);
+        assert_eq!(code.matches("let mut line").count(), 1, "{}", code);
+    }
+
+    #[test]
+    fn test_walrus_inside_condition() {
+        let code = rust_for("def f(a):\n    if (n := len(a)) > 10:\n        print(n)");
+        assert!(code.contains("let n ; if ({ n = len (a) ; n . clone () }) > (10)"), "{}", code);
+
+        let code = rust_for("def f(xs):\n    if (n := len(xs)) > 3:\n        n = 0\n    return n");
+        assert!(code.contains("let mut n ; if ({ n = len (xs) ; n . clone () }) > (3) { n = 0 ; }"), "{}", code);
+        assert_eq!(code.matches("let ").count(), 1, "{}", code);
+    }
+
+    #[test]
+    fn test_comprehension_binding_leaks() {
+        let code = rust_for("def f(data):\n    values = [y for x in data if (y := g(x))]\n    return y");
+        assert!(code.contains("let mut y = Default :: default () ;"));
+        assert!(code.contains("for x in data { if ! ({ y = g (x) ; y . clone () }) { continue ; }"));
+    }
+
+    #[test]
+    fn test_comprehension_binding_in_enclosing_scope() {
+        let module = crate::parse("[y for x in data if (y := g(x))]", "test.py").unwrap();
+        let symbols = module.find_symbols(SymbolTableScopes::new());
+        assert!(symbols.get("y").is_some());
+        assert!(symbols.get("x").is_none());
     }
 }
@@ -1,10 +1,11 @@
-use proc_macro2::TokenStream;
+use proc_macro2::{TokenStream, TokenTree};
 use pyo3::{Bound, FromPyObject, PyAny, PyResult, prelude::PyAnyMethods, types::PyTypeMethods};
 use quote::quote;
 
 use crate::{
-    dump, Assign, AugAssign, Call, ClassDef, CodeGen, CodeGenContext, Error, Expr, FunctionDef, Import,
+    dump, unparse, Assign, AugAssign, Call, ClassDef, CodeGen, CodeGenContext, Error, Expr, FunctionDef, Import,
     ImportFrom, Node, PythonOptions, SymbolTableScopes, If, For, While, Try, AsyncWith, AsyncFor, Raise, With,
+    bound_names, statement_fallback, ExprType, FallbackAudit, SymbolTableNode, Trivia,
 };
 
 use log::debug;
@@ -22,6 +23,9 @@ pub struct Statement {
     pub end_lineno: Option<usize>,
     pub end_col_offset: Option<usize>,
     pub statement: StatementType,
+    /// Comments and blank lines around the statement, filled in by the parser.
+    #[serde(default)]
+    pub trivia: Trivia,
 }
 
 impl<'a> FromPyObject<'a> for Statement {
@@ -32,6 +36,7 @@ impl<'a> FromPyObject<'a> for Statement {
             end_lineno: ob.end_lineno(),
             end_col_offset: ob.end_col_offset(),
             statement: StatementType::extract_bound(ob)?,
+            trivia: Trivia::default(),
         })
     }
 }
@@ -66,20 +71,63 @@ impl CodeGen for Statement {
         options: Self::Options,
         symbols: Self::SymbolTable,
     ) -> Result<TokenStream, Box<dyn std::error::Error>> {
-        Ok(self
-            .statement
-            .clone()
-            .to_rust(ctx, options, symbols)
-            .expect(
-                self.error_message(
-                    "<unknown>",
-                    format!("failed to compile statement {:#?}", self),
-                )
-                .as_str(),
-            ))
+        let trivia = if options.preserve_comments { self.trivia.to_rust() } else { quote!() };
+        let audit = options.python_fallback.clone();
+        let start = audit.as_ref().map_or(0, FallbackAudit::len);
+        let statement = match self.statement.clone().to_rust(ctx, options, symbols.clone()) {
+            Ok(statement) => statement,
+            Err(error) => {
+                // A definition is called from the Rust around it, so it has to be Rust itself; only what's
+                // inside it can fall back.
+                let defines = matches!(
+                    self.statement,
+                    StatementType::FunctionDef(_) | StatementType::AsyncFunctionDef(_) | StatementType::ClassDef(_)
+                );
+                let fallback = audit.as_ref().filter(|_| !defines).map(|audit| {
+                    // Whatever fell back inside it is replaced by the whole statement.
+                    audit.truncate(start);
+                    statement_fallback(&self, error.as_ref(), audit, &symbols)
+                });
+                match fallback {
+                    Some(Ok(statement)) => statement,
+                    Some(Err(fallback_error)) => return Err(Box::new(StatementError(self.error_message("<unknown>", fallback_error.to_string())))),
+                    // A statement inside this one has already said where it is.
+                    None if error.is::<StatementError>() => return Err(error),
+                    None => {
+                        let message = match &self.statement {
+                            StatementType::Unimplemented(source) => format!("`{}` can't be transpiled", source),
+                            _ => format!("failed to compile statement: {}", error),
+                        };
+                        return Err(Box::new(StatementError(self.error_message("<unknown>", message))));
+                    }
+                }
+            }
+        };
+        if let Some(audit) = &audit {
+            audit.locate(start, self.lineno, self.col_offset);
+        }
+        // A simple statement ends in exactly one `;`, whether or not its own tokens have one.
+        let statement = match statement.clone().into_iter().last() {
+            Some(TokenTree::Punct(p)) if p.as_char() == ';' => statement,
+            Some(_) if !self.statement.is_compound() => quote!(#statement;),
+            _ => statement,
+        };
+        Ok(quote!(#trivia #statement))
     }
 }
 
+/// A statement that failed to compile, and where it is.
+#[derive(Debug)]
+struct StatementError(String);
+
+impl std::fmt::Display for StatementError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for StatementError {}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum StatementType {
     AsyncFunctionDef(FunctionDef),
@@ -104,9 +152,30 @@ pub enum StatementType {
     Raise(Raise),
     With(With),
 
+    /// A statement the tree doesn't model, such as `del` or `global`, kept as its source. Codegen reports
+    /// it as an error, or hands it to the Python fallback when that is enabled.
     Unimplemented(String),
 }
 
+impl StatementType {
+    /// Whether the statement is built of blocks, like `if` or `def`, and so isn't followed by a `;`.
+    fn is_compound(&self) -> bool {
+        matches!(
+            self,
+            StatementType::If(_)
+                | StatementType::For(_)
+                | StatementType::While(_)
+                | StatementType::Try(_)
+                | StatementType::With(_)
+                | StatementType::AsyncWith(_)
+                | StatementType::AsyncFor(_)
+                | StatementType::FunctionDef(_)
+                | StatementType::AsyncFunctionDef(_)
+                | StatementType::ClassDef(_)
+        )
+    }
+}
+
 impl<'a> FromPyObject<'a> for StatementType {
     fn extract_bound(ob: &Bound<'a, PyAny>) -> PyResult<Self> {
         let err_msg = format!("getting type for statement {:?}", ob);
@@ -233,11 +302,11 @@ impl<'a> FromPyObject<'a> for StatementType {
                     .unwrap_or_else(|_| panic!("With statement {:?}", dump(ob, None)));
                 Ok(StatementType::With(with_stmt))
             }
-            _ => Err(pyo3::exceptions::PyValueError::new_err(format!(
-                "Unimplemented statement type {}, {}",
-                ob_type,
-                dump(ob, None)?
-            ))),
+            // Kept as source, so codegen can report it or hand it to the Python fallback.
+            _ => {
+                debug!("unimplemented statement type {}", ob_type);
+                Ok(StatementType::Unimplemented(unparse(ob)?))
+            }
         }
     }
 }
@@ -264,6 +333,14 @@ impl CodeGen for StatementType {
             StatementType::AsyncFor(af) => af.find_symbols(symbols),
             StatementType::Raise(r) => r.find_symbols(symbols),
             StatementType::With(w) => w.find_symbols(symbols),
+            StatementType::Unimplemented(source) => {
+                let mut symbols = symbols;
+                for name in bound_names(&source) {
+                    let value = ExprType::Unimplemented(source.clone());
+                    symbols.insert(name, SymbolTableNode::Assign { position: 0, value });
+                }
+                symbols
+            }
             _ => symbols,
         }
     }
@@ -417,4 +494,19 @@ def foo():
         );
         log::info!("module: {:?}", code);
     }
+
+    #[test]
+    fn test_one_terminator_per_statement() {
+        let code = crate::macros::rust_for("def f():\n    x = len([])\n");
+        assert_eq!(code, "pub fn f () { let x = len (vec ! []) ; }");
+
+        let code = crate::macros::rust_for("def f(xs, g):\n    for n in xs:\n        print(n)\n        continue\n    pass\n    m = g()\n    return m\n");
+        assert_eq!(
+            code,
+            "pub fn f (xs : impl Into < PyObject > , g : impl Into < PyObject >) { for n in xs { print (n) ; continue ; } let m = g () ; return m ; }"
+        );
+
+        let code = crate::macros::rust_for("x = 1\nprint(x)\nfor i in x:\n    print(i)\n");
+        assert_eq!(code, "fn __module_init__ () { let x = 1 ; print (x) ; for i in x { print (i) ; } } fn main () { __module_init__ () ; }");
+    }
 }
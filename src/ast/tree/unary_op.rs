@@ -29,8 +29,8 @@ impl<'a> FromPyObject<'a> for Ops {
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct UnaryOp {
-    op: Ops,
-    operand: Box<ExprType>,
+    pub op: Ops,
+    pub operand: Box<ExprType>,
 }
 
 impl<'a> FromPyObject<'a> for UnaryOp {
@@ -1,11 +1,11 @@
 use proc_macro2::TokenStream;
 use pyo3::{Bound, FromPyObject, PyAny, PyResult, types::PyAnyMethods};
-use quote::quote;
+use quote::{format_ident, quote};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     CodeGen, CodeGenContext, ExprType, PythonOptions, SymbolTableScopes,
-    Node, impl_node_with_positions, PyAttributeExtractor, extract_list
+    Node, impl_node_with_positions, PyAttributeExtractor, extract_list, bare_walrus,
 };
 
 use super::Statement;
@@ -60,6 +60,7 @@ impl CodeGen for While {
         options: Self::Options,
         symbols: Self::SymbolTable,
     ) -> Result<TokenStream, Box<dyn std::error::Error>> {
+        let walrus = bare_walrus(&self.test).map(|(name, value)| (format_ident!("{}", name), value.clone()));
         let test = self.test.to_rust(ctx.clone(), options.clone(), symbols.clone())?;
         
         let body_stmts: Result<Vec<_>, _> = self.body
@@ -68,6 +69,28 @@ impl CodeGen for While {
             .collect();
         let body_stmts = body_stmts?;
         
+        let else_stmts: Result<Vec<_>, _> = self.orelse
+            .iter()
+            .map(|stmt| stmt.clone().to_rust(ctx.clone(), options.clone(), symbols.clone()))
+            .collect();
+
+        // `while (line := f.readline()):` rebinds `line` on every pass, which `while` can't express,
+        // so it becomes a loop that binds, tests, and runs the `else` block on the way out.
+        if let Some((name, value)) = walrus {
+            let value = value.to_rust(ctx.clone(), options.clone(), symbols.clone())?;
+            let else_stmts = else_stmts?;
+            return Ok(quote! {
+                loop {
+                    #name = #value;
+                    if !(#name) {
+                        #(#else_stmts)*
+                        break;
+                    }
+                    #(#body_stmts)*
+                }
+            });
+        }
+
         if self.orelse.is_empty() {
             Ok(quote! {
                 while #test {
@@ -76,10 +99,6 @@ impl CodeGen for While {
             })
         } else {
             // Note: Rust doesn't have while-else, so we need to track completion
-            let else_stmts: Result<Vec<_>, _> = self.orelse
-                .into_iter()
-                .map(|stmt| stmt.to_rust(ctx.clone(), options.clone(), symbols.clone()))
-                .collect();
             let else_stmts = else_stmts?;
             
             Ok(quote! {
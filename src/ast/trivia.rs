@@ -0,0 +1,266 @@
+//! Comments and blank lines, which CPython's `ast` drops.
+//!
+//! The parser tokenizes the source separately and attaches each comment to the nearest statement by
+//! position, so the intent of the original code survives into the generated Rust. A token stream cannot
+//! carry comments, so [`Trivia::to_rust`] emits `__comment__!("...")` markers in their place and
+//! [`restore_comments`] turns those into `//` comments once the stream has been rendered to text.
+
+use std::sync::LazyLock;
+
+use litrs::StringLit;
+use proc_macro2::TokenStream;
+use quote::quote;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+use crate::{Module, Statement, StatementType};
+
+/// A `#` comment from the source.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Comment {
+    /// The comment without its leading `#`.
+    pub text: String,
+    pub lineno: usize,
+    pub col_offset: usize,
+}
+
+/// The comments and blank lines attached to a statement.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Trivia {
+    /// Whether blank lines separate this statement, or its leading comments, from what precedes it.
+    pub blank_line_before: bool,
+    /// Comments on their own lines just above the statement.
+    pub leading: Vec<Comment>,
+    /// Comments that share a line with the statement, including those inside a multi-line expression.
+    pub trailing: Vec<Comment>,
+}
+
+impl Trivia {
+    pub fn is_empty(&self) -> bool {
+        !self.blank_line_before && self.leading.is_empty() && self.trailing.is_empty()
+    }
+
+    /// Markers for the blank line and comments, all placed ahead of the statement. Trailing comments go
+    /// there too, since the statement may end in a position where nothing can follow it.
+    pub fn to_rust(&self) -> TokenStream {
+        let blank = self.blank_line_before.then(|| quote!(__blank_line__!();));
+        let comments = self.leading.iter().chain(&self.trailing).map(|c| comment_marker(&c.text));
+        quote!(#blank #(#comments)*)
+    }
+}
+
+pub(crate) fn comment_marker(text: &str) -> TokenStream {
+    quote!(__comment__!(#text);)
+}
+
+static COMMENT_MARKER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"__comment__\s*!\s*\(\s*("(?:[^"\\]|\\.)*")\s*\)\s*;[ \t]*\n?"#).unwrap()
+});
+static BLANK_LINE_MARKER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(\{\s*)?[ \t]*__blank_line__\s*!\s*\(\s*\)\s*;[ \t]*\n?").unwrap()
+});
+
+/// Replaces the markers left by [`Trivia::to_rust`] in rendered Rust with `//` comments and blank lines.
+pub fn restore_comments(code: &str) -> String {
+    let code = COMMENT_MARKER.replace_all(code, |captures: &Captures| {
+        let text = StringLit::parse(&captures[1]).map(|s| s.value().to_string()).unwrap_or_default();
+        format!("//{}\n", text)
+    });
+    // A block never opens with a blank line, though its first statement may have followed one in Python.
+    BLANK_LINE_MARKER
+        .replace_all(&code, |captures: &Captures| if captures.get(1).is_some() { "{\n" } else { "\n" })
+        .into_owned()
+}
+
+/// The statement lists nested directly inside a statement.
+fn bodies_mut(statement: &mut Statement) -> Vec<&mut Vec<Statement>> {
+    match &mut statement.statement {
+        StatementType::FunctionDef(f) | StatementType::AsyncFunctionDef(f) => vec![&mut f.body],
+        StatementType::ClassDef(c) => vec![&mut c.body],
+        StatementType::If(i) => vec![&mut i.body, &mut i.orelse],
+        StatementType::While(w) => vec![&mut w.body, &mut w.orelse],
+        StatementType::For(f) => vec![&mut f.body, &mut f.orelse],
+        StatementType::AsyncFor(f) => vec![&mut f.body, &mut f.orelse],
+        StatementType::With(w) => vec![&mut w.body],
+        StatementType::AsyncWith(w) => vec![&mut w.body],
+        StatementType::Try(t) => {
+            let mut bodies = vec![&mut t.body];
+            bodies.extend(t.handlers.iter_mut().map(|h| &mut h.body));
+            bodies.push(&mut t.orelse);
+            bodies.push(&mut t.finalbody);
+            bodies
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Visits every statement in source order, parents before their bodies.
+fn for_each_statement(body: &mut [Statement], f: &mut impl FnMut(&mut Statement)) {
+    for statement in body {
+        f(statement);
+        for nested in bodies_mut(statement) {
+            for_each_statement(nested, f);
+        }
+    }
+}
+
+enum Attachment {
+    Leading(usize),
+    Trailing(usize),
+    EndOfModule,
+}
+
+/// Decides where a comment belongs, given the line spans of every statement in source order.
+fn attachment(spans: &[(usize, usize)], comment: &Comment, inline: bool) -> Attachment {
+    let line = comment.lineno;
+    // The innermost statement whose text includes the comment's line.
+    let enclosing = spans.iter().rposition(|&(start, end)| start <= line && line <= end);
+    if inline && let Some(index) = enclosing {
+        return Attachment::Trailing(index);
+    }
+
+    let next = spans.iter().position(|&(start, _)| start > line);
+    match (enclosing, next) {
+        // Between statements nested inside the enclosing one, e.g. above an `else:` body.
+        (Some(index), Some(next)) if spans[next].0 <= spans[index].1 => Attachment::Leading(next),
+        // Inside the enclosing statement's own text, such as a multi-line list.
+        (Some(index), _) => Attachment::Trailing(index),
+        (None, Some(next)) => Attachment::Leading(next),
+        (None, None) => Attachment::EndOfModule,
+    }
+}
+
+/// The line a statement's text begins on, counting decorators above a definition.
+fn first_line(lines: &[&str], lineno: usize) -> usize {
+    let mut first = lineno;
+    while first > 1 && lines.get(first - 2).is_some_and(|l| l.trim_start().starts_with('@')) {
+        first -= 1;
+    }
+    first
+}
+
+fn blank_before(lines: &[&str], line: usize) -> bool {
+    line > 1 && lines.get(line - 2).is_some_and(|l| l.trim().is_empty())
+}
+
+/// Attaches comments, given as `(lineno, col_offset, text, inline)` with the `#` already removed, to the
+/// statements of a freshly parsed module, and marks statements that follow blank lines.
+pub fn attach_trivia(module: &mut Module, source: &str, comments: Vec<(usize, usize, String, bool)>) {
+    let lines: Vec<&str> = source.lines().collect();
+
+    let mut spans = Vec::new();
+    for_each_statement(&mut module.raw.body, &mut |s| {
+        if let Some(lineno) = s.lineno {
+            spans.push((first_line(&lines, lineno), s.end_lineno.unwrap_or(lineno)));
+        }
+    });
+
+    let mut trivia = vec![Trivia::default(); spans.len()];
+    for (lineno, col_offset, text, inline) in comments {
+        let comment = Comment { text, lineno, col_offset };
+        match attachment(&spans, &comment, inline) {
+            Attachment::Leading(index) => trivia[index].leading.push(comment),
+            Attachment::Trailing(index) => trivia[index].trailing.push(comment),
+            Attachment::EndOfModule => module.comments.push(comment),
+        }
+    }
+
+    for (index, &(start, _)) in spans.iter().enumerate() {
+        let first = trivia[index].leading.first().map_or(start, |c| c.lineno);
+        trivia[index].blank_line_before = index > 0 && blank_before(&lines, first);
+    }
+
+    let mut trivia = trivia.into_iter();
+    for_each_statement(&mut module.raw.body, &mut |s| {
+        if s.lineno.is_some() {
+            s.trivia = trivia.next().unwrap_or_default();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CodeGen, CodeGenContext, PythonOptions, SymbolTableScopes};
+
+    const SOURCE: &str = r#"# Configuration loader.
+import os
+
+# The default path.
+PATH = "/etc"  # overridable
+
+
+def load(path):
+    # Prefer the environment.
+    if path:
+        return path
+    # Fall back to the default.
+    else:
+        return [
+            PATH,  # system
+        ]
+
+# end of file
+"#;
+
+    fn comments(statement: &Statement) -> Vec<&str> {
+        let trivia = &statement.trivia;
+        trivia.leading.iter().chain(&trivia.trailing).map(|c| c.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_attaches_comments_by_position() {
+        let module = crate::parse(SOURCE, "test.py").unwrap();
+        let body = &module.raw.body;
+        assert_eq!(comments(&body[0]), [" Configuration loader."]);
+        assert_eq!(comments(&body[1]), [" The default path.", " overridable"]);
+        assert!(body[1].trivia.blank_line_before);
+        assert!(body[2].trivia.blank_line_before);
+
+        let StatementType::FunctionDef(load) = &body[2].statement else { panic!("expected a function") };
+        assert_eq!(comments(&load.body[0]), [" Prefer the environment."]);
+        let StatementType::If(test) = &load.body[0].statement else { panic!("expected an if") };
+        assert_eq!(comments(&test.orelse[0]), [" Fall back to the default.", " system"]);
+
+        assert_eq!(module.comments.len(), 1);
+        assert_eq!(module.comments[0].text, " end of file");
+    }
+
+    #[test]
+    fn test_emits_comments() {
+        let module = crate::parse(SOURCE, "test.py").unwrap();
+        let options = PythonOptions { with_std_python: false, preserve_comments: true, ..Default::default() };
+        let rust = module
+            .to_rust(CodeGenContext::Module("test".to_string()), options, SymbolTableScopes::new())
+            .unwrap()
+            .to_string();
+        let rust = restore_comments(&rust);
+        assert!(!rust.contains("__comment__"));
+        assert!(!rust.contains("__blank_line__"));
+        assert!(rust.contains("// Prefer the environment.\n"));
+        assert!(rust.contains("// end of file\n"));
+    }
+
+    #[test]
+    fn test_comments_can_be_left_out() {
+        let module = crate::parse(SOURCE, "test.py").unwrap();
+        let options = PythonOptions { with_std_python: false, ..Default::default() };
+        let rust = module
+            .to_rust(CodeGenContext::Module("test".to_string()), options, SymbolTableScopes::new())
+            .unwrap()
+            .to_string();
+        assert!(!rust.contains("__comment__"));
+    }
+
+    #[test]
+    fn test_restore_comments() {
+        let code = "fn f () { __comment__ ! (\" say \\\"hi\\\"\") ; g () ; __blank_line__ ! () ; h () ; }";
+        assert_eq!(restore_comments(code), "fn f () { // say \"hi\"\ng () ;\nh () ; }");
+
+        let formatted = "fn f() {\n    __comment__!(\" note\");\n    g();\n}\n";
+        assert_eq!(restore_comments(formatted), "fn f() {\n    // note\n    g();\n}\n");
+
+        let opening = "fn f() {\n    __blank_line__!();\n    g();\n}\n";
+        assert_eq!(restore_comments(opening), "fn f() {\n    g();\n}\n");
+    }
+}
@@ -0,0 +1,304 @@
+//! A read-only visitor over the AST.
+//!
+//! Implement [`Visitor`] and override the `visit_*` methods for the nodes you care about. Each default method
+//! calls the matching `walk_*` function, which visits the node's children, so an override that still wants to
+//! descend should call the `walk_*` function itself.
+
+use crate::tree::{
+    Alias, Arguments, Comprehension, ExceptHandler, ExprType, Keyword, Statement, StatementType, WithItem,
+};
+
+pub trait Visitor<'a> {
+    fn visit_stmt(&mut self, stmt: &'a Statement) {
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &'a ExprType) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_arguments(&mut self, arguments: &'a Arguments) {
+        walk_arguments(self, arguments);
+    }
+
+    fn visit_keyword(&mut self, keyword: &'a Keyword) {
+        self.visit_expr(&keyword.value);
+    }
+
+    fn visit_comprehension(&mut self, comprehension: &'a Comprehension) {
+        walk_comprehension(self, comprehension);
+    }
+
+    fn visit_except_handler(&mut self, handler: &'a ExceptHandler) {
+        walk_except_handler(self, handler);
+    }
+
+    fn visit_with_item(&mut self, item: &'a WithItem) {
+        self.visit_expr(&item.context_expr);
+        if let Some(vars) = &item.optional_vars {
+            self.visit_expr(vars);
+        }
+    }
+
+    fn visit_alias(&mut self, _alias: &'a Alias) {}
+}
+
+pub fn walk_body<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, body: &'a [Statement]) {
+    for stmt in body {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_stmt<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmt: &'a Statement) {
+    match &stmt.statement {
+        StatementType::FunctionDef(f) | StatementType::AsyncFunctionDef(f) => {
+            for decorator in &f.decorator_list {
+                visitor.visit_expr(decorator);
+            }
+            visitor.visit_arguments(&f.args);
+            walk_body(visitor, &f.body);
+        }
+        StatementType::ClassDef(c) => {
+            for decorator in &c.decorator_list {
+                visitor.visit_expr(decorator);
+            }
+            for keyword in &c.keywords {
+                visitor.visit_keyword(keyword);
+            }
+            walk_body(visitor, &c.body);
+        }
+        StatementType::Assign(a) => {
+            for target in &a.targets {
+                visitor.visit_expr(target);
+            }
+            visitor.visit_expr(&a.value);
+        }
+        StatementType::AugAssign(a) => {
+            visitor.visit_expr(&a.target);
+            visitor.visit_expr(&a.value);
+        }
+        StatementType::Call(c) => {
+            visitor.visit_expr(&c.func);
+            for arg in &c.args {
+                visitor.visit_expr(arg);
+            }
+            for keyword in &c.keywords {
+                visitor.visit_keyword(keyword);
+            }
+        }
+        StatementType::Return(Some(e)) | StatementType::Expr(e) => visitor.visit_expr(&e.value),
+        StatementType::Import(i) => {
+            for alias in &i.names {
+                visitor.visit_alias(alias);
+            }
+        }
+        StatementType::ImportFrom(i) => {
+            for alias in &i.names {
+                visitor.visit_alias(alias);
+            }
+        }
+        StatementType::If(i) => {
+            visitor.visit_expr(&i.test);
+            walk_body(visitor, &i.body);
+            walk_body(visitor, &i.orelse);
+        }
+        StatementType::While(w) => {
+            visitor.visit_expr(&w.test);
+            walk_body(visitor, &w.body);
+            walk_body(visitor, &w.orelse);
+        }
+        StatementType::For(f) => {
+            visitor.visit_expr(&f.target);
+            visitor.visit_expr(&f.iter);
+            walk_body(visitor, &f.body);
+            walk_body(visitor, &f.orelse);
+        }
+        StatementType::AsyncFor(f) => {
+            visitor.visit_expr(&f.target);
+            visitor.visit_expr(&f.iter);
+            walk_body(visitor, &f.body);
+            walk_body(visitor, &f.orelse);
+        }
+        StatementType::With(w) => {
+            for item in &w.items {
+                visitor.visit_with_item(item);
+            }
+            walk_body(visitor, &w.body);
+        }
+        StatementType::AsyncWith(w) => {
+            for item in &w.items {
+                visitor.visit_with_item(item);
+            }
+            walk_body(visitor, &w.body);
+        }
+        StatementType::Try(t) => {
+            walk_body(visitor, &t.body);
+            for handler in &t.handlers {
+                visitor.visit_except_handler(handler);
+            }
+            walk_body(visitor, &t.orelse);
+            walk_body(visitor, &t.finalbody);
+        }
+        StatementType::Raise(r) => {
+            if let Some(exc) = &r.exc {
+                visitor.visit_expr(exc);
+            }
+            if let Some(cause) = &r.cause {
+                visitor.visit_expr(cause);
+            }
+        }
+        StatementType::Return(None)
+        | StatementType::Break
+        | StatementType::Continue
+        | StatementType::Pass
+        | StatementType::Unimplemented(_) => {}
+    }
+}
+
+pub fn walk_expr<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, expr: &'a ExprType) {
+    match expr {
+        ExprType::BoolOp(b) => {
+            visitor.visit_expr(&b.left);
+            visitor.visit_expr(&b.right);
+        }
+        ExprType::NamedExpr(n) => {
+            visitor.visit_expr(&n.target);
+            visitor.visit_expr(&n.value);
+        }
+        ExprType::BinOp(b) => {
+            visitor.visit_expr(&b.left);
+            visitor.visit_expr(&b.right);
+        }
+        ExprType::UnaryOp(u) => visitor.visit_expr(&u.operand),
+        ExprType::Lambda(l) => {
+            visitor.visit_arguments(&l.args);
+            visitor.visit_expr(&l.body);
+        }
+        ExprType::IfExp(i) => {
+            visitor.visit_expr(&i.test);
+            visitor.visit_expr(&i.body);
+            visitor.visit_expr(&i.orelse);
+        }
+        ExprType::Dict(d) => {
+            for key in d.keys.iter().flatten() {
+                visitor.visit_expr(key);
+            }
+            for value in &d.values {
+                visitor.visit_expr(value);
+            }
+        }
+        ExprType::Set(s) => s.elts.iter().for_each(|e| visitor.visit_expr(e)),
+        ExprType::ListComp(c) => {
+            c.generators.iter().for_each(|g| visitor.visit_comprehension(g));
+            visitor.visit_expr(&c.elt);
+        }
+        ExprType::SetComp(c) => {
+            c.generators.iter().for_each(|g| visitor.visit_comprehension(g));
+            visitor.visit_expr(&c.elt);
+        }
+        ExprType::GeneratorExp(c) => {
+            c.generators.iter().for_each(|g| visitor.visit_comprehension(g));
+            visitor.visit_expr(&c.elt);
+        }
+        ExprType::DictComp(c) => {
+            c.generators.iter().for_each(|g| visitor.visit_comprehension(g));
+            visitor.visit_expr(&c.key);
+            visitor.visit_expr(&c.value);
+        }
+        ExprType::Await(a) => visitor.visit_expr(&a.value),
+        ExprType::Yield(y) => {
+            if let Some(value) = &y.value {
+                visitor.visit_expr(value);
+            }
+        }
+        ExprType::YieldFrom(y) => visitor.visit_expr(&y.value),
+        ExprType::Compare(c) => {
+            visitor.visit_expr(&c.left);
+            c.comparators.iter().for_each(|e| visitor.visit_expr(e));
+        }
+        ExprType::Call(c) => {
+            visitor.visit_expr(&c.func);
+            c.args.iter().for_each(|e| visitor.visit_expr(e));
+            c.keywords.iter().for_each(|k| visitor.visit_keyword(k));
+        }
+        ExprType::FormattedValue(f) => {
+            visitor.visit_expr(&f.value);
+            if let Some(spec) = &f.format_spec {
+                visitor.visit_expr(spec);
+            }
+        }
+        ExprType::JoinedStr(j) => j.values.iter().for_each(|e| visitor.visit_expr(e)),
+        ExprType::Attribute(a) => visitor.visit_expr(&a.value),
+        ExprType::Subscript(s) => {
+            visitor.visit_expr(&s.value);
+            visitor.visit_expr(&s.slice);
+        }
+        ExprType::Starred(s) => visitor.visit_expr(&s.value),
+        ExprType::List(elts) => elts.iter().for_each(|e| visitor.visit_expr(e)),
+        ExprType::Tuple(t) => t.elts.iter().for_each(|e| visitor.visit_expr(e)),
+        ExprType::Constant(_)
+        | ExprType::Name(_)
+        | ExprType::NoneType(_)
+        | ExprType::Unimplemented(_)
+        | ExprType::Unknown => {}
+    }
+}
+
+pub fn walk_arguments<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, arguments: &'a Arguments) {
+    for default in &arguments.defaults {
+        visitor.visit_expr(default);
+    }
+    for default in arguments.kw_defaults.iter().flatten() {
+        visitor.visit_expr(default);
+    }
+    let parameters = arguments
+        .posonlyargs
+        .iter()
+        .chain(&arguments.args)
+        .chain(&arguments.vararg)
+        .chain(&arguments.kwonlyargs)
+        .chain(&arguments.kwarg);
+    for annotation in parameters.filter_map(|p| p.annotation.as_deref()) {
+        visitor.visit_expr(annotation);
+    }
+}
+
+pub fn walk_comprehension<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, comprehension: &'a Comprehension) {
+    visitor.visit_expr(&comprehension.target);
+    visitor.visit_expr(&comprehension.iter);
+    for condition in &comprehension.ifs {
+        visitor.visit_expr(condition);
+    }
+}
+
+pub fn walk_except_handler<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, handler: &'a ExceptHandler) {
+    if let Some(exception_type) = &handler.exception_type {
+        visitor.visit_expr(exception_type);
+    }
+    walk_body(visitor, &handler.body);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Names(Vec<String>);
+
+    impl<'a> Visitor<'a> for Names {
+        fn visit_expr(&mut self, expr: &'a ExprType) {
+            if let ExprType::Name(name) = expr {
+                self.0.push(name.id.clone());
+            }
+            walk_expr(self, expr);
+        }
+    }
+
+    #[test]
+    fn test_visits_nested_names() {
+        let module = crate::parse("if a:\n    b = [c for d in e if f]\nelse:\n    g(h=i)", "test.py").unwrap();
+        let mut names = Names::default();
+        walk_body(&mut names, &module.raw.body);
+        assert_eq!(names.0, vec!["a", "b", "d", "e", "f", "c", "g", "i"]);
+    }
+}
@@ -0,0 +1,364 @@
+//! `python-ast-lsp`, a language server that shows how Python will transpile as it is edited.
+//!
+//! It speaks the Language Server Protocol over stdin and stdout, and offers:
+//!
+//! * diagnostics for each statement that can't be transpiled, and for syntax errors, published whenever a
+//!   document is opened or changed;
+//! * hover, showing the Rust generated for the statement under the cursor;
+//! * go-to-definition for names and parameters, through the scopes the name analysis finds.
+//!
+//! Documents are synchronized in full on each change. Positions are converted between the protocol's UTF-16
+//! columns and the byte columns Python's parser reports.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    panic::{AssertUnwindSafe, catch_unwind},
+    process::ExitCode,
+};
+
+use python_ast::{
+    CodeGen, CodeGenContext, Diagnostic, Error, Module, NodeRef, PositionIndex, PythonOptions, Severity, SourceLocation,
+    StatementType, SymbolTableScopes, parse_enhanced, untranspilable,
+};
+use serde_json::{Value, json};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_NOT_INITIALIZED: i64 = -32002;
+
+/// An open document, and its tree if it parsed.
+struct Document {
+    text: String,
+    module: Result<Module, Diagnostic>,
+}
+
+impl Document {
+    fn new(uri: &str, text: String) -> Self {
+        let filename = uri.rsplit('/').next().map(decode).unwrap_or_default();
+        let module = match guarded(|| parse_enhanced(&text, &filename).map_err(Box::new)) {
+            Ok(Ok(module)) => Ok(module),
+            Ok(Err(error)) => Err(syntax_error(&filename, error)),
+            Err(reason) => Err(internal_error(&filename, &reason)),
+        };
+        Self { text, module }
+    }
+
+    fn line(&self, line: usize) -> &str {
+        let line = self.text.split('\n').nth(line).unwrap_or_default();
+        line.strip_suffix('\r').unwrap_or(line)
+    }
+
+    /// A protocol position as a 1-based line and a byte column, as the tree has them.
+    fn to_python(&self, position: &Value) -> Option<(usize, usize)> {
+        let line = position["line"].as_u64()? as usize;
+        let character = position["character"].as_u64()? as usize;
+        Some((line + 1, byte_column(self.line(line), character)))
+    }
+
+    /// A 1-based line and column, as in a [`SourceLocation`], as a protocol position.
+    fn to_protocol(&self, line: usize, column: usize) -> Value {
+        let line = line.saturating_sub(1);
+        json!({ "line": line, "character": utf16_column(self.line(line), column.saturating_sub(1)) })
+    }
+
+    fn range(&self, location: &SourceLocation) -> Value {
+        let (line, column) = (location.line.unwrap_or(1), location.column.unwrap_or(1));
+        let end = (location.end_line.unwrap_or(line), location.end_column.unwrap_or(column));
+        json!({ "start": self.to_protocol(line, column), "end": self.to_protocol(end.0, end.1) })
+    }
+
+    fn diagnostics(&self) -> Vec<Value> {
+        let diagnostics = match &self.module {
+            Ok(module) => guarded(|| untranspilable(module, &PythonOptions::default())).unwrap_or_else(|reason| {
+                vec![internal_error(module.filename.as_deref().unwrap_or_default(), &reason)]
+            }),
+            Err(failure) => vec![failure.clone()],
+        };
+        diagnostics
+            .iter()
+            .map(|d| {
+                let severity = match d.severity {
+                    Severity::Error => 1,
+                    Severity::Warning => 2,
+                    Severity::Info => 3,
+                };
+                json!({
+                    "range": self.range(&d.location),
+                    "severity": severity,
+                    "code": d.code,
+                    "source": "python-ast",
+                    "message": d.message,
+                })
+            })
+            .collect()
+    }
+
+    /// The Rust generated for the innermost statement at the position.
+    fn hover(&self, position: &Value) -> Option<Value> {
+        let module = self.module.as_ref().ok()?;
+        let (line, column) = self.to_python(position)?;
+        let index = PositionIndex::new(module);
+        let arena = index.arena();
+        let found = index.at(line, column)?;
+        let statement = std::iter::once(found.node).chain(found.enclosing).find(|&id| matches!(arena.node(id), NodeRef::Statement(_)))?;
+        let NodeRef::Statement(code) = arena.node(statement) else {
+            return None;
+        };
+
+        // The statement is generated as it would be in the definition around it.
+        let name = module.name.as_ref().map_or_else(|| "main".to_string(), |name| name.id.clone());
+        let ctx = arena
+            .ancestors(statement)
+            .find_map(|id| match arena.node(id) {
+                NodeRef::Statement(s) => match s.statement {
+                    StatementType::FunctionDef(_) => Some(CodeGenContext::Function),
+                    StatementType::AsyncFunctionDef(_) => Some(CodeGenContext::Async(Box::new(CodeGenContext::Function))),
+                    StatementType::ClassDef(_) => Some(CodeGenContext::Class),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .unwrap_or(CodeGenContext::Module(name));
+        let generated = guarded(|| {
+            let symbols = module.clone().find_symbols(SymbolTableScopes::new());
+            code.to_rust_source(ctx, PythonOptions::default(), symbols).map_err(|e| e.to_string())
+        });
+        let value = match generated {
+            Ok(Ok(rust)) if rust.trim().is_empty() => "Generates no Rust.".to_string(),
+            Ok(Ok(rust)) => format!("```rust\n{}```", rust),
+            Ok(Err(_)) | Err(_) => "This statement can't be transpiled.".to_string(),
+        };
+        let range = index.location(statement).map(|location| self.range(&location));
+        Some(json!({ "contents": { "kind": "markdown", "value": value }, "range": range }))
+    }
+
+    /// Where the name at the position is bound.
+    fn definition(&self, uri: &str, position: &Value) -> Option<Value> {
+        let module = self.module.as_ref().ok()?;
+        let (line, column) = self.to_python(position)?;
+        let index = PositionIndex::new(module);
+        let found = index.at(line, column)?;
+        let location = index.definition(found.node)?;
+        Some(json!({ "uri": uri, "range": self.range(&location) }))
+    }
+}
+
+/// Runs `f`, catching a panic and returning its message, so one file the crate can't handle doesn't stop the
+/// server.
+fn guarded<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    catch_unwind(AssertUnwindSafe(f)).map_err(|panic| {
+        let reason = panic.downcast_ref::<String>().map(String::as_str).or_else(|| panic.downcast_ref::<&str>().copied());
+        reason.and_then(|r| r.lines().next()).unwrap_or("unknown error").to_string()
+    })
+}
+
+/// A panic while handling the file, reported at its top.
+fn internal_error(filename: &str, reason: &str) -> Diagnostic {
+    Diagnostic {
+        code: "internal-error",
+        severity: Severity::Error,
+        message: format!("python-ast failed on this file: {}", reason),
+        location: SourceLocation::with_position(filename, Some(1), Some(1)),
+    }
+}
+
+fn syntax_error(filename: &str, error: Box<Error>) -> Diagnostic {
+    let (location, message) = match *error {
+        Error::ParseError { location, message, .. } => (location, message),
+        other => (SourceLocation::with_position(filename, Some(1), Some(1)), other.to_string()),
+    };
+    let message = message.strip_prefix("Python parsing failed: ").unwrap_or(&message).to_string();
+    Diagnostic { code: "syntax-error", severity: Severity::Error, message, location }
+}
+
+/// Decodes the `%XX` escapes in part of a URI.
+fn decode(part: &str) -> String {
+    let bytes = part.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok()).and_then(|h| u8::from_str_radix(h, 16).ok());
+        match hex {
+            Some(byte) if bytes[i] == b'%' => {
+                decoded.push(byte);
+                i += 3;
+            }
+            _ => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The byte offset in `line` of a column counted in UTF-16 code units.
+fn byte_column(line: &str, utf16: usize) -> usize {
+    let mut units = 0;
+    for (offset, c) in line.char_indices() {
+        if units >= utf16 {
+            return offset;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+/// The column in UTF-16 code units of a byte offset in `line`.
+fn utf16_column(line: &str, byte: usize) -> usize {
+    line.char_indices().take_while(|(offset, _)| *offset < byte).map(|(_, c)| c.len_utf16()).sum()
+}
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<String, Document>,
+    initialized: bool,
+    shutting_down: bool,
+}
+
+impl Server {
+    /// Handles one message, returning the messages to send back, and the exit status once told to exit.
+    fn handle(&mut self, message: &Value) -> (Vec<Value>, Option<ExitCode>) {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let Some(id) = message.get("id").cloned() else {
+            return match method {
+                "exit" => (Vec::new(), Some(if self.shutting_down { ExitCode::SUCCESS } else { ExitCode::FAILURE })),
+                _ if !self.initialized => (Vec::new(), None),
+                _ => (self.notification(method, params), None),
+            };
+        };
+
+        let result = match method {
+            _ if self.shutting_down => Err((INVALID_REQUEST, "the server is shutting down".to_string())),
+            "initialize" => {
+                self.initialized = true;
+                Ok(json!({
+                    "capabilities": {
+                        "textDocumentSync": { "openClose": true, "change": 1 },
+                        "hoverProvider": true,
+                        "definitionProvider": true,
+                    },
+                    "serverInfo": { "name": "python-ast-lsp", "version": env!("CARGO_PKG_VERSION") },
+                }))
+            }
+            _ if !self.initialized => Err((SERVER_NOT_INITIALIZED, "the server has not been initialized".to_string())),
+            "shutdown" => {
+                self.shutting_down = true;
+                Ok(Value::Null)
+            }
+            "textDocument/hover" | "textDocument/definition" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                match self.documents.get(uri) {
+                    Some(document) if method == "textDocument/hover" => Ok(document.hover(&params["position"]).into()),
+                    Some(document) => Ok(document.definition(uri, &params["position"]).into()),
+                    None => Err((INVALID_PARAMS, format!("{} is not open", uri))),
+                }
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("unsupported method `{}`", method))),
+        };
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+        };
+        (vec![response], None)
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let Some(uri) = params["textDocument"]["uri"].as_str() else {
+            return Vec::new();
+        };
+        let text = match method {
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            // Changes are whole documents, so the last one is the text.
+            "textDocument/didChange" => params["contentChanges"].as_array().and_then(|c| c.last()).and_then(|c| c["text"].as_str()),
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return vec![publish(uri, Vec::new())];
+            }
+            _ => None,
+        };
+        let Some(text) = text else {
+            return Vec::new();
+        };
+        let document = Document::new(uri, text.to_string());
+        let diagnostics = document.diagnostics();
+        self.documents.insert(uri.to_string(), document);
+        vec![publish(uri, diagnostics)]
+    }
+}
+
+fn publish(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+/// Reads one message's body, or `None` at the end of the input.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "message without a Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn main() -> ExitCode {
+    env_logger::init();
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    let mut server = Server::default();
+    loop {
+        let body = match read_message(&mut input) {
+            Ok(Some(body)) => body,
+            // The client went away without saying so.
+            Ok(None) => return ExitCode::FAILURE,
+            Err(e) => {
+                eprintln!("error: reading a message: {}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+        let (replies, exit) = match serde_json::from_slice::<Value>(&body) {
+            Ok(message) => server.handle(&message),
+            Err(e) => {
+                let error = json!({ "jsonrpc": "2.0", "id": null, "error": { "code": PARSE_ERROR, "message": e.to_string() } });
+                (vec![error], None)
+            }
+        };
+        for reply in &replies {
+            if let Err(e) = write_message(&mut output, reply) {
+                eprintln!("error: writing a message: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        if let Some(exit) = exit {
+            return exit;
+        }
+    }
+}
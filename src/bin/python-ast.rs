@@ -0,0 +1,536 @@
+//! The `python-ast` command-line transpiler.
+//!
+//! Transpiles a Python file, or every `.py` file under a directory, to Rust, or dumps the parsed tree.
+//! Failures are reported per file on stderr, and the exit status is non-zero if any file failed.
+
+use std::{
+    error::Error,
+    fs,
+    io::Write,
+    panic::{AssertUnwindSafe, catch_unwind},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Parser, ValueEnum};
+use proc_macro2::TokenStream;
+use python_ast::{
+    AsyncRuntime, CallGraph, CargoProject, CodeGen, CodeGenContext, Diagnostic, FallbackAudit, LintOptions, Module, ParseCache,
+    PythonOptions, PythonVersion, Query, QueryMatch, Rule, Severity, SourceOptions, Suppressions, SymbolTableScopes, check_names,
+    check_version, control_flow_graphs, lint, metrics, parse_enhanced, parse_for_version,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum Emit {
+    /// Formatted Rust source.
+    Rust,
+    /// The unformatted token stream, for code that the formatter rejects.
+    Tokens,
+    /// The tree as CPython-compatible JSON.
+    Json,
+    /// The tree in Rust's debug form.
+    Debug,
+    /// The control-flow graphs of the module and each function in it, in Graphviz's DOT language.
+    Cfg,
+    /// A table of size and complexity metrics for the module and each class and function in it.
+    Metrics,
+    /// The same metrics as JSON.
+    MetricsJson,
+    /// The call graph across every input file, in Graphviz's DOT language, to --output or stdout.
+    Calls,
+    /// The same call graph as JSON, with each call's location and how its target was resolved.
+    CallsJson,
+    /// A Cargo project, with a manifest naming the crates the code uses, in the --output directory.
+    Project,
+}
+
+impl Emit {
+    fn extension(self) -> &'static str {
+        match self {
+            Emit::Rust | Emit::Tokens => "rs",
+            Emit::Json | Emit::MetricsJson | Emit::CallsJson => "json",
+            Emit::Debug | Emit::Metrics => "txt",
+            Emit::Cfg | Emit::Calls => "dot",
+            Emit::Project => "",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum Runtime {
+    Tokio,
+    AsyncStd,
+    Smol,
+    /// Requires --runtime-attribute and --runtime-import.
+    Custom,
+}
+
+/// Transpiles Python to Rust.
+#[derive(Debug, Parser)]
+#[command(name = "python-ast", version, about)]
+struct Args {
+    /// A Python file, or a directory to transpile every `.py` file under.
+    input: PathBuf,
+
+    /// Where to write the output: a file for a file input, a directory for a directory input. A file
+    /// input is written to stdout when this is left out.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// What to produce.
+    #[arg(long, value_enum, default_value_t = Emit::Rust)]
+    emit: Emit,
+
+    /// The namespace Python imports are mapped into.
+    #[arg(long)]
+    namespace: Option<String>,
+
+    /// The name of the Python runtime crate.
+    #[arg(long)]
+    stdpython: Option<String>,
+
+    /// Don't import the Python runtime crate.
+    #[arg(long)]
+    no_stdpython: bool,
+
+    /// Allow generating unsafe code.
+    #[arg(long)]
+    allow_unsafe: bool,
+
+    /// The runtime for async code.
+    #[arg(long, value_enum, default_value_t = Runtime::Tokio)]
+    async_runtime: Runtime,
+
+    /// The attribute for the entry point of a custom async runtime, e.g. `my_runtime::main`.
+    #[arg(long, required_if_eq("async_runtime", "custom"))]
+    runtime_attribute: Option<String>,
+
+    /// The crate to import for a custom async runtime.
+    #[arg(long, required_if_eq("async_runtime", "custom"))]
+    runtime_import: Option<String>,
+
+    /// A directory to search for imported modules, ahead of Python's own path. May be repeated.
+    #[arg(long = "python-path", value_name = "DIR")]
+    python_path: Vec<String>,
+
+    /// The Python version the input is written for, e.g. 3.8. Constructs from newer versions are errors.
+    #[arg(long, value_name = "VERSION")]
+    target_version: Option<PythonVersion>,
+
+    /// Run constructs that can't be transpiled in an embedded Python interpreter, through pyo3, instead of
+    /// failing. Each place is listed on stderr.
+    #[arg(long)]
+    python_fallback: bool,
+
+    /// Leave the Python comments out of the generated Rust.
+    #[arg(long)]
+    no_comments: bool,
+
+    /// Sort top-level items by kind and name instead of keeping the source order.
+    #[arg(long)]
+    sort_items: bool,
+
+    /// The widest a line of generated Rust may be. Widths other than 100 need rustfmt.
+    #[arg(long, default_value_t = 100)]
+    line_width: usize,
+
+    /// The package name for --emit project. Defaults to the name of the input.
+    #[arg(long)]
+    project_name: Option<String>,
+
+    /// Reuse parsed trees from this directory, and store new ones there.
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+
+    /// Check the Python for problems instead of transpiling it, such as names that are undefined or read
+    /// before they're assigned, or imports nothing uses. The problems are listed on stdout, and any error fails
+    /// the run. A `# noqa` comment silences those on its line.
+    #[arg(long, conflicts_with_all = ["output", "emit"])]
+    check: bool,
+
+    /// List the nodes that match a structural pattern instead of transpiling, such as
+    /// "Call(func=Attribute(value=Name('os'), attr='system'))", with what each `$name` in it captured.
+    #[arg(long, value_name = "PATTERN", conflicts_with_all = ["output", "emit", "check"])]
+    query: Option<String>,
+
+    /// Run a lint rule with --check, such as unused-parameter, optionally at a given severity, as in
+    /// unused-import=error. May be repeated.
+    #[arg(long, value_name = "RULE[=SEVERITY]", value_parser = rule_setting, requires = "check")]
+    enable: Vec<(Rule, Option<Severity>)>,
+
+    /// Don't run a lint rule with --check. May be repeated.
+    #[arg(long, value_name = "RULE", requires = "check")]
+    disable: Vec<Rule>,
+}
+
+fn rule_setting(setting: &str) -> Result<(Rule, Option<Severity>), String> {
+    match setting.split_once('=') {
+        Some((rule, severity)) => Ok((rule.parse()?, Some(severity.parse()?))),
+        None => Ok((setting.parse()?, None)),
+    }
+}
+
+impl Args {
+    fn python_options(&self) -> PythonOptions {
+        let mut options = PythonOptions::default();
+        if let Some(namespace) = &self.namespace {
+            options.python_namespace = namespace.clone();
+        }
+        if let Some(stdpython) = &self.stdpython {
+            options.stdpython = stdpython.clone();
+        }
+        options.with_std_python = !self.no_stdpython;
+        options.allow_unsafe = self.allow_unsafe;
+        options.async_runtime = match self.async_runtime {
+            Runtime::Tokio => AsyncRuntime::Tokio,
+            Runtime::AsyncStd => AsyncRuntime::AsyncStd,
+            Runtime::Smol => AsyncRuntime::Smol,
+            Runtime::Custom => AsyncRuntime::Custom {
+                attribute: self.runtime_attribute.clone().unwrap_or_default(),
+                import: self.runtime_import.clone().unwrap_or_default(),
+            },
+        };
+        options.python_path.splice(0..0, self.python_path.iter().cloned());
+        options.target_version = self.target_version;
+        options.python_fallback = self.python_fallback.then(FallbackAudit::new);
+        options
+    }
+
+    fn lint_options(&self) -> LintOptions {
+        let options = self.enable.iter().fold(LintOptions::default(), |options, &(rule, severity)| options.enable(rule, severity));
+        self.disable.iter().fold(options, |options, &rule| options.disable(rule))
+    }
+
+    fn source_options(&self, filename: &str) -> SourceOptions {
+        let mut source_options = SourceOptions::default()
+            .with_header(format!("Generated by python-ast from {}.", filename))
+            .with_line_width(self.line_width);
+        if self.emit == Emit::Project {
+            source_options.header = vec!["Generated by python-ast.".to_string()];
+        }
+        if self.sort_items {
+            source_options = source_options.with_sorted_items();
+        }
+        if self.no_comments {
+            source_options = source_options.without_comments();
+        }
+        source_options
+    }
+
+    fn parse_module(&self, source: &str, filename: &str) -> Result<Module, Box<dyn Error>> {
+        match (&self.cache_dir, self.target_version) {
+            (Some(dir), target) => {
+                // The cache holds trees, not whether they suit the target, so check that first.
+                if let Some(target) = target {
+                    let issues: Vec<_> = check_version(source, filename, target)?.iter().map(|i| i.to_string()).collect();
+                    if !issues.is_empty() {
+                        return Err(format!("features newer than Python {}:\n{}", target, issues.join("\n")).into());
+                    }
+                }
+                Ok(ParseCache::new(dir).parse(source, filename)?)
+            }
+            (None, Some(target)) => Ok(parse_for_version(source, filename, target)?),
+            (None, None) => Ok(parse_enhanced(source, filename)?),
+        }
+    }
+
+    /// Transpiles or dumps one file. `filename` is its path relative to the input, which also names the
+    /// module.
+    fn convert(&self, path: &Path, filename: &str) -> Result<String, Box<dyn Error>> {
+        let source = fs::read_to_string(path)?;
+        let module = self.parse_module(&source, filename)?;
+        let options = self.python_options();
+        let converted = match self.emit {
+            Emit::Rust => module.to_rust_source(options.clone(), &self.source_options(filename)),
+            Emit::Tokens => Ok(tokens(module, options.clone())?.to_string()),
+            Emit::Json => Ok(module.to_json()),
+            Emit::Debug => Ok(format!("{:#?}", module)),
+            Emit::Cfg => {
+                let graphs: Vec<_> = control_flow_graphs(&module).iter().map(|(name, cfg)| cfg.to_dot(name)).collect();
+                Ok(graphs.join("\n"))
+            }
+            Emit::Metrics => Ok(metrics(&module).to_table()),
+            Emit::MetricsJson => Ok(metrics(&module).to_json()),
+            Emit::Project => unreachable!("projects are written by run_project"),
+            Emit::Calls | Emit::CallsJson => unreachable!("call graphs are written by run_call_graph"),
+        }?;
+        report_fallbacks(&format!("{}:", filename), &options);
+        Ok(converted)
+    }
+}
+
+/// Lists where the generated code calls into Python, if the fallback was on.
+fn report_fallbacks(prefix: &str, options: &PythonOptions) {
+    for fallback in options.python_fallback.iter().flat_map(FallbackAudit::uses) {
+        eprintln!("warning: {}{}", prefix, fallback);
+    }
+}
+
+fn tokens(module: Module, options: PythonOptions) -> Result<TokenStream, Box<dyn Error>> {
+    let name = module.name.as_ref().map_or_else(|| "main".to_string(), |name| name.id.clone());
+    module.to_rust(CodeGenContext::Module(name), options, SymbolTableScopes::new())
+}
+
+/// Every `.py` file under `dir`, in a stable order.
+fn python_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.path());
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            files.extend(python_files(&path)?);
+        } else if path.extension().is_some_and(|ext| ext == "py") {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+fn run_file(args: &Args) -> bool {
+    let filename = args.input.file_name().map_or_else(|| "main.py".into(), |n| n.to_string_lossy().into_owned());
+    let output = args.convert(&args.input, &filename).and_then(|code| match &args.output {
+        Some(path) => Ok(fs::write(path, code)?),
+        None => match writeln!(std::io::stdout().lock(), "{}", code) {
+            // Piped into something like `head` that stopped reading.
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+            written => Ok(written?),
+        },
+    });
+    match output {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("error: {}: {}", args.input.display(), e);
+            false
+        }
+    }
+}
+
+/// Runs `f`, turning a panic into an error, so one file the crate can't handle doesn't stop the others.
+fn guarded<T>(f: impl FnOnce() -> Result<T, Box<dyn Error>>) -> Result<T, Box<dyn Error>> {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|panic| {
+        let reason = panic.downcast_ref::<String>().map(String::as_str).or_else(|| panic.downcast_ref::<&str>().copied());
+        Err(format!("python-ast failed on this file: {}", reason.and_then(|r| r.lines().next()).unwrap_or("unknown error")).into())
+    })
+}
+
+fn run_directory(args: &Args) -> bool {
+    let Some(output_dir) = &args.output else {
+        eprintln!("error: {} is a directory, so --output must name a directory to write to", args.input.display());
+        return false;
+    };
+    let files = match python_files(&args.input) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("error: {}: {}", args.input.display(), e);
+            return false;
+        }
+    };
+
+    let mut failures = 0;
+    for path in &files {
+        let relative = path.strip_prefix(&args.input).unwrap_or(path);
+        let filename = relative.to_string_lossy().into_owned();
+        let target = output_dir.join(relative).with_extension(args.emit.extension());
+        let written = guarded(|| args.convert(path, &filename)).and_then(|code| {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            Ok(fs::write(&target, code)?)
+        });
+        if let Err(e) = written {
+            eprintln!("error: {}: {}", path.display(), e);
+            failures += 1;
+        }
+    }
+    eprintln!("transpiled {} of {} files", files.len() - failures, files.len());
+    failures == 0
+}
+
+/// Parses every input file, naming each module by its path relative to the input. Files that fail are
+/// reported and left out, and the flag says whether any did.
+fn parse_input(args: &Args) -> (Vec<(PathBuf, Module)>, bool) {
+    let (root, files) = if args.input.is_dir() {
+        match python_files(&args.input) {
+            Ok(files) => (args.input.as_path(), files),
+            Err(e) => {
+                eprintln!("error: {}: {}", args.input.display(), e);
+                return (Vec::new(), true);
+            }
+        }
+    } else {
+        (args.input.parent().unwrap_or(Path::new("")), vec![args.input.clone()])
+    };
+
+    let mut modules = Vec::new();
+    let mut failed = false;
+    for path in &files {
+        let filename = path.strip_prefix(root).unwrap_or(path).to_string_lossy().into_owned();
+        match fs::read_to_string(path).map_err(Into::into).and_then(|source| args.parse_module(&source, &filename)) {
+            Ok(module) => modules.push((path.clone(), module)),
+            Err(e) => {
+                eprintln!("error: {}: {}", path.display(), e);
+                failed = true;
+            }
+        }
+    }
+    (modules, failed)
+}
+
+fn run_project(args: &Args) -> bool {
+    let Some(output_dir) = &args.output else {
+        eprintln!("error: --emit project needs --output to name the project directory");
+        return false;
+    };
+    let (modules, failed) = parse_input(args);
+    if failed {
+        return false;
+    }
+    let modules: Vec<Module> = modules.into_iter().map(|(_, module)| module).collect();
+
+    let name = args.project_name.clone().unwrap_or_else(|| {
+        let stem = args.input.file_stem().map_or_else(|| "python".into(), |s| s.to_string_lossy());
+        stem.replace(|c: char| !c.is_ascii_alphanumeric() && c != '_' && c != '-', "-")
+    });
+    let options = args.python_options();
+    let written = CargoProject::from_modules(name, &modules, options.clone(), &args.source_options(""))
+        .and_then(|project| Ok(project.write(output_dir)?));
+    report_fallbacks("", &options);
+    match written {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("error: {}: {}", output_dir.display(), e);
+            false
+        }
+    }
+}
+
+/// Writes the call graph of the files that parse, which is all of them if the run succeeds.
+fn run_call_graph(args: &Args) -> bool {
+    let (modules, failed) = parse_input(args);
+    let modules: Vec<Module> = modules.into_iter().map(|(_, module)| module).collect();
+    let graph = CallGraph::new(&modules);
+    let code = if args.emit == Emit::CallsJson { graph.to_json() } else { graph.to_dot() };
+    let written = match &args.output {
+        Some(path) => fs::write(path, code),
+        None => match writeln!(std::io::stdout().lock(), "{}", code) {
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+            written => written,
+        },
+    };
+    if let Err(e) = written {
+        eprintln!("error: {}: {}", args.output.as_deref().unwrap_or(Path::new("stdout")).display(), e);
+        return false;
+    }
+    !failed
+}
+
+/// Lists each node the pattern matches as `file:line:column: source`, followed by its captures.
+fn run_query(args: &Args, pattern: &str) -> bool {
+    let query = match Query::parse(pattern) {
+        Ok(query) => query,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return false;
+        }
+    };
+    let (modules, failed) = parse_input(args);
+    let mut stdout = std::io::stdout().lock();
+    let mut matches = 0;
+    for (path, mut module) in modules {
+        module.filename = Some(path.display().to_string());
+        for found in query.find(&module) {
+            matches += 1;
+            // Only the first line of a compound statement, which is enough to find it by.
+            let source = |node: Result<String, _>| node.ok().and_then(|s| s.lines().next().map(str::to_string));
+            let text = source(found.to_python_source()).unwrap_or_else(|| found.node_type().to_string());
+            let location = &found.location;
+            let position = |p: Option<usize>| p.map_or("?".to_string(), |p| p.to_string());
+            let mut lines = vec![format!("{}:{}:{}: {}", location.filename, position(location.line), position(location.column), text)];
+            for (name, value) in found.captures.iter() {
+                let node = QueryMatch { node: value.clone(), ..found.clone() };
+                let captured = source(node.to_python_source()).unwrap_or_else(|| value.to_string());
+                lines.push(format!("    ${} = {}", name, captured));
+            }
+            if writeln!(stdout, "{}", lines.join("\n")).is_err() {
+                return false;
+            }
+        }
+    }
+    eprintln!("{} matches", matches);
+    !failed
+}
+
+/// The problems the analyses find in `module`, in source order, less those `# noqa` comments suppress.
+fn diagnostics(module: &Module, options: &LintOptions) -> Vec<Diagnostic> {
+    let suppressions = Suppressions::from_module(module);
+    let mut diagnostics: Vec<_> = check_names(module)
+        .iter()
+        .map(|issue| issue.to_diagnostic())
+        .filter(|diagnostic| !suppressions.suppresses(diagnostic))
+        .chain(lint(module, options).iter().map(|lint| lint.to_diagnostic()))
+        .collect();
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.location.line, diagnostic.location.column));
+    diagnostics
+}
+
+fn run_check(args: &Args) -> bool {
+    let (root, files) = if args.input.is_dir() {
+        match python_files(&args.input) {
+            Ok(files) => (args.input.as_path(), files),
+            Err(e) => {
+                eprintln!("error: {}: {}", args.input.display(), e);
+                return false;
+            }
+        }
+    } else {
+        (args.input.parent().unwrap_or(Path::new("")), vec![args.input.clone()])
+    };
+
+    let options = args.lint_options();
+    let mut passed = true;
+    let mut problems = 0;
+    let mut stdout = std::io::stdout().lock();
+    for path in &files {
+        // The name relative to the input names the module; problems are reported against the path itself.
+        let filename = path.strip_prefix(root).unwrap_or(path).to_string_lossy().into_owned();
+        let mut module = match fs::read_to_string(path).map_err(Into::into).and_then(|source| args.parse_module(&source, &filename)) {
+            Ok(module) => module,
+            Err(e) => {
+                eprintln!("error: {}: {}", path.display(), e);
+                passed = false;
+                continue;
+            }
+        };
+        module.filename = Some(path.display().to_string());
+        for diagnostic in diagnostics(&module, &options) {
+            passed &= diagnostic.severity < Severity::Error;
+            problems += 1;
+            if writeln!(stdout, "{}", diagnostic).is_err() {
+                return false;
+            }
+        }
+    }
+    eprintln!("{} problems in {} files", problems, files.len());
+    passed
+}
+
+fn main() -> ExitCode {
+    env_logger::init();
+    let args = Args::parse();
+
+    let succeeded = if args.check {
+        run_check(&args)
+    } else if let Some(pattern) = &args.query {
+        run_query(&args, pattern)
+    } else if args.emit == Emit::Project {
+        run_project(&args)
+    } else if matches!(args.emit, Emit::Calls | Emit::CallsJson) {
+        run_call_graph(&args)
+    } else if args.input.is_dir() {
+        run_directory(&args)
+    } else {
+        run_file(&args)
+    };
+    if succeeded { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
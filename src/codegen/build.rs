@@ -0,0 +1,276 @@
+//! Transpiling a tree of Python from a build script.
+//!
+//! Projects that keep their Python as the source of truth can regenerate the Rust on every build:
+//!
+//! ```no_run
+//! // In build.rs's `main`:
+//! python_ast::PythonBuild::new("python").run().unwrap();
+//! ```
+//!
+//! ```ignore
+//! // src/lib.rs
+//! include!(concat!(env!("OUT_DIR"), "/python/mod.rs"));
+//! ```
+//!
+//! Each module becomes a file under `OUT_DIR/python`, and each package a directory with its own `mod.rs`.
+//! The `mod` items use absolute `#[path]`s, since a file pulled in with `include!` can't find its modules
+//! by the usual rules.
+
+use std::{
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use quote::quote;
+
+use crate::{PythonOptions, SourceOptions, format_rust, module_ident, parse_enhanced, parse_for_version};
+
+/// The files a build produced.
+#[derive(Clone, Debug, Default)]
+pub struct BuildOutput {
+    /// The generated `mod.rs` to `include!`.
+    pub mod_rs: PathBuf,
+    /// The Python files that were transpiled.
+    pub sources: Vec<PathBuf>,
+    /// Every file written, or left alone because it was already up to date.
+    pub generated: Vec<PathBuf>,
+}
+
+/// Transpiles every module under a directory of Python into Rust, keeping the package structure.
+#[derive(Clone, Debug)]
+pub struct PythonBuild {
+    source_dir: PathBuf,
+    out_dir: Option<PathBuf>,
+    options: PythonOptions,
+    source_options: SourceOptions,
+}
+
+impl PythonBuild {
+    /// A build of the Python under `source_dir`, which is relative to the package root when run by cargo.
+    pub fn new(source_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            source_dir: source_dir.into(),
+            out_dir: None,
+            options: PythonOptions::default(),
+            source_options: SourceOptions::default().with_header("Generated by python-ast. Do not edit."),
+        }
+    }
+
+    pub fn with_options(mut self, options: PythonOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_source_options(mut self, source_options: SourceOptions) -> Self {
+        self.source_options = source_options;
+        self
+    }
+
+    /// Writes the Rust here instead of `$OUT_DIR/python`.
+    pub fn with_out_dir(mut self, out_dir: impl Into<PathBuf>) -> Self {
+        self.out_dir = Some(out_dir.into());
+        self
+    }
+
+    fn out_dir(&self) -> io::Result<PathBuf> {
+        match &self.out_dir {
+            Some(dir) => Ok(dir.clone()),
+            None => std::env::var_os("OUT_DIR").map(|dir| PathBuf::from(dir).join("python")).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "OUT_DIR is not set; run from a build script or use with_out_dir")
+            }),
+        }
+    }
+
+    /// Transpiles the tree, for use from a build script: prints the `cargo:rerun-if-changed` lines that
+    /// rerun it when the Python changes, and returns the `mod.rs` to `include!`.
+    pub fn run(&self) -> Result<PathBuf, Box<dyn Error>> {
+        let output = self.transpile()?;
+        println!("cargo:rerun-if-changed={}", self.source_dir.display());
+        for source in &output.sources {
+            println!("cargo:rerun-if-changed={}", source.display());
+        }
+        Ok(output.mod_rs)
+    }
+
+    /// Transpiles the tree without printing anything.
+    pub fn transpile(&self) -> Result<BuildOutput, Box<dyn Error>> {
+        let out_dir = std::path::absolute(self.out_dir()?)?;
+        let mut output = BuildOutput { mod_rs: out_dir.join("mod.rs"), ..Default::default() };
+        self.transpile_dir(&self.source_dir, &out_dir, &mut output)?;
+        Ok(output)
+    }
+
+    /// Transpiles the modules in `dir`, and the packages below it, and writes the `mod.rs` that declares
+    /// them. Returns whether there was any Python to transpile.
+    fn transpile_dir(&self, dir: &Path, out_dir: &Path, output: &mut BuildOutput) -> Result<bool, Box<dyn Error>> {
+        let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.path());
+
+        let mut declarations = Vec::new();
+        for entry in entries {
+            let path = entry.path();
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()).map(str::to_string) else {
+                continue;
+            };
+            if name.starts_with('.') {
+                continue;
+            }
+
+            let ident = || module_ident(&name).map_err(|e| format!("{}: {}", path.display(), e));
+            if entry.file_type()?.is_dir() {
+                let package_dir = out_dir.join(&name);
+                if self.transpile_dir(&path, &package_dir, output)? {
+                    declarations.push((name.clone(), ident()?, package_dir.join("mod.rs")));
+                }
+            } else if path.extension().is_some_and(|ext| ext == "py") {
+                let ident = ident()?;
+                let target = out_dir.join(&name).with_extension("rs");
+                self.transpile_file(&path, &target, output)?;
+                declarations.push((name, ident, target));
+            }
+        }
+        if declarations.is_empty() {
+            return Ok(false);
+        }
+
+        // A package's `__init__.py` supplies the package's own items.
+        let items = declarations.iter().map(|(name, ident, target)| {
+            let target = target.to_string_lossy();
+            if name == "__init__" {
+                quote! {
+                    #[path = #target]
+                    mod #ident;
+                    pub use #ident::*;
+                }
+            } else {
+                quote! {
+                    #[path = #target]
+                    pub mod #ident;
+                }
+            }
+        });
+        let mod_rs = format_rust(quote!(#(#items)*), &self.source_options)?;
+        let target = out_dir.join("mod.rs");
+        write_if_changed(&target, &mod_rs)?;
+        output.generated.push(target);
+        Ok(true)
+    }
+
+    fn transpile_file(&self, path: &Path, target: &Path, output: &mut BuildOutput) -> Result<(), Box<dyn Error>> {
+        let filename = path.strip_prefix(&self.source_dir).unwrap_or(path).to_string_lossy().into_owned();
+        let code = fs::read_to_string(path)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|source| match self.options.target_version {
+                Some(target) => Ok(parse_for_version(source, &filename, target)?),
+                None => Ok(parse_enhanced(source, &filename)?),
+            })
+            .and_then(|module| module.to_rust_source(self.options.clone(), &self.source_options))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        write_if_changed(target, &code)?;
+        output.sources.push(path.to_path_buf());
+        output.generated.push(target.to_path_buf());
+        Ok(())
+    }
+}
+
+/// Writes `contents` to `path` unless it already holds them, so an unchanged file keeps its timestamp and
+/// doesn't cause needless recompiles.
+fn write_if_changed(path: &Path, contents: &str) -> io::Result<()> {
+    if fs::read_to_string(path).is_ok_and(|existing| existing == contents) {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("python-ast-build-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn write(path: PathBuf, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_transpiles_packages() {
+        let dir = scratch("packages");
+        let src = dir.join("python");
+        write(src.join("util.py"), "\"\"\"This module has helpers.\"\"\"\ndef helper():\n    pass\n");
+        write(src.join("shapes").join("__init__.py"), "def area(r):\n    return r\n");
+        write(src.join("shapes").join("circle.py"), "def draw():\n    pass\n");
+        write(src.join("empty").join("notes.txt"), "no python here");
+        let out = dir.join("out");
+
+        let output = PythonBuild::new(&src).with_out_dir(&out).transpile().unwrap();
+        assert_eq!(output.mod_rs, out.join("mod.rs"));
+        assert_eq!(output.sources.len(), 3);
+
+        let root = fs::read_to_string(out.join("mod.rs")).unwrap();
+        assert!(root.contains(&format!("#[path = {:?}]\npub mod shapes;", out.join("shapes").join("mod.rs"))), "{}", root);
+        assert!(root.contains("pub mod util;"));
+        assert!(!root.contains("empty"));
+
+        let shapes = fs::read_to_string(out.join("shapes").join("mod.rs")).unwrap();
+        assert!(shapes.contains("mod __init__;\npub use __init__::*;"), "{}", shapes);
+        assert!(shapes.contains("pub mod circle;"));
+        assert!(fs::read_to_string(out.join("util.rs")).unwrap().contains("//!This module has helpers."));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_leaves_unchanged_files_alone() {
+        let dir = scratch("unchanged");
+        write(dir.join("python").join("a.py"), "def f():\n    pass\n");
+        let build = PythonBuild::new(dir.join("python")).with_out_dir(dir.join("out"));
+        build.transpile().unwrap();
+        let modified = fs::metadata(dir.join("out").join("a.rs")).unwrap().modified().unwrap();
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        build.transpile().unwrap();
+        assert_eq!(fs::metadata(dir.join("out").join("a.rs")).unwrap().modified().unwrap(), modified);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_module_names() {
+        let dir = scratch("names");
+        write(dir.join("python").join("type.py"), "def f():\n    pass\n");
+        PythonBuild::new(dir.join("python")).with_out_dir(dir.join("out")).transpile().unwrap();
+        let root = fs::read_to_string(dir.join("out").join("mod.rs")).unwrap();
+        assert!(root.contains(&format!("#[path = {:?}]\npub mod r#type;", dir.join("out").join("type.rs"))), "{}", root);
+
+        write(dir.join("python").join("data-files").join("load.py"), "def load():\n    pass\n");
+        let error = PythonBuild::new(dir.join("python")).with_out_dir(dir.join("out")).transpile().unwrap_err();
+        assert!(error.to_string().contains("data-files"), "{}", error);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_reports_the_failing_file() {
+        let dir = scratch("failing");
+        write(dir.join("python").join("broken.py"), "x = (\n");
+        let error = PythonBuild::new(dir.join("python")).with_out_dir(dir.join("out")).transpile().unwrap_err();
+        assert!(error.to_string().contains("broken.py"), "{}", error);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_unformattable_code_is_an_error() {
+        let dir = scratch("unformattable");
+        write(dir.join("python").join("point.py"), "class Point:\n    def norm(self):\n        return 1\n");
+        let error = PythonBuild::new(dir.join("python")).with_out_dir(dir.join("out")).transpile().unwrap_err();
+        let expected = format!("{}: generated code is not valid Rust", dir.join("python").join("point.py").display());
+        assert!(error.to_string().starts_with(&expected), "{}", error);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+//! Which parts of a module can't be transpiled.
+//!
+//! Generating a module stops at the first statement that fails, and a statement fails if anything inside it
+//! does, so the error for a function names the function rather than the line in it that is the problem.
+//! [`untranspilable`] transpiles the statements one at a time instead, and when a compound statement fails,
+//! looks inside it for the statements that fail on their own, so each problem is reported where it is.
+
+use crate::{
+    CodeGen, CodeGenContext, Diagnostic, Error, Module, PythonOptions, Severity, Statement, StatementType,
+    SymbolTableScopes, locate, nested_bodies,
+};
+
+/// Every statement in the module that fails to transpile and has no statement inside it that fails, as an
+/// `untranspilable` error. The fallback to Python in `options` is ignored, since it would hide them.
+pub fn untranspilable(module: &Module, options: &PythonOptions) -> Vec<Diagnostic> {
+    let filename = module.filename.as_deref().unwrap_or("<unknown>");
+    let name = module.name.as_ref().map_or_else(|| "main".to_string(), |name| name.id.clone());
+    let options = PythonOptions { python_fallback: None, ..options.clone() };
+    let symbols = module.clone().find_symbols(SymbolTableScopes::new());
+
+    let mut diagnostics = Vec::new();
+    let checker = Checker { filename, options: &options, symbols: &symbols };
+    checker.body(&module.raw.body, &CodeGenContext::Module(name), &mut diagnostics);
+    diagnostics
+}
+
+struct Checker<'a> {
+    filename: &'a str,
+    options: &'a PythonOptions,
+    symbols: &'a SymbolTableScopes,
+}
+
+impl Checker<'_> {
+    fn body(&self, body: &[Statement], ctx: &CodeGenContext, diagnostics: &mut Vec<Diagnostic>) {
+        for statement in body {
+            let generated = statement.statement.clone().to_rust(ctx.clone(), self.options.clone(), self.symbols.clone());
+            let Err(error) = generated else {
+                continue;
+            };
+
+            let found = diagnostics.len();
+            let inner = match &statement.statement {
+                StatementType::FunctionDef(f) => Some((&f.body, CodeGenContext::Function)),
+                StatementType::AsyncFunctionDef(f) => {
+                    Some((&f.body, CodeGenContext::Async(Box::new(CodeGenContext::Function))))
+                }
+                StatementType::ClassDef(c) => Some((&c.body, CodeGenContext::Class)),
+                _ => None,
+            };
+            match inner {
+                Some((body, ctx)) => self.body(body, &ctx, diagnostics),
+                None => nested_bodies(statement).into_iter().for_each(|body| self.body(body, ctx, diagnostics)),
+            }
+            if diagnostics.len() == found {
+                diagnostics.push(Diagnostic {
+                    code: "untranspilable",
+                    severity: Severity::Error,
+                    message: message(statement, error.as_ref()),
+                    location: locate(self.filename, statement),
+                });
+            }
+        }
+    }
+}
+
+/// Names the statement by its first line, with the reason it failed unless that's only that its kind isn't
+/// supported, which the errors for those say by dumping the tree.
+fn message(statement: &Statement, error: &(dyn std::error::Error + 'static)) -> String {
+    let source = match &statement.statement {
+        StatementType::Unimplemented(source) => source.clone(),
+        _ => statement.to_python_source().unwrap_or_default(),
+    };
+    let first_line = source.lines().next().unwrap_or_default();
+    let unsupported = matches!(
+        error.downcast_ref::<Error>(),
+        Some(
+            Error::BinOpNotYetImplemented(_)
+                | Error::BoolOpNotYetImplemented(_)
+                | Error::CompareNotYetImplemented(_)
+                | Error::ExprNotYetImplemented(_)
+                | Error::ExprTypeNotYetImplemented(_)
+                | Error::StatementNotYetImplemented(_)
+                | Error::UnaryOpNotYetImplemented(_)
+        )
+    ) || matches!(statement.statement, StatementType::Unimplemented(_));
+    match error.to_string().lines().next() {
+        Some(reason) if !unsupported => format!("`{}` can't be transpiled: {}", first_line, reason),
+        _ => format!("`{}` can't be transpiled", first_line),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn test_reports_innermost_statements() {
+        let source = "\
+x = 1
+def f(items):
+    del items[0]
+    return items
+class A:
+    y: int = 3
+";
+        let module = parse(source, "test.py").unwrap();
+        let diagnostics = untranspilable(&module, &PythonOptions::default());
+        let found: Vec<_> = diagnostics.iter().map(|d| (d.location.line, d.message.as_str())).collect();
+        assert_eq!(
+            found,
+            [(Some(3), "`del items[0]` can't be transpiled"), (Some(6), "`y: int = 3` can't be transpiled")]
+        );
+        assert!(diagnostics.iter().all(|d| d.code == "untranspilable" && d.severity == Severity::Error));
+    }
+
+    #[test]
+    fn test_transpilable_module_has_no_diagnostics() {
+        let module = parse("def add(a, b):\n    return a + b\n", "test.py").unwrap();
+        assert!(untranspilable(&module, &PythonOptions::default()).is_empty());
+    }
+}
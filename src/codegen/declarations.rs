@@ -0,0 +1,396 @@
+//! Where the variables of a function body are declared in the generated Rust.
+//!
+//! Python has one scope per function, but a Rust `let` only lasts until the end of its block, so a
+//! variable first assigned inside an `if` or a loop has to be declared ahead of it, and one that is
+//! assigned again needs `mut`. [`Declarations`] works that out for a body before it is generated:
+//!
+//! * a variable whose first assignment is a statement of the body itself is declared by that
+//!   statement, as `let x = ...`, since everything after it is inside its scope;
+//! * any other variable is hoisted to the top of the body, as `let x: T;`, with its type when every
+//!   value assigned to it is a literal of the same type, and every assignment becomes a plain `x = ...`;
+//! * a variable is `mut` only when some path through the body can assign it twice, which is always
+//!   the case inside a loop;
+//! * a parameter the body assigns to is rebound as `let mut x = x;`;
+//! * a name bound by a walrus is always hoisted, since there's no `let` inside an expression, and one
+//!   bound inside a comprehension starts out as `Default::default()`, as its loop may never run.
+
+use std::collections::HashSet;
+
+use litrs::Literal;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+use crate::{ExprType, Ops, Statement, StatementType, SymbolTableNode, SymbolTableScopes, statement_walrus_targets};
+
+/// The type of a variable whose every assigned value is a literal of that type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InferredType {
+    Int,
+    Float,
+    Bool,
+    Str,
+}
+
+impl InferredType {
+    /// The type of a literal expression, e.g. `Int` for `-1`.
+    pub fn of(expr: &ExprType) -> Option<Self> {
+        match expr {
+            ExprType::Constant(c) => match c.0.as_ref()? {
+                Literal::Integer(_) => Some(InferredType::Int),
+                Literal::Float(_) => Some(InferredType::Float),
+                Literal::Bool(_) => Some(InferredType::Bool),
+                Literal::String(_) => Some(InferredType::Str),
+                _ => None,
+            },
+            ExprType::UnaryOp(u) if matches!(u.op, Ops::USub | Ops::UAdd) => {
+                Self::of(&u.operand).filter(|t| matches!(t, InferredType::Int | InferredType::Float))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn to_rust(self) -> TokenStream {
+        match self {
+            InferredType::Int => quote!(i64),
+            InferredType::Float => quote!(f64),
+            InferredType::Bool => quote!(bool),
+            InferredType::Str => quote!(&str),
+        }
+    }
+}
+
+/// A variable of the body being generated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalVariable {
+    pub name: String,
+    /// Some path through the body assigns it more than once.
+    pub mutable: bool,
+    /// Declared at the top of the body rather than by its first assignment.
+    pub hoisted: bool,
+    /// The type of a hoisted variable, when it can be told from what is assigned to it.
+    pub inferred_type: Option<InferredType>,
+    /// Hoisted with an initial `Default::default()`, for a walrus inside a comprehension.
+    pub defaulted: bool,
+}
+
+/// The declarations a function body, or the code of a module, needs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Declarations {
+    /// The body's variables, in the order they are first assigned.
+    pub locals: Vec<LocalVariable>,
+    /// The parameters the body assigns to.
+    pub reassigned_parameters: Vec<String>,
+}
+
+impl Declarations {
+    /// Analyzes a body whose parameters, if it's a function's, are `parameters`.
+    pub fn new(body: &[Statement], parameters: &[String]) -> Self {
+        let mut analysis = Analysis { parameters, ..Default::default() };
+        analysis.excluded = body.iter().flat_map(declared_elsewhere).collect();
+        analysis.block(body, &mut HashSet::new());
+        let mut declarations = analysis.declarations;
+        for local in declarations.locals.iter_mut() {
+            if analysis.untyped.contains(&local.name) || !local.hoisted {
+                local.inferred_type = None;
+            }
+        }
+        declarations
+    }
+
+    /// Records the variables in the current scope, so that each assignment knows whether it declares
+    /// its target.
+    pub fn register(&self, symbols: SymbolTableScopes) -> SymbolTableScopes {
+        let mut symbols = symbols;
+        for local in self.locals.iter() {
+            symbols.insert(local.name.clone(), SymbolTableNode::Local(local.clone()));
+        }
+        symbols
+    }
+
+    /// The declarations that go at the top of the body.
+    pub fn to_rust(&self) -> TokenStream {
+        let parameters = self.reassigned_parameters.iter().map(|name| {
+            let name = format_ident!("{}", name);
+            quote!(let mut #name = #name;)
+        });
+        let hoisted = self.locals.iter().filter(|local| local.hoisted).map(|local| {
+            let name = format_ident!("{}", local.name);
+            let binding = if local.mutable { quote!(mut #name) } else { quote!(#name) };
+            if local.defaulted {
+                return quote!(let #binding = Default::default(););
+            }
+            match local.inferred_type {
+                Some(t) => {
+                    let t = t.to_rust();
+                    quote!(let #binding: #t;)
+                }
+                None => quote!(let #binding;),
+            }
+        });
+        quote!(#(#parameters)* #(#hoisted)*)
+    }
+}
+
+/// How an assignment to `name` binds it, given the declarations registered in `symbols`.
+pub fn declares(symbols: &SymbolTableScopes, name: &str) -> Option<bool> {
+    match symbols.get_local(name) {
+        Some(SymbolTableNode::Local(local)) if !local.hoisted => Some(local.mutable),
+        Some(SymbolTableNode::Local(_)) | Some(SymbolTableNode::Assign { .. }) => None,
+        // Code generated without a declaration analysis, such as a class body, declares as it goes.
+        _ => Some(false),
+    }
+}
+
+/// The names a body declares `global` or `nonlocal`, which aren't its own variables.
+fn declared_elsewhere(statement: &Statement) -> Vec<String> {
+    let StatementType::Unimplemented(source) = &statement.statement else {
+        return Vec::new();
+    };
+    let names = source.strip_prefix("global ").or_else(|| source.strip_prefix("nonlocal "));
+    names.map_or_else(Vec::new, |names| names.split(',').map(|name| name.trim().to_string()).collect())
+}
+
+#[derive(Default)]
+struct Analysis<'a> {
+    parameters: &'a [String],
+    excluded: HashSet<String>,
+    declarations: Declarations,
+    /// Variables assigned something other than a literal, or literals of different types.
+    untyped: HashSet<String>,
+    /// How many blocks deep the statement being analyzed is.
+    depth: usize,
+    /// How many loops the statement being analyzed is in.
+    loops: usize,
+}
+
+impl Analysis<'_> {
+    /// Analyzes a block, given the variables that may have been assigned before it, and adds those it
+    /// may assign.
+    fn block(&mut self, body: &[Statement], assigned: &mut HashSet<String>) {
+        for statement in body {
+            self.statement(statement, assigned);
+        }
+    }
+
+    fn nested(&mut self, body: &[Statement], assigned: &mut HashSet<String>) {
+        self.depth += 1;
+        self.block(body, assigned);
+        self.depth -= 1;
+    }
+
+    fn looped(&mut self, body: &[Statement], orelse: &[Statement], assigned: &mut HashSet<String>) {
+        self.loops += 1;
+        self.nested(body, assigned);
+        self.loops -= 1;
+        self.nested(orelse, assigned);
+    }
+
+    fn statement(&mut self, statement: &Statement, assigned: &mut HashSet<String>) {
+        // A `while` test, and a comprehension, may bind their walrus targets over and over.
+        let repeats = matches!(statement.statement, StatementType::While(_));
+        for target in statement_walrus_targets(statement) {
+            let looped = usize::from(repeats || target.in_comprehension);
+            self.loops += looped;
+            self.assign(&target.name, None, false, assigned);
+            self.loops -= looped;
+            if target.in_comprehension
+                && let Some(local) = self.declarations.locals.iter_mut().find(|local| local.name == target.name)
+                && local.hoisted
+            {
+                local.defaulted = true;
+            }
+        }
+
+        match &statement.statement {
+            StatementType::Assign(a) => {
+                for target in a.targets.iter() {
+                    // Only a pattern of names can be declared by the assignment itself.
+                    let declares = self.depth == 0 && is_pattern(target);
+                    match target {
+                        ExprType::Name(name) => self.assign(&name.id, InferredType::of(&a.value), declares, assigned),
+                        _ => self.assign_pattern(target, declares, assigned),
+                    }
+                }
+            }
+            StatementType::AugAssign(a) => {
+                if let ExprType::Name(name) = &a.target {
+                    self.reassign(&name.id, assigned);
+                }
+            }
+            StatementType::If(i) => {
+                let mut orelse = assigned.clone();
+                self.nested(&i.body, assigned);
+                self.nested(&i.orelse, &mut orelse);
+                assigned.extend(orelse);
+            }
+            StatementType::For(f) => self.looped(&f.body, &f.orelse, assigned),
+            StatementType::AsyncFor(f) => self.looped(&f.body, &f.orelse, assigned),
+            StatementType::While(w) => self.looped(&w.body, &w.orelse, assigned),
+            StatementType::With(w) => self.nested(&w.body, assigned),
+            StatementType::AsyncWith(w) => self.nested(&w.body, assigned),
+            StatementType::Try(t) => {
+                // A handler can start anywhere in the body, after any of its assignments.
+                self.nested(&t.body, assigned);
+                let raised = assigned.clone();
+                self.nested(&t.orelse, assigned);
+                for handler in t.handlers.iter() {
+                    let mut handled = raised.clone();
+                    self.nested(&handler.body, &mut handled);
+                    assigned.extend(handled);
+                }
+                self.nested(&t.finalbody, assigned);
+            }
+            _ => (),
+        }
+    }
+
+    fn assign_pattern(&mut self, target: &ExprType, declares: bool, assigned: &mut HashSet<String>) {
+        match target {
+            ExprType::Name(name) => {
+                self.untyped.insert(name.id.clone());
+                self.assign(&name.id, None, declares, assigned);
+            }
+            ExprType::Tuple(t) => t.elts.iter().for_each(|e| self.assign_pattern(e, declares, assigned)),
+            ExprType::List(elts) => elts.iter().for_each(|e| self.assign_pattern(e, declares, assigned)),
+            ExprType::Starred(s) => self.assign_pattern(&s.value, declares, assigned),
+            _ => (),
+        }
+    }
+
+    fn assign(&mut self, name: &str, inferred_type: Option<InferredType>, declares: bool, assigned: &mut HashSet<String>) {
+        if self.excluded.contains(name) {
+            return;
+        }
+        if self.parameters.iter().any(|p| p == name) {
+            return self.reassign(name, assigned);
+        }
+        let again = !assigned.insert(name.to_string()) || self.loops > 0;
+        match self.declarations.locals.iter_mut().find(|local| local.name == name) {
+            Some(local) => {
+                local.mutable |= again;
+                if local.inferred_type != inferred_type {
+                    self.untyped.insert(name.to_string());
+                }
+            }
+            None => {
+                if inferred_type.is_none() {
+                    self.untyped.insert(name.to_string());
+                }
+                self.declarations.locals.push(LocalVariable {
+                    name: name.to_string(),
+                    mutable: again,
+                    hoisted: !declares,
+                    inferred_type,
+                    defaulted: false,
+                });
+            }
+        }
+    }
+
+    /// An assignment that also reads the variable, like `x += 1`, or one to a parameter.
+    fn reassign(&mut self, name: &str, assigned: &mut HashSet<String>) {
+        if self.excluded.contains(name) {
+            return;
+        }
+        if self.parameters.iter().any(|p| p == name) {
+            if !self.declarations.reassigned_parameters.iter().any(|p| p == name) {
+                self.declarations.reassigned_parameters.push(name.to_string());
+            }
+            return;
+        }
+        self.assign(name, None, false, assigned);
+        if let Some(local) = self.declarations.locals.iter_mut().find(|local| local.name == name) {
+            local.mutable = true;
+        }
+    }
+}
+
+/// Whether an assignment target is a name, or a tuple or list of them.
+fn is_pattern(target: &ExprType) -> bool {
+    match target {
+        ExprType::Name(_) => true,
+        ExprType::Tuple(t) => t.elts.iter().all(is_pattern),
+        ExprType::List(elts) => elts.iter().all(is_pattern),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyze(code: &str) -> Declarations {
+        let module = crate::parse(code, "test.py").unwrap();
+        match &module.raw.body[0].statement {
+            StatementType::FunctionDef(f) => {
+                let parameters: Vec<String> = f.args.args.iter().map(|p| p.arg.clone()).collect();
+                Declarations::new(&f.body, &parameters)
+            }
+            _ => Declarations::new(&module.raw.body, &[]),
+        }
+    }
+
+    fn local<'a>(declarations: &'a Declarations, name: &str) -> &'a LocalVariable {
+        declarations.locals.iter().find(|local| local.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_single_assignment_is_declared_in_place() {
+        let declarations = analyze("def f():\n    x = 1\n    return x\n");
+        let x = local(&declarations, "x");
+        assert!(!x.mutable && !x.hoisted);
+        assert!(declarations.to_rust().is_empty());
+    }
+
+    #[test]
+    fn test_reassignment_in_loop_is_mutable() {
+        let declarations = analyze("def f(n):\n    total = 0\n    for i in range(n):\n        total = total + i\n    return total\n");
+        let total = local(&declarations, "total");
+        assert!(total.mutable && !total.hoisted);
+
+        let declarations = analyze("def f():\n    while True:\n        line = input()\n");
+        let line = local(&declarations, "line");
+        assert!(line.mutable && line.hoisted);
+    }
+
+    #[test]
+    fn test_branches_hoist_one_declaration() {
+        let declarations = analyze("def f(c):\n    if c:\n        x = 1\n    else:\n        x = -2\n    return x\n");
+        let x = local(&declarations, "x");
+        // Each path assigns it once.
+        assert!(!x.mutable && x.hoisted);
+        assert_eq!(x.inferred_type, Some(InferredType::Int));
+        assert_eq!(declarations.to_rust().to_string(), "let x : i64 ;");
+
+        let declarations = analyze("def f(c):\n    if c:\n        x = 1\n    x = 'one'\n");
+        let x = local(&declarations, "x");
+        assert!(x.mutable && x.hoisted);
+        assert_eq!(x.inferred_type, None);
+    }
+
+    #[test]
+    fn test_try_handlers_may_follow_the_body() {
+        let declarations = analyze("def f():\n    try:\n        x = g()\n    except E:\n        x = None\n");
+        assert!(local(&declarations, "x").mutable);
+    }
+
+    #[test]
+    fn test_walrus_targets_are_hoisted() {
+        let declarations = analyze("def f(g):\n    while (line := g()):\n        line = line + 1\n");
+        let line = local(&declarations, "line");
+        assert!(line.mutable && line.hoisted);
+        assert_eq!(declarations.to_rust().to_string(), "let mut line ;");
+
+        let declarations = analyze("def f(xs):\n    ys = [y for x in xs if (y := x)]\n");
+        assert!(local(&declarations, "y").defaulted);
+        assert_eq!(declarations.to_rust().to_string(), "let mut y = Default :: default () ;");
+    }
+
+    #[test]
+    fn test_parameters_and_globals() {
+        let declarations = analyze("def f(n):\n    global seen\n    seen = n\n    n += 1\n");
+        assert_eq!(declarations.reassigned_parameters, vec!["n".to_string()]);
+        assert!(declarations.locals.is_empty());
+        assert_eq!(declarations.to_rust().to_string(), "let mut n = n ;");
+    }
+}
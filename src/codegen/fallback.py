@@ -0,0 +1,153 @@
+import ast
+
+# Works out which variables cross the boundary when a snippet runs in the embedded interpreter: the
+# names it reads and the names it binds, in its own scope. Nested functions, lambdas, classes and
+# comprehensions have scopes of their own, so only the parts of them evaluated in place are visited.
+
+# Constructs that would leave the surrounding Rust code, which a snippet run on its own can't do.
+_ESCAPES = {
+    ast.Return: "returns",
+    ast.Yield: "yields",
+    ast.YieldFrom: "yields",
+    ast.Await: "awaits",
+}
+
+class _Names(ast.NodeVisitor):
+    def __init__(self, strict=True):
+        self.loaded, self.stored, self.deleted = [], [], []
+        # Whether to refuse code that couldn't run on its own, rather than only listing its names.
+        self.strict = strict
+        # How many of the snippet's own loops enclose the node being visited.
+        self.loops = 0
+
+    def _add(self, names, name):
+        if name not in names:
+            names.append(name)
+
+    def _visit_all(self, nodes):
+        for node in nodes:
+            if node is not None:
+                self.visit(node)
+
+    def _refuse(self, action):
+        if self.strict:
+            raise ValueError(f"the code {action}, which it can't do from the embedded interpreter")
+
+    def generic_visit(self, node):
+        for kind, action in _ESCAPES.items():
+            if isinstance(node, kind):
+                self._refuse(action)
+        super().generic_visit(node)
+
+    def visit_Break(self, node):
+        if not self.loops:
+            self._refuse("breaks out of a loop")
+
+    def visit_Continue(self, node):
+        if not self.loops:
+            self._refuse("continues a loop")
+
+    def _visit_loop(self, node, header):
+        self._visit_all(header)
+        self.loops += 1
+        self._visit_all(node.body)
+        self.loops -= 1
+        self._visit_all(node.orelse)
+
+    def visit_For(self, node):
+        self._visit_loop(node, [node.target, node.iter])
+
+    visit_AsyncFor = visit_For
+
+    def visit_While(self, node):
+        self._visit_loop(node, [node.test])
+
+    def visit_Name(self, node):
+        if isinstance(node.ctx, ast.Load):
+            self._add(self.loaded, node.id)
+        elif isinstance(node.ctx, ast.Store):
+            self._add(self.stored, node.id)
+        else:
+            self._add(self.deleted, node.id)
+
+    def visit_AugAssign(self, node):
+        if isinstance(node.target, ast.Name):
+            self._add(self.loaded, node.target.id)
+        self.generic_visit(node)
+
+    def _visit_arguments(self, args):
+        self._visit_all(args.defaults + args.kw_defaults)
+
+    def visit_FunctionDef(self, node):
+        self._visit_all(node.decorator_list)
+        self._visit_arguments(node.args)
+        self._add(self.stored, node.name)
+
+    visit_AsyncFunctionDef = visit_FunctionDef
+
+    def visit_ClassDef(self, node):
+        self._visit_all(node.decorator_list + node.bases + [k.value for k in node.keywords])
+        self._add(self.stored, node.name)
+
+    def visit_Lambda(self, node):
+        self._visit_arguments(node.args)
+
+    def _visit_comprehension(self, node, parts):
+        # The first iterable is evaluated in place; the rest reads the comprehension's own variables.
+        inner = _Names(self.strict)
+        inner._visit_all(parts + [g.iter for g in node.generators[1:]] +
+                         [c for g in node.generators for c in g.ifs])
+        targets = _Names(self.strict)
+        targets._visit_all([g.target for g in node.generators])
+        self.visit(node.generators[0].iter)
+        for name in inner.loaded:
+            if name not in targets.stored:
+                self._add(self.loaded, name)
+
+    def visit_ListComp(self, node):
+        self._visit_comprehension(node, [node.elt])
+
+    visit_SetComp = visit_GeneratorExp = visit_ListComp
+
+    def visit_DictComp(self, node):
+        self._visit_comprehension(node, [node.key, node.value])
+
+    def visit_Import(self, node):
+        for alias in node.names:
+            self._add(self.stored, alias.asname or alias.name.split(".")[0])
+
+    def visit_ImportFrom(self, node):
+        for alias in node.names:
+            if alias.name != "*":
+                self._add(self.stored, alias.asname or alias.name)
+
+    def visit_ExceptHandler(self, node):
+        if node.name:
+            self._add(self.stored, node.name)
+        self.generic_visit(node)
+
+    def visit_MatchAs(self, node):
+        if node.name:
+            self._add(self.stored, node.name)
+        self.generic_visit(node)
+
+    def visit_MatchStar(self, node):
+        if node.name:
+            self._add(self.stored, node.name)
+
+    def visit_MatchMapping(self, node):
+        if node.rest:
+            self._add(self.stored, node.rest)
+        self.generic_visit(node)
+
+def boundary_names(source, mode, strict=True):
+    names = _Names(strict)
+    names.visit(ast.parse(source, mode=mode))
+    stored = [name for name in names.stored if name not in names.deleted]
+    return names.loaded, stored
+
+
+def deleted_names(source):
+    names = _Names(False)
+    names.visit(ast.parse(source, mode="exec"))
+    return names.deleted
@@ -0,0 +1,320 @@
+//! Running what the transpiler can't lower in an embedded Python interpreter.
+//!
+//! With [`PythonOptions::python_fallback`] set, a statement that fails to transpile, or an expression the
+//! tree doesn't model, is emitted as a call into Python through pyo3 instead of failing the whole module.
+//! The local variables it reads are converted into Python objects on the way in, and the ones it assigns are
+//! converted back on the way out, with pyo3's `IntoPyObject` and `FromPyObject`. A variable the Rust code
+//! already has keeps its type; a new one is a `pyo3::Py<pyo3::PyAny>`. Each use is recorded in the
+//! [`FallbackAudit`], so it's clear which parts of a program still run in Python.
+//!
+//! Code that returns, yields, awaits or leaves a loop can't run on its own, so it is still an error.
+
+use std::{
+    error::Error,
+    ffi::CString,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use proc_macro2::TokenStream;
+use pyo3::{prelude::*, sync::GILOnceCell};
+use quote::{format_ident, quote};
+
+use crate::{Statement, StatementType, SymbolTableNode, SymbolTableScopes};
+
+/// Whether the fallback stood in for a statement or an expression.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FallbackKind {
+    Statement,
+    Expression,
+}
+
+impl fmt::Display for FallbackKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FallbackKind::Statement => write!(f, "statement"),
+            FallbackKind::Expression => write!(f, "expression"),
+        }
+    }
+}
+
+/// One place the generated code calls into Python.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FallbackUse {
+    pub kind: FallbackKind,
+    /// The line of the statement, or of the statement holding the expression.
+    pub line: Option<usize>,
+    /// The column of the statement. Columns count from 1.
+    pub column: Option<usize>,
+    /// The Python that runs.
+    pub source: String,
+    /// Why it couldn't be transpiled.
+    pub reason: String,
+    /// The variables passed into Python.
+    pub inputs: Vec<String>,
+    /// The variables read back from Python.
+    pub outputs: Vec<String>,
+}
+
+impl fmt::Display for FallbackUse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let position = |n: Option<usize>| n.map_or("?".to_string(), |n| n.to_string());
+        let first_line = self.source.lines().next().unwrap_or_default();
+        write!(f, "{}:{}: {} runs in Python: {}", position(self.line), position(self.column), self.kind, first_line)
+    }
+}
+
+/// Turns the fallback on, and collects where it was used. Clones share the list, so the handle the caller
+/// keeps sees what codegen recorded through the copy in [`PythonOptions`](crate::PythonOptions).
+#[derive(Clone, Debug, Default)]
+pub struct FallbackAudit(Arc<Mutex<Vec<FallbackUse>>>);
+
+impl FallbackAudit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The uses so far, in the order they were generated.
+    pub fn uses(&self) -> Vec<FallbackUse> {
+        self.lock().clone()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub(crate) fn truncate(&self, len: usize) {
+        self.lock().truncate(len);
+    }
+
+    fn record(&self, fallback: FallbackUse) {
+        self.lock().push(fallback);
+    }
+
+    /// Gives the uses recorded since `start` that have no position yet, the expressions, the position of
+    /// the statement that holds them.
+    pub(crate) fn locate(&self, start: usize, line: Option<usize>, col_offset: Option<usize>) {
+        for fallback in self.lock().iter_mut().skip(start).filter(|f| f.line.is_none()) {
+            fallback.line = line;
+            fallback.column = col_offset.map(|c| c + 1);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<FallbackUse>> {
+        // A panic elsewhere while holding the lock leaves the list itself intact.
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// The Python helper, so it is only built once per interpreter.
+static FALLBACK_MODULE: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+
+fn helper(py: Python<'_>) -> PyResult<&Py<PyModule>> {
+    FALLBACK_MODULE.get_or_try_init(py, || -> PyResult<_> {
+        let code = CString::new(include_str!("fallback.py"))?;
+        Ok(PyModule::from_code(py, &code, c"fallback.py", c"fallback")?.unbind())
+    })
+}
+
+/// The names `source` reads, and the names it binds, in its own scope. When `strict`, fails for code that
+/// can't run on its own.
+pub(crate) fn boundary_names(source: &str, mode: &str, strict: bool) -> PyResult<(Vec<String>, Vec<String>)> {
+    Python::with_gil(|py| {
+        helper(py)?.bind(py).getattr("boundary_names")?.call1((source, mode, strict))?.extract()
+    })
+}
+
+/// The variables `source` deletes with `del`, which are unbound afterwards.
+pub(crate) fn deleted_names(source: &str) -> PyResult<Vec<String>> {
+    Python::with_gil(|py| {
+        helper(py)?.bind(py).getattr("deleted_names")?.call1((source,))?.extract()
+    })
+}
+
+/// The variables an unsupported statement binds, which the generated code has afterwards if it runs in
+/// Python.
+pub(crate) fn bound_names(source: &str) -> Vec<String> {
+    boundary_names(source, "exec", false).map(|(_, stored)| stored).unwrap_or_default()
+}
+
+/// Whether `name` is a variable the generated Rust has in scope.
+fn is_local(symbols: &SymbolTableScopes, name: &str) -> bool {
+    matches!(symbols.get(name), Some(SymbolTableNode::Assign { .. }))
+}
+
+/// The code that puts the variables `names` into the `locals` passed to Python.
+fn pass_in(names: &[String]) -> TokenStream {
+    let idents = names.iter().map(|name| format_ident!("{}", name));
+    quote!(#(locals.set_item(#names, &#idents)?;)*)
+}
+
+/// The code that runs `statement` in Python, for when it couldn't be transpiled.
+pub(crate) fn statement_fallback(
+    statement: &Statement,
+    reason: &dyn Error,
+    audit: &FallbackAudit,
+    symbols: &SymbolTableScopes,
+) -> Result<TokenStream, Box<dyn Error>> {
+    let (source, reason) = match &statement.statement {
+        StatementType::Unimplemented(source) => (source.clone(), "not supported by the transpiler".to_string()),
+        _ => (statement.to_python_source()?, reason.to_string().lines().next().unwrap_or_default().to_string()),
+    };
+    let (loaded, stored) = boundary_names(&source, "exec", true).map_err(|e| format!("can't run `{}` in Python: {}", source, e))?;
+    let inputs: Vec<String> = loaded.into_iter().filter(|name| is_local(symbols, name)).collect();
+    // Python may change what it's given, such as a list, in place, so the inputs come back too.
+    let mut outputs = inputs.clone();
+    outputs.extend(stored.into_iter().filter(|name| !inputs.contains(name)));
+
+    let pass_in = pass_in(&inputs);
+    // Variables Rust already has are read back as the same type; new ones stay Python objects.
+    let read_back = outputs.iter().map(|name| {
+        let ident = format_ident!("{}", name);
+        let value = quote! {
+            locals.get_item(#name)?.ok_or_else(|| pyo3::exceptions::PyNameError::new_err(#name))?
+        };
+        if is_local(symbols, name) { quote!(extract_like(&#ident, #value)?) } else { quote!(#value.unbind()) }
+    });
+    let targets = outputs.iter().map(|name| format_ident!("{}", name));
+    let failed = format!("Python fallback at line {} failed", statement.lineno.map_or("?".to_string(), |line| line.to_string()));
+
+    audit.record(FallbackUse {
+        kind: FallbackKind::Statement,
+        line: statement.lineno,
+        column: statement.col_offset.map(|c| c + 1),
+        reason,
+        source: source.clone(),
+        inputs,
+        outputs: outputs.clone(),
+    });
+
+    let extract_like = outputs.iter().any(|name| is_local(symbols, name)).then(|| {
+        quote! {
+            fn extract_like<'py, T: pyo3::FromPyObject<'py>>(_: &T, value: pyo3::Bound<'py, pyo3::PyAny>) -> pyo3::PyResult<T> {
+                value.extract()
+            }
+        }
+    });
+    let run = quote! {
+        {
+            use pyo3::prelude::*;
+            #extract_like
+            pyo3::Python::with_gil(|py| -> pyo3::PyResult<_> {
+                let locals = pyo3::types::PyDict::new(py);
+                #pass_in
+                py.run(&std::ffi::CString::new(#source)?, None, Some(&locals))?;
+                Ok((#(#read_back,)*))
+            })
+            .unwrap_or_else(|e| panic!("{}: {}", #failed, e))
+        }
+    };
+    Ok(if outputs.is_empty() { quote!(#run;) } else { quote!(let (#(#targets,)*) = #run;) })
+}
+
+/// The code that evaluates the expression `source` in Python, giving a `pyo3::Py<pyo3::PyAny>`.
+pub(crate) fn expression_fallback(
+    source: &str,
+    audit: &FallbackAudit,
+    symbols: &SymbolTableScopes,
+) -> Result<TokenStream, Box<dyn Error>> {
+    let (loaded, _) = boundary_names(source, "eval", true).map_err(|e| format!("can't run `{}` in Python: {}", source, e))?;
+    let inputs: Vec<String> = loaded.into_iter().filter(|name| is_local(symbols, name)).collect();
+    let pass_in = pass_in(&inputs);
+    let failed = format!("Python fallback for `{}` failed", source);
+
+    audit.record(FallbackUse {
+        kind: FallbackKind::Expression,
+        line: None,
+        column: None,
+        source: source.to_string(),
+        reason: "not supported by the transpiler".to_string(),
+        inputs,
+        outputs: Vec::new(),
+    });
+
+    Ok(quote! {
+        {
+            use pyo3::prelude::*;
+            pyo3::Python::with_gil(|py| -> pyo3::PyResult<pyo3::Py<pyo3::PyAny>> {
+                let locals = pyo3::types::PyDict::new(py);
+                #pass_in
+                Ok(py.eval(&std::ffi::CString::new(#source)?, None, Some(&locals))?.unbind())
+            })
+            .unwrap_or_else(|e| panic!("{}: {}", #failed, e))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CodeGen, CodeGenContext, PythonOptions};
+
+    fn transpile(source: &str) -> (Result<String, Box<dyn Error>>, Vec<FallbackUse>) {
+        let audit = FallbackAudit::new();
+        let options = PythonOptions { python_fallback: Some(audit.clone()), with_std_python: false, ..Default::default() };
+        let code = crate::parse(source, "test.py")
+            .unwrap()
+            .to_rust(CodeGenContext::Module("test".to_string()), options, SymbolTableScopes::new())
+            .map(|tokens| tokens.to_string());
+        (code, audit.uses())
+    }
+
+    #[test]
+    fn test_statement_fallback() {
+        let (code, uses) = transpile("def f():\n    xs = [1]\n    del xs[0]\n    total: int = len(xs)\n    print(total)\n");
+        let code = code.unwrap();
+        assert_eq!(uses.len(), 2);
+        assert_eq!((uses[0].line, uses[0].column), (Some(3), Some(5)));
+        assert_eq!(uses[0].source, "del xs[0]");
+        // The list may change in Python, so it comes back.
+        assert_eq!((uses[0].inputs.as_slice(), uses[0].outputs.as_slice()), (&["xs".to_string()][..], &["xs".to_string()][..]));
+        assert_eq!(uses[1].outputs, ["xs", "total"]);
+        assert!(code.contains("let (xs ,) ="), "{}", code);
+        assert!(code.contains("let (xs , total ,) ="), "{}", code);
+        assert_eq!(uses[1].to_string(), "4:5: statement runs in Python: total: int = len(xs)");
+    }
+
+    #[test]
+    fn test_unsupported_code_fails_without_fallback() {
+        let module = crate::parse("x = 1\nassert x\n", "test.py").unwrap();
+        let options = PythonOptions { with_std_python: false, ..Default::default() };
+        let error = module.to_rust(CodeGenContext::Module("test".to_string()), options, SymbolTableScopes::new()).unwrap_err();
+        assert!(error.to_string().contains("`assert x` can't be transpiled"), "{}", error);
+    }
+
+    #[test]
+    fn test_refuses_code_that_leaves_its_block() {
+        let (code, _) = transpile("def f(x):\n    match x:\n        case 0:\n            return 1\n    return 2\n");
+        let error = code.unwrap_err().to_string();
+        assert!(error.contains("the code returns"), "{}", error);
+    }
+
+    #[test]
+    fn test_whole_statement_replaces_inner_fallbacks() {
+        let (code, uses) = transpile("def f(xs):\n    for x in xs:\n        assert x\n        match x:\n            case 0:\n                break\n");
+        code.unwrap();
+        assert_eq!(uses.len(), 1);
+        assert_eq!((uses[0].line, uses[0].column), (Some(2), Some(5)));
+    }
+
+    #[test]
+    fn test_passes_in_parameters() {
+        let (code, uses) = transpile("def f(xs):\n    for x in xs:\n        match x:\n            case 0:\n                break\n");
+        code.unwrap();
+        assert_eq!(uses[0].inputs, ["xs"]);
+        assert_eq!(uses[0].outputs, ["xs", "x"]);
+    }
+
+    #[test]
+    fn test_boundary_names() {
+        let (loaded, stored) = boundary_names("y: int = x + [z for z in w if z > k]", "exec", true).unwrap();
+        assert_eq!(loaded, ["int", "x", "w", "k"]);
+        assert_eq!(stored, ["y"]);
+        let (loaded, stored) = boundary_names("for a in b:\n    if a: break\nelse:\n    c = 1", "exec", true).unwrap();
+        assert_eq!((loaded, stored), (vec!["b".to_string(), "a".to_string()], vec!["a".to_string(), "c".to_string()]));
+    }
+}
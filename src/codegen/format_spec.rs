@@ -0,0 +1,867 @@
+//! Lowering of Python string formatting to Rust's `format!`.
+//!
+//! Python has three ways to build formatted strings: f-strings, `%`-style
+//! interpolation and `str.format`. All three share the format specification
+//! mini-language, so they are lowered through the same [`FormatArgs`] builder,
+//! which produces a single `format!` invocation. Specifications with a direct
+//! Rust equivalent are translated statically; anything else (grouping, `%`
+//! presentation, general float formats, ...) falls back to the runtime
+//! `format(value, spec)` builtin.
+
+use proc_macro2::{Delimiter, Group, Ident, Literal, TokenStream, TokenTree};
+use quote::{format_ident, quote};
+
+/// The conversion applied to a value before it is formatted (`!s`, `!r`, `!a`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Conversion {
+    #[default]
+    None,
+    Str,
+    Repr,
+    Ascii,
+}
+
+impl Conversion {
+    /// Converts the integer code CPython stores in `FormattedValue.conversion`.
+    pub fn from_code(code: Option<i32>) -> Self {
+        match code.and_then(|c| char::from_u32(c as u32)) {
+            Some('s') => Conversion::Str,
+            Some('r') => Conversion::Repr,
+            Some('a') => Conversion::Ascii,
+            _ => Conversion::None,
+        }
+    }
+
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            's' => Some(Conversion::Str),
+            'r' => Some(Conversion::Repr),
+            'a' => Some(Conversion::Ascii),
+            _ => None,
+        }
+    }
+
+    /// Applies the conversion at runtime, as Python does before calling `format()`.
+    fn apply(self, value: TokenStream) -> TokenStream {
+        match self {
+            Conversion::None => value,
+            Conversion::Str => quote!(str(#value)),
+            Conversion::Repr => quote!(repr(#value)),
+            Conversion::Ascii => quote!(ascii(#value)),
+        }
+    }
+}
+
+/// One element of a Python format spec: either a literal character or a nested
+/// replacement field such as the `{width}` in `{x:>{width}}`.
+#[derive(Clone, Debug)]
+pub enum SpecToken {
+    Char(char),
+    Arg(TokenStream),
+}
+
+impl SpecToken {
+    /// Splits literal spec text into tokens.
+    pub fn chars(s: &str) -> impl Iterator<Item = SpecToken> + '_ {
+        s.chars().map(SpecToken::Char)
+    }
+}
+
+/// A width or precision, either literal or taken from another argument.
+#[derive(Clone, Debug)]
+enum Count {
+    Literal(usize),
+    Arg(TokenStream),
+}
+
+/// A parsed Python format specification:
+/// `[[fill]align][sign][z][#][0][width][grouping][.precision][type]`.
+#[derive(Clone, Debug, Default)]
+struct PySpec {
+    fill: Option<char>,
+    align: Option<char>,
+    sign: Option<char>,
+    z: bool,
+    alternate: bool,
+    zero: bool,
+    width: Option<Count>,
+    grouping: Option<char>,
+    precision: Option<Count>,
+    ty: Option<char>,
+}
+
+impl PySpec {
+    /// Parses a spec, returning `None` if nested fields appear anywhere other
+    /// than the width or precision (which would need a runtime formatter).
+    fn parse(tokens: &[SpecToken]) -> Option<Self> {
+        let mut spec = PySpec::default();
+        let mut i = 0;
+        let char_at = |i: usize| match tokens.get(i) {
+            Some(SpecToken::Char(c)) => Some(*c),
+            _ => None,
+        };
+        let is_align = |c: Option<char>| matches!(c, Some('<' | '>' | '=' | '^'));
+
+        if is_align(char_at(1)) && char_at(0).is_some() {
+            spec.fill = char_at(0);
+            spec.align = char_at(1);
+            i = 2;
+        } else if is_align(char_at(0)) {
+            spec.align = char_at(0);
+            i = 1;
+        }
+        if let Some(c @ ('+' | '-' | ' ')) = char_at(i) {
+            spec.sign = Some(c);
+            i += 1;
+        }
+        if char_at(i) == Some('z') {
+            spec.z = true;
+            i += 1;
+        }
+        if char_at(i) == Some('#') {
+            spec.alternate = true;
+            i += 1;
+        }
+        if char_at(i) == Some('0') {
+            spec.zero = true;
+            i += 1;
+        }
+        spec.width = Self::parse_count(tokens, &mut i);
+        if let Some(c @ (',' | '_')) = char_at(i) {
+            spec.grouping = Some(c);
+            i += 1;
+        }
+        if char_at(i) == Some('.') {
+            i += 1;
+            spec.precision = Some(Self::parse_count(tokens, &mut i)?);
+        }
+        if let Some(c) = char_at(i).filter(|c| c.is_ascii_alphabetic() || *c == '%') {
+            spec.ty = Some(c);
+            i += 1;
+        }
+
+        (i == tokens.len()).then_some(spec)
+    }
+
+    fn parse_count(tokens: &[SpecToken], i: &mut usize) -> Option<Count> {
+        if let Some(SpecToken::Arg(arg)) = tokens.get(*i) {
+            *i += 1;
+            return Some(Count::Arg(arg.clone()));
+        }
+        let mut digits = String::new();
+        while let Some(SpecToken::Char(c)) = tokens.get(*i) {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            digits.push(*c);
+            *i += 1;
+        }
+        digits.parse().ok().map(Count::Literal)
+    }
+
+    /// Translates the spec into a Rust one, if Rust can express it.
+    fn to_rust(&self, conversion: Conversion) -> Option<RustSpec> {
+        if self.z || self.grouping.is_some() || self.sign == Some(' ') {
+            return None;
+        }
+        if matches!(self.fill, Some('{' | '}')) {
+            return None;
+        }
+
+        let mut rust = RustSpec {
+            fill: self.fill,
+            align: self.align,
+            plus: self.sign == Some('+'),
+            alternate: self.alternate,
+            zero: self.zero,
+            width: self.width.clone(),
+            precision: self.precision.clone(),
+            ty: "",
+            float: false,
+        };
+
+        // Python's `=` alignment pads after the sign, which Rust only offers as
+        // the `0` flag, so it only translates when the padding is zeros.
+        if self.align == Some('=') {
+            let zeros = self.fill == Some('0') || (self.fill.is_none() && self.zero);
+            if !zeros {
+                return None;
+            }
+            rust.fill = None;
+            rust.align = None;
+            rust.zero = true;
+        } else if self.zero && self.align.is_some() {
+            return None;
+        }
+
+        rust.ty = match (conversion, self.ty) {
+            (Conversion::Repr, None | Some('s')) => "?",
+            (Conversion::Repr, _) => return None,
+            (_, None | Some('s' | 'd')) => "",
+            // Python's default of six digits isn't Rust's, so only an explicit
+            // precision translates.
+            (_, Some('f' | 'F')) if rust.precision.is_some() => {
+                rust.float = true;
+                ""
+            }
+            (_, Some('x')) => "x",
+            (_, Some('X')) if !self.alternate => "X",
+            (_, Some('o')) => "o",
+            (_, Some('b')) => "b",
+            _ => return None,
+        };
+
+        // `{:#}` and `{:#?}` mean something entirely different in Rust.
+        if rust.alternate && matches!(rust.ty, "" | "?") {
+            return None;
+        }
+
+        Some(rust)
+    }
+
+    /// Rebuilds the spec as Python text, for use with the runtime formatter.
+    fn into_tokens(self) -> Vec<SpecToken> {
+        let mut tokens = Vec::new();
+        let push_count = |tokens: &mut Vec<SpecToken>, count: Count| match count {
+            Count::Literal(n) => tokens.extend(SpecToken::chars(&n.to_string())),
+            Count::Arg(arg) => tokens.push(SpecToken::Arg(arg)),
+        };
+
+        let flags = [
+            self.fill,
+            self.align,
+            self.sign,
+            self.z.then_some('z'),
+            self.alternate.then_some('#'),
+            self.zero.then_some('0'),
+        ];
+        tokens.extend(flags.into_iter().flatten().map(SpecToken::Char));
+        if let Some(width) = self.width {
+            push_count(&mut tokens, width);
+        }
+        if let Some(grouping) = self.grouping {
+            tokens.push(SpecToken::Char(grouping));
+        }
+        if let Some(precision) = self.precision {
+            tokens.push(SpecToken::Char('.'));
+            push_count(&mut tokens, precision);
+        }
+        if let Some(ty) = self.ty {
+            tokens.push(SpecToken::Char(ty));
+        }
+        tokens
+    }
+}
+
+/// A Rust format spec: `[[fill]align][+][#][0][width][.precision][type]`.
+#[derive(Clone, Debug)]
+struct RustSpec {
+    fill: Option<char>,
+    align: Option<char>,
+    plus: bool,
+    alternate: bool,
+    zero: bool,
+    width: Option<Count>,
+    precision: Option<Count>,
+    ty: &'static str,
+    /// Whether the value is formatted as a float. Rust ignores the precision of
+    /// integers, so it is converted first.
+    float: bool,
+}
+
+impl RustSpec {
+    /// Renders the spec, registering any width or precision arguments with `args`.
+    fn render(self, args: &mut FormatArgs) -> String {
+        let mut out = String::new();
+        if let Some(fill) = self.fill {
+            out.push(fill);
+        }
+        if let Some(align) = self.align {
+            out.push(align);
+        }
+        if self.plus {
+            out.push('+');
+        }
+        if self.alternate {
+            out.push('#');
+        }
+        if self.zero {
+            out.push('0');
+        }
+        if let Some(width) = self.width {
+            out.push_str(&args.count(width));
+        }
+        if let Some(precision) = self.precision {
+            out.push('.');
+            out.push_str(&args.count(precision));
+        }
+        out.push_str(self.ty);
+        out
+    }
+}
+
+/// A `format!` invocation under construction.
+#[derive(Clone, Debug, Default)]
+pub struct FormatArgs {
+    template: String,
+    args: Vec<TokenStream>,
+    /// Number of `{}` placeholders written so far, which is the argument
+    /// the next implicit placeholder refers to.
+    implicit: usize,
+    /// Whether an argument that is already passed is referred to by its index
+    /// rather than passed again. Only safe when equal tokens are the same value,
+    /// as with the [`StandIns`] of a template's arguments.
+    shared: bool,
+}
+
+impl FormatArgs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn shared() -> Self {
+        Self { shared: true, ..Self::default() }
+    }
+
+    /// Appends literal text, escaping braces.
+    pub fn push_str(&mut self, s: &str) {
+        for c in s.chars() {
+            match c {
+                '{' => self.template.push_str("{{"),
+                '}' => self.template.push_str("}}"),
+                c => self.template.push(c),
+            }
+        }
+    }
+
+    /// Appends `value` formatted with a Python conversion and format spec.
+    pub fn push_value(&mut self, value: TokenStream, conversion: Conversion, spec: Vec<SpecToken>) {
+        match PySpec::parse(&spec).and_then(|s| s.to_rust(conversion)) {
+            Some(rust) => {
+                let value = match conversion {
+                    Conversion::Ascii => conversion.apply(value),
+                    _ if rust.float => quote!((#value) as f64),
+                    _ => value,
+                };
+                let index = self.push_arg(value);
+                let spec = rust.render(self);
+                self.push_placeholder(index, &spec);
+            }
+            None => {
+                let value = conversion.apply(value);
+                let spec = Self::runtime_spec(spec);
+                let index = self.push_arg(quote!(format(#value, #spec)));
+                self.push_placeholder(index, "");
+            }
+        }
+    }
+
+    /// Finishes the builder, producing the `format!` call.
+    pub fn into_tokens(self) -> TokenStream {
+        let template = Literal::string(&self.template);
+        let args = self.args;
+        quote!(format!(#template #(, #args)*))
+    }
+
+    fn push_arg(&mut self, value: TokenStream) -> usize {
+        if self.shared {
+            let text = value.to_string();
+            if let Some(index) = self.args.iter().position(|arg| arg.to_string() == text) {
+                return index;
+            }
+        }
+        self.args.push(value);
+        self.args.len() - 1
+    }
+
+    fn push_placeholder(&mut self, index: usize, spec: &str) {
+        self.template.push('{');
+        if index == self.implicit {
+            self.implicit += 1;
+        } else {
+            self.template.push_str(&index.to_string());
+        }
+        if !spec.is_empty() {
+            self.template.push(':');
+            self.template.push_str(spec);
+        }
+        self.template.push('}');
+    }
+
+    fn count(&mut self, count: Count) -> String {
+        match count {
+            Count::Literal(n) => n.to_string(),
+            Count::Arg(arg) => format!("{}$", self.push_arg(quote!((#arg) as usize))),
+        }
+    }
+
+    /// Builds the Python spec string passed to the runtime formatter. Nested
+    /// fields are themselves formatted, so the spec may be computed at runtime.
+    fn runtime_spec(spec: Vec<SpecToken>) -> TokenStream {
+        if spec.iter().all(|t| matches!(t, SpecToken::Char(_))) {
+            let text: String = spec
+                .into_iter()
+                .filter_map(|t| match t {
+                    SpecToken::Char(c) => Some(c),
+                    SpecToken::Arg(_) => None,
+                })
+                .collect();
+            let text = Literal::string(&text);
+            return quote!(#text);
+        }
+
+        let mut nested = FormatArgs::new();
+        for token in spec {
+            match token {
+                SpecToken::Char(c) => nested.push_str(&c.to_string()),
+                SpecToken::Arg(arg) => nested.push_value(arg, Conversion::None, Vec::new()),
+            }
+        }
+        nested.into_tokens()
+    }
+}
+
+/// The arguments of a template, which Python evaluates once however many fields
+/// refer to them. Each is formatted under a name of its own, so that a field used
+/// twice is passed to `format!` once, and [`StandIns::finish`] puts the values back.
+#[derive(Default)]
+struct StandIns(Vec<(Ident, TokenStream)>);
+
+impl StandIns {
+    /// What to format in place of `value`: the value itself if it is a name or
+    /// literal, which can be repeated freely, and otherwise a name for it.
+    fn stand_in(&mut self, value: TokenStream) -> TokenStream {
+        let mut tokens = value.clone().into_iter();
+        if let (Some(TokenTree::Ident(_) | TokenTree::Literal(_)), None) = (tokens.next(), tokens.next()) {
+            return value;
+        }
+        let name = format_ident!("__arg{}", self.0.len());
+        self.0.push((name.clone(), value));
+        quote!(#name)
+    }
+
+    /// Finishes `args`. A value whose name appears once goes back in its place, and
+    /// one that is needed in several forms, such as `{0} {0!r:>5}`, is evaluated first.
+    fn finish(self, mut args: FormatArgs) -> TokenStream {
+        let mut lets = Vec::new();
+        for (name, value) in self.0 {
+            let mut count = 0;
+            let replaced: Vec<_> = args.args.iter().map(|arg| substitute(arg, &name, &value, &mut count)).collect();
+            match count {
+                0 => {}
+                1 => args.args = replaced,
+                _ => lets.push(quote!(let #name = #value;)),
+            }
+        }
+        let call = args.into_tokens();
+        if lets.is_empty() { call } else { quote!({ #(#lets)* #call }) }
+    }
+}
+
+/// `tokens` with each `name` replaced by `value`, counting the replacements.
+fn substitute(tokens: &TokenStream, name: &Ident, value: &TokenStream, count: &mut usize) -> TokenStream {
+    tokens
+        .clone()
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Ident(ident) if ident == *name => {
+                *count += 1;
+                TokenTree::Group(Group::new(Delimiter::None, value.clone()))
+            }
+            TokenTree::Group(group) => {
+                let mut inner = Group::new(group.delimiter(), substitute(&group.stream(), name, value, count));
+                inner.set_span(group.span());
+                TokenTree::Group(inner)
+            }
+            token => token,
+        })
+        .collect()
+}
+
+/// The right-hand side of a `%` format operation.
+#[derive(Clone, Debug)]
+pub enum PercentArgs {
+    /// A tuple, or a single non-tuple value.
+    Positional(Vec<TokenStream>),
+    /// A dict literal with string keys, for `%(name)s` style templates.
+    Mapping(Vec<(String, TokenStream)>),
+}
+
+/// Lowers `template % args`. Returns `None` if the template is malformed or
+/// does not match the arguments, in which case the caller keeps the `%` operator.
+pub fn percent_format(template: &str, args: PercentArgs) -> Option<TokenStream> {
+    let mut out = FormatArgs::shared();
+    let mut stand_ins = StandIns::default();
+    let args = match args {
+        PercentArgs::Positional(values) => PercentArgs::Positional(values.into_iter().map(|v| stand_ins.stand_in(v)).collect()),
+        PercentArgs::Mapping(entries) => PercentArgs::Mapping(entries.into_iter().map(|(k, v)| (k, stand_ins.stand_in(v))).collect()),
+    };
+    let mut positional = match &args {
+        PercentArgs::Positional(values) => values.clone().into_iter(),
+        PercentArgs::Mapping(_) => Vec::new().into_iter(),
+    };
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push_str(&c.to_string());
+            continue;
+        }
+        if chars.peek() == Some(&'%') {
+            chars.next();
+            out.push_str("%");
+            continue;
+        }
+
+        let mut key = None;
+        if chars.peek() == Some(&'(') {
+            chars.next();
+            let name: String = chars.by_ref().take_while(|c| *c != ')').collect();
+            key = Some(name);
+        }
+
+        let mut spec = PySpec::default();
+        let mut left = false;
+        while let Some(flag @ ('#' | '0' | '-' | '+' | ' ')) = chars.peek().copied() {
+            match flag {
+                '#' => spec.alternate = true,
+                '0' => spec.zero = true,
+                '-' => left = true,
+                sign => {
+                    if spec.sign != Some('+') {
+                        spec.sign = Some(sign);
+                    }
+                }
+            }
+            chars.next();
+        }
+
+        let mut count = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                return positional.next().map(|arg| Some(Count::Arg(arg))).ok_or(());
+            }
+            let mut digits = String::new();
+            while let Some(d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                digits.push(*d);
+                chars.next();
+            }
+            Ok(digits.parse().ok().map(Count::Literal))
+        };
+        spec.width = count(&mut chars).ok()?;
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            spec.precision = Some(count(&mut chars).ok()?.unwrap_or(Count::Literal(0)));
+        }
+        while matches!(chars.peek(), Some('h' | 'l' | 'L')) {
+            chars.next();
+        }
+
+        let (conversion, ty) = match chars.next()? {
+            's' => (Conversion::Str, None),
+            'r' => (Conversion::Repr, None),
+            'a' => (Conversion::Ascii, None),
+            'd' | 'i' | 'u' => (Conversion::None, Some('d')),
+            ty @ ('f' | 'F' | 'e' | 'E' | 'g' | 'G' | 'x' | 'X' | 'o' | 'c') => (Conversion::None, Some(ty)),
+            _ => return None,
+        };
+        spec.ty = ty;
+
+        // `%` interpolation right-aligns everything unless `-` is given, while
+        // Rust left-aligns strings by default.
+        if left {
+            spec.align = Some('<');
+            spec.zero = false;
+        } else if spec.width.is_some() && !spec.zero {
+            spec.align = Some('>');
+        }
+
+        let value = match (&args, key) {
+            (PercentArgs::Mapping(entries), Some(key)) => {
+                entries.iter().find(|(k, _)| *k == key)?.1.clone()
+            }
+            (PercentArgs::Positional(_), None) => positional.next()?,
+            _ => return None,
+        };
+        out.push_value(value, conversion, spec.into_tokens());
+    }
+
+    if positional.next().is_some() {
+        return None;
+    }
+    Some(stand_ins.finish(out))
+}
+
+/// Lowers `template.format(*args, **keywords)`. Returns `None` if the template
+/// is malformed or refers to arguments that were not given.
+pub fn str_format(
+    template: &str,
+    args: &[TokenStream],
+    keywords: &[(String, TokenStream)],
+) -> Option<TokenStream> {
+    let mut out = FormatArgs::shared();
+    let mut stand_ins = StandIns::default();
+    let args: Vec<_> = args.iter().map(|arg| stand_ins.stand_in(arg.clone())).collect();
+    let keywords: Vec<_> = keywords.iter().map(|(k, v)| (k.clone(), stand_ins.stand_in(v.clone()))).collect();
+    let mut numbering = Numbering::default();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push_str("{");
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push_str("}");
+            }
+            '}' => return None,
+            '{' => {
+                // Collect the field, allowing one level of nesting in the spec.
+                let mut field = String::new();
+                let mut depth = 0;
+                loop {
+                    match chars.next()? {
+                        '{' => {
+                            depth += 1;
+                            field.push('{');
+                        }
+                        '}' if depth == 0 => break,
+                        '}' => {
+                            depth -= 1;
+                            field.push('}');
+                        }
+                        c => field.push(c),
+                    }
+                }
+
+                let (name, rest) = split_field(&field, &['!', ':']);
+                let (conversion, spec) = match rest.strip_prefix('!') {
+                    Some(rest) => {
+                        let mut rest = rest.chars();
+                        let conversion = Conversion::from_char(rest.next()?)?;
+                        let rest = rest.as_str();
+                        if !rest.is_empty() && !rest.starts_with(':') {
+                            return None;
+                        }
+                        (conversion, rest.strip_prefix(':').unwrap_or(""))
+                    }
+                    None => (Conversion::None, rest.strip_prefix(':').unwrap_or("")),
+                };
+
+                let value = format_field(name, &mut numbering, &args, &keywords)?;
+
+                let mut spec_tokens = Vec::new();
+                let mut spec_chars = spec.chars();
+                while let Some(c) = spec_chars.next() {
+                    if c == '{' {
+                        let inner: String = spec_chars.by_ref().take_while(|c| *c != '}').collect();
+                        spec_tokens.push(SpecToken::Arg(format_field(&inner, &mut numbering, &args, &keywords)?));
+                    } else {
+                        spec_tokens.push(SpecToken::Char(c));
+                    }
+                }
+
+                out.push_value(value, conversion, spec_tokens);
+            }
+            c => out.push_str(&c.to_string()),
+        }
+    }
+
+    Some(stand_ins.finish(out))
+}
+
+/// Splits `field` at the first of `delimiters` outside square brackets.
+fn split_field<'a>(field: &'a str, delimiters: &[char]) -> (&'a str, &'a str) {
+    let mut in_index = false;
+    for (i, c) in field.char_indices() {
+        match c {
+            c if !in_index && delimiters.contains(&c) => return field.split_at(i),
+            '[' => in_index = true,
+            ']' => in_index = false,
+            _ => {}
+        }
+    }
+    (field, "")
+}
+
+/// How positional `str.format` fields are numbered. Python rejects templates
+/// that mix automatic (`{}`) and manual (`{0}`) numbering.
+#[derive(Clone, Copy, Debug, Default)]
+struct Numbering {
+    next: usize,
+    automatic: bool,
+    manual: bool,
+}
+
+impl Numbering {
+    fn automatic(&mut self) -> Option<usize> {
+        if self.manual {
+            return None;
+        }
+        self.automatic = true;
+        self.next += 1;
+        Some(self.next - 1)
+    }
+
+    fn manual(&mut self, index: usize) -> Option<usize> {
+        if self.automatic {
+            return None;
+        }
+        self.manual = true;
+        Some(index)
+    }
+}
+
+/// Resolves a `str.format` field name such as `0`, `name`, `0.attr` or `items[2]`.
+fn format_field(
+    field: &str,
+    numbering: &mut Numbering,
+    args: &[TokenStream],
+    keywords: &[(String, TokenStream)],
+) -> Option<TokenStream> {
+    let (head, mut accessors) = split_field(field, &['.', '[']);
+
+    let mut value = if head.is_empty() {
+        args.get(numbering.automatic()?)?.clone()
+    } else if let Ok(index) = head.parse::<usize>() {
+        args.get(numbering.manual(index)?)?.clone()
+    } else {
+        keywords.iter().find(|(k, _)| k == head)?.1.clone()
+    };
+
+    while !accessors.is_empty() {
+        if let Some(rest) = accessors.strip_prefix('.') {
+            let (attr, rest) = split_field(rest, &['.', '[']);
+            // Python allows any attribute name here, such as `{0.1}`, but only identifiers are Rust fields.
+            let attr = syn::parse_str::<syn::Ident>(attr).ok()?;
+            value = quote!((#value).#attr);
+            accessors = rest;
+        } else if let Some(rest) = accessors.strip_prefix('[') {
+            let (key, rest) = rest.split_once(']')?;
+            value = match key.parse::<usize>() {
+                Ok(index) => quote!((#value)[#index]),
+                Err(_) => quote!((#value)[#key]),
+            };
+            accessors = rest;
+        } else {
+            return None;
+        }
+    }
+
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(spec: &str, conversion: Conversion) -> String {
+        let mut args = FormatArgs::new();
+        args.push_value(quote!(x), conversion, SpecToken::chars(spec).collect());
+        args.into_tokens().to_string()
+    }
+
+    #[test]
+    fn test_static_specs() {
+        assert_eq!(value("", Conversion::None), "format ! (\"{}\" , x)");
+        assert_eq!(value("", Conversion::Repr), "format ! (\"{:?}\" , x)");
+        assert_eq!(value(">10", Conversion::None), "format ! (\"{:>10}\" , x)");
+        assert_eq!(value("*^8.2f", Conversion::None), "format ! (\"{:*^8.2}\" , (x) as f64)");
+        assert_eq!(value("+08d", Conversion::None), "format ! (\"{:+08}\" , x)");
+        assert_eq!(value("#x", Conversion::None), "format ! (\"{:#x}\" , x)");
+        assert_eq!(value("0=6", Conversion::None), "format ! (\"{:06}\" , x)");
+        assert_eq!(value("=06", Conversion::None), "format ! (\"{:06}\" , x)");
+    }
+
+    #[test]
+    fn test_runtime_specs() {
+        assert_eq!(value(",", Conversion::None), "format ! (\"{}\" , format (x , \",\"))");
+        assert_eq!(value("=6", Conversion::None), "format ! (\"{}\" , format (x , \"=6\"))");
+        assert_eq!(value("f", Conversion::None), "format ! (\"{}\" , format (x , \"f\"))");
+        assert_eq!(value(".3e", Conversion::None), "format ! (\"{}\" , format (x , \".3e\"))");
+        assert_eq!(value(".1%", Conversion::Repr), "format ! (\"{}\" , format (repr (x) , \".1%\"))");
+    }
+
+    #[test]
+    fn test_nested_width() {
+        let mut args = FormatArgs::new();
+        let spec = vec![
+            SpecToken::Char('>'),
+            SpecToken::Arg(quote!(width)),
+            SpecToken::Char('.'),
+            SpecToken::Char('2'),
+            SpecToken::Char('f'),
+        ];
+        args.push_value(quote!(x), Conversion::None, spec);
+        assert_eq!(
+            args.into_tokens().to_string(),
+            "format ! (\"{:>1$.2}\" , (x) as f64 , (width) as usize)"
+        );
+    }
+
+    #[test]
+    fn test_escapes() {
+        let mut args = FormatArgs::new();
+        args.push_str("{literal}");
+        assert_eq!(args.into_tokens().to_string(), "format ! (\"{{literal}}\")");
+    }
+
+    #[test]
+    fn test_percent_format() {
+        let tokens = percent_format(
+            "%s is %5.1f%% done",
+            PercentArgs::Positional(vec![quote!(name), quote!(pct)]),
+        )
+        .unwrap();
+        assert_eq!(tokens.to_string(), "format ! (\"{} is {:>5.1}% done\" , name , (pct) as f64)");
+
+        let tokens = percent_format(
+            "%(who)s: %(n)-4d|",
+            PercentArgs::Mapping(vec![("n".to_string(), quote!(1)), ("who".to_string(), quote!(me))]),
+        )
+        .unwrap();
+        assert_eq!(tokens.to_string(), "format ! (\"{}: {:<4}|\" , me , 1)");
+
+        assert!(percent_format("%s %s", PercentArgs::Positional(vec![quote!(a)])).is_none());
+
+        // A key used twice is evaluated once, and equal expressions in different places are each evaluated.
+        let tokens = percent_format("%(a)s %(a)s", PercentArgs::Mapping(vec![("a".to_string(), quote!(g()))])).unwrap();
+        assert_eq!(tokens.to_string(), "format ! (\"{} {0}\" , g ())");
+        let tokens = percent_format("%s %s", PercentArgs::Positional(vec![quote!(g()), quote!(g())])).unwrap();
+        assert_eq!(tokens.to_string(), "format ! (\"{} {}\" , g () , g ())");
+    }
+
+    #[test]
+    fn test_str_format() {
+        let args = [quote!(a), quote!(b)];
+        let keywords = [("name".to_string(), quote!(n))];
+        let tokens = str_format("{0} {name!r} {1:{0}} {{}}", &args, &keywords).unwrap();
+        assert_eq!(
+            tokens.to_string(),
+            "format ! (\"{} {:?} {:3$} {{}}\" , a , n , b , (a) as usize)"
+        );
+
+        // Each argument is evaluated once, however many fields use it.
+        let calls = [quote!(g()), quote!(h())];
+        let tokens = str_format("{0} {1} {0}", &calls, &[]).unwrap();
+        assert_eq!(tokens.to_string(), "format ! (\"{} {} {0}\" , g () , h ())");
+        let tokens = str_format("{0} {1:{0}}", &calls, &[]).unwrap();
+        assert_eq!(
+            tokens.to_string(),
+            "{ let __arg0 = g () ; format ! (\"{} {:2$}\" , __arg0 , h () , (__arg0) as usize) }"
+        );
+
+        let tokens = str_format("{0.real}/{0[1]}", &args, &keywords).unwrap();
+        assert_eq!(tokens.to_string(), "format ! (\"{}/{}\" , (a) . real , (a) [1usize])");
+
+        assert!(str_format("{2}", &args, &keywords).is_none());
+        assert!(str_format("{} {0}", &args, &keywords).is_none());
+        assert!(str_format("{1} {}", &args, &keywords).is_none());
+        assert!(str_format("{0:{}}", &args, &keywords).is_none());
+        assert!(str_format("{0.1}", &args, &keywords).is_none());
+        assert!(str_format("{0.}", &args, &keywords).is_none());
+        assert!(str_format("{0.type}", &args, &keywords).is_none());
+    }
+}
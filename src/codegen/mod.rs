@@ -2,9 +2,30 @@
 
 use std::fmt::Debug;
 
+pub mod format_spec;
+pub use format_spec::*;
+
 pub mod python_options;
 pub use python_options::*;
 
+pub mod rust_source;
+pub use rust_source::*;
+
+pub mod project;
+pub use project::*;
+
+pub mod build;
+pub use build::*;
+
+pub mod fallback;
+pub use fallback::*;
+
+pub mod declarations;
+pub use declarations::*;
+
+pub mod coverage;
+pub use coverage::*;
+
 /// Reexport the CodeGen from to_tokenstream
 pub use to_tokenstream::CodeGen;
 
@@ -0,0 +1,293 @@
+//! Generating a complete Cargo project for transpiled modules.
+//!
+//! The generated code `use`s crates such as `stdpython` and the async runtime, so building it needs a
+//! manifest that names them. [`CargoProject`] finds the crates the code actually refers to, maps them to
+//! dependencies, and lays the modules out as a crate that can be built as it is.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use proc_macro2::{Ident, TokenStream, TokenTree};
+use quote::quote;
+
+use crate::{AsyncRuntime, CodeGen, CodeGenContext, Module, PythonOptions, SourceOptions, SymbolTableScopes, format_rust};
+
+/// A dependency in the generated `Cargo.toml`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dependency {
+    pub version: String,
+    pub features: Vec<String>,
+}
+
+impl Dependency {
+    pub fn new(version: impl Into<String>) -> Self {
+        Self { version: version.into(), features: Vec::new() }
+    }
+
+    pub fn with_features(mut self, features: &[&str]) -> Self {
+        self.features = features.iter().map(|f| f.to_string()).collect();
+        self
+    }
+
+    fn to_toml(&self) -> String {
+        if self.features.is_empty() {
+            format!("{:?}", self.version)
+        } else {
+            format!("{{ version = {:?}, features = {:?} }}", self.version, self.features)
+        }
+    }
+}
+
+/// The identifier a module named after a Python file is declared with. Python module names that are Rust
+/// keywords, such as `type`, become raw identifiers; names that aren't identifiers at all, such as a
+/// `my-tool.py` that can only be run as a script, are an error.
+pub(crate) fn module_ident(name: &str) -> Result<Ident, String> {
+    syn::parse_str::<Ident>(name)
+        .or_else(|_| syn::parse_str::<Ident>(&format!("r#{}", name)))
+        .map_err(|_| format!("{:?} can't be the name of a Rust module", name))
+}
+
+/// The package and dependency for a crate the generated code may use, by the name it is used under.
+fn known_dependency(name: &str, options: &PythonOptions) -> Option<(String, Dependency)> {
+    let known = match name {
+        "tokio" => ("tokio", Dependency::new("1").with_features(&["macros", "rt-multi-thread"])),
+        "async_std" => ("async-std", Dependency::new("1").with_features(&["attributes"])),
+        "smol" => ("smol", Dependency::new("2")),
+        "futures" => ("futures", Dependency::new("0.3")),
+        "regex" => ("regex", Dependency::new("1")),
+        "pyo3" => ("pyo3", Dependency::new("0.25").with_features(&["auto-initialize"])),
+        _ if name == options.stdpython || name == "stdpython" => (options.stdpython.as_str(), Dependency::new("*")),
+        _ => match &options.async_runtime {
+            AsyncRuntime::Custom { import, .. } if import == name => (import.as_str(), Dependency::new("*")),
+            _ => return None,
+        },
+    };
+    Some((known.0.to_string(), known.1))
+}
+
+/// The names that begin a path in `tokens`, such as `tokio` in `#[tokio::main]` or `regex` in
+/// `use regex::Regex;`. These are the crates, or local modules, the code refers to.
+pub fn path_roots(tokens: TokenStream) -> BTreeSet<String> {
+    let mut roots = BTreeSet::new();
+    collect_path_roots(tokens, &mut roots);
+    roots
+}
+
+fn collect_path_roots(tokens: TokenStream, roots: &mut BTreeSet<String>) {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    let is_path_separator = |i: usize| {
+        matches!((tokens.get(i), tokens.get(i + 1)),
+            (Some(TokenTree::Punct(a)), Some(TokenTree::Punct(b))) if a.as_char() == ':' && b.as_char() == ':')
+    };
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            TokenTree::Group(group) => collect_path_roots(group.stream(), roots),
+            TokenTree::Ident(ident) => {
+                let starts_path = i < 2 || !is_path_separator(i - 2);
+                let after_use = i > 0 && matches!(&tokens[i - 1], TokenTree::Ident(u) if u == "use");
+                if starts_path && (is_path_separator(i + 1) || after_use) {
+                    roots.insert(ident.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A generated crate: its manifest's dependencies and its source files.
+#[derive(Clone, Debug)]
+pub struct CargoProject {
+    pub name: String,
+    /// Dependencies by package name. Versions can be pinned here before the project is written.
+    pub dependencies: BTreeMap<String, Dependency>,
+    /// Source files, relative to the project directory.
+    pub files: BTreeMap<PathBuf, String>,
+}
+
+impl CargoProject {
+    /// A project for a single module.
+    pub fn from_module(
+        name: impl Into<String>,
+        module: &Module,
+        options: PythonOptions,
+        source_options: &SourceOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::from_modules(name, std::slice::from_ref(module), options, source_options)
+    }
+
+    /// A project for several modules. The one named `__main__` or `main`, or else the first that defines a
+    /// `main` function, becomes `src/main.rs` and the rest its modules. Without one, the project is a
+    /// library; a lone module is then `src/lib.rs` itself.
+    pub fn from_modules(
+        name: impl Into<String>,
+        modules: &[Module],
+        options: PythonOptions,
+        source_options: &SourceOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        let options = source_options.python_options(options);
+        let mut generated = Vec::new();
+        for (index, module) in modules.iter().enumerate() {
+            let module_name = module.name.as_ref().map_or_else(|| format!("module{}", index), |n| n.id.clone());
+            let tokens = module
+                .clone()
+                .to_rust(CodeGenContext::Module(module_name.clone()), options.clone(), SymbolTableScopes::new())?;
+            generated.push((module_name, tokens));
+        }
+
+        let mut dependencies = BTreeMap::new();
+        for (_, tokens) in &generated {
+            for root in path_roots(tokens.clone()) {
+                if let Some((package, dependency)) = known_dependency(&root, &options) {
+                    dependencies.insert(package, dependency);
+                }
+            }
+        }
+
+        let defines_main = |tokens: &TokenStream| {
+            syn::parse2::<syn::File>(tokens.clone())
+                .is_ok_and(|file| file.items.iter().any(|item| matches!(item, syn::Item::Fn(f) if f.sig.ident == "main")))
+        };
+        let entry = generated
+            .iter()
+            .position(|(name, _)| name == "__main__" || name == "main")
+            .or_else(|| generated.iter().position(|(_, tokens)| defines_main(tokens)));
+
+        let mut files = BTreeMap::new();
+        let root_file = if entry.is_some() { "src/main.rs" } else { "src/lib.rs" };
+        if entry.is_none() && generated.len() == 1 {
+            let (_, tokens) = generated.remove(0);
+            files.insert(PathBuf::from(root_file), format_rust(tokens, source_options)?);
+        } else {
+            let root = entry.map(|index| generated.remove(index));
+            let submodules = generated.iter().map(|(name, _)| module_ident(name)).collect::<Result<Vec<_>, _>>()?;
+            let root_tokens = match root {
+                Some((_, tokens)) => quote!(#tokens #(mod #submodules;)*),
+                None => quote!(#(pub mod #submodules;)*),
+            };
+            files.insert(PathBuf::from(root_file), format_rust(root_tokens, source_options)?);
+            for (module_name, tokens) in generated {
+                files.insert(PathBuf::from(format!("src/{}.rs", module_name)), format_rust(tokens, source_options)?);
+            }
+        }
+
+        Ok(Self { name: name.into(), dependencies, files })
+    }
+
+    /// The text of `Cargo.toml`.
+    pub fn manifest(&self) -> String {
+        let mut manifest = format!("[package]\nname = {:?}\nversion = \"0.1.0\"\nedition = \"2024\"\n\n[dependencies]\n", self.name);
+        for (package, dependency) in &self.dependencies {
+            manifest.push_str(&format!("{} = {}\n", package, dependency.to_toml()));
+        }
+        manifest
+    }
+
+    /// Writes the manifest and sources under `dir`, creating it if needed.
+    pub fn write(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir.join("src"))?;
+        fs::write(dir.join("Cargo.toml"), self.manifest())?;
+        for (path, code) in &self.files {
+            fs::write(dir.join(path), code)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FallbackAudit;
+
+    fn project(sources: &[(&str, &str)], options: PythonOptions) -> CargoProject {
+        let modules: Vec<_> = sources.iter().map(|(file, code)| crate::parse(code, file).unwrap()).collect();
+        CargoProject::from_modules("demo", &modules, options, &SourceOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn test_path_roots() {
+        let roots = path_roots(quote! {
+            use stdpython::*;
+            use regex;
+            #[tokio::main]
+            async fn main() { let r = regex::Regex::new("a").unwrap(); std::mem::drop(a::b::c); }
+        });
+        let expected: BTreeSet<String> = ["a", "regex", "std", "stdpython", "tokio"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(roots, expected);
+    }
+
+    #[test]
+    fn test_binary_with_runtime() {
+        let project = project(&[("app.py", "async def main():\n    pass\n")], PythonOptions::default());
+        let manifest = project.manifest();
+        assert!(manifest.contains("name = \"demo\""));
+        assert!(manifest.contains("stdpython = \"*\""));
+        assert!(manifest.contains("tokio = { version = \"1\", features = [\"macros\", \"rt-multi-thread\"] }"), "{}", manifest);
+        assert!(project.files[Path::new("src/main.rs")].contains("async fn main()"));
+    }
+
+    #[test]
+    fn test_only_used_crates() {
+        let options = PythonOptions { with_std_python: false, ..Default::default() };
+        let project = project(&[("lib.py", "def f(x):\n    return x\n")], options);
+        assert!(project.dependencies.is_empty(), "{:?}", project.dependencies);
+        assert_eq!(project.files.keys().collect::<Vec<_>>(), [Path::new("src/lib.rs")]);
+    }
+
+    #[test]
+    fn test_python_fallback_needs_pyo3() {
+        let options = PythonOptions { with_std_python: false, python_fallback: Some(FallbackAudit::new()), ..Default::default() };
+        let manifest = project(&[("lib.py", "def f(xs):\n    del xs[0]\n")], options).manifest();
+        assert!(manifest.contains("pyo3 = { version = \"0.25\", features = [\"auto-initialize\"] }"), "{}", manifest);
+    }
+
+    #[test]
+    fn test_modules_under_main() {
+        let project = project(
+            &[("util.py", "def helper():\n    pass\n"), ("__main__.py", "print('hi')\n")],
+            PythonOptions::default(),
+        );
+        assert!(project.files[Path::new("src/main.rs")].contains("mod util;"));
+        assert!(project.files.contains_key(Path::new("src/util.rs")));
+    }
+
+    #[test]
+    fn test_library_of_modules() {
+        let binary = project(&[("a.py", "x = 1\ndef f():\n    pass\n"), ("b.py", "def g():\n    pass\n")], PythonOptions::default());
+        // `a` has module-level code, so it gets a `main` and becomes the binary.
+        assert!(binary.files[Path::new("src/main.rs")].contains("mod b;"));
+
+        let library = project(&[("a.py", "def f():\n    pass\n"), ("b.py", "def g():\n    pass\n")], PythonOptions::default());
+        let lib = &library.files[Path::new("src/lib.rs")];
+        assert!(lib.contains("pub mod a;") && lib.contains("pub mod b;"), "{}", lib);
+    }
+
+    #[test]
+    fn test_module_names() {
+        let library = project(&[("type.py", "def f():\n    pass\n"), ("b.py", "def g():\n    pass\n")], PythonOptions::default());
+        assert!(library.files[Path::new("src/lib.rs")].contains("pub mod r#type;"));
+        assert!(library.files.contains_key(Path::new("src/type.rs")));
+
+        let mut tool = crate::parse("y = 2\n", "tool.py").unwrap();
+        if let Some(name) = tool.name.as_mut() {
+            name.id = "my-tool".to_string();
+        }
+        let modules = [crate::parse("x = 1\n", "main.py").unwrap(), tool];
+        let error = CargoProject::from_modules("demo", &modules, PythonOptions::default(), &SourceOptions::default()).unwrap_err();
+        assert!(error.to_string().contains("\"my-tool\""), "{}", error);
+    }
+
+    #[test]
+    fn test_write() {
+        let dir = std::env::temp_dir().join(format!("python-ast-project-{}", std::process::id()));
+        let project = project(&[("app.py", "print('hi')\n")], PythonOptions::default());
+        project.write(&dir).unwrap();
+        assert!(fs::read_to_string(dir.join("Cargo.toml")).unwrap().contains("[dependencies]"));
+        assert!(dir.join("src/main.rs").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
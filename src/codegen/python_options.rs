@@ -5,7 +5,7 @@ use std::{
     default::Default,
 };
 
-use crate::Scope;
+use crate::{FallbackAudit, PythonVersion, Scope};
 use pyo3::{prelude::*, PyResult};
 use std::ffi::CString;
 
@@ -93,6 +93,20 @@ pub struct PythonOptions {
 
     /// The async runtime to use for async Python code
     pub async_runtime: AsyncRuntime,
+
+    /// Emit the source's comments as `__comment__!` markers, which `restore_comments` turns into `//`
+    /// comments once the code is rendered. The markers are not valid Rust on their own, so this is off by
+    /// default; the functions that render source, such as `Module::to_rust_source`, turn it on as their
+    /// `SourceOptions` ask.
+    pub preserve_comments: bool,
+
+    /// The Python version the source is written for. Code using anything newer is rejected where the
+    /// source is parsed with these options. `None` accepts whatever the linked interpreter does.
+    pub target_version: Option<PythonVersion>,
+
+    /// Run what can't be transpiled in an embedded Python interpreter instead of failing, recording each
+    /// use in the audit. `None` leaves such code an error.
+    pub python_fallback: Option<FallbackAudit>,
 }
 
 impl Default for PythonOptions {
@@ -107,6 +121,9 @@ impl Default for PythonOptions {
             with_std_python: true,
             allow_unsafe: false,
             async_runtime: AsyncRuntime::default(),
+            preserve_comments: false,
+            target_version: None,
+            python_fallback: None,
         }
     }
 }
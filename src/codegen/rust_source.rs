@@ -0,0 +1,388 @@
+//! Rendering generated code as formatted Rust source.
+//!
+//! `to_rust` produces a `TokenStream`, whose `to_string()` is a single unformatted line. The functions
+//! here parse it into a `syn::File`, pretty-print it with `prettyplease` and turn comment markers back
+//! into comments, giving a file that can be read and checked in.
+
+use std::{
+    error::Error,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use proc_macro2::TokenStream;
+use syn::{
+    Expr, File, ForeignItem, ImplItem, Item, Pat, TraitItem, Type, TypeParamBound,
+    visit::{self, Visit},
+};
+
+use quote::quote;
+
+use crate::{CodeGen, CodeGenContext, Module, PythonOptions, Statement, SymbolTableScopes, restore_comments};
+
+/// The width `prettyplease` always wraps at.
+const PRETTYPLEASE_WIDTH: usize = 100;
+
+/// How generated Rust is laid out as source.
+#[derive(Clone, Debug)]
+pub struct SourceOptions {
+    /// Lines written as `//` comments at the top of the file.
+    pub header: Vec<String>,
+    /// The widest a line may be. `prettyplease` only wraps at 100 columns, so any other width is handed
+    /// to `rustfmt`, which must then be installed.
+    pub line_width: usize,
+    /// Sort top-level items by kind and then by name instead of keeping the order of the Python source.
+    pub sort_items: bool,
+    /// Carry the Python source's comments and blank lines over into the generated code.
+    pub comments: bool,
+}
+
+impl Default for SourceOptions {
+    fn default() -> Self {
+        Self {
+            header: Vec::new(),
+            line_width: PRETTYPLEASE_WIDTH,
+            sort_items: false,
+            comments: true,
+        }
+    }
+}
+
+impl SourceOptions {
+    /// Adds a line to the header.
+    pub fn with_header(mut self, line: impl Into<String>) -> Self {
+        self.header.push(line.into());
+        self
+    }
+
+    pub fn with_line_width(mut self, line_width: usize) -> Self {
+        self.line_width = line_width;
+        self
+    }
+
+    pub fn with_sorted_items(mut self) -> Self {
+        self.sort_items = true;
+        self
+    }
+
+    pub fn without_comments(mut self) -> Self {
+        self.comments = false;
+        self
+    }
+
+    /// `options` with comment markers turned on or off to match these options.
+    pub(crate) fn python_options(&self, options: PythonOptions) -> PythonOptions {
+        PythonOptions { preserve_comments: self.comments, ..options }
+    }
+}
+
+/// Whether an item is one of the markers left for comments and blank lines.
+fn is_trivia_marker(item: &Item) -> bool {
+    match item {
+        Item::Macro(m) => m.mac.path.is_ident("__comment__") || m.mac.path.is_ident("__blank_line__"),
+        _ => false,
+    }
+}
+
+/// Where an item goes when items are sorted: imports, then constants, then types, then functions, with
+/// the module's entry points last.
+fn sort_key(item: &Item) -> (u8, String) {
+    match item {
+        Item::ExternCrate(i) => (0, i.ident.to_string()),
+        Item::Use(u) => (0, quote::quote!(#u).to_string()),
+        Item::Const(c) => (1, c.ident.to_string()),
+        Item::Static(s) => (1, s.ident.to_string()),
+        Item::Mod(m) => (2, m.ident.to_string()),
+        Item::Struct(s) => (2, s.ident.to_string()),
+        Item::Enum(e) => (2, e.ident.to_string()),
+        Item::Trait(t) => (2, t.ident.to_string()),
+        Item::Type(t) => (2, t.ident.to_string()),
+        Item::Fn(f) if f.sig.ident == "__module_init__" => (4, String::new()),
+        Item::Fn(f) if f.sig.ident == "main" => (5, String::new()),
+        Item::Fn(f) => (3, f.sig.ident.to_string()),
+        _ => (2, String::new()),
+    }
+}
+
+/// Sorts a file's items by [`sort_key`], keeping comment markers with the item that follows them.
+fn sort_items(file: &mut File) {
+    let mut groups: Vec<Vec<Item>> = Vec::new();
+    let mut pending = Vec::new();
+    for item in file.items.drain(..) {
+        let marker = is_trivia_marker(&item);
+        pending.push(item);
+        if !marker {
+            groups.push(std::mem::take(&mut pending));
+        }
+    }
+    // The sort is stable, so items with equal keys keep their order.
+    groups.sort_by_cached_key(|group| sort_key(group.last().expect("groups are never empty")));
+    file.items = groups.into_iter().flatten().chain(pending).collect();
+}
+
+fn rustfmt(code: &str, line_width: usize) -> Result<String, Box<dyn Error>> {
+    let mut child = Command::new("rustfmt")
+        .args(["--edition", "2024", "--emit", "stdout", "--config"])
+        .arg(format!("max_width={}", line_width))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("running rustfmt for a line width of {}: {}", line_width, e))?;
+    child.stdin.take().expect("stdin is piped").write_all(code.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(format!("rustfmt failed: {}", String::from_utf8_lossy(&output.stderr)).into());
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Finds tokens that `syn` kept as `Verbatim` because they aren't valid Rust where they are, such as a `pub fn`
+/// inside a trait. `prettyplease` can't print those, and panics on them. It does print empty items and
+/// expressions, as from a stray `;`.
+#[derive(Default)]
+struct Verbatim(Option<String>);
+
+impl Verbatim {
+    fn found(&mut self, tokens: &TokenStream) {
+        self.0.get_or_insert_with(|| tokens.to_string());
+    }
+
+    fn found_nonempty(&mut self, tokens: &TokenStream) {
+        if !tokens.is_empty() {
+            self.found(tokens);
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Verbatim {
+    fn visit_item(&mut self, node: &'ast Item) {
+        match node {
+            Item::Verbatim(tokens) => self.found_nonempty(tokens),
+            _ => visit::visit_item(self, node),
+        }
+    }
+
+    fn visit_trait_item(&mut self, node: &'ast TraitItem) {
+        match node {
+            TraitItem::Verbatim(tokens) => self.found_nonempty(tokens),
+            _ => visit::visit_trait_item(self, node),
+        }
+    }
+
+    fn visit_impl_item(&mut self, node: &'ast ImplItem) {
+        match node {
+            ImplItem::Verbatim(tokens) => self.found_nonempty(tokens),
+            _ => visit::visit_impl_item(self, node),
+        }
+    }
+
+    fn visit_foreign_item(&mut self, node: &'ast ForeignItem) {
+        match node {
+            ForeignItem::Verbatim(tokens) => self.found_nonempty(tokens),
+            _ => visit::visit_foreign_item(self, node),
+        }
+    }
+
+    fn visit_expr(&mut self, node: &'ast Expr) {
+        match node {
+            Expr::Verbatim(tokens) => self.found_nonempty(tokens),
+            _ => visit::visit_expr(self, node),
+        }
+    }
+
+    fn visit_type(&mut self, node: &'ast Type) {
+        match node {
+            Type::Verbatim(tokens) => self.found(tokens),
+            _ => visit::visit_type(self, node),
+        }
+    }
+
+    fn visit_pat(&mut self, node: &'ast Pat) {
+        match node {
+            Pat::Verbatim(tokens) => self.found(tokens),
+            _ => visit::visit_pat(self, node),
+        }
+    }
+
+    fn visit_type_param_bound(&mut self, node: &'ast TypeParamBound) {
+        match node {
+            TypeParamBound::Verbatim(tokens) => self.found(tokens),
+            _ => visit::visit_type_param_bound(self, node),
+        }
+    }
+}
+
+/// Pretty-prints `file`, or fails if it holds code that `prettyplease` can't print.
+fn unparse(file: &File) -> Result<String, Box<dyn Error>> {
+    let mut verbatim = Verbatim::default();
+    verbatim.visit_file(file);
+    match verbatim.0 {
+        Some(tokens) => Err(format!("generated code is not valid Rust: `{}`", tokens).into()),
+        None => Ok(restore_comments(&prettyplease::unparse(file))),
+    }
+}
+
+/// Formats generated tokens as a Rust source file.
+pub fn format_rust(tokens: TokenStream, options: &SourceOptions) -> Result<String, Box<dyn Error>> {
+    let mut file: File = syn::parse2(tokens).map_err(|e| format!("generated code is not a valid Rust file: {}", e))?;
+    if options.sort_items {
+        sort_items(&mut file);
+    }
+
+    let mut code = unparse(&file)?;
+    if options.line_width != PRETTYPLEASE_WIDTH {
+        code = rustfmt(&code, options.line_width)?;
+    }
+
+    let header: String = options.header.iter().map(|line| format!("// {}\n", line)).collect();
+    if header.is_empty() {
+        Ok(code)
+    } else {
+        Ok(format!("{}\n{}", header, code))
+    }
+}
+
+impl Module {
+    /// Generates the module as formatted Rust source.
+    pub fn to_rust_source(&self, options: PythonOptions, source_options: &SourceOptions) -> Result<String, Box<dyn Error>> {
+        let name = self.name.as_ref().map_or_else(|| "main".to_string(), |name| name.id.clone());
+        let options = source_options.python_options(options);
+        let tokens = self.clone().to_rust(CodeGenContext::Module(name), options, SymbolTableScopes::new())?;
+        format_rust(tokens, source_options)
+    }
+
+    /// Generates the module as formatted Rust source and writes it to `path`.
+    pub fn write_rust_source(
+        &self,
+        path: impl AsRef<Path>,
+        options: PythonOptions,
+        source_options: &SourceOptions,
+    ) -> Result<(), Box<dyn Error>> {
+        let code = self.to_rust_source(options, source_options)?;
+        std::fs::write(path, code)?;
+        Ok(())
+    }
+}
+
+impl Statement {
+    /// Generates one statement as formatted Rust, as it would be generated in `ctx` with the module's
+    /// `symbols`. A definition is formatted as an item, and anything else as the lines of a function body.
+    pub fn to_rust_source(
+        &self,
+        ctx: CodeGenContext,
+        options: PythonOptions,
+        symbols: SymbolTableScopes,
+    ) -> Result<String, Box<dyn Error>> {
+        let tokens = self.clone().to_rust(ctx, options, symbols)?;
+        if let Ok(file) = syn::parse2::<File>(tokens.clone()) {
+            return unparse(&file);
+        }
+
+        // Statements aren't items, so they are formatted inside a function and taken back out of it.
+        let file: File = syn::parse2(quote!(fn statement() { #tokens }))
+            .map_err(|e| format!("generated code is not valid Rust: {}", e))?;
+        let code = unparse(&file)?;
+        let lines: Vec<&str> = code.lines().collect();
+        let body = lines.get(1..lines.len().saturating_sub(1)).unwrap_or_default();
+        Ok(body.iter().map(|line| format!("{}\n", line.strip_prefix("    ").unwrap_or(line))).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(code: &str, source_options: &SourceOptions) -> String {
+        let module = crate::parse(code, "test.py").unwrap();
+        let options = PythonOptions {
+            with_std_python: false,
+            ..Default::default()
+        };
+        module.to_rust_source(options, source_options).unwrap()
+    }
+
+    #[test]
+    fn test_formats_source() {
+        let code = source("def add(a, b):\n    return a + b\n", &SourceOptions::default());
+        assert!(code.contains("pub fn add(a: impl Into<PyObject>, b: impl Into<PyObject>)"), "{}", code);
+        assert!(code.lines().count() > 1);
+    }
+
+    #[test]
+    fn test_header_and_comments() {
+        let options = SourceOptions::default().with_header("Generated from test.py.");
+        let code = source("def f():\n    # Nothing yet.\n    pass\n", &options);
+        assert!(code.starts_with("// Generated from test.py.\n\n"));
+        assert!(code.contains("\n    // Nothing yet.\n"), "{}", code);
+        assert!(!code.contains("__comment__"));
+    }
+
+    #[test]
+    fn test_without_comments() {
+        let code = source("def f():\n    # Nothing yet.\n    pass\n", &SourceOptions::default().without_comments());
+        assert!(!code.contains("Nothing yet"), "{}", code);
+    }
+
+    #[test]
+    fn test_unprintable_code_is_an_error() {
+        // Methods come out as `pub fn` inside the class's trait, which `prettyplease` can't print.
+        let module = crate::parse("class Point:\n    def norm(self):\n        return 1\n", "test.py").unwrap();
+        for source_options in [SourceOptions::default(), SourceOptions::default().without_comments()] {
+            let error = module.to_rust_source(PythonOptions::default(), &source_options).unwrap_err();
+            assert!(error.to_string().starts_with("generated code is not valid Rust: `pub fn norm"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_sorted_items() {
+        let python = "def b():\n    pass\n\n# About a.\ndef a():\n    pass\n\nimport os\nx = 1\n";
+        let code = source(python, &SourceOptions::default().with_sorted_items());
+        let position = |needle: &str| code.find(needle).unwrap_or_else(|| panic!("{} missing from {}", needle, code));
+        assert!(position("// About a.") < position("pub fn a()"));
+        assert!(position("pub fn a()") < position("pub fn b()"));
+        assert!(position("pub fn b()") < position("fn __module_init__()"));
+        assert!(position("fn __module_init__()") < position("fn main()"));
+    }
+
+    #[test]
+    fn test_long_comments_survive_wrapping() {
+        let comment = "x".repeat(120);
+        let code = source(&format!("def f():\n    # {}\n    pass\n", comment), &SourceOptions::default());
+        assert!(code.contains(&format!("// {}\n", comment)), "{}", code);
+    }
+
+    #[test]
+    fn test_line_width() {
+        let python = "def f(first, second):\n    pass\n";
+        let wide = source(python, &SourceOptions::default());
+        let narrow = source(python, &SourceOptions::default().with_line_width(60));
+        assert!(wide.lines().any(|line| line.len() > 60));
+        assert!(narrow.lines().all(|line| line.len() <= 60), "{}", narrow);
+    }
+
+    #[test]
+    fn test_statement_source() {
+        let module = crate::parse("def f(x):\n    if x:\n        print(x)\n", "test.py").unwrap();
+        let symbols = module.clone().find_symbols(SymbolTableScopes::new());
+        let generate = |statement: &Statement, ctx| statement.to_rust_source(ctx, PythonOptions::default(), symbols.clone()).unwrap();
+
+        let function = &module.raw.body[0];
+        assert!(generate(function, CodeGenContext::Module("test".into())).starts_with("pub fn f("));
+        let crate::StatementType::FunctionDef(f) = &function.statement else { unreachable!() };
+        let code = generate(&f.body[0], CodeGenContext::Function);
+        assert!(code.starts_with("if "), "{}", code);
+        assert!(code.ends_with("}\n"), "{}", code);
+    }
+
+    #[test]
+    fn test_write_rust_source() {
+        let path = std::env::temp_dir().join(format!("python-ast-source-{}.rs", std::process::id()));
+        let module = crate::parse("x = 1", "test.py").unwrap();
+        module.write_rust_source(&path, PythonOptions::default(), &SourceOptions::default()).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(written.contains("use stdpython::*;"));
+    }
+}
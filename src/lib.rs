@@ -14,6 +14,12 @@ pub use scope::*;
 pub mod symbols;
 pub use symbols::*;
 
+pub mod stubs;
+pub use stubs::*;
+
+pub mod analysis;
+pub use analysis::*;
+
 pub mod pytypes;
 
 pub use pyo3::PyResult;
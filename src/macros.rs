@@ -144,6 +144,22 @@ macro_rules! create_parse_test {
     };
 }
 
+/// Transpiles `code` as a module named `test`, without `stdpython`, for tests that check the generated code.
+#[cfg(test)]
+pub(crate) fn rust_for(code: &str) -> String {
+    use crate::{CodeGen, CodeGenContext, PythonOptions, SymbolTableScopes};
+
+    let module = crate::parse(code, "test.py").unwrap();
+    let options = PythonOptions {
+        with_std_python: false,
+        ..Default::default()
+    };
+    module
+        .to_rust(CodeGenContext::Module("test".to_string()), options, SymbolTableScopes::new())
+        .unwrap()
+        .to_string()
+}
+
 /// Macro for generating Node trait implementations with optional position fields.
 /// This macro automatically implements the Node trait for types that have position fields.
 #[macro_export]
@@ -1,5 +1,69 @@
 import ast
+import io
+import tokenize
 
-# Read the parse tree
-def parse(string, filename):
-    return ast.parse(string, filename=filename)
+# Read the parse tree. feature_version is a (major, minor) tuple to parse as that version of Python would.
+def parse(string, filename, feature_version=None):
+    return ast.parse(string, filename=filename, feature_version=feature_version)
+
+# Whether a with statement puts its items in parentheses, as in `with (open(a) as f, open(b) as g):`,
+# which the tree doesn't record. The parentheses must open right after `with` and close just before the
+# colon; `with (a) as b:` only parenthesizes an expression.
+def _parenthesized_with(node, lines):
+    if len(node.items) < 2 and node.items[0].optional_vars is None:
+        return False
+    tokens = tokenize.generate_tokens(io.StringIO("".join(lines[node.lineno - 1:])).readline)
+    skipped = (tokenize.NL, tokenize.COMMENT, tokenize.INDENT)
+    significant = (token.string for token in tokens if token.type not in skipped)
+    try:
+        if "with" not in significant or next(significant) != "(":
+            return False
+        depth = 1
+        while depth:
+            string = next(significant)
+            if string in ("(", "[", "{"):
+                depth += 1
+            elif string in (")", "]", "}"):
+                depth -= 1
+        return next(significant) == ":"
+    except (StopIteration, tokenize.TokenError, SyntaxError):
+        return False
+
+# The construct a node is, and the minor version of Python 3 that introduced it, if it is newer than 3.7.
+def _version_feature(node, lines):
+    kind = type(node).__name__
+    if kind == "NamedExpr":
+        return ("assignment expressions (:=)", 8)
+    if kind in ("With", "AsyncWith") and _parenthesized_with(node, lines):
+        return ("parenthesized context managers", 9)
+    if kind == "Match":
+        return ("match statements", 10)
+    if kind == "TryStar":
+        return ("except* clauses", 11)
+    if kind == "TypeAlias":
+        return ("type alias statements", 12)
+    if getattr(node, "type_params", None):
+        return ("type parameter lists", 12)
+    return None
+
+# Finds the constructs that need a recent Python, as (feature, major, minor, line, column) in source order.
+def version_features(string, filename):
+    tree = ast.parse(string, filename=filename)
+    lines = string.splitlines(keepends=True)
+    found = []
+    for node in ast.walk(tree):
+        feature = _version_feature(node, lines)
+        if feature:
+            found.append((feature[0], 3, feature[1], node.lineno, node.col_offset))
+    return sorted(found, key=lambda f: (f[3], f[4]))
+
+# The ast drops comments, so find them with the tokenizer. Returns (line, column, text, inline) for
+# each one, where inline means code precedes it on the same line.
+def comments(string):
+    found = []
+    for token in tokenize.generate_tokens(io.StringIO(string).readline):
+        if token.type == tokenize.COMMENT:
+            line, column = token.start
+            inline = token.line[:column].strip() != ""
+            found.append((line, column, token.string[1:], inline))
+    return found
@@ -0,0 +1,259 @@
+//! An on-disk cache of parsed modules.
+//!
+//! Extracting a tree from Python dominates the cost of parsing, so [`ParseCache`] keeps extracted
+//! [`Module`]s on disk, pickled with their serde derives. Entries are filed under a hash of the source, the
+//! filename, the Python version and this crate's version, and keep the source itself, so a change to any
+//! of them simply misses.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use pyo3::Python;
+use serde::{Deserialize, Serialize};
+
+use crate::{Module, Result as CrateResult, parse_enhanced};
+
+/// The environment variable that overrides the default cache directory.
+pub const CACHE_DIR_ENV: &str = "PYTHON_AST_CACHE_DIR";
+
+/// Bumped whenever the layout of a cache entry changes.
+const CACHE_FORMAT_VERSION: u32 = 3;
+
+/// What an entry was parsed from. It is stored alongside the module and compared in full on load, source
+/// included, so that a hash collision or a stale file can never return the wrong tree.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CacheKey {
+    pub format_version: u32,
+    pub crate_version: String,
+    pub python_version: String,
+    pub filename: String,
+    pub source_hash: u64,
+    /// The source itself, as the 64-bit hash only picks the file an entry is stored in.
+    pub source: String,
+}
+
+impl CacheKey {
+    pub fn new(input: &str, filename: &str) -> Self {
+        Self {
+            format_version: CACHE_FORMAT_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            python_version: Python::with_gil(|py| py.version().to_string()),
+            filename: filename.to_string(),
+            source_hash: fnv1a(input.as_bytes()),
+            source: input.to_string(),
+        }
+    }
+
+    /// The name of the file this entry is stored in.
+    fn file_name(&self) -> String {
+        let mut bytes = Vec::new();
+        for part in [&self.crate_version, &self.python_version, &self.filename] {
+            bytes.extend_from_slice(part.as_bytes());
+            bytes.push(0);
+        }
+        bytes.extend_from_slice(&self.format_version.to_le_bytes());
+        bytes.extend_from_slice(&self.source_hash.to_le_bytes());
+        format!("{:016x}.pickle", fnv1a(&bytes))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    key: CacheKey,
+    module: Module,
+}
+
+/// 64-bit FNV-1a. Stable across Rust releases and platforms, unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+/// A directory of pickled modules.
+#[derive(Clone, Debug)]
+pub struct ParseCache {
+    dir: PathBuf,
+}
+
+impl Default for ParseCache {
+    /// Uses `$PYTHON_AST_CACHE_DIR`, or a `python-ast` directory under the system temp directory.
+    fn default() -> Self {
+        let dir = std::env::var_os(CACHE_DIR_ENV)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::temp_dir().join("python-ast"));
+        Self::new(dir)
+    }
+}
+
+impl ParseCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Parses `input`, reusing the cached tree when the same source was parsed before.
+    ///
+    /// Failing to read or write the cache is never an error; it only costs a parse.
+    #[allow(clippy::result_large_err)] // Same error as `parse_enhanced`.
+    pub fn parse(&self, input: impl AsRef<str>, filename: impl AsRef<str>) -> CrateResult<Module> {
+        let (input, filename) = (input.as_ref(), filename.as_ref());
+        let key = CacheKey::new(input, filename);
+        if let Some(module) = self.load(&key) {
+            return Ok(module);
+        }
+
+        let module = parse_enhanced(input, filename)?;
+        if let Err(e) = self.store(key, &module) {
+            log::debug!("not caching {}: {}", filename, e);
+        }
+        Ok(module)
+    }
+
+    /// The cached tree for `input`, if there is one.
+    pub fn get(&self, input: impl AsRef<str>, filename: impl AsRef<str>) -> Option<Module> {
+        self.load(&CacheKey::new(input.as_ref(), filename.as_ref()))
+    }
+
+    /// Stores a tree parsed from `input`.
+    pub fn insert(&self, input: impl AsRef<str>, filename: impl AsRef<str>, module: &Module) -> io::Result<()> {
+        self.store(CacheKey::new(input.as_ref(), filename.as_ref()), module)
+    }
+
+    /// Drops the entry for `input`, returning whether there was one.
+    pub fn invalidate(&self, input: impl AsRef<str>, filename: impl AsRef<str>) -> io::Result<bool> {
+        let path = self.path(&CacheKey::new(input.as_ref(), filename.as_ref()));
+        match fs::remove_file(path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Removes every entry in the cache directory.
+    pub fn clear(&self) -> io::Result<()> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "pickle") {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self, key: &CacheKey) -> PathBuf {
+        self.dir.join(key.file_name())
+    }
+
+    fn load(&self, key: &CacheKey) -> Option<Module> {
+        let path = self.path(key);
+        let bytes = fs::read(&path).ok()?;
+        match serde_pickle::from_slice::<CacheEntry>(&bytes, Default::default()) {
+            Ok(entry) if entry.key == *key => {
+                let mut module = entry.module;
+                module.filename = Some(key.filename.clone());
+                Some(module)
+            }
+            Ok(_) => None,
+            Err(e) => {
+                // Left behind by an older build or a crash; it will be rewritten.
+                log::debug!("discarding unreadable cache entry {}: {}", path.display(), e);
+                let _ = fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    fn store(&self, key: CacheKey, module: &Module) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(&key);
+        let entry = CacheEntry { key, module: module.clone() };
+        let bytes = serde_pickle::to_vec(&entry, Default::default()).map_err(io::Error::other)?;
+
+        // Write then rename, so concurrent readers never see a partial entry.
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&tmp, bytes)?;
+        fs::rename(&tmp, &path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "import os\n\ndef f(x=None):\n    return [y * 2 for y in x if y]\n";
+
+    fn cache(name: &str) -> ParseCache {
+        let dir = std::env::temp_dir().join(format!("python-ast-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        ParseCache::new(dir)
+    }
+
+    #[test]
+    fn test_hit_returns_same_tree() {
+        let cache = cache("hit");
+        let parsed = cache.parse(SOURCE, "test.py").unwrap();
+        assert_eq!(fs::read_dir(cache.dir()).unwrap().count(), 1);
+
+        let cached = cache.get(SOURCE, "test.py").expect("entry should be cached");
+        assert_eq!(parsed.raw.body, cached.raw.body);
+        assert_eq!(cached.filename.as_deref(), Some("test.py"));
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn test_key_includes_source_and_filename() {
+        let cache = cache("key");
+        cache.parse(SOURCE, "test.py").unwrap();
+        assert!(cache.get(SOURCE, "other.py").is_none());
+        assert!(cache.get("x = 1", "test.py").is_none());
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn test_hash_collision_misses() {
+        let cache = cache("collision");
+        // An entry for other source that landed in the file `SOURCE` hashes to.
+        let key = CacheKey::new(SOURCE, "test.py");
+        let colliding = CacheKey { source: "x = 1".to_string(), ..key.clone() };
+        let module = crate::parse("x = 1", "test.py").unwrap();
+        cache.store(colliding, &module).unwrap();
+        assert!(cache.path(&key).exists());
+
+        assert!(cache.get(SOURCE, "test.py").is_none());
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn test_invalidate_and_clear() {
+        let cache = cache("invalidate");
+        cache.parse(SOURCE, "test.py").unwrap();
+        assert!(cache.invalidate(SOURCE, "test.py").unwrap());
+        assert!(!cache.invalidate(SOURCE, "test.py").unwrap());
+        assert!(cache.get(SOURCE, "test.py").is_none());
+
+        cache.parse(SOURCE, "test.py").unwrap();
+        cache.clear().unwrap();
+        assert!(cache.get(SOURCE, "test.py").is_none());
+    }
+
+    #[test]
+    fn test_corrupt_entry_is_reparsed() {
+        let cache = cache("corrupt");
+        cache.parse(SOURCE, "test.py").unwrap();
+        let path = cache.path(&CacheKey::new(SOURCE, "test.py"));
+        fs::write(&path, b"not a pickle").unwrap();
+
+        assert!(cache.get(SOURCE, "test.py").is_none());
+        assert!(!path.exists());
+        assert!(cache.parse(SOURCE, "test.py").is_ok());
+        cache.clear().unwrap();
+    }
+}
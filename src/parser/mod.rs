@@ -1,24 +1,42 @@
 use crate::{dump, Module, Name, SourceLocation, Error, Result as CrateResult, *};
 
-use pyo3::prelude::*;
+use pyo3::{prelude::*, sync::GILOnceCell};
 use std::ffi::CString;
 
 use std::path::MAIN_SEPARATOR;
 
+pub mod cache;
+pub use cache::*;
+
+pub mod version;
+pub use version::*;
+
+/// The compiled parser helper, so it is only built once per interpreter.
+static PARSER_MODULE: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+
+/// The parser helper module.
+fn parser_module(py: Python<'_>) -> PyResult<&Bound<'_, PyModule>> {
+    let pymodule = PARSER_MODULE.get_or_try_init(py, || -> PyResult<_> {
+        let pymodule_code = include_str!("__init__.py");
+
+        // We want to call tokenize.tokenize from Python.
+        let code_cstr = CString::new(pymodule_code)?;
+        Ok(PyModule::from_code(py, &code_cstr, c"__init__.py", c"parser")?.unbind())
+    })?;
+    Ok(pymodule.bind(py))
+}
+
 /// Takes a string of Python code and emits a Python struct that represents the AST.
 fn parse_to_py(
     input: impl AsRef<str>,
     filename: impl AsRef<str>,
+    feature_version: Option<PythonVersion>,
     py: Python<'_>,
 ) -> PyResult<PyObject> {
-    let pymodule_code = include_str!("__init__.py");
-
-    // We want to call tokenize.tokenize from Python.
-    let code_cstr = CString::new(pymodule_code)?;
-    let pymodule = PyModule::from_code(py, &code_cstr, c"__init__.py", c"parser")?;
-    let t = pymodule.getattr("parse")?;
+    let t = parser_module(py)?.getattr("parse")?;
     assert!(t.is_callable());
-    let args = (input.as_ref(), filename.as_ref());
+    let feature_version = feature_version.map(|v| (v.major, v.minor));
+    let args = (input.as_ref(), filename.as_ref(), feature_version);
 
     let py_tree = t.call1(args)?;
     log::debug!("py_tree: {}", dump(&py_tree, Some(4))?);
@@ -26,12 +44,22 @@ fn parse_to_py(
     Ok(py_tree.into())
 }
 
+/// Finds the comments in a string of Python code, as `(lineno, col_offset, text, inline)`.
+fn comments_from_py(input: &str, py: Python<'_>) -> PyResult<Vec<(usize, usize, String, bool)>> {
+    parser_module(py)?.getattr("comments")?.call1((input,))?.extract()
+}
+
 /// Parses Python code and returns the AST as a Module with improved error handling.
 /// 
 /// This function accepts any type that can be converted to a string reference,
 /// making it flexible for different input types. It provides detailed error information
 /// including file location and helpful guidance when parsing fails.
 /// 
+/// Valid Python that the tree doesn't model, such as a `del` or `global` statement, doesn't fail
+/// the parse: the statement is kept as [`StatementType::Unimplemented`] holding its source. Codegen
+/// reports it as an error unless `PythonOptions::python_fallback` is set, and the AST JSON writes
+/// it as the CPython node. Before, such code failed to parse.
+/// 
 /// # Arguments
 /// * `input` - The Python source code to parse
 /// * `filename` - The filename to associate with the parsed code
@@ -47,36 +75,56 @@ fn parse_to_py(
 /// let module = parse_enhanced(code, "example.py").unwrap();
 /// ```
 pub fn parse_enhanced(input: impl AsRef<str>, filename: impl AsRef<str>) -> CrateResult<Module> {
-    let filename = filename.as_ref();
-    let input_str = input.as_ref();
+    // Spelled out, as the glob import brings in the data model's Python `None`.
+    parse_module(input.as_ref(), filename.as_ref(), Option::None)
+}
+
+/// Converts an exception from parsing into our error, with the location a `SyntaxError` gives.
+fn python_error(py: Python<'_>, py_err: PyErr, filename: &str) -> Error {
+    // Convert PyO3 errors to our more detailed error format
+    let error_msg = format!("Python parsing failed: {}", py_err);
+    let help_msg = if error_msg.contains("SyntaxError") {
+        "Check your Python syntax. Common issues include missing colons, incorrect indentation, or unclosed brackets."
+    } else if error_msg.contains("IndentationError") {
+        "Fix indentation issues. Python requires consistent indentation (use either spaces or tabs, not both)."
+    } else {
+        "Ensure the input contains valid Python code. Check for syntax errors or unsupported constructs."
+    };
+
+    // Syntax errors say where they are.
+    let value = py_err.value(py);
+    let position = |attr: &str| value.getattr(attr).and_then(|v| v.extract::<usize>()).ok();
+    let location = SourceLocation::with_position(filename, position("lineno"), position("offset"));
+
+    Error::parsing_error(location, error_msg, help_msg)
+}
+
+/// Parses a module, as `feature_version` of Python would if it is given.
+#[allow(clippy::result_large_err)] // Same error as `parse_enhanced`.
+fn parse_module(input_str: &str, filename: &str, feature_version: Option<PythonVersion>) -> CrateResult<Module> {
     let location = SourceLocation::new(filename);
     
     // Empty files are valid in Python (they create empty modules), so we don't treat them as errors
     
     let mut module: Module = Python::with_gil(|py| {
-        let py_tree = parse_to_py(input_str, filename, py)
-            .map_err(|py_err| {
-                // Convert PyO3 errors to our more detailed error format
-                let error_msg = format!("Python parsing failed: {}", py_err);
-                let help_msg = if error_msg.contains("SyntaxError") {
-                    "Check your Python syntax. Common issues include missing colons, incorrect indentation, or unclosed brackets."
-                } else if error_msg.contains("IndentationError") {
-                    "Fix indentation issues. Python requires consistent indentation (use either spaces or tabs, not both)."
-                } else {
-                    "Ensure the input contains valid Python code. Check for syntax errors or unsupported constructs."
-                };
-                
-                Error::parsing_error(location.clone(), error_msg, help_msg)
-            })?;
+        let py_tree = parse_to_py(input_str, filename, feature_version, py)
+            .map_err(|py_err| python_error(py, py_err, filename))?;
             
-        py_tree.extract(py)
+        let mut module = py_tree.extract(py)
             .map_err(|py_err| {
                 Error::parsing_error(
                     location.clone(),
                     format!("Failed to extract AST: {}", py_err),
                     "The Python code was parsed but could not be converted to our AST format. This may indicate unsupported Python features."
                 )
-            })
+            })?;
+
+        // Comments are a nicety, so failing to find them doesn't fail the parse.
+        match comments_from_py(input_str, py) {
+            Ok(comments) => attach_trivia(&mut module, input_str, comments),
+            Err(e) => log::debug!("not reading comments from {}: {}", filename, e),
+        }
+        Ok(module)
     })?;
     
     module.filename = Some(filename.into());
@@ -102,6 +150,8 @@ pub fn parse_enhanced(input: impl AsRef<str>, filename: impl AsRef<str>) -> Crat
 /// 
 /// This is the original parse function that returns PyResult for backward compatibility.
 /// For better error messages with location information, use `parse_enhanced` instead.
+/// Like `parse_enhanced`, it keeps statements the tree doesn't model as
+/// [`StatementType::Unimplemented`] rather than failing.
 /// 
 /// # Arguments
 /// * `input` - The Python source code to parse
@@ -197,6 +247,18 @@ condition = (a > b) and (c < d) or (e == f)
         assert_eq!(module.raw.body.len(), 3);
     }
 
+    #[test]
+    fn test_syntax_error_location() {
+        match parse_enhanced("x = 1\ny = (\n", "broken.py") {
+            Err(Error::ParseError { location, .. }) => {
+                assert_eq!(location.filename, "broken.py");
+                assert_eq!(location.line, Some(2));
+                assert_eq!(location.column, Some(5));
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_parse_control_flow() {
         let code = r#"
@@ -264,6 +326,19 @@ def getter(self):
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_parse_keeps_unmodelled_statements() {
+        let module = parse("del x\nglobal y\n", "unmodelled.py").unwrap();
+        let statements: Vec<_> = module.raw.body.iter().map(|s| s.statement.clone()).collect();
+        assert_eq!(
+            statements,
+            [
+                StatementType::Unimplemented("del x".to_string()),
+                StatementType::Unimplemented("global y".to_string()),
+            ]
+        );
+    }
+
     #[test]
     fn test_parse_empty_file() {
         let code = "";
@@ -0,0 +1,159 @@
+//! Checking source against the version of Python it targets.
+//!
+//! The linked interpreter accepts everything its own version does, so code meant for an older Python can
+//! use constructs that Python won't run. [`check_version`] lists those constructs with their locations,
+//! and [`parse_for_version`] refuses them.
+
+use std::{fmt, str::FromStr};
+
+use pyo3::prelude::*;
+
+use crate::{Error, Module, Result as CrateResult, SourceLocation};
+
+use super::{parse_module, parser_module, python_error};
+
+/// A Python language version, such as 3.8.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PythonVersion {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl PythonVersion {
+    pub const fn new(major: u8, minor: u8) -> Self {
+        Self { major, minor }
+    }
+}
+
+impl fmt::Display for PythonVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for PythonVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid Python version '{}', expected one like 3.8", s);
+        let (major, minor) = s.trim().split_once('.').ok_or_else(invalid)?;
+        Ok(Self::new(major.parse().map_err(|_| invalid())?, minor.parse().map_err(|_| invalid())?))
+    }
+}
+
+/// A construct that needs a newer Python than the one targeted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VersionIssue {
+    pub feature: String,
+    /// The first version with the feature.
+    pub requires: PythonVersion,
+    /// Where the construct starts. Columns count from 1.
+    pub location: SourceLocation,
+}
+
+impl fmt::Display for VersionIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} require Python {}", self.location, self.feature, self.requires)
+    }
+}
+
+/// Finds the constructs in `input` that `target` doesn't support, in source order: assignment
+/// expressions, parenthesized context managers, `match`, `except*` and PEP 695 type parameters.
+#[allow(clippy::result_large_err)] // Same error as `parse_enhanced`.
+pub fn check_version(
+    input: impl AsRef<str>,
+    filename: impl AsRef<str>,
+    target: PythonVersion,
+) -> CrateResult<Vec<VersionIssue>> {
+    let filename = filename.as_ref();
+    let features: Vec<(String, u8, u8, usize, usize)> = Python::with_gil(|py| -> CrateResult<_> {
+        parser_module(py)
+            .and_then(|parser| parser.getattr("version_features")?.call1((input.as_ref(), filename))?.extract())
+            .map_err(|py_err| python_error(py, py_err, filename))
+    })?;
+
+    Ok(features
+        .into_iter()
+        .filter_map(|(feature, major, minor, line, column)| {
+            let requires = PythonVersion::new(major, minor);
+            (requires > target).then(|| VersionIssue {
+                feature,
+                requires,
+                location: SourceLocation::with_position(filename, Some(line), Some(column + 1)),
+            })
+        })
+        .collect())
+}
+
+/// Parses Python code written for `target`, failing with every construct it doesn't support. The parse
+/// itself also runs as `target` would, which catches the grammar changes [`check_version`] doesn't know.
+#[allow(clippy::result_large_err)] // Same error as `parse_enhanced`.
+pub fn parse_for_version(
+    input: impl AsRef<str>,
+    filename: impl AsRef<str>,
+    target: PythonVersion,
+) -> CrateResult<Module> {
+    let (input, filename) = (input.as_ref(), filename.as_ref());
+    let issues = check_version(input, filename, target)?;
+    if let Some(first) = issues.first() {
+        let list: Vec<_> = issues.iter().map(|issue| issue.to_string()).collect();
+        return Err(Error::parsing_error(
+            first.location.clone(),
+            format!("The code uses features newer than Python {}:\n{}", target, list.join("\n")),
+            "Rewrite these constructs, or target a newer Python version.",
+        ));
+    }
+    parse_module(input, filename, Some(target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"if (n := 3):
+    with (open("a") as f, open("b") as g):
+        pass
+    with (open("a")) as f:
+        pass
+match n:
+    case 1:
+        pass
+"#;
+
+    #[test]
+    fn test_version_parsing() {
+        assert_eq!("3.8".parse::<PythonVersion>(), Ok(PythonVersion::new(3, 8)));
+        assert!("3".parse::<PythonVersion>().is_err());
+        assert!(PythonVersion::new(3, 10) > PythonVersion::new(3, 9));
+        assert_eq!(PythonVersion::new(3, 11).to_string(), "3.11");
+    }
+
+    #[test]
+    fn test_reports_newer_constructs() {
+        let issues = check_version(SOURCE, "new.py", PythonVersion::new(3, 7)).unwrap();
+        let found: Vec<_> = issues.iter().map(|i| (i.feature.as_str(), i.requires.minor, i.location.line)).collect();
+        assert_eq!(
+            found,
+            [
+                ("assignment expressions (:=)", 8, Some(1)),
+                ("parenthesized context managers", 9, Some(2)),
+                ("match statements", 10, Some(6)),
+            ]
+        );
+        assert_eq!(issues[0].location.column, Some(5));
+
+        let issues = check_version(SOURCE, "new.py", PythonVersion::new(3, 9)).unwrap();
+        assert_eq!(issues.len(), 1);
+        assert!(check_version(SOURCE, "new.py", PythonVersion::new(3, 10)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_for_version() {
+        let error = parse_for_version("x = 1\nif (y := x):\n    pass\n", "walrus.py", PythonVersion::new(3, 7)).unwrap_err();
+        let Error::ParseError { location, message, .. } = error else { panic!("expected a parse error") };
+        assert_eq!(location.line, Some(2));
+        assert!(message.contains("assignment expressions (:=) require Python 3.8"), "{}", message);
+
+        assert!(parse_for_version("if (y := 1):\n    pass\n", "walrus.py", PythonVersion::new(3, 8)).is_ok());
+    }
+}
@@ -0,0 +1,75 @@
+import ast
+import json
+
+# Reads the signatures out of a stub (.pyi) file and returns them as JSON. Annotations are kept as their
+# source text. Stubs branch on sys.version_info and platform, and repeat definitions with @overload; the
+# first definition of a name is the one kept.
+
+def _annotation(node):
+    return ast.unparse(node) if node is not None else None
+
+def _parameters(args):
+    params = []
+    positional = args.posonlyargs + args.args
+    # Defaults belong to the last positional parameters.
+    defaults = [None] * (len(positional) - len(args.defaults)) + args.defaults
+    for index, (arg, default) in enumerate(zip(positional, defaults)):
+        kind = "positional_only" if index < len(args.posonlyargs) else "positional"
+        params.append(_parameter(arg, kind, default))
+    if args.vararg:
+        params.append(_parameter(args.vararg, "var_positional", None))
+    for arg, default in zip(args.kwonlyargs, args.kw_defaults):
+        params.append(_parameter(arg, "keyword_only", default))
+    if args.kwarg:
+        params.append(_parameter(args.kwarg, "var_keyword", None))
+    return params
+
+def _parameter(arg, kind, default):
+    # Stubs usually leave the value out as `...`, which says nothing about it.
+    elided = isinstance(default, ast.Constant) and default.value is Ellipsis
+    return {
+        "name": arg.arg,
+        "kind": kind,
+        "annotation": _annotation(arg.annotation),
+        "has_default": default is not None,
+        "default": None if default is None or elided else ast.unparse(default),
+    }
+
+def _function(node):
+    return {
+        "name": node.name,
+        "is_async": isinstance(node, ast.AsyncFunctionDef),
+        "parameters": _parameters(node.args),
+        "returns": _annotation(node.returns),
+    }
+
+def _definitions(body):
+    # The statements of a body, with those under `if` branches brought up.
+    for node in body:
+        if isinstance(node, ast.If):
+            yield from _definitions(node.body)
+            yield from _definitions(node.orelse)
+        else:
+            yield node
+
+def _members(body):
+    functions, classes, attributes = {}, {}, {}
+    for node in _definitions(body):
+        if isinstance(node, (ast.FunctionDef, ast.AsyncFunctionDef)):
+            functions.setdefault(node.name, _function(node))
+        elif isinstance(node, ast.ClassDef):
+            if node.name not in classes:
+                methods, _, class_attributes = _members(node.body)
+                classes[node.name] = {
+                    "name": node.name,
+                    "bases": [ast.unparse(base) for base in node.bases],
+                    "methods": methods,
+                    "attributes": class_attributes,
+                }
+        elif isinstance(node, ast.AnnAssign) and isinstance(node.target, ast.Name):
+            attributes.setdefault(node.target.id, _annotation(node.annotation))
+    return functions, classes, attributes
+
+def signatures(string, filename):
+    functions, classes, attributes = _members(ast.parse(string, filename=filename).body)
+    return json.dumps({"functions": functions, "classes": classes, "attributes": attributes})
@@ -0,0 +1,17 @@
+# A subset of typeshed's asyncio/__init__.pyi.
+from typing import Any, Awaitable, Coroutine, TypeVar
+
+_T = TypeVar("_T")
+
+async def sleep(delay: float, result: _T = ...) -> _T: ...
+async def gather(*coros_or_futures: Awaitable[Any], return_exceptions: bool = False) -> list[Any]: ...
+async def wait_for(fut: Awaitable[_T], timeout: float | None) -> _T: ...
+def run(main: Coroutine[Any, Any, _T], *, debug: bool | None = None) -> _T: ...
+def create_task(coro: Coroutine[Any, Any, _T], *, name: str | None = None) -> Any: ...
+
+class Queue:
+    def __init__(self, maxsize: int = 0) -> None: ...
+    async def get(self) -> Any: ...
+    async def put(self, item: Any) -> None: ...
+    def qsize(self) -> int: ...
+    def empty(self) -> bool: ...
@@ -0,0 +1,40 @@
+# A subset of typeshed's builtins.pyi.
+from typing import Any, Iterable, Iterator, Callable, TypeVar
+
+_T = TypeVar("_T")
+
+def abs(x: float, /) -> float: ...
+def all(iterable: Iterable[object], /) -> bool: ...
+def any(iterable: Iterable[object], /) -> bool: ...
+def callable(obj: object, /) -> bool: ...
+def chr(i: int, /) -> str: ...
+def divmod(x: int, y: int, /) -> tuple[int, int]: ...
+def getattr(o: object, name: str, default: Any = ..., /) -> Any: ...
+def hasattr(obj: object, name: str, /) -> bool: ...
+def hash(obj: object, /) -> int: ...
+def id(obj: object, /) -> int: ...
+def input(prompt: object = "", /) -> str: ...
+def isinstance(obj: object, class_or_tuple: type | tuple[type, ...], /) -> bool: ...
+def iter(object: Iterable[_T], /) -> Iterator[_T]: ...
+def len(obj: Sized, /) -> int: ...
+def max(*args: _T, key: Callable[[_T], Any] | None = None, default: _T = ...) -> _T: ...
+def min(*args: _T, key: Callable[[_T], Any] | None = None, default: _T = ...) -> _T: ...
+def next(i: Iterator[_T], default: _T = ..., /) -> _T: ...
+def open(file: str, mode: str = "r", buffering: int = -1, encoding: str | None = None, errors: str | None = None, newline: str | None = None) -> IO[Any]: ...
+def ord(c: str, /) -> int: ...
+def pow(base: int, exp: int, mod: int | None = None) -> int: ...
+def print(*values: object, sep: str | None = " ", end: str | None = "\n", file: Any = None, flush: bool = False) -> None: ...
+def repr(obj: object, /) -> str: ...
+def round(number: float, ndigits: int | None = None) -> float: ...
+def sorted(iterable: Iterable[_T], /, *, key: Callable[[_T], Any] | None = None, reverse: bool = False) -> list[_T]: ...
+def sum(iterable: Iterable[_T], /, start: int = 0) -> _T: ...
+def zip(*iterables: Iterable[Any], strict: bool = False) -> Iterator[tuple[Any, ...]]: ...
+
+class range:
+    start: int
+    stop: int
+    step: int
+    def __init__(self, start: int, stop: int = ..., step: int = ..., /) -> None: ...
+
+class enumerate:
+    def __init__(self, iterable: Iterable[_T], start: int = 0) -> None: ...
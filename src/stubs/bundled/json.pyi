@@ -0,0 +1,14 @@
+# A subset of typeshed's json/__init__.pyi.
+from typing import Any, IO
+
+def dump(obj: Any, fp: IO[str], *, skipkeys: bool = False, ensure_ascii: bool = True, indent: None | int | str = None, sort_keys: bool = False) -> None: ...
+def dumps(obj: Any, *, skipkeys: bool = False, ensure_ascii: bool = True, indent: None | int | str = None, sort_keys: bool = False) -> str: ...
+def load(fp: IO[str]) -> Any: ...
+def loads(s: str | bytes) -> Any: ...
+
+class JSONDecodeError(ValueError):
+    msg: str
+    doc: str
+    pos: int
+    lineno: int
+    colno: int
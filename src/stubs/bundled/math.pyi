@@ -0,0 +1,21 @@
+# A subset of typeshed's math.pyi.
+e: float
+inf: float
+nan: float
+pi: float
+tau: float
+
+def ceil(x: float, /) -> int: ...
+def cos(x: float, /) -> float: ...
+def exp(x: float, /) -> float: ...
+def fabs(x: float, /) -> float: ...
+def floor(x: float, /) -> int: ...
+def gcd(*integers: int) -> int: ...
+def hypot(*coordinates: float) -> float: ...
+def isclose(a: float, b: float, *, rel_tol: float = 1e-09, abs_tol: float = 0.0) -> bool: ...
+def isnan(x: float, /) -> bool: ...
+def log(x: float, base: float = ...) -> float: ...
+def pow(x: float, y: float, /) -> float: ...
+def sin(x: float, /) -> float: ...
+def sqrt(x: float, /) -> float: ...
+def tan(x: float, /) -> float: ...
@@ -0,0 +1,15 @@
+# A subset of typeshed's posixpath.pyi, which os.path is.
+# Functions that stdpython implements as returning a Rust `Result` are annotated `-> Result[T]`, so codegen unwraps them.
+def abspath(path: str) -> Result[str]: ...
+def basename(p: str) -> str: ...
+def dirname(p: str) -> str: ...
+def exists(path: str) -> bool: ...
+def expanduser(path: str) -> str: ...
+def getsize(filename: str) -> int: ...
+def isabs(s: str) -> bool: ...
+def isdir(s: str) -> bool: ...
+def isfile(path: str) -> bool: ...
+def join(a: str, /, *paths: str) -> str: ...
+def normpath(path: str) -> str: ...
+def realpath(filename: str, *, strict: bool = False) -> str: ...
+def splitext(p: str) -> tuple[str, str]: ...
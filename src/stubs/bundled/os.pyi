@@ -0,0 +1,21 @@
+# A subset of typeshed's os/__init__.pyi.
+# Functions that stdpython implements as returning a Rust `Result` are annotated `-> Result[T]`, so codegen unwraps them.
+from typing import Mapping
+
+environ: dict[str, str]
+name: str
+sep: str
+linesep: str
+
+def chdir(path: str) -> Result[None]: ...
+def execv(path: str, argv: list[str], /) -> Result[None]: ...
+def getcwd() -> Result[str]: ...
+def getenv(key: str, default: str | None = None) -> str | None: ...
+def getpid() -> int: ...
+def listdir(path: str | None = None) -> list[str]: ...
+def makedirs(name: str, mode: int = 0o777, exist_ok: bool = False) -> None: ...
+def mkdir(path: str, mode: int = 0o777) -> None: ...
+def remove(path: str) -> None: ...
+def rename(src: str, dst: str) -> None: ...
+def rmdir(path: str) -> None: ...
+def system(command: str) -> int: ...
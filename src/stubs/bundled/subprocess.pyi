@@ -0,0 +1,22 @@
+# A subset of typeshed's subprocess.pyi.
+# Functions that stdpython implements as returning a Rust `Result` are annotated `-> Result[T]`, so codegen unwraps them.
+from typing import Any, Mapping, Sequence
+
+class CompletedProcess:
+    args: Any
+    returncode: int
+    stdout: Any
+    stderr: Any
+    def __init__(self, args: Any, returncode: int, stdout: Any = None, stderr: Any = None) -> None: ...
+    def check_returncode(self) -> None: ...
+
+class CalledProcessError(Exception):
+    returncode: int
+    cmd: Any
+    output: Any
+    def __init__(self, returncode: int, cmd: Any, output: Any = None, stderr: Any = None) -> None: ...
+
+def run(args: str | Sequence[str], cwd: str | None = None, *, capture_output: bool = False, check: bool = False, env: Mapping[str, str] | None = None, shell: bool = False, text: bool | None = None, timeout: float | None = None) -> Result[CompletedProcess]: ...
+def call(args: str | Sequence[str], cwd: str | None = None, *, env: Mapping[str, str] | None = None, shell: bool = False, timeout: float | None = None) -> int: ...
+def check_call(args: str | Sequence[str], cwd: str | None = None, *, env: Mapping[str, str] | None = None, shell: bool = False, timeout: float | None = None) -> Result[int]: ...
+def check_output(args: str | Sequence[str], cwd: str | None = None, *, env: Mapping[str, str] | None = None, shell: bool = False, text: bool | None = None, timeout: float | None = None) -> Result[Any]: ...
@@ -0,0 +1,14 @@
+# A subset of typeshed's sys/__init__.pyi.
+from typing import Any, NoReturn, TextIO
+
+argv: list[str]
+path: list[str]
+platform: str
+version: str
+stdin: TextIO
+stdout: TextIO
+stderr: TextIO
+
+def exit(status: object = None, /) -> NoReturn: ...
+def getrecursionlimit() -> int: ...
+def getsizeof(obj: object, default: int = ...) -> int: ...
@@ -0,0 +1,5 @@
+# A subset of typeshed's time.pyi.
+def monotonic() -> float: ...
+def perf_counter() -> float: ...
+def sleep(secs: float, /) -> None: ...
+def time() -> float: ...
@@ -0,0 +1,351 @@
+//! Type stubs (`.pyi` files) for the modules Python code imports.
+//!
+//! Imported modules are usually not transpiled with the code that calls them, so their signatures are
+//! unknown to codegen. Stubs supply them: they are looked for along the Python path, including PEP 561
+//! `-stubs` packages, and then among a small bundled set for common standard library modules. The
+//! signatures are registered in the symbol table under the names the importing code uses.
+//!
+//! Stubs describe the Python API, but codegen calls stdpython's Rust one. A function stdpython implements
+//! as returning a Rust `Result` is annotated `-> Result[T]` in its stub, and calls to it are unwrapped.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::CString,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use pyo3::{prelude::*, sync::GILOnceCell};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, ExprType, Module, Result as CrateResult, SourceLocation, StatementType, SymbolTableNode, SymbolTableScopes};
+
+/// The stubs shipped with the crate, by module name.
+const BUNDLED: &[(&str, &str)] = &[
+    ("asyncio", include_str!("bundled/asyncio.pyi")),
+    ("builtins", include_str!("bundled/builtins.pyi")),
+    ("json", include_str!("bundled/json.pyi")),
+    ("math", include_str!("bundled/math.pyi")),
+    ("os", include_str!("bundled/os.pyi")),
+    ("os.path", include_str!("bundled/os.path.pyi")),
+    ("subprocess", include_str!("bundled/subprocess.pyi")),
+    ("sys", include_str!("bundled/sys.pyi")),
+    ("time", include_str!("bundled/time.pyi")),
+];
+
+/// How an argument can be passed to a parameter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParameterKind {
+    PositionalOnly,
+    Positional,
+    VarPositional,
+    KeywordOnly,
+    VarKeyword,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StubParameter {
+    pub name: String,
+    pub kind: ParameterKind,
+    /// The annotation as written in the stub.
+    pub annotation: Option<String>,
+    pub has_default: bool,
+    /// The default as written in the stub, unless it is left out as `...`.
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+impl StubParameter {
+    /// The default value, when the stub gives it.
+    pub fn default_value(&self) -> Option<ExprType> {
+        let module = crate::parse(self.default.as_ref()?, "<stub default>").ok()?;
+        match &module.raw.body.first()?.statement {
+            StatementType::Expr(expr) => Some(expr.value.clone()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StubFunction {
+    pub name: String,
+    pub is_async: bool,
+    pub parameters: Vec<StubParameter>,
+    /// The return annotation as written in the stub.
+    pub returns: Option<String>,
+}
+
+impl StubFunction {
+    /// The parameters other than `*args` and `**kwargs`, in the order they are declared.
+    pub fn fixed_parameters(&self) -> impl Iterator<Item = &StubParameter> {
+        self.parameters
+            .iter()
+            .filter(|p| !matches!(p.kind, ParameterKind::VarPositional | ParameterKind::VarKeyword))
+    }
+
+    /// Whether the stub marks the function as returning a Rust `Result`, with `-> Result[T]`.
+    pub fn returns_result(&self) -> bool {
+        self.returns.as_deref().is_some_and(|returns| returns == "Result" || returns.starts_with("Result["))
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StubClass {
+    pub name: String,
+    pub bases: Vec<String>,
+    pub methods: BTreeMap<String, StubFunction>,
+    /// Annotated attributes and their annotations.
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl StubClass {
+    pub fn constructor(&self) -> Option<&StubFunction> {
+        self.methods.get("__init__")
+    }
+}
+
+/// The signatures in one stub file. Where a stub defines a name more than once, under version checks or
+/// with `@overload`, the first definition is kept.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StubModule {
+    pub functions: BTreeMap<String, StubFunction>,
+    pub classes: BTreeMap<String, StubClass>,
+    /// Annotated module attributes and their annotations.
+    pub attributes: BTreeMap<String, String>,
+}
+
+/// The stub reader, so it is only built once per interpreter.
+static STUB_MODULE: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+
+impl StubModule {
+    /// Reads the signatures from the text of a stub.
+    #[allow(clippy::result_large_err)] // Same error as `parse_enhanced`.
+    pub fn parse(source: impl AsRef<str>, filename: impl AsRef<str>) -> CrateResult<Self> {
+        let filename = filename.as_ref();
+        let json = Python::with_gil(|py| -> PyResult<String> {
+            let reader = STUB_MODULE.get_or_try_init(py, || -> PyResult<_> {
+                let code = CString::new(include_str!("__init__.py"))?;
+                Ok(PyModule::from_code(py, &code, c"__init__.py", c"stubs")?.unbind())
+            })?;
+            reader.bind(py).getattr("signatures")?.call1((source.as_ref(), filename))?.extract()
+        })
+        .map_err(|e| {
+            Error::parsing_error(SourceLocation::new(filename), format!("Failed to read stub: {}", e), "Check the stub's syntax.")
+        })?;
+        serde_json::from_str(&json).map_err(|e| {
+            Error::parsing_error(SourceLocation::new(filename), format!("Failed to read stub: {}", e), "This is a bug in python-ast.")
+        })
+    }
+
+    /// The bundled stub for `module`, if there is one.
+    pub fn bundled(module: &str) -> Option<&'static StubModule> {
+        static PARSED: OnceLock<HashMap<&str, StubModule>> = OnceLock::new();
+        PARSED
+            .get_or_init(|| {
+                BUNDLED
+                    .iter()
+                    .map(|(name, source)| {
+                        let stub = Self::parse(source, format!("{}.pyi", name)).expect("reading a bundled stub");
+                        (*name, stub)
+                    })
+                    .collect()
+            })
+            .get(module)
+    }
+}
+
+/// Finds and reads stubs, remembering each module's so it is only read once.
+#[derive(Clone, Debug, Default)]
+pub struct Stubs {
+    search_path: Vec<PathBuf>,
+    loaded: HashMap<String, Option<StubModule>>,
+}
+
+impl Stubs {
+    /// Stubs found under the directories of `search_path`, or else bundled.
+    pub fn new<P: Into<PathBuf>>(search_path: impl IntoIterator<Item = P>) -> Self {
+        Self { search_path: search_path.into_iter().map(Into::into).collect(), loaded: HashMap::new() }
+    }
+
+    /// The stub file for `module` on the search path.
+    pub fn find(&self, module: &str) -> Option<PathBuf> {
+        let parts: Vec<&str> = module.split('.').collect();
+        let stubs_package = format!("{}-stubs", parts[0]);
+        let relative: PathBuf = parts.iter().collect();
+        let in_stubs_package: PathBuf = std::iter::once(stubs_package.as_str()).chain(parts[1..].iter().copied()).collect();
+
+        self.search_path.iter().find_map(|dir| {
+            [&in_stubs_package, &relative]
+                .into_iter()
+                .flat_map(|base| [base.with_extension("pyi"), base.join("__init__.pyi")])
+                .map(|candidate| dir.join(candidate))
+                .find(|candidate| candidate.is_file())
+        })
+    }
+
+    /// The signatures for `module`, from the search path or the bundled stubs. A stub that fails to read is
+    /// logged and treated as missing.
+    pub fn load(&mut self, module: &str) -> Option<&StubModule> {
+        if !self.loaded.contains_key(module) {
+            let stub = match self.find(module) {
+                Some(path) => read_stub(&path),
+                None => StubModule::bundled(module).cloned(),
+            };
+            self.loaded.insert(module.to_string(), stub);
+        }
+        self.loaded[module].as_ref()
+    }
+
+    /// Adds a scope to `symbols` holding the builtins and everything `module` imports at its top level,
+    /// under the names the module uses for them: `os.getcwd` after `import os`, `getcwd` after
+    /// `from os import getcwd`.
+    pub fn register_imports(&mut self, module: &Module, mut symbols: SymbolTableScopes) -> SymbolTableScopes {
+        symbols.new_scope();
+        if let Some(mut builtins) = self.load("builtins").cloned() {
+            // The module's own definitions hide the builtins of the same name.
+            for statement in &module.raw.body {
+                let defined = match &statement.statement {
+                    StatementType::FunctionDef(f) | StatementType::AsyncFunctionDef(f) => &f.name,
+                    StatementType::ClassDef(c) => &c.name,
+                    _ => continue,
+                };
+                builtins.functions.remove(defined);
+                builtins.classes.remove(defined);
+            }
+            register(&mut symbols, "", &builtins);
+        }
+
+        for statement in &module.raw.body {
+            match &statement.statement {
+                StatementType::Import(import) => {
+                    for alias in &import.names {
+                        if let Some(stub) = self.load(&alias.name).cloned() {
+                            let local = alias.asname.as_ref().unwrap_or(&alias.name);
+                            register(&mut symbols, &format!("{}.", local), &stub);
+                        }
+                    }
+                }
+                StatementType::ImportFrom(import) if import.level == 0 => {
+                    let stub = self.load(&import.module).cloned();
+                    for alias in &import.names {
+                        let local = alias.asname.clone().unwrap_or_else(|| alias.name.clone());
+                        let item = stub.as_ref().and_then(|stub| {
+                            let function = stub.functions.get(&alias.name).cloned().map(SymbolTableNode::StubFunction);
+                            function.or_else(|| stub.classes.get(&alias.name).cloned().map(SymbolTableNode::StubClass))
+                        });
+                        match item {
+                            Some(item) => symbols.insert(local, item),
+                            // `from os import path` imports a module.
+                            None => {
+                                if let Some(submodule) = self.load(&format!("{}.{}", import.module, alias.name)).cloned() {
+                                    register(&mut symbols, &format!("{}.", local), &submodule);
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        symbols
+    }
+}
+
+fn read_stub(path: &Path) -> Option<StubModule> {
+    let read = fs::read_to_string(path).map_err(Box::<dyn std::error::Error>::from).and_then(|source| {
+        Ok(StubModule::parse(source, path.to_string_lossy())?)
+    });
+    match read {
+        Ok(stub) => Some(stub),
+        Err(e) => {
+            log::warn!("ignoring stub {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+fn register(symbols: &mut SymbolTableScopes, prefix: &str, stub: &StubModule) {
+    for (name, function) in &stub.functions {
+        symbols.insert(format!("{}{}", prefix, name), SymbolTableNode::StubFunction(function.clone()));
+    }
+    for (name, class) in &stub.classes {
+        symbols.insert(format!("{}{}", prefix, name), SymbolTableNode::StubClass(class.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_signatures() {
+        let stub = StubModule::parse(
+            r#"import sys
+if sys.version_info >= (3, 10):
+    def fetch(url: str, /, timeout: float = ..., *, retries: int = 3) -> bytes: ...
+else:
+    def fetch(url: str) -> bytes: ...
+async def wait(seconds: float) -> None: ...
+VERSION: str
+class Client(Base):
+    name: str
+    def __init__(self, name: str) -> None: ...
+"#,
+            "net.pyi",
+        )
+        .unwrap();
+
+        let fetch = &stub.functions["fetch"];
+        let kinds: Vec<_> = fetch.parameters.iter().map(|p| (p.name.as_str(), p.kind, p.has_default)).collect();
+        assert_eq!(
+            kinds,
+            [
+                ("url", ParameterKind::PositionalOnly, false),
+                ("timeout", ParameterKind::Positional, true),
+                ("retries", ParameterKind::KeywordOnly, true),
+            ]
+        );
+        let defaults: Vec<_> = fetch.parameters.iter().map(|p| p.default.as_deref()).collect();
+        assert_eq!(defaults, [None, None, Some("3")]);
+        assert_eq!(fetch.returns.as_deref(), Some("bytes"));
+        assert!(stub.functions["wait"].is_async);
+        assert_eq!(stub.attributes["VERSION"], "str");
+
+        let client = &stub.classes["Client"];
+        assert_eq!(client.bases, ["Base"]);
+        assert_eq!(client.attributes["name"], "str");
+        assert_eq!(client.constructor().unwrap().parameters[1].annotation.as_deref(), Some("str"));
+    }
+
+    #[test]
+    fn test_finds_stubs_on_the_path() {
+        let dir = std::env::temp_dir().join(format!("python-ast-stubs-{}", std::process::id()));
+        fs::create_dir_all(dir.join("acme-stubs")).unwrap();
+        fs::create_dir_all(dir.join("local")).unwrap();
+        fs::write(dir.join("acme-stubs").join("__init__.pyi"), "def ping() -> None: ...\n").unwrap();
+        fs::write(dir.join("local").join("tools.pyi"), "async def fetch() -> str: ...\n").unwrap();
+
+        let mut stubs = Stubs::new([&dir]);
+        assert_eq!(stubs.find("acme"), Some(dir.join("acme-stubs").join("__init__.pyi")));
+        assert!(stubs.load("local.tools").unwrap().functions["fetch"].is_async);
+        // Not on the path, but bundled.
+        assert!(stubs.load("os.path").unwrap().functions.contains_key("join"));
+        assert!(stubs.load("nonexistent").is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_registers_imports() {
+        let module = crate::parse("import os.path\nimport asyncio as aio\nfrom subprocess import run\nfrom os import path as p\n", "m.py").unwrap();
+        let symbols = Stubs::new(Vec::<PathBuf>::new()).register_imports(&module, SymbolTableScopes::new());
+
+        assert!(matches!(symbols.get("os.path.join"), Some(SymbolTableNode::StubFunction(_))));
+        assert!(matches!(symbols.get("aio.sleep"), Some(SymbolTableNode::StubFunction(f)) if f.is_async));
+        assert!(matches!(symbols.get("run"), Some(SymbolTableNode::StubFunction(_))));
+        assert!(matches!(symbols.get("p.exists"), Some(SymbolTableNode::StubFunction(_))));
+        assert!(matches!(symbols.get("len"), Some(SymbolTableNode::StubFunction(_))));
+        assert!(matches!(symbols.get("range"), Some(SymbolTableNode::StubClass(_))));
+    }
+}
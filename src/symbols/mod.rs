@@ -5,6 +5,7 @@ use std::collections::VecDeque;
 use std::fmt;
 
 use crate::tree::{ClassDef, FunctionDef, Import, ImportFrom};
+use crate::{LocalVariable, StubClass, StubFunction};
 
 //use log::{debug, info};
 
@@ -38,6 +39,11 @@ impl SymbolTableScopes {
         }
     }
 
+    /// Looks `key` up in the current scope only.
+    pub fn get_local(&self, key: &str) -> Option<&SymbolTableNode> {
+        self.0.front().and_then(|table| table.get(key))
+    }
+
     pub fn get(&self, key: &str) -> Option<&SymbolTableNode> {
         for table in self.0.iter() {
             if let Some(value) = table.get(key) {
@@ -46,6 +52,11 @@ impl SymbolTableScopes {
         }
         None
     }
+
+    /// Like `get`, but also says how many scopes out `key` was found, where the current scope is 0.
+    pub fn get_with_depth(&self, key: &str) -> Option<(usize, &SymbolTableNode)> {
+        self.0.iter().enumerate().find_map(|(depth, table)| table.get(key).map(|value| (depth, value)))
+    }
 }
 
 impl Default for SymbolTableScopes {
@@ -68,6 +79,58 @@ pub enum SymbolTableNode {
     Import(Import),
     ImportFrom(ImportFrom),
     Alias(String),
+    /// A function known from a stub file.
+    StubFunction(StubFunction),
+    /// A class known from a stub file.
+    StubClass(StubClass),
+    /// A variable of the body being generated, before the statement that declares it.
+    Local(LocalVariable),
+}
+
+impl SymbolTableNode {
+    /// The parameters of a callable symbol with a known signature, other than `*args` and `**kwargs`, in
+    /// declaration order, each with its default value when that is known. A class's are its constructor's,
+    /// without `self`.
+    pub fn parameters(&self) -> Option<Vec<(String, Option<ExprType>)>> {
+        let stub_parameters = |f: &StubFunction, skip| {
+            f.fixed_parameters().skip(skip).map(|p| (p.name.clone(), p.default_value())).collect()
+        };
+        match self {
+            SymbolTableNode::FunctionDef(f) => {
+                let args = &f.args;
+                let positional: Vec<_> = args.posonlyargs.iter().chain(&args.args).collect();
+                // Defaults belong to the last positional parameters.
+                let first_default = positional.len().saturating_sub(args.defaults.len());
+                let defaults = (0..positional.len())
+                    .map(|index| index.checked_sub(first_default).map(|i| (*args.defaults[i]).clone()))
+                    .chain(args.kw_defaults.iter().map(|default| default.as_deref().cloned()));
+                let names = positional.into_iter().chain(&args.kwonlyargs).map(|p| p.arg.clone());
+                Some(names.zip(defaults.chain(std::iter::repeat(None))).collect())
+            }
+            SymbolTableNode::StubFunction(f) => Some(stub_parameters(f, 0)),
+            // The constructor's first parameter is `self`.
+            SymbolTableNode::StubClass(c) => Some(stub_parameters(c.constructor()?, 1)),
+            _ => None,
+        }
+    }
+
+    /// Whether calling the symbol returns something to await, when that is known.
+    pub fn is_async(&self) -> Option<bool> {
+        match self {
+            SymbolTableNode::StubFunction(f) => Some(f.is_async),
+            SymbolTableNode::StubClass(_) => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Whether calling the symbol returns a Rust `Result` to unwrap, when that is known.
+    pub fn returns_result(&self) -> Option<bool> {
+        match self {
+            SymbolTableNode::StubFunction(f) => Some(f.returns_result()),
+            SymbolTableNode::StubClass(_) => Some(false),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -234,7 +297,7 @@ mod tests {
         // Test different node types
         let assign_node = SymbolTableNode::Assign {
             position: 42,
-            value: ExprType::Name(Name { id: "test".to_string() }),
+            value: ExprType::Name(Name::new("test")),
         };
         
         match assign_node {
@@ -0,0 +1,299 @@
+use std::{fs, path::PathBuf, process::Command};
+
+fn python_ast() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_python-ast"))
+}
+
+/// A fresh directory for one test's files.
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("python-ast-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_transpiles_file_to_stdout() {
+    let dir = scratch("file");
+    let input = dir.join("greet.py");
+    fs::write(&input, "def greet(name):\n    # Say hello.\n    print(name)\n").unwrap();
+
+    let output = python_ast().arg(&input).arg("--no-stdpython").output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("// Generated by python-ast from greet.py.\n"));
+    assert!(stdout.contains("pub fn greet(name: impl Into<PyObject>)"));
+    assert!(stdout.contains("// Say hello."));
+    assert!(!stdout.contains("use stdpython"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_dumps_json() {
+    let dir = scratch("json");
+    let input = dir.join("x.py");
+    fs::write(&input, "x = 1\n").unwrap();
+
+    let output = python_ast().arg(&input).args(["--emit", "json"]).output().unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["_type"], "Module");
+    assert_eq!(json["body"][0]["_type"], "Assign");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_directory_reports_failures() {
+    let dir = scratch("dir");
+    let input = dir.join("src");
+    fs::create_dir_all(input.join("pkg")).unwrap();
+    fs::write(input.join("good.py"), "x = 1\n").unwrap();
+    fs::write(input.join("pkg").join("inner.py"), "def f():\n    pass\n").unwrap();
+    fs::write(input.join("broken.py"), "x = (\n").unwrap();
+    let out = dir.join("out");
+
+    let output = python_ast().arg(&input).arg("-o").arg(&out).output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("broken.py"), "{}", stderr);
+    assert!(stderr.contains("transpiled 2 of 3 files"), "{}", stderr);
+    assert!(out.join("good.rs").exists());
+    assert!(out.join("pkg").join("inner.rs").exists());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_directory_carries_on_after_a_crash() {
+    let dir = scratch("crash");
+    let input = dir.join("src");
+    fs::create_dir_all(&input).unwrap();
+    // Generates code that can't be formatted.
+    fs::write(input.join("a_class.py"), "class Point:\n    def norm(self):\n        return 1\n").unwrap();
+    // Panics while the tree is extracted.
+    fs::write(input.join("b_base.py"), "import abc\nclass A(abc.ABC):\n    pass\n").unwrap();
+    fs::write(input.join("c_ok.py"), "x = 1\n").unwrap();
+    let out = dir.join("out");
+
+    let output = python_ast().arg(&input).arg("-o").arg(&out).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&format!("error: {}: generated code is not valid Rust", input.join("a_class.py").display())), "{}", stderr);
+    assert!(stderr.contains(&format!("error: {}: python-ast failed on this file", input.join("b_base.py").display())), "{}", stderr);
+    assert!(stderr.contains("transpiled 1 of 3 files"), "{}", stderr);
+    assert!(out.join("c_ok.rs").exists());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_custom_runtime_needs_details() {
+    let dir = scratch("runtime");
+    let input = dir.join("a.py");
+    fs::write(&input, "async def f():\n    pass\n").unwrap();
+
+    let output = python_ast().arg(&input).args(["--async-runtime", "custom"]).output().unwrap();
+    assert!(!output.status.success());
+
+    let output = python_ast()
+        .arg(&input)
+        .args(["--async-runtime", "custom", "--runtime-attribute", "rt::main", "--runtime-import", "rt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8(output.stdout).unwrap().contains("use rt;"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_emits_cargo_project() {
+    let dir = scratch("project");
+    let input = dir.join("app");
+    fs::create_dir_all(&input).unwrap();
+    fs::write(input.join("__main__.py"), "import helpers\nhelpers.run()\n").unwrap();
+    fs::write(input.join("helpers.py"), "async def run():\n    pass\n").unwrap();
+    let out = dir.join("out");
+
+    let output = python_ast().arg(&input).args(["--emit", "project", "-o"]).arg(&out).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let manifest = fs::read_to_string(out.join("Cargo.toml")).unwrap();
+    assert!(manifest.contains("name = \"app\""));
+    assert!(manifest.contains("tokio = "), "{}", manifest);
+    assert!(fs::read_to_string(out.join("src/main.rs")).unwrap().contains("mod helpers;"));
+    assert!(out.join("src/helpers.rs").exists());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_target_version() {
+    let dir = scratch("target");
+    let input = dir.join("walrus.py");
+    fs::write(&input, "if (n := 3):\n    pass\n").unwrap();
+
+    let output = python_ast().arg(&input).args(["--target-version", "3.7"]).output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("walrus.py:1:5: assignment expressions (:=) require Python 3.8"), "{}", stderr);
+
+    let output = python_ast().arg(&input).args(["--target-version", "3.8"]).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_python_fallback() {
+    let dir = scratch("fallback");
+    let input = dir.join("trim.py");
+    fs::write(&input, "def trim(xs):\n    items = xs\n    del items[0]\n").unwrap();
+
+    let output = python_ast().arg(&input).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("`del items[0]` can't be transpiled"));
+
+    let output = python_ast().arg(&input).arg("--python-fallback").output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("warning: trim.py:3:5: statement runs in Python: del items[0]"), "{}", stderr);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("pyo3::Python::with_gil"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_emit_cfg() {
+    let dir = scratch("cfg");
+    let input = dir.join("loop.py");
+    fs::write(&input, "def f(xs):\n    for x in xs:\n        if x:\n            break\n").unwrap();
+
+    let output = python_ast().arg(&input).args(["--emit", "cfg"]).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("digraph \"loop\" {\n"), "{}", stdout);
+    assert!(stdout.contains("digraph \"f\" {\n"));
+    assert!(stdout.contains("[label=\"2: for x in xs\\l\"];"));
+    assert!(stdout.contains("[label=\"break\"];"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_emit_metrics() {
+    let dir = scratch("metrics");
+    let input = dir.join("shapes.py");
+    fs::write(&input, "class Square:\n    def area(self, scale):\n        if scale:\n            return scale * scale\n        return 0\n").unwrap();
+
+    let output = python_ast().arg(&input).args(["--emit", "metrics"]).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let rows: Vec<_> = stdout.lines().filter(|row| !row.is_empty()).collect();
+    assert_eq!(rows.len(), 4, "{}", stdout);
+    assert!(rows[0].starts_with("name         kind      line"), "{}", stdout);
+    assert!(rows[3].starts_with("Square.area  function     2      4      3   2     1       2"), "{}", stdout);
+
+    let output = python_ast().arg(&input).args(["--emit", "metrics-json"]).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\"name\": \"Square.area\""), "{}", stdout);
+    assert!(stdout.contains("\"maintainability_index\": "));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_emit_call_graph() {
+    let dir = scratch("calls");
+    fs::create_dir_all(dir.join("pkg")).unwrap();
+    fs::write(dir.join("pkg/__init__.py"), "from .util import tidy\n\ndef run():\n    tidy(1)\n").unwrap();
+    fs::write(dir.join("pkg/util.py"), "def tidy(x):\n    return str(x)\n").unwrap();
+    fs::write(dir.join("main.py"), "import pkg\npkg.run()\n").unwrap();
+
+    let output = python_ast().arg(&dir).args(["--emit", "calls"]).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\"main\" -> \"pkg.run\";"), "{}", stdout);
+    assert!(stdout.contains("\"pkg.run\" -> \"pkg.util.tidy\";"), "{}", stdout);
+    assert!(stdout.contains("\"builtins.str (external)\" [style=dashed];"), "{}", stdout);
+
+    let output = python_ast().arg(&dir).args(["--emit", "calls-json"]).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\"resolution\": \"external\""), "{}", stdout);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_query() {
+    let dir = scratch("query");
+    fs::write(dir.join("run.py"), "import os\n\ndef run(cmd):\n    os.system(cmd)\n    os.getcwd()\n").unwrap();
+
+    let output = python_ast().arg(&dir).args(["--query", "Call(func=Attribute(value=Name('os'), attr='system'), args=[$arg])"]).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let expected = format!("{}:4:5: os.system(cmd)\n    $arg = cmd\n", dir.join("run.py").display());
+    assert_eq!(stdout, expected);
+
+    let output = python_ast().arg(&dir).args(["--query", "Call(fun=_)"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("`Call` has no field `fun`"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_check() {
+    let dir = scratch("check");
+    fs::write(dir.join("ok.py"), "def f(x):\n    return x\n").unwrap();
+    fs::write(dir.join("bad.py"), "def f(flag):\n    if flag:\n        y = 1\n    return y + z\n").unwrap();
+
+    let output = python_ast().arg(dir.join("ok.py")).arg("--check").output().unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let output = python_ast().arg(&dir).arg("--check").output().unwrap();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let bad = dir.join("bad.py").display().to_string();
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            format!("{}:4:12-13: warning[possibly-used-before-assignment]: `y` may be used before it's assigned", bad),
+            format!("{}:4:16-17: error[undefined-name]: `z` is not defined", bad),
+        ]
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("2 problems in 2 files"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_check_lints() {
+    let dir = scratch("lints");
+    let input = dir.join("app.py");
+    fs::write(&input, "import os
+import re  # noqa
+
+def f(a, b):
+    c = a
+    return b
+").unwrap();
+    let file = input.display().to_string();
+
+    let output = python_ast().arg(&input).arg("--check").output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).lines().collect::<Vec<_>>(),
+        [
+            format!("{}:1:1-10: warning[unused-import]: `os` is imported but never used", file),
+            format!("{}:5:5-6: warning[unused-variable]: local variable `c` is assigned but never read", file),
+        ]
+    );
+
+    let output = python_ast()
+        .arg(&input)
+        .args(["--check", "--disable", "unused-import", "--enable", "unused-variable=error"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        format!("{}:5:5-6: error[unused-variable]: local variable `c` is assigned but never read", file)
+    );
+
+    let output = python_ast().arg(&input).args(["--check", "--enable", "unused-everything"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown lint rule `unused-everything`"));
+    fs::remove_dir_all(dir).unwrap();
+}
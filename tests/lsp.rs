@@ -0,0 +1,176 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use serde_json::{Value, json};
+
+/// A minimal client that talks to `python-ast-lsp` over its stdin and stdout.
+struct Client {
+    child: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+    next_id: i64,
+}
+
+impl Client {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_python-ast-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let input = child.stdin.take().unwrap();
+        let output = BufReader::new(child.stdout.take().unwrap());
+        Self { child, input, output, next_id: 1 }
+    }
+
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        write!(self.input, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.input.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            assert!(self.output.read_line(&mut header).unwrap() > 0, "the server closed its output");
+            match header.trim_end().split_once(": ") {
+                Some(("Content-Length", value)) => length = value.parse().unwrap(),
+                _ if header.trim_end().is_empty() => break,
+                _ => {}
+            }
+        }
+        let mut body = vec![0; length];
+        self.output.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        let response = self.receive();
+        assert_eq!(response["id"], id, "{}", response);
+        response
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn initialize(&mut self) -> Value {
+        let response = self.request("initialize", json!({ "processId": null, "rootUri": null, "capabilities": {} }));
+        self.notify("initialized", json!({}));
+        response
+    }
+
+    /// Shuts the server down and returns whether it exited cleanly.
+    fn shutdown(mut self) -> bool {
+        let response = self.request("shutdown", Value::Null);
+        assert_eq!(response["result"], Value::Null);
+        self.notify("exit", Value::Null);
+        self.child.wait().unwrap().success()
+    }
+}
+
+const URI: &str = "file:///project/util.py";
+
+fn open(client: &mut Client, text: &str) -> Value {
+    client.notify(
+        "textDocument/didOpen",
+        json!({ "textDocument": { "uri": URI, "languageId": "python", "version": 1, "text": text } }),
+    );
+    let published = client.receive();
+    assert_eq!(published["method"], "textDocument/publishDiagnostics");
+    assert_eq!(published["params"]["uri"], URI);
+    published["params"]["diagnostics"].clone()
+}
+
+fn at(line: u64, character: u64) -> Value {
+    json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } })
+}
+
+#[test]
+fn test_initialize_and_shutdown() {
+    let mut client = Client::start();
+    let response = client.initialize();
+    let capabilities = &response["result"]["capabilities"];
+    assert_eq!(capabilities["hoverProvider"], true);
+    assert_eq!(capabilities["definitionProvider"], true);
+    assert_eq!(capabilities["textDocumentSync"]["change"], 1);
+
+    let unknown = client.request("textDocument/completion", at(0, 0));
+    assert_eq!(unknown["error"]["code"], -32601);
+    assert!(client.shutdown());
+}
+
+#[test]
+fn test_diagnostics_follow_edits() {
+    let mut client = Client::start();
+    client.initialize();
+
+    let diagnostics = open(&mut client, "def clear(items):\n    del items[0]\n    return items\n");
+    assert_eq!(diagnostics.as_array().unwrap().len(), 1, "{}", diagnostics);
+    assert_eq!(diagnostics[0]["code"], "untranspilable");
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 1, "character": 4 }));
+    assert_eq!(diagnostics[0]["message"], "`del items[0]` can't be transpiled");
+
+    client.notify(
+        "textDocument/didChange",
+        json!({ "textDocument": { "uri": URI, "version": 2 }, "contentChanges": [{ "text": "def clear(items):\n    return items\n" }] }),
+    );
+    let published = client.receive();
+    assert_eq!(published["params"]["diagnostics"], json!([]));
+
+    client.notify(
+        "textDocument/didChange",
+        json!({ "textDocument": { "uri": URI, "version": 3 }, "contentChanges": [{ "text": "def clear(items:\n" }] }),
+    );
+    let published = client.receive();
+    assert_eq!(published["params"]["diagnostics"][0]["code"], "syntax-error");
+    assert_eq!(published["params"]["diagnostics"][0]["range"]["start"]["line"], 0);
+    assert!(client.shutdown());
+}
+
+#[test]
+fn test_hover_shows_generated_rust() {
+    let mut client = Client::start();
+    client.initialize();
+    open(&mut client, "def add(a, b):\n    total = a + b\n    return total\n");
+
+    let hover = client.request("textDocument/hover", at(2, 12));
+    let value = hover["result"]["contents"]["value"].as_str().unwrap();
+    assert!(value.starts_with("```rust\nreturn"), "{}", value);
+    assert_eq!(hover["result"]["range"]["start"], json!({ "line": 2, "character": 4 }));
+
+    let hover = client.request("textDocument/hover", at(0, 4));
+    let value = hover["result"]["contents"]["value"].as_str().unwrap();
+    assert!(value.contains("pub fn add("), "{}", value);
+    assert!(client.shutdown());
+}
+
+#[test]
+fn test_definition() {
+    let mut client = Client::start();
+    client.initialize();
+    // The non-ASCII string moves `name` two bytes but one UTF-16 unit to the right.
+    open(&mut client, "import os\n\ndef greet(name):\n    return 'é' + name + os.sep\n");
+
+    let definition = client.request("textDocument/definition", at(3, 18));
+    assert_eq!(definition["result"]["uri"], URI);
+    assert_eq!(definition["result"]["range"]["start"], json!({ "line": 2, "character": 10 }));
+    assert_eq!(definition["result"]["range"]["end"], json!({ "line": 2, "character": 14 }));
+
+    let definition = client.request("textDocument/definition", at(3, 25));
+    assert_eq!(definition["result"]["range"]["start"], json!({ "line": 0, "character": 0 }));
+
+    // Builtins are defined nowhere in the file.
+    open(&mut client, "print(len([]))\n");
+    let definition = client.request("textDocument/definition", at(0, 1));
+    assert_eq!(definition["result"], Value::Null);
+    assert!(client.shutdown());
+}